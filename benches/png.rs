@@ -0,0 +1,51 @@
+//! Parsing and serialization speed. Files of many small chunks stress the per-chunk work,
+//! large chunks the CRC. Run with `cargo bench`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use pngme::{Chunk, ChunkType, Png};
+
+/// A png of `chunk_count` IDAT chunks of `chunk_len` bytes each.
+fn png_bytes(chunk_count: usize, chunk_len: usize) -> Vec<u8> {
+    let chunk = |chunk_type: &[u8; 4], data: Vec<u8>| Chunk::new(ChunkType::try_from(*chunk_type).unwrap(), data);
+    let mut chunks = vec![chunk(b"IHDR", b"0123456789abc".to_vec())];
+    chunks.extend((0..chunk_count).map(|index| chunk(b"IDAT", vec![index as u8; chunk_len])));
+    chunks.push(chunk(b"IEND", Vec::new()));
+    Png::from_chunks(chunks).as_bytes()
+}
+
+fn png(c: &mut Criterion) {
+    for (name, chunk_count, chunk_len) in [("many_chunks", 1000, 1024), ("large_chunks", 4, 1 << 20)] {
+        let bytes = png_bytes(chunk_count, chunk_len);
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+        group.bench_function("parse", |b| b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap()));
+        group.bench_function("parse_owned", |b| b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap().into_owned()));
+        group.bench_function("as_bytes", |b| b.iter(|| black_box(&png).as_bytes()));
+        group.bench_function("write_to", |b| {
+            let mut out = Vec::with_capacity(bytes.len());
+            b.iter(|| {
+                out.clear();
+                black_box(&png).write_to(&mut out).unwrap();
+            })
+        });
+        group.finish();
+    }
+}
+
+fn chunk(c: &mut Criterion) {
+    let data = vec![0x5a; 1 << 20];
+    let chunk_type = ChunkType::try_from(*b"ruSt").unwrap();
+    let mut group = c.benchmark_group("chunk");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+
+    // Building a chunk computes its CRC.
+    group.bench_function("new", |b| b.iter(|| Chunk::new(chunk_type.clone(), black_box(data.as_slice()))));
+    group.finish();
+}
+
+criterion_group!(benches, png, chunk);
+criterion_main!(benches);
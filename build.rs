@@ -0,0 +1,11 @@
+//! Generates `include/pngme.h` for the C API when building with `--features ffi`.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("cargo sets CARGO_MANIFEST_DIR");
+        cbindgen::generate(&crate_dir).expect("Can't generate the C header").write_to_file(format!("{crate_dir}/include/pngme.h"));
+    }
+}
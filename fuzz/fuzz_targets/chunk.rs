@@ -0,0 +1,17 @@
+//! Parses arbitrary bytes as a chunk followed by anything, with `cargo +nightly fuzz run chunk`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::Chunk;
+
+fuzz_target!(|data: &[u8]| {
+    let parsed = Chunk::parse(data);
+    if let Ok((chunk, consumed)) = &parsed {
+        // The chunk is exactly the bytes it took, and those alone parse the same.
+        assert_eq!(chunk.as_bytes(), data[..*consumed]);
+        assert_eq!(Chunk::try_from(&data[..*consumed]).map(|chunk| chunk.as_bytes()), Ok(chunk.as_bytes()));
+    }
+    // Converting accepts the inputs that are a single chunk and nothing more.
+    let whole = parsed.is_ok_and(|(_, consumed)| consumed == data.len());
+    assert_eq!(Chunk::try_from(data).is_ok(), whole);
+});
@@ -0,0 +1,18 @@
+//! Parses arbitrary bytes as an untrusted png, with
+//! `cargo +nightly fuzz run parse_untrusted fuzz/corpus/png`: it shares the `png` corpus.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::{ParseLimits, Png};
+use pngme_fuzz::LIMITS;
+
+fuzz_target!(|data: &[u8]| {
+    for limits in [LIMITS, ParseLimits::default()] {
+        if let Ok(png) = Png::parse_untrusted(data, limits) {
+            assert!(data.len() <= limits.max_total_bytes);
+            assert!(png.chunk_count() <= limits.max_chunks);
+            assert!(png.chunks().all(|chunk| chunk.data().len() <= limits.max_chunk_size));
+            assert_eq!(png.as_bytes(), data);
+        }
+    }
+});
@@ -0,0 +1,21 @@
+//! Parses arbitrary bytes as a png, with `cargo +nightly fuzz run png`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::Png;
+use pngme_fuzz::LIMITS;
+
+fuzz_target!(|data: &[u8]| {
+    let parsed = Png::try_from(data);
+    if let Ok(png) = &parsed {
+        // A file that parses is kept byte for byte, trailing data included.
+        assert_eq!(png.as_bytes(), data);
+    }
+    // Streaming accepts exactly the files parsing in memory does.
+    let streamed = Png::from_reader(data);
+    assert_eq!(streamed.ok().map(|png| png.as_bytes()), parsed.as_ref().ok().map(|png| png.as_bytes()));
+    // Limits only ever reject files, never accept or change one.
+    if let Ok(limited) = Png::parse_untrusted(data, LIMITS) {
+        assert_eq!(Some(limited.as_bytes()), parsed.ok().map(|png| png.as_bytes()));
+    }
+});
@@ -0,0 +1,6 @@
+//! What the fuzz targets share.
+
+use pngme::ParseLimits;
+
+/// Small enough for inputs to reach every limit, not only the parser behind them.
+pub const LIMITS: ParseLimits = ParseLimits { max_chunks: 16, max_chunk_size: 4096, max_total_bytes: 1 << 16 };
@@ -0,0 +1,67 @@
+/* Generated by cbindgen from src/ffi.rs, don't edit. */
+
+#ifndef PNGME_H
+#define PNGME_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Stores `payload` in a new `chunk_type` chunk before IEND of the png in `png`, in the
+ envelope `pngme decode` reads, and returns the new file through `out` and `out_len`.
+
+ # Safety
+
+ `png` and `payload` must be valid for reads of `png_len` and `payload_len` bytes,
+ `chunk_type` must be a NUL-terminated string, and `out` and `out_len` valid for writes.
+ */
+int pngme_encode(const uint8_t *png,
+                 size_t png_len,
+                 const char *chunk_type,
+                 const uint8_t *payload,
+                 size_t payload_len,
+                 uint8_t **out,
+                 size_t *out_len);
+
+/*
+ Returns through `out` and `out_len` the payload of the first `chunk_type` chunk of the png
+ in `png`, as stored by `pngme_encode` or `pngme encode` without encryption.
+
+ # Safety
+
+ `png` must be valid for reads of `png_len` bytes, `chunk_type` must be a NUL-terminated
+ string, and `out` and `out_len` valid for writes.
+ */
+int pngme_decode(const uint8_t *png,
+                 size_t png_len,
+                 const char *chunk_type,
+                 uint8_t **out,
+                 size_t *out_len);
+
+/*
+ Frees a buffer returned by `pngme_encode` or `pngme_decode`. NULL is ignored.
+
+ # Safety
+
+ `buffer` and `len` must be a pair returned by this library and not freed yet.
+ */
+void pngme_free(uint8_t *buffer, size_t len);
+
+/*
+ The message of the last error on this thread, or NULL after a success. The string is
+ owned by the library and valid until the next call on this thread.
+ */
+const char *pngme_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PNGME_H */
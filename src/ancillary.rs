@@ -0,0 +1,151 @@
+use crate::chunk::Chunk;
+use crate::ihdr::{ColorType, Ihdr};
+use crate::png::Png;
+
+fn expect_len(chunk_type: &str, data: &[u8], expected: usize, ihdr: &Ihdr) -> Result<(), String> {
+    if data.len() != expected {
+        return Err(format!(
+            "{chunk_type} should be {expected} bytes long for {} images, not {}",
+            ihdr.color_type,
+            data.len()
+        ));
+    }
+    Ok(())
+}
+
+/// 16-bit samples, as stored by tRNS and bKGD for non-indexed images.
+fn samples(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect()
+}
+
+/// `palette_entries` is the number of PLTE entries, which limits the tRNS length of indexed
+/// images.
+pub(crate) fn describe_trns(data: &[u8], ihdr: &Ihdr, palette_entries: Option<usize>) -> Result<String, String> {
+    match ihdr.color_type {
+        ColorType::Grayscale => {
+            expect_len("tRNS", data, 2, ihdr)?;
+            Ok(format!("transparent gray({})", samples(data)[0]))
+        }
+        ColorType::Rgb => {
+            expect_len("tRNS", data, 6, ihdr)?;
+            let rgb = samples(data);
+            Ok(format!("transparent rgb({},{},{})", rgb[0], rgb[1], rgb[2]))
+        }
+        ColorType::Indexed => {
+            if let Some(entries) = palette_entries.filter(|&entries| data.len() > entries) {
+                return Err(format!("tRNS has {} alpha values but the palette only has {entries} entries", data.len()));
+            }
+            Ok(format!("{} palette entries with alpha", data.len()))
+        }
+        ColorType::GrayscaleAlpha | ColorType::Rgba => Err(format!("tRNS is not allowed in {} images", ihdr.color_type)),
+    }
+}
+
+pub(crate) fn describe_bkgd(data: &[u8], ihdr: &Ihdr) -> Result<String, String> {
+    match ihdr.color_type {
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+            expect_len("bKGD", data, 2, ihdr)?;
+            Ok(format!("gray({})", samples(data)[0]))
+        }
+        ColorType::Rgb | ColorType::Rgba => {
+            expect_len("bKGD", data, 6, ihdr)?;
+            let rgb = samples(data);
+            Ok(format!("rgb({},{},{})", rgb[0], rgb[1], rgb[2]))
+        }
+        ColorType::Indexed => {
+            expect_len("bKGD", data, 1, ihdr)?;
+            Ok(format!("palette index {}", data[0]))
+        }
+    }
+}
+
+pub(crate) fn describe_sbit(data: &[u8], ihdr: &Ihdr) -> Result<String, String> {
+    let (channels, expected) = match ihdr.color_type {
+        ColorType::Grayscale => (["gray"].as_slice(), 1),
+        ColorType::Rgb | ColorType::Indexed => (["r", "g", "b"].as_slice(), 3),
+        ColorType::GrayscaleAlpha => (["gray", "alpha"].as_slice(), 2),
+        ColorType::Rgba => (["r", "g", "b", "alpha"].as_slice(), 4),
+    };
+    expect_len("sBIT", data, expected, ihdr)?;
+
+    let bits: Vec<String> = channels.iter().zip(data).map(|(channel, bits)| format!("{channel} {bits}")).collect();
+    Ok(format!("significant bits: {}", bits.join(", ")))
+}
+
+/// Human-readable summary of `chunk` for the image `png`, if pngme knows its layout.
+///
+/// Returns `None` for other chunk types and when the image header can't be read, and an error
+/// when the data doesn't fit the color type.
+pub(crate) fn describe(png: &Png, chunk: &Chunk) -> Option<Result<String, String>> {
+    let ihdr = Ihdr::from_png(png)?;
+    let data = chunk.data();
+
+    match chunk.chunk_type().to_string().as_str() {
+        "tRNS" => {
+            let palette_entries = png.chunk_by_type("PLTE").map(|plte| plte.data().len() / 3);
+            Some(describe_trns(data, &ihdr, palette_entries))
+        }
+        "bKGD" => Some(describe_bkgd(data, &ihdr)),
+        "sBIT" => Some(describe_sbit(data, &ihdr)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr(color_type: ColorType) -> Ihdr {
+        Ihdr { width: 1, height: 1, bit_depth: 8, color_type, interlaced: false }
+    }
+
+    #[test]
+    fn test_trns_grayscale() {
+        assert_eq!(describe_trns(&[0, 255], &ihdr(ColorType::Grayscale), None).unwrap(), "transparent gray(255)");
+        assert!(describe_trns(&[0, 255, 0], &ihdr(ColorType::Grayscale), None).is_err());
+    }
+
+    #[test]
+    fn test_trns_rgb() {
+        let data = [0, 255, 0, 0, 1, 0];
+
+        assert_eq!(describe_trns(&data, &ihdr(ColorType::Rgb), None).unwrap(), "transparent rgb(255,0,256)");
+        assert!(describe_trns(&data[..4], &ihdr(ColorType::Rgb), None).is_err());
+    }
+
+    #[test]
+    fn test_trns_indexed() {
+        let data = [0; 14];
+
+        assert_eq!(describe_trns(&data, &ihdr(ColorType::Indexed), Some(16)).unwrap(), "14 palette entries with alpha");
+        assert_eq!(describe_trns(&data, &ihdr(ColorType::Indexed), None).unwrap(), "14 palette entries with alpha");
+        assert!(describe_trns(&data, &ihdr(ColorType::Indexed), Some(8)).is_err());
+    }
+
+    #[test]
+    fn test_trns_with_alpha_channel() {
+        assert!(describe_trns(&[0, 0], &ihdr(ColorType::GrayscaleAlpha), None).is_err());
+        assert!(describe_trns(&[0; 6], &ihdr(ColorType::Rgba), None).is_err());
+    }
+
+    #[test]
+    fn test_bkgd() {
+        assert_eq!(describe_bkgd(&[0, 128], &ihdr(ColorType::Grayscale)).unwrap(), "gray(128)");
+        assert_eq!(describe_bkgd(&[0, 128], &ihdr(ColorType::GrayscaleAlpha)).unwrap(), "gray(128)");
+        assert_eq!(describe_bkgd(&[0, 255, 0, 255, 0, 255], &ihdr(ColorType::Rgb)).unwrap(), "rgb(255,255,255)");
+        assert_eq!(describe_bkgd(&[0, 255, 0, 255, 0, 255], &ihdr(ColorType::Rgba)).unwrap(), "rgb(255,255,255)");
+        assert_eq!(describe_bkgd(&[3], &ihdr(ColorType::Indexed)).unwrap(), "palette index 3");
+        assert!(describe_bkgd(&[0, 3], &ihdr(ColorType::Indexed)).is_err());
+        assert!(describe_bkgd(&[0, 3], &ihdr(ColorType::Rgb)).is_err());
+    }
+
+    #[test]
+    fn test_sbit() {
+        assert_eq!(describe_sbit(&[5], &ihdr(ColorType::Grayscale)).unwrap(), "significant bits: gray 5");
+        assert_eq!(describe_sbit(&[5, 6, 5], &ihdr(ColorType::Rgb)).unwrap(), "significant bits: r 5, g 6, b 5");
+        assert_eq!(describe_sbit(&[5, 6, 5], &ihdr(ColorType::Indexed)).unwrap(), "significant bits: r 5, g 6, b 5");
+        assert_eq!(describe_sbit(&[5, 8], &ihdr(ColorType::GrayscaleAlpha)).unwrap(), "significant bits: gray 5, alpha 8");
+        assert_eq!(describe_sbit(&[5, 6, 5, 8], &ihdr(ColorType::Rgba)).unwrap(), "significant bits: r 5, g 6, b 5, alpha 8");
+        assert!(describe_sbit(&[5, 6, 5], &ihdr(ColorType::Rgba)).is_err());
+    }
+}
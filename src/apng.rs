@@ -0,0 +1,293 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+
+pub(crate) const ACTL_CHUNK_TYPE: &str = "acTL";
+pub(crate) const FCTL_CHUNK_TYPE: &str = "fcTL";
+pub(crate) const FDAT_CHUNK_TYPE: &str = "fdAT";
+
+/// The animation control chunk: how many frames there are and how often they play.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct AnimationControl {
+    pub(crate) num_frames: u32,
+    /// 0 means forever.
+    pub(crate) num_plays: u32,
+}
+
+impl AnimationControl {
+    pub(crate) const LEN: usize = 8;
+}
+
+impl TryFrom<&[u8]> for AnimationControl {
+    type Error = String;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let data: [u8; Self::LEN] =
+            data.try_into().map_err(|_| format!("acTL data is {} bytes long, it should be {}", data.len(), Self::LEN))?;
+        Ok(AnimationControl {
+            num_frames: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            num_plays: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        })
+    }
+}
+
+/// A frame control chunk: the region a frame covers, how long it shows and how it is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct FrameControl {
+    pub(crate) sequence_number: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) x_offset: u32,
+    pub(crate) y_offset: u32,
+    pub(crate) delay_num: u16,
+    /// 0 means hundredths of a second.
+    pub(crate) delay_den: u16,
+    pub(crate) dispose_op: u8,
+    pub(crate) blend_op: u8,
+}
+
+impl FrameControl {
+    pub(crate) const LEN: usize = 26;
+
+    /// The frame delay in seconds.
+    pub(crate) fn delay(&self) -> f64 {
+        let den = if self.delay_den == 0 { 100 } else { self.delay_den };
+        f64::from(self.delay_num) / f64::from(den)
+    }
+}
+
+impl TryFrom<&[u8]> for FrameControl {
+    type Error = String;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let data: [u8; Self::LEN] =
+            data.try_into().map_err(|_| format!("fcTL data is {} bytes long, it should be {}", data.len(), Self::LEN))?;
+        let u32_at = |pos: usize| u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+        Ok(FrameControl {
+            sequence_number: u32_at(0),
+            width: u32_at(4),
+            height: u32_at(8),
+            x_offset: u32_at(12),
+            y_offset: u32_at(16),
+            delay_num: u16::from_be_bytes([data[20], data[21]]),
+            delay_den: u16::from_be_bytes([data[22], data[23]]),
+            dispose_op: data[24],
+            blend_op: data[25],
+        })
+    }
+}
+
+fn dispose_name(dispose_op: u8) -> String {
+    match dispose_op {
+        0 => "none".to_string(),
+        1 => "background".to_string(),
+        2 => "previous".to_string(),
+        op => format!("unknown ({op})"),
+    }
+}
+
+fn blend_name(blend_op: u8) -> String {
+    match blend_op {
+        0 => "source".to_string(),
+        1 => "over".to_string(),
+        op => format!("unknown ({op})"),
+    }
+}
+
+/// One frame of an animated png, as `pngme frames` lists it.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Frame {
+    pub(crate) sequence_number: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) x_offset: u32,
+    pub(crate) y_offset: u32,
+    /// In seconds.
+    pub(crate) delay: f64,
+    pub(crate) dispose: String,
+    pub(crate) blend: String,
+    /// Whether the frame is the default image, stored in the IDAT chunks.
+    pub(crate) uses_idat: bool,
+    pub(crate) fdat_sequence_numbers: Vec<u32>,
+}
+
+impl Frame {
+    fn new(control: &FrameControl) -> Frame {
+        Frame {
+            sequence_number: control.sequence_number,
+            width: control.width,
+            height: control.height,
+            x_offset: control.x_offset,
+            y_offset: control.y_offset,
+            delay: control.delay(),
+            dispose: dispose_name(control.dispose_op),
+            blend: blend_name(control.blend_op),
+            uses_idat: false,
+            fdat_sequence_numbers: Vec::new(),
+        }
+    }
+}
+
+/// `64x64 at (0, 0), delay 0.1s, dispose none, blend over, sequence 1, fdAT 2, 3`
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} at ({}, {}), delay {}s, dispose {}, blend {}, sequence {}",
+            self.width, self.height, self.x_offset, self.y_offset, self.delay, self.dispose, self.blend, self.sequence_number
+        )?;
+        if self.uses_idat {
+            write!(f, ", IDAT")?;
+        }
+        if !self.fdat_sequence_numbers.is_empty() {
+            let numbers: Vec<String> = self.fdat_sequence_numbers.iter().map(ToString::to_string).collect();
+            write!(f, ", fdAT {}", numbers.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The frames of an animated png, with whatever breaks the APNG rules.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Animation {
+    /// Frame count declared by acTL, which should match `frames`.
+    pub(crate) num_frames: u32,
+    pub(crate) num_plays: u32,
+    pub(crate) frames: Vec<Frame>,
+    pub(crate) problems: Vec<String>,
+}
+
+/// The sequence number that starts an fcTL or fdAT chunk.
+fn sequence_number(chunk: &Chunk) -> Option<u32> {
+    chunk.data().get(..4).map(|bytes| u32::from_be_bytes(bytes.try_into().expect("Slice should be of length 4")))
+}
+
+/// Checks that the fcTL and fdAT sequence numbers count up from 0 without gaps or repeats.
+pub(crate) fn sequence_problems(png: &Png) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut expected = 0;
+    for (index, chunk) in png.chunks().enumerate() {
+        let chunk_type = chunk.chunk_type().to_string();
+        if chunk_type != FCTL_CHUNK_TYPE && chunk_type != FDAT_CHUNK_TYPE {
+            continue;
+        }
+        match sequence_number(chunk) {
+            Some(number) if number == expected => {}
+            Some(number) => problems.push(format!("{chunk_type}, chunk {index}, has sequence number {number}, expected {expected}")),
+            None => problems.push(format!("{chunk_type}, chunk {index}, is too short to hold a sequence number")),
+        }
+        expected = sequence_number(chunk).map_or(expected, |number| number.wrapping_add(1));
+    }
+    problems
+}
+
+/// Reads the animation of `png`, if it has an acTL chunk.
+pub(crate) fn read(png: &Png) -> Option<Result<Animation, String>> {
+    let control = png.chunk_by_type(ACTL_CHUNK_TYPE)?;
+    Some(read_frames(png, control))
+}
+
+fn read_frames(png: &Png, control: &Chunk) -> Result<Animation, String> {
+    let control = AnimationControl::try_from(control.data())?;
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut problems = sequence_problems(png);
+
+    for (index, chunk) in png.chunks().enumerate() {
+        match chunk.chunk_type().to_string().as_str() {
+            FCTL_CHUNK_TYPE => frames.push(Frame::new(&FrameControl::try_from(chunk.data())?)),
+            FDAT_CHUNK_TYPE => match (frames.last_mut(), sequence_number(chunk)) {
+                (Some(frame), Some(number)) => frame.fdat_sequence_numbers.push(number),
+                (None, _) => problems.push(format!("fdAT, chunk {index}, comes before any fcTL")),
+                (_, None) => {}
+            },
+            "IDAT" => {
+                if let Some(frame) = frames.last_mut().filter(|frame| frame.fdat_sequence_numbers.is_empty()) {
+                    frame.uses_idat = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    if frames.len() as u64 != u64::from(control.num_frames) {
+        problems.push(format!("acTL declares {} frames, found {}", control.num_frames, frames.len()));
+    }
+
+    Ok(Animation { num_frames: control.num_frames, num_plays: control.num_plays, frames, problems })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn fctl(sequence_number: u32, delay_num: u16, delay_den: u16) -> Chunk<'static> {
+        let mut data = sequence_number.to_be_bytes().to_vec();
+        for value in [16u32, 8, 0, 0] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend_from_slice(&delay_num.to_be_bytes());
+        data.extend_from_slice(&delay_den.to_be_bytes());
+        data.extend_from_slice(&[1, 1]);
+        chunk("fcTL", data)
+    }
+
+    fn fdat(sequence_number: u32) -> Chunk<'static> {
+        chunk("fdAT", [sequence_number.to_be_bytes().as_slice(), b"data"].concat())
+    }
+
+    fn actl(num_frames: u32) -> Chunk<'static> {
+        chunk("acTL", [num_frames.to_be_bytes(), 0u32.to_be_bytes()].concat())
+    }
+
+    fn testing_apng() -> Png<'static> {
+        Png::from_chunks(vec![
+            chunk("IHDR", vec![0; 13]),
+            actl(2),
+            fctl(0, 1, 10),
+            chunk("IDAT", vec![0; 4]),
+            fctl(1, 0, 0),
+            fdat(2),
+            fdat(3),
+            chunk("IEND", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_frames() {
+        let animation = read(&testing_apng()).unwrap().unwrap();
+
+        assert_eq!((animation.num_frames, animation.num_plays), (2, 0));
+        assert!(animation.problems.is_empty());
+        assert_eq!(animation.frames[0].to_string(), "16x8 at (0, 0), delay 0.1s, dispose background, blend over, sequence 0, IDAT");
+        assert_eq!(animation.frames[1].fdat_sequence_numbers, [2, 3]);
+        assert_eq!(animation.frames[1].delay, 0.0);
+        assert!(read(&Png::from_chunks(vec![chunk("IHDR", vec![0; 13])])).is_none());
+    }
+
+    #[test]
+    fn test_sequence_problems() {
+        let mut png = testing_apng();
+        png.remove_chunk_at_unchecked(5);
+
+        let animation = read(&png).unwrap().unwrap();
+
+        assert_eq!(animation.problems, ["fdAT, chunk 5, has sequence number 3, expected 2"]);
+        png.insert_before_iend(fctl(4, 1, 1));
+        assert_eq!(read(&png).unwrap().unwrap().problems.last().unwrap(), "acTL declares 2 frames, found 3");
+    }
+
+    #[test]
+    fn test_invalid_control_chunks() {
+        assert!(AnimationControl::try_from([0; 7].as_slice()).is_err());
+        assert_eq!(FrameControl::try_from([0; 25].as_slice()).unwrap_err(), "fcTL data is 25 bytes long, it should be 26");
+        assert_eq!(FrameControl::try_from(fctl(0, 3, 0).data()).unwrap().delay(), 0.03);
+    }
+}
@@ -0,0 +1,1321 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+#[cfg(feature = "watch")]
+use clap::CommandFactory;
+use clap::Parser;
+use chrono::Utc;
+
+#[cfg(feature = "watch")]
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+use std::fs;
+use std::io::{self, IsTerminal};
+
+use crate::chunk::Chunk;
+use crate::codec::{Encryption, Hmac, Settings, Signature};
+use crate::crypto::{SigningKey, VerifyingKey};
+use crate::encode::{Mode, Position};
+use crate::commands::{Commands, DpiAction, ExifAction, IccAction, IhdrAction, TextAction, TimeAction, XmpAction};
+use crate::selector::{ChunkSelector, Occurrence};
+use crate::cli::{Cli, ErrorFormat};
+use crate::error::{ErrorReport, PngmeError};
+use crate::png::{ParseLimits, Png};
+use crate::size::format_size;
+use crate::{apng, batch, capacity, clean, codec, copy_chunk, crc_check, encode, envelope, escape, exif, explode, extract, glob, icc, ihdr, list, lock, lsb, merge, order, output, palette, pipe};
+use crate::{passphrase, phys, print, repair, retype, scan, size, split, stamp, stream, summary, text, text_check, time, timeout, validate, verify, walk, xmp};
+#[cfg(feature = "watch")]
+use crate::watch;
+use crate::Result;
+
+/// Reads `file`, or stdin when `file` is `-`, giving up after `timeout`.
+fn read_file(file: &Path, timeout: Option<Duration>) -> Result<Vec<u8>> {
+    let path = file.to_path_buf();
+    let contents = timeout::with_timeout(timeout, move || output::read_input(&path))?
+        .map_err(|source| PngmeError::Io { path: Some(file.to_path_buf()), source })?;
+    Ok(contents)
+}
+
+/// Parses `contents`, read from `file`, into a png whose chunks borrow their data from it.
+fn parse_file<'a>(file: &Path, contents: &'a [u8]) -> Result<Png<'a>> {
+    let png = Png::parse_untrusted(contents, ParseLimits::default()).map_err(|error| match error {
+        PngmeError::InvalidPng { reason, .. } => PngmeError::InvalidPng { path: Some(file.to_path_buf()), reason },
+        error => error,
+    })?;
+    if png.is_cgbi() {
+        eprintln!("warning: {} is an Apple CgBI png, its chunks can be read but its pixel data is not standard", file.display());
+    }
+    Ok(png)
+}
+
+/// Reads and parses `file`, copying its chunk data so the png can be edited and kept.
+///
+/// Commands that only read a file use `read_file` and `parse_file` instead, which don't copy.
+fn load_file(file: &Path, timeout: Option<Duration>) -> Result<Png<'static>> {
+    Ok(parse_file(file, &read_file(file, timeout)?)?.into_owned())
+}
+
+fn chunk_not_found(chunk_type: &str) -> PngmeError {
+    PngmeError::ChunkNotFound { chunk_type: chunk_type.to_string() }
+}
+
+/// Locks `file` for an in-place edit, unless `--no-lock` was given.
+///
+/// The lock is held until the returned guard is dropped, which must be after the write.
+fn lock_file(file: &Path, cli: &Cli) -> Result<Option<lock::FileLock>> {
+    if cli.no_lock || output::is_stdio(file) {
+        return Ok(None);
+    }
+    lock::lock(file, cli.lock_timeout()).map(Some)
+}
+
+/// `stats --json` output. Summaries are only serializable with the `serde` feature.
+#[cfg(feature = "serde")]
+fn summary_json(summary: &summary::PngSummary) -> Result<String> {
+    Ok(serde_json::to_string_pretty(summary)?)
+}
+
+#[cfg(not(feature = "serde"))]
+fn summary_json(_summary: &summary::PngSummary) -> Result<String> {
+    Err("stats --json needs pngme built with the serde feature".into())
+}
+
+/// Validates one file, prefixing each reported line with its path when `labelled`.
+fn check_file(file: &Path, fix_duplicates: bool, cli: &Cli, labelled: bool) -> Result<()> {
+    let label = if labelled { format!("{}: ", file.display()) } else { String::new() };
+    let _lock = if fix_duplicates { lock_file(file, cli)? } else { None };
+    let mut png = load_file(file, cli.timeout())?;
+
+    if fix_duplicates {
+        let removed = validate::fix_duplicates(&mut png);
+        output::save_png(file, &png, cli.write_options())?;
+        if removed > 0 && !cli.check_only {
+            println!("{label}Removed {removed} duplicate chunk(s)");
+        }
+    }
+
+    let issues = validate::validate(&png);
+    if issues.is_empty() {
+        println!("{label}No issues found");
+    }
+    for issue in &issues {
+        let level = if issue.is_warning() { "warning" } else { "error" };
+        println!("{label}{level}: {issue}");
+    }
+
+    let errors = issues.iter().filter(|issue| !issue.is_warning()).count();
+    if errors > 0 {
+        return Err(format!("{errors} issue(s) found").into());
+    }
+
+    Ok(())
+}
+
+/// The `encode` options that apply to every file.
+#[derive(Clone, Copy)]
+struct EncodeOptions {
+    position: Position,
+    max_output_size: Option<u64>,
+    allow_no_image: bool,
+}
+
+/// Loads `file` and inserts `chunks`, checking first that it has an image and that the size
+/// budget holds.
+fn prepare_encode(file: &Path, chunks: &[Chunk<'static>], options: EncodeOptions, timeout: Option<Duration>) -> Result<Png<'static>> {
+    let mut png = load_file(file, timeout)?;
+
+    if !options.allow_no_image {
+        encode::check_has_image(&png)?;
+    }
+    if let Some(limit) = options.max_output_size {
+        encode::check_size_budget(&png, chunks, limit)?;
+    }
+
+    // Problem messages name chunk indexes, which the insertion shifts, so only counts compare.
+    let sequence_problems = apng::sequence_problems(&png).len();
+    options.position.insert(&mut png, chunks)?;
+    if png.is_apng() {
+        let added = apng::sequence_problems(&png).len().saturating_sub(sequence_problems);
+        if added > 0 {
+            eprintln!("warning: {}: encoding breaks the APNG frame sequence numbers ({added} new problem(s), see `pngme frames`)", file.display());
+        }
+    }
+    Ok(png)
+}
+
+/// The last line of a `--dry-run` report.
+fn resulting_size(len: u64, cli: &Cli) -> String {
+    format!("Resulting file size: {}", format_size(len, cli.size_style()))
+}
+
+fn encode_file(file: &Path, output: Option<&Path>, chunks: &[Chunk<'static>], options: EncodeOptions, dry_run: bool, cli: &Cli) -> Result<()> {
+    let EncodeOptions { max_output_size, allow_no_image, .. } = options;
+    if dry_run {
+        let png = load_file(file, cli.timeout())?;
+        let index = options.position.index(&png)?;
+        for (offset, chunk) in chunks.iter().enumerate() {
+            println!("{}: {} ({})", index + offset, chunk.chunk_type(), format_size(chunk.data().len() as u64, cli.size_style()));
+        }
+        println!("Would add {} chunk(s)", chunks.len());
+        println!("{}", resulting_size(encode::projected_len(&png, chunks), cli));
+        if !allow_no_image {
+            if let Err(error) = encode::check_has_image(&png) {
+                println!("Warning: {error}");
+            }
+        }
+        if let Some(limit) = max_output_size {
+            if let Err(error) = encode::check_size_budget(&png, chunks, limit) {
+                println!("Warning: {error}");
+            }
+        }
+        return Ok(());
+    }
+
+    let in_place = output.is_none_or(|output| output::same_file(file, output));
+    let _lock = if in_place { lock_file(file, cli)? } else { None };
+    let png = prepare_encode(file, chunks, options, cli.timeout())?;
+    match output {
+        Some(output) => output::write_output(output, &png, cli.write_options()),
+        None => output::save_png(file, &png, cli.write_options()),
+    }
+}
+
+/// Makes Ctrl-C, or a termination signal, stop `watch` once the update in progress is done,
+/// so it exits normally instead of being killed mid-write.
+#[cfg(feature = "watch")]
+fn stop_on_ctrl_c(stopper: watch::Stopper) -> Result<()> {
+    ctrlc::set_handler(move || stopper.stop()).map_err(|error| format!("Can't handle Ctrl-C: {error}"))?;
+    Ok(())
+}
+
+/// Encodes the content of `input_file` into `file`, replacing the chunk written last time.
+#[cfg(feature = "watch")]
+fn refresh_chunk(file: &Path, chunk_type: &str, input_file: &Path, cli: &Cli) -> Result<usize> {
+    let content = fs::read(input_file)?;
+    let chunk = encode::build_chunk(chunk_type, &content, false, 0, Settings::default(), false)?;
+    let _lock = lock_file(file, cli)?;
+    let mut png = load_file(file, cli.timeout())?;
+
+    png.replace_or_insert_chunk(chunk);
+    output::save_png(file, &png, cli.write_options())?;
+    Ok(content.len())
+}
+
+/// The one-line image description shown before decode and print output.
+///
+/// An unreadable IHDR only costs the image part of the line, with a warning.
+fn image_header(file: &Path, png: &Png) -> String {
+    if let Err(reason) = ihdr::Ihdr::read(png) {
+        eprintln!("warning: can't read the image header: {reason}");
+    }
+    print::header_line(file, png)
+}
+
+/// Tells the user that only chunk headers of `file` were read.
+fn large_file_note(file: &Path) {
+    eprintln!(
+        "note: {} is larger than {}, only its chunk headers were read: no previews, no validation",
+        file.display(),
+        format_size(stream::STREAMING_THRESHOLD, size::SizeStyle::Human)
+    );
+}
+
+/// The command line of a `watch --on-change` operation, split into `words`, run on `file`.
+///
+/// `{}` stands for the file; without it the file follows the command name, and the action of
+/// commands that have one, like `text set`.
+#[cfg(feature = "watch")]
+fn operation_cli(words: &[String], file: &Path) -> Result<Cli> {
+    let Some(name) = words.first() else {
+        return Err("The --on-change operation is empty".into());
+    };
+    let mut args = vec![OsString::from("pngme")];
+    if words.iter().any(|word| word == "{}") {
+        args.extend(words.iter().map(|word| if word == "{}" { file.as_os_str().to_owned() } else { OsString::from(word) }));
+    } else {
+        let has_action = Cli::command().find_subcommand(name).is_some_and(|command| command.has_subcommands());
+        let split = if has_action { 2 } else { 1 }.min(words.len());
+        args.extend(words[..split].iter().map(OsString::from));
+        args.push(file.as_os_str().to_owned());
+        args.extend(words[split..].iter().map(OsString::from));
+    }
+
+    let cli = Cli::try_parse_from(args).map_err(|error| {
+        // The message without the usage that follows it.
+        let error = error.to_string();
+        let message: Vec<&str> = error.lines().take_while(|line| !line.is_empty()).map(str::trim).collect();
+        format!("Invalid --on-change operation: {}", message.join(" ").trim_start_matches("error: "))
+    })?;
+    if matches!(cli.command, Commands::Watch { .. }) {
+        return Err("The --on-change operation can't be another watch".into());
+    }
+    Ok(cli)
+}
+
+/// Scans every png under `dir` and prints the findings of each, then a summary of them all.
+fn scan_tree(dir: &Path, options: &walk::WalkOptions, pngme: bool, json: bool, cli: &Cli) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory, scan --recursive needs one", dir.display()).into());
+    }
+    let walk = walk::walk(dir, options);
+    let mut report = scan::TreeReport::default();
+    for (path, error) in walk.unreadable {
+        report.errors.push(scan::ScanError { error: format!("{}: {error}", path.display()), path: path.into() });
+    }
+    for path in walk.pngs {
+        let scanned = read_file(&path, cli.timeout()).and_then(|contents| Ok(scan::scan(&parse_file(&path, &contents)?)));
+        match scanned {
+            Ok(file_report) => report.files.push(scan::FileScan { path: path.into(), report: file_report }),
+            Err(error) => {
+                let error = PngmeError::from_boxed(error);
+                let error = match error.path() {
+                    Some(_) => error.to_string(),
+                    None => format!("{}: {error}", path.display()),
+                };
+                report.errors.push(scan::ScanError { path: path.into(), error });
+            }
+        }
+    }
+
+    if cli.json(json) {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for file in &report.files {
+            let payloads = if pngme { file.report.payloads.as_slice() } else { &[] };
+            if file.report.findings.is_empty() && payloads.is_empty() {
+                continue;
+            }
+            println!("{}:", file.path.as_path().display());
+            for finding in &file.report.findings {
+                println!("  warning: {}", finding.line(cli.size_style()));
+            }
+            for payload in payloads {
+                println!("  {}", payload.line());
+            }
+        }
+        for error in &report.errors {
+            eprintln!("Error: {}", error.error);
+        }
+        println!("{}", report.summary_line());
+    }
+    if !report.errors.is_empty() {
+        return Err(format!("{} file(s) couldn't be scanned", report.errors.len()).into());
+    }
+    Ok(())
+}
+
+/// Where batch progress events go, if `--progress` asked for them.
+fn progress_writer<'a>(cli: &Cli, stderr: &'a mut io::Stderr) -> Option<&'a mut dyn io::Write> {
+    cli.progress.map(|_| stderr as &mut dyn io::Write)
+}
+
+/// Runs the command once per file matching the FILE `pattern`, each file's output under a
+/// header, then prints the status of every file.
+fn run_pattern(cli: &Cli, pattern: &Path) -> Result<()> {
+    #[cfg(feature = "watch")]
+    if matches!(cli.command, Commands::Watch { .. }) {
+        return Err("watch takes a single file, not a pattern".into());
+    }
+    let files = glob::expand(pattern)?;
+    let output = match &cli.command {
+        Commands::Decode { output, .. } => output.as_ref(),
+        Commands::Cat { output, .. } | Commands::Assemble { output, .. } | Commands::Merge { output, .. } => Some(output),
+        Commands::Icc { action: IccAction::Extract(extract) } => Some(&extract.output),
+        _ => None,
+    };
+    if files.len() > 1 && output.is_some_and(|output| !output::is_stdio(output)) {
+        return Err(format!("{} matches {} files, they can't all be written to one --output", pattern.display(), files.len()).into());
+    }
+
+    let mut statuses = Vec::new();
+    let summary = batch::run_batch(&files, &[], progress_writer(cli, &mut io::stderr()), |file| {
+        println!("==> {} <==", file.display());
+        let mut cli = cli.clone();
+        if let Some(path) = cli.command.file_mut() {
+            *path = file.to_path_buf();
+        }
+        let result = run(&cli);
+        statuses.push((file.to_path_buf(), result.is_ok()));
+        result
+    });
+    for (file, ok) in statuses {
+        println!("{:<8}{}", if ok { "ok" } else { "failed" }, file.display());
+    }
+    println!("{} file(s): {} ok, {} failed", summary.total(), summary.ok, summary.failed);
+    if summary.failed > 0 {
+        return Err(format!("{} file(s) failed", summary.failed).into());
+    }
+    Ok(())
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    // check and encode expand patterns among their own list of files.
+    if !matches!(cli.command, Commands::Check { .. } | Commands::Encode { .. }) {
+        if let Some(pattern) = cli.command.file().filter(|file| glob::is_pattern(file)) {
+            return run_pattern(cli, pattern);
+        }
+    }
+    match &cli.command {
+        Commands::Encode {
+            file, chunk_type, content, escaped, input_file, base64, raw_payload, split_size, strict_text, checksum, compress, encrypt, hmac, sign, force, allow_no_image, position, max_output_size, dry_run, files_from, all_or_nothing, pipe_through, output, mode,
+        } => {
+            let issues = match content {
+                Some(content) => text_check::check_text(content, chunk_type),
+                None => Vec::new(),
+            };
+            if *strict_text && !issues.is_empty() {
+                let issues: Vec<String> = issues.iter().map(ToString::to_string).collect();
+                return Err(format!("Content rejected by --strict-text: {}", issues.join("; ")).into());
+            }
+            for issue in &issues {
+                eprintln!("warning: {issue}");
+            }
+
+            let content = match (content, escaped, input_file) {
+                (Some(content), _, _) => content.as_bytes().to_vec(),
+                (None, Some(escaped), _) => escape::parse_escaped(escaped)?,
+                (None, None, Some(input_file)) => {
+                    let input = input_file.clone();
+                    timeout::with_timeout(cli.timeout(), move || output::read_input(&input))?
+                        .map_err(|source| PngmeError::Io { path: Some(input_file.clone()), source })?
+                }
+                (None, None, None) => unreachable!("clap requires content, --escaped or --input-file"),
+            };
+            let content = if *base64 {
+                // Skipping whitespace accepts text wrapped by `base64` and the like.
+                let digits: String = String::from_utf8_lossy(&content).split_ascii_whitespace().collect();
+                BASE64.decode(digits).map_err(|error| format!("Content isn't valid base64: {error}"))?
+            } else {
+                content
+            };
+            let content = match pipe_through {
+                Some(command) => pipe::pipe_through(command, &content)?,
+                None => content,
+            };
+            let mut flags = if *checksum { envelope::FLAG_CHECKSUM } else { 0 };
+            if compress.is_some() {
+                flags |= envelope::FLAG_COMPRESSED;
+            }
+            if *encrypt {
+                flags |= envelope::FLAG_ENCRYPTED;
+            }
+            if hmac.is_some() {
+                flags |= envelope::FLAG_HMAC;
+            }
+            if sign.is_some() {
+                flags |= envelope::FLAG_SIGNED;
+            }
+            let passphrase = if *encrypt { Some(passphrase::read(true)?) } else { None };
+            let key = sign.as_deref().map(SigningKey::load).transpose()?;
+            let settings = Settings {
+                compression: compress.unwrap_or_default(),
+                encryption: Encryption(passphrase.as_deref()),
+                hmac: Hmac(hmac.as_deref()),
+                signature: Signature { key: key.as_ref(), public_key: None },
+            };
+            if *mode == Mode::Lsb {
+                let chunk = encode::build_chunk(chunk_type, &content, *raw_payload, flags, settings, *force)?;
+                let in_place = output.as_deref().is_none_or(|output| output::same_file(file, output));
+                let _lock = if in_place { lock_file(file, cli)? } else { None };
+                let mut png = load_file(file, cli.timeout())?;
+                lsb::write(&mut png, &chunk)?;
+                return match output {
+                    Some(output) => output::write_output(output, &png, cli.write_options()),
+                    None => output::save_png(file, &png, cli.write_options()),
+                };
+            }
+            let chunks = if *raw_payload {
+                vec![encode::build_chunk(chunk_type, &content, true, flags, settings, *force)?]
+            } else {
+                encode::build_split_chunks(chunk_type, &content, flags, settings, *force, split_size.unwrap_or(Chunk::MAX_DATA_LEN))?
+            };
+            let (files, missing) = batch::collect_files(std::slice::from_ref(file), files_from.as_deref())?;
+            let options = EncodeOptions { position: *position, max_output_size: *max_output_size, allow_no_image: *allow_no_image };
+
+            if *all_or_nothing && !*dry_run {
+                if let Some(entry) = missing.first() {
+                    return Err(format!("{entry}, nothing was written").into());
+                }
+                let mut locks = Vec::new();
+                let prepared = files
+                    .into_iter()
+                    .map(|file| {
+                        locks.push(lock_file(&file, cli)?);
+                        let png = prepare_encode(&file, &chunks, options, cli.timeout())?;
+                        Ok((file, png))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                return output::save_all(&prepared, cli.write_options());
+            }
+
+            if files.len() == 1 && missing.is_empty() {
+                return encode_file(&files[0], output.as_deref(), &chunks, options, *dry_run, cli);
+            }
+            if output.is_some() {
+                return Err(format!("{} matches {} files, they can't all be written to one --output", file.display(), files.len()).into());
+            }
+
+            let summary = batch::run_batch(&files, &missing, progress_writer(cli, &mut io::stderr()), |file| encode_file(file, None, &chunks, options, *dry_run, cli));
+            println!("{} file(s) encoded: {} ok, {} failed", summary.total(), summary.ok, summary.failed);
+            if summary.failed > 0 {
+                return Err(format!("{} file(s) failed", summary.failed).into());
+            }
+        }
+        Commands::Decode { file, chunk_type, nth, all, index, decrypt, hmac, verify_key, pipe_through, output, base64, mode } => {
+            let occurrence = if *all { Some(Occurrence::All) } else { index.map(Occurrence::Nth) };
+            let chunk_type = chunk_type.as_ref().map(|selector| selector.with_occurrence(occurrence)).transpose()?;
+            let png = load_file(file, cli.timeout())?;
+            // stdout only carries the payload, so it can be redirected as is. JSON output has
+            // the header fields instead.
+            if !cli.quiet && !cli.json(false) {
+                eprintln!("{}", image_header(file, &png));
+            }
+
+            let hidden;
+            let chunks: Vec<&Chunk> = match (nth, &chunk_type) {
+                (None, Some(selector)) if *mode == Mode::Lsb => {
+                    hidden = lsb::read(&png)?;
+                    if hidden.chunk_type().to_string() != selector.chunk_type {
+                        return Err(chunk_not_found(&selector.chunk_type).into());
+                    }
+                    vec![&hidden]
+                }
+                (Some(index), _) => vec![png.nth_chunk(*index)?],
+                (None, Some(selector)) => {
+                    let matches = selector.matches(&png);
+                    if matches.is_empty() {
+                        return Err(chunk_not_found(&selector.to_string()).into());
+                    }
+                    matches.into_iter().map(|(_, index)| &png[index]).collect()
+                }
+                (None, None) => unreachable!("clap requires a chunk type or --nth"),
+            };
+            let payloads = split::payloads(&png, &chunks)?;
+            if output.is_some() && payloads.len() > 1 {
+                return Err(format!("--output takes a single payload, but {} were selected", payloads.len()).into());
+            }
+            let passphrase = if *decrypt { Some(passphrase::read(false)?) } else { None };
+            let public_key = verify_key.as_deref().map(VerifyingKey::load).transpose()?;
+            let settings = Settings {
+                encryption: Encryption(passphrase.as_deref()),
+                hmac: Hmac(hmac.as_deref()),
+                signature: Signature { key: None, public_key: public_key.as_ref() },
+                ..Settings::default()
+            };
+            let mut entries = Vec::new();
+            for (chunk, data) in payloads {
+                let mut payload = codec::open_with(&data, settings)?;
+                if let Some(command) = pipe_through {
+                    payload = pipe::pipe_through(command, &payload)?;
+                }
+                match output {
+                    Some(path) => {
+                        let data = if *base64 { BASE64.encode(&payload).into_bytes() } else { payload };
+                        output::write_raw(path, &data).map_err(|source| PngmeError::Io { path: Some(path.clone()), source })?;
+                    }
+                    // JSON output carries payloads in base64 already.
+                    None if cli.json(false) => entries.push(print::PayloadEntry::new(chunk, &payload)),
+                    None if *base64 => println!("{}", BASE64.encode(&payload)),
+                    None => println!("{}", String::from_utf8_lossy(&payload)),
+                }
+            }
+            if output.is_none() && cli.json(false) {
+                println!("{}", serde_json::to_string_pretty(&print::decoded(&png, entries))?);
+            }
+        }
+        Commands::Remove { file, chunk_type, all, matching, dry_run, .. } => {
+            let chunk_type = chunk_type.as_ref().map(|selector| selector.with_occurrence(all.then_some(Occurrence::All))).transpose()?;
+            let _lock = if *dry_run { None } else { lock_file(file, cli)? };
+            let mut png = load_file(file, cli.timeout())?;
+
+            let selected: Vec<usize> = match (&chunk_type, matching) {
+                (_, Some(filter)) => png
+                    .chunks()
+                    .enumerate()
+                    .filter(|&(index, chunk)| filter.matches(chunk, index))
+                    .map(|(index, _)| index)
+                    .collect(),
+                (Some(selector), None) => {
+                    let matches = selector.matches(&png);
+                    if matches.is_empty() {
+                        return Err(chunk_not_found(&selector.to_string()).into());
+                    }
+                    matches.into_iter().map(|(_, index)| index).collect()
+                }
+                (None, None) => unreachable!("clap requires a chunk type or --matching"),
+            };
+
+            if *dry_run {
+                for &index in &selected {
+                    let chunk = &png[index];
+                    println!("{index}: {} ({})", chunk.chunk_type(), format_size(chunk.data().len() as u64, cli.size_style()));
+                }
+                println!("Would remove {} chunk(s)", selected.len());
+                let removed: u64 = selected.iter().map(|&index| png[index].serialized_len()).sum();
+                println!("{}", resulting_size(png.serialized_len() - removed, cli));
+                return Ok(());
+            }
+            png.remove_chunks_where(|_, index| selected.contains(&index));
+            output::save_png(file, &png, cli.write_options())?;
+            // With `-` stdout carries the png itself.
+            if png.is_modified() && !cli.check_only && !output::is_stdio(file) {
+                println!("Removed {} chunk(s)", selected.len());
+            }
+        }
+        Commands::Retype { file, old_type, new_type, all, force } => {
+            let selector = old_type.with_occurrence(all.then_some(Occurrence::All))?;
+            let new_type = encode::checked_chunk_type(new_type, *force)?;
+            let _lock = lock_file(file, cli)?;
+            let mut png = load_file(file, cli.timeout())?;
+            let retyped = retype::retype(&mut png, &selector, &new_type)?;
+            output::save_png(file, &png, cli.write_options())?;
+            if png.is_modified() && !cli.check_only && !output::is_stdio(file) {
+                println!("Retyped {retyped} chunk(s) to {new_type}");
+            }
+        }
+        Commands::CopyChunk { source, file, chunk_type, all, force } => {
+            if output::is_stdio(source) && output::is_stdio(file) {
+                return Err("The source and destination can't both be read from stdin".into());
+            }
+            let selector = chunk_type.with_occurrence(all.then_some(Occurrence::All))?;
+            let contents = read_file(source, cli.timeout())?;
+            let source = parse_file(source, &contents)?;
+            let _lock = lock_file(file, cli)?;
+            let mut png = load_file(file, cli.timeout())?;
+            let copied = copy_chunk::copy_chunks(&source, &mut png, &selector, *force)?;
+            output::save_png(file, &png, cli.write_options())?;
+            if png.is_modified() && !cli.check_only && !output::is_stdio(file) {
+                println!("Copied {copied} chunk(s)");
+            }
+        }
+        Commands::Extract { file, chunk_type, nth, all, dir, force, json } => {
+            let png = load_file(file, cli.timeout())?;
+            let extracted = match (nth, chunk_type) {
+                (Some(index), _) => extract::extract_nth(&png, *index, dir, *force)?,
+                (None, Some(selector)) if *all => {
+                    let selector = ChunkSelector { occurrence: Occurrence::All, ..selector.clone() };
+                    extract::extract_chunks(&png, &selector, dir, *force)?
+                }
+                (None, Some(selector)) => extract::extract_chunks(&png, selector, dir, *force)?,
+                (None, None) => unreachable!("clap requires a chunk type or --nth"),
+            };
+
+            if cli.json(*json) {
+                println!("{}", serde_json::to_string_pretty(&extracted)?);
+            } else {
+                for extracted_file in &extracted {
+                    println!("{} ({})", extracted_file.path.as_path().display(), format_size(extracted_file.size as u64, cli.size_style()));
+                }
+            }
+        }
+        Commands::Clean { file, dry_run, include_raw, .. } => {
+            let _lock = if *dry_run { None } else { lock_file(file, cli)? };
+            let mut png = load_file(file, cli.timeout())?;
+            let removed = clean::clean_chunks(&mut png, include_raw);
+
+            if cli.check_only {
+                return output::save_png(file, &png, cli.write_options());
+            }
+
+            for chunk in &removed {
+                let mut notes = Vec::new();
+                if chunk.compressed {
+                    notes.push("compressed");
+                }
+                if chunk.encrypted {
+                    notes.push("encrypted");
+                }
+                let notes = if notes.is_empty() { String::new() } else { format!(", {}", notes.join(", ")) };
+                println!("{} ({}{notes})", chunk.chunk_type, format_size(chunk.size as u64, cli.size_style()));
+            }
+
+            if removed.is_empty() {
+                println!("No pngme chunks found");
+            }
+            if *dry_run {
+                println!("Would remove {} chunk(s)", removed.len());
+                println!("{}", resulting_size(png.serialized_len(), cli));
+            } else {
+                output::save_png(file, &png, cli.write_options())?;
+                if png.is_modified() {
+                    println!("Removed {} chunk(s)", removed.len());
+                }
+            }
+        }
+        Commands::Stamp { file, pairs, deterministic, show, json, .. } => {
+            let _lock = if *show { None } else { lock_file(file, cli)? };
+            let mut png = load_file(file, cli.timeout())?;
+
+            if *show {
+                let stamp = stamp::read_stamp(&png)?.ok_or("File has no stamp")?;
+                if cli.json(*json) {
+                    println!("{}", serde_json::to_string_pretty(&stamp.to_json())?);
+                } else {
+                    print!("{stamp}");
+                }
+            } else {
+                stamp::apply_stamp(&mut png, &stamp::Stamp::new(*deterministic, pairs));
+                output::save_png(file, &png, cli.write_options())?;
+            }
+        }
+        Commands::Crc { file, fail_fast } => {
+            let (file, fail_fast) = (file.clone(), *fail_fast);
+            let entries = timeout::with_timeout(cli.timeout(), move || {
+                let reader: Box<dyn io::Read> = if output::is_stdio(&file) {
+                    Box::new(io::stdin().lock())
+                } else {
+                    Box::new(fs::File::open(file).map_err(|error| error.to_string())?)
+                };
+                crc_check::read_crcs(io::BufReader::new(reader), fail_fast).map_err(|error| error.to_string())
+            })??;
+
+            for entry in &entries {
+                println!("{}", entry.row());
+            }
+
+            let mismatches = entries.iter().filter(|entry| !entry.is_match()).count();
+            if mismatches > 0 {
+                return Err(format!("{mismatches} chunk(s) with a CRC mismatch").into());
+            }
+        }
+        Commands::Palette { file, json } => {
+            let png = load_file(file, cli.timeout())?;
+            let colors = png
+                .palette()
+                .ok_or_else(|| chunk_not_found("PLTE"))?
+                .map_err(|reason| PngmeError::InvalidChunkData { chunk_type: "PLTE".to_string(), reason })?;
+            // tRNS holds one alpha per palette entry only in indexed images.
+            let indexed = ihdr::Ihdr::from_png(&png).is_some_and(|ihdr| ihdr.color_type == ihdr::ColorType::Indexed);
+            let alphas = png.chunk_by_type("tRNS").filter(|_| indexed).map(|chunk| chunk.data());
+
+            if cli.json(*json) {
+                println!("{}", serde_json::to_string_pretty(&palette::entries(&colors, alphas))?);
+                return Ok(());
+            }
+            let swatches = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+            for line in palette::lines(&colors, alphas, swatches) {
+                println!("{line}");
+            }
+        }
+        Commands::Frames { file, json } => {
+            let png = load_file(file, cli.timeout())?;
+            let animation = apng::read(&png).ok_or_else(|| chunk_not_found(apng::ACTL_CHUNK_TYPE))??;
+
+            if cli.json(*json) {
+                println!("{}", serde_json::to_string_pretty(&animation)?);
+                return Ok(());
+            }
+            let plays = match animation.num_plays {
+                0 => "plays forever".to_string(),
+                1 => "plays once".to_string(),
+                plays => format!("plays {plays} times"),
+            };
+            println!("{} frame(s), {plays}", animation.frames.len());
+            for (index, frame) in animation.frames.iter().enumerate() {
+                println!("frame {index}: {frame}");
+            }
+            for problem in &animation.problems {
+                eprintln!("warning: {problem}");
+            }
+        }
+        Commands::Capacity { file, mode, json } => {
+            let png = load_file(file, cli.timeout())?;
+            let modes = match mode {
+                Some(mode) => vec![*mode],
+                None => vec![Mode::Chunk, Mode::Lsb],
+            };
+            let capacities: Vec<capacity::Capacity> = modes.into_iter().map(|mode| capacity::capacity(&png, mode)).collect();
+
+            if cli.json(*json) {
+                println!("{}", serde_json::to_string_pretty(&capacities)?);
+                return Ok(());
+            }
+            for capacity in &capacities {
+                println!("{}", capacity::line(capacity, cli.size_style()));
+            }
+        }
+        Commands::Verify { file, image_data } => {
+            let (file, image_data) = (file.clone(), *image_data);
+            let problems = timeout::with_timeout(cli.timeout(), move || {
+                let reader: Box<dyn io::Read> = if output::is_stdio(&file) {
+                    Box::new(io::stdin().lock())
+                } else {
+                    Box::new(fs::File::open(file).map_err(|error| error.to_string())?)
+                };
+                verify::verify(io::BufReader::new(reader), image_data).map_err(|error| error.to_string())
+            })??;
+
+            if problems.is_empty() {
+                println!("No problems found");
+            }
+            for problem in &problems {
+                println!("error: {problem}");
+            }
+            if !problems.is_empty() {
+                return Err(format!("{} problem(s) found", problems.len()).into());
+            }
+        }
+        Commands::Repair { file, .. } => {
+            let _lock = lock_file(file, cli)?;
+            let input = file.clone();
+            let contents = timeout::with_timeout(cli.timeout(), move || output::read_input(&input))??;
+            let (png, fixes) = repair::repair(&contents).map_err(|reason| PngmeError::InvalidPng { path: Some(file.clone()), reason })?;
+
+            if fixes.is_empty() {
+                if cli.fail_unchanged {
+                    return Err(output::Unchanged.into());
+                }
+                if !output::is_stdio(file) {
+                    println!("Nothing to repair");
+                    return Ok(());
+                }
+            }
+            output::save_png(file, &png, cli.write_options())?;
+            // With `-` stdout carries the png itself.
+            let mut report: Box<dyn io::Write> = if output::is_stdio(file) { Box::new(io::stderr()) } else { Box::new(io::stdout()) };
+            for fix in &fixes {
+                writeln!(report, "{fix}")?;
+            }
+        }
+        Commands::Cat { file, output, strip_trailing, fix_crc, reorder_spec } => {
+            let input = file.clone();
+            let contents = timeout::with_timeout(cli.timeout(), move || output::read_input(&input))??;
+            let mut png = if *fix_crc {
+                Png::try_from_ignoring_crc(contents.as_ref())?
+            } else {
+                Png::parse_untrusted(&contents, ParseLimits::default())?
+            };
+
+            if *strip_trailing {
+                png.strip_trailing_data();
+            }
+            if *reorder_spec {
+                order::reorder_spec(&mut png);
+            }
+
+            if !(*strip_trailing || *fix_crc || *reorder_spec) && png.as_bytes() != contents {
+                return Err("Internal error: re-serializing the file changed its bytes, nothing was written".into());
+            }
+
+            output::write_output(output, &png, cli.write_options())?;
+        }
+        Commands::Merge { file, overlays, output, on_conflict, policies, force } => {
+            if overlays.iter().chain([file]).filter(|path| output::is_stdio(path)).count() > 1 {
+                return Err("Only one of the files to merge can be read from stdin".into());
+            }
+            let policies = merge::Policies { default: *on_conflict, by_type: policies.clone() };
+            let mut png = load_file(file, cli.timeout())?;
+            for overlay in overlays {
+                let contents = read_file(overlay, cli.timeout())?;
+                let skipped = merge::merge(&mut png, &parse_file(overlay, &contents)?, overlay, &policies, *force)?;
+                if !skipped.is_empty() {
+                    eprintln!("warning: left out the {} chunks of {}, which aren't safe to copy; use --force to merge them", skipped.join(", "), overlay.display());
+                }
+            }
+            output::write_output(output, &png, cli.write_options())?;
+        }
+        Commands::Truncate { file, save_trailing, .. } => {
+            let _lock = lock_file(file, cli)?;
+            let mut png = load_file(file, cli.timeout())?;
+            let end = png.iend_end_offset().ok_or("File has no IEND chunk")?;
+            let trailing = png.truncate_after_iend();
+
+            if trailing.is_empty() {
+                if cli.fail_unchanged {
+                    return Err(output::Unchanged.into());
+                }
+                println!("Nothing to truncate");
+                return Ok(());
+            }
+
+            if let Some(path) = save_trailing {
+                if !cli.check_only {
+                    fs::write(path, &trailing)?;
+                }
+            }
+            output::save_png(file, &png, cli.write_options())?;
+            if !cli.check_only {
+                println!("Removed {} after IEND, the file now ends at offset {end}", format_size(trailing.len() as u64, cli.size_style()));
+            }
+        }
+        Commands::Explode { file, dir, force } => {
+            let png = load_file(file, cli.timeout())?;
+
+            for path in explode::explode(&png, dir, *force)? {
+                println!("{}", path.display());
+            }
+        }
+        Commands::Assemble { dir, output, recompute_crc } => {
+            let (png, skipped) = explode::assemble(dir, *recompute_crc)?;
+
+            for name in &skipped {
+                eprintln!("warning: {name} is missing, skipped");
+            }
+            for issue in validate::validate(&png) {
+                eprintln!("warning: {issue}");
+            }
+            output::write_png(output, &png, cli.write_options())?;
+        }
+        #[cfg(feature = "watch")]
+        Commands::Watch { file, chunk_type, input_file, on_change, debounce, .. } => {
+            let log = |message: String| println!("{} {message}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+            if let Some(operation) = on_change {
+                let words = watch::split_operation(operation)?;
+                // Parsed once up front, so a mistake shows before anything is watched.
+                operation_cli(&words, file)?;
+                if !file.is_dir() {
+                    return Err(format!("{} is not a directory, watch --on-change needs one", file.display()).into());
+                }
+
+                let mut watcher = watch::DirWatcher::new(file, Duration::from_millis(*debounce))?;
+                stop_on_ctrl_c(watcher.stopper())?;
+                log(format!("watching {} for pngs, press Ctrl-C to stop", file.display()));
+                while let Some(paths) = watcher.wait() {
+                    for path in paths {
+                        if !walk::has_png_signature(&path).unwrap_or(false) {
+                            continue;
+                        }
+                        match operation_cli(&words, &path).and_then(|cli| run(&cli)) {
+                            Ok(()) => log(format!("ran {operation:?} on {}", path.display())),
+                            Err(error) => log(format!("error: {}: {error}", path.display())),
+                        }
+                    }
+                    // The operation's own writes aren't changes to act on.
+                    watcher.rescan();
+                }
+                log("stopped watching".to_string());
+                return Ok(());
+            }
+            let chunk_type = chunk_type.as_deref().expect("clap requires CHUNK_TYPE without --on-change");
+            let input_file = input_file.as_deref().expect("clap requires --input-file without --on-change");
+            let refresh = || match refresh_chunk(file, chunk_type, input_file, cli) {
+                Ok(len) => log(format!("updated {chunk_type} in {} ({})", file.display(), format_size(len as u64, cli.size_style()))),
+                Err(error) => log(format!("error: {error}")),
+            };
+
+            // Start watching before the first refresh so a write in between isn't missed.
+            let mut watcher = watch::Watcher::new(input_file, Duration::from_millis(*debounce))?;
+            stop_on_ctrl_c(watcher.stopper())?;
+            if input_file.exists() {
+                refresh();
+            }
+            log(format!("watching {}, press Ctrl-C to stop", input_file.display()));
+
+            while let Some(change) = watcher.wait() {
+                match change {
+                    watch::Change::Updated => refresh(),
+                    watch::Change::Removed => log(format!("{} was removed, waiting for it to come back", input_file.display())),
+                }
+            }
+            log("stopped watching".to_string());
+        }
+        Commands::Ihdr { action: IhdrAction::Set(set) } => {
+            let edit = ihdr::IhdrEdit {
+                width: set.width,
+                height: set.height,
+                bit_depth: set.bit_depth,
+                color_type: set.color_type.map(ihdr::ColorType::try_from).transpose()?,
+                interlaced: set.interlace.map(|interlace| interlace == 1),
+                allow_unsafe: set.allow_unsafe,
+            };
+            let _lock = lock_file(&set.file, cli)?;
+            let mut png = load_file(&set.file, cli.timeout())?;
+
+            for warning in ihdr::apply_edit(&mut png, &edit)? {
+                eprintln!("warning: {warning}");
+            }
+            output::save_png(&set.file, &png, cli.write_options())?;
+        }
+        Commands::Text { action: TextAction::Set(set) } => {
+            let _lock = lock_file(&set.file, cli)?;
+            let mut png = load_file(&set.file, cli.timeout())?;
+
+            text::set(&mut png, &set.keyword, &set.text, set.kind, set.language.as_deref())?;
+            output::save_png(&set.file, &png, cli.write_options())?;
+        }
+        Commands::Text { action: TextAction::Get(key) } => {
+            let png = load_file(&key.file, cli.timeout())?;
+            let entries = text::get(&png, &key.keyword)?;
+            if entries.is_empty() {
+                return Err(format!("No text chunk with keyword {:?}", key.keyword).into());
+            }
+            for entry in entries {
+                println!("{}", entry.text);
+            }
+        }
+        Commands::Text { action: TextAction::Del(del) } => {
+            let _lock = lock_file(&del.file, cli)?;
+            let mut png = load_file(&del.file, cli.timeout())?;
+
+            let removed = text::remove(&mut png, &del.keyword);
+            if removed == 0 {
+                return Err(format!("No text chunk with keyword {:?}", del.keyword).into());
+            }
+            output::save_png(&del.file, &png, cli.write_options())?;
+            if png.is_modified() && !cli.check_only && !output::is_stdio(&del.file) {
+                println!("Removed {removed} chunk(s)");
+            }
+        }
+        Commands::Text { action: TextAction::Convert(convert) } => {
+            let _lock = lock_file(&convert.file, cli)?;
+            let mut png = load_file(&convert.file, cli.timeout())?;
+
+            if text::convert(&mut png, &convert.keyword, convert.to)? == 0 {
+                return Err(format!("No text chunk with keyword {:?}", convert.keyword).into());
+            }
+            output::save_png(&convert.file, &png, cli.write_options())?;
+        }
+        Commands::Time { action: TimeAction::Get(get) } => {
+            let png = load_file(&get.file, cli.timeout())?;
+            let time = time::LastModified::read(&png)
+                .ok_or_else(|| chunk_not_found(time::TIME_CHUNK_TYPE))?
+                .map_err(|reason| PngmeError::InvalidChunkData { chunk_type: time::TIME_CHUNK_TYPE.to_string(), reason })?;
+            println!("{time}");
+        }
+        Commands::Time { action: TimeAction::Set(set) } => {
+            let time = set.timestamp.unwrap_or_else(|| time::LastModified::new(Utc::now()));
+            let _lock = lock_file(&set.file, cli)?;
+            let mut png = load_file(&set.file, cli.timeout())?;
+
+            time.write(&mut png);
+            output::save_png(&set.file, &png, cli.write_options())?;
+        }
+        Commands::Exif { action: ExifAction::Get(get) } => {
+            let png = load_file(&get.file, cli.timeout())?;
+            let chunk = png.chunk_by_type(exif::EXIF_CHUNK_TYPE).ok_or_else(|| chunk_not_found(exif::EXIF_CHUNK_TYPE))?;
+            let summary = exif::ExifSummary::parse(chunk.data())
+                .map_err(|reason| PngmeError::InvalidChunkData { chunk_type: exif::EXIF_CHUNK_TYPE.to_string(), reason })?;
+            if summary.is_empty() {
+                println!("No camera, date or GPS tags in {} bytes of EXIF data", chunk.data().len());
+            } else {
+                println!("{summary}");
+            }
+        }
+        Commands::Exif { action: ExifAction::Set(set) } => {
+            let input = set.exif.clone();
+            let data = timeout::with_timeout(cli.timeout(), move || output::read_input(&input))?
+                .map_err(|source| PngmeError::Io { path: Some(set.exif.clone()), source })?;
+            let data = exif::prepare(&data)?;
+            let _lock = lock_file(&set.file, cli)?;
+            let mut png = load_file(&set.file, cli.timeout())?;
+
+            exif::write(&mut png, data);
+            output::save_png(&set.file, &png, cli.write_options())?;
+        }
+        Commands::Exif { action: ExifAction::Strip(strip) } => {
+            let _lock = if strip.dry_run { None } else { lock_file(&strip.file, cli)? };
+            let mut png = load_file(&strip.file, cli.timeout())?;
+
+            if strip.dry_run {
+                let exif = png.chunks().enumerate().filter(|(_, chunk)| chunk.chunk_type().to_string() == exif::EXIF_CHUNK_TYPE);
+                for (index, chunk) in exif {
+                    println!("{index}: {} ({})", chunk.chunk_type(), format_size(chunk.data().len() as u64, cli.size_style()));
+                }
+                println!("Would remove {} chunk(s)", exif::strip(&mut png));
+                println!("{}", resulting_size(png.serialized_len(), cli));
+                return Ok(());
+            }
+            if exif::strip(&mut png) == 0 && !output::is_stdio(&strip.file) {
+                if cli.fail_unchanged {
+                    return Err(output::Unchanged.into());
+                }
+                println!("No eXIf chunk to strip");
+                return Ok(());
+            }
+            output::save_png(&strip.file, &png, cli.write_options())?;
+        }
+        Commands::Icc { action: IccAction::Extract(extract) } => {
+            let png = load_file(&extract.file, cli.timeout())?;
+            let icc = icc::IccProfile::read(&png)
+                .ok_or_else(|| chunk_not_found(icc::ICCP_CHUNK_TYPE))?
+                .map_err(|reason| PngmeError::InvalidChunkData { chunk_type: icc::ICCP_CHUNK_TYPE.to_string(), reason })?;
+
+            output::write_raw(&extract.output, &icc.profile).map_err(|source| PngmeError::Io { path: Some(extract.output.clone()), source })?;
+            if !output::is_stdio(&extract.output) {
+                println!("Profile {:?} ({}) written to {}", icc.name, format_size(icc.profile.len() as u64, cli.size_style()), extract.output.display());
+            }
+        }
+        Commands::Icc { action: IccAction::Embed(embed) } => {
+            let input = embed.profile.clone();
+            let profile = timeout::with_timeout(cli.timeout(), move || output::read_input(&input))?
+                .map_err(|source| PngmeError::Io { path: Some(embed.profile.clone()), source })?;
+            let icc = icc::IccProfile::new(&embed.name, profile)?;
+            let _lock = lock_file(&embed.file, cli)?;
+            let mut png = load_file(&embed.file, cli.timeout())?;
+
+            if png.chunk_by_type("sRGB").is_some() {
+                eprintln!("warning: the file also has an sRGB chunk, which the embedded profile should replace");
+            }
+            icc.write(&mut png)?;
+            output::save_png(&embed.file, &png, cli.write_options())?;
+        }
+        Commands::Xmp { action: XmpAction::Get(get) } => {
+            let png = load_file(&get.file, cli.timeout())?;
+            let packet = xmp::read(&png)?.ok_or_else(|| format!("No XMP packet, no text chunk with keyword {:?}", xmp::XMP_KEYWORD))?;
+            print!("{packet}");
+        }
+        Commands::Xmp { action: XmpAction::Set(set) } => {
+            let input = set.xmp.clone().unwrap_or_else(|| PathBuf::from("-"));
+            let path = input.clone();
+            let packet = timeout::with_timeout(cli.timeout(), move || output::read_input(&input))?
+                .map_err(|source| PngmeError::Io { path: Some(path), source })?;
+            let packet = String::from_utf8(packet).map_err(|_| "XMP packet should be UTF-8")?;
+            let _lock = lock_file(&set.file, cli)?;
+            let mut png = load_file(&set.file, cli.timeout())?;
+
+            xmp::write(&mut png, &packet)?;
+            output::save_png(&set.file, &png, cli.write_options())?;
+        }
+        Commands::Dpi { action: DpiAction::Get(get) } => {
+            let png = load_file(&get.file, cli.timeout())?;
+            let phys = phys::Phys::read(&png)
+                .ok_or_else(|| chunk_not_found(phys::PHYS_CHUNK_TYPE))?
+                .map_err(|reason| PngmeError::InvalidChunkData { chunk_type: phys::PHYS_CHUNK_TYPE.to_string(), reason })?;
+            println!("{phys}");
+        }
+        Commands::Dpi { action: DpiAction::Set(set) } => {
+            let phys = phys::Phys::from_dpi(set.dpi)?;
+            let _lock = lock_file(&set.file, cli)?;
+            let mut png = load_file(&set.file, cli.timeout())?;
+
+            phys.write(&mut png);
+            output::save_png(&set.file, &png, cli.write_options())?;
+        }
+        Commands::Check { files, files_from, fix_duplicates, .. } => {
+            let (files, missing) = batch::collect_files(files, files_from.as_deref())?;
+
+            if files.len() == 1 && missing.is_empty() {
+                return check_file(&files[0], *fix_duplicates, cli, false);
+            }
+
+            let summary = batch::run_batch(&files, &missing, progress_writer(cli, &mut io::stderr()), |file| check_file(file, *fix_duplicates, cli, true));
+            println!("{} file(s) checked: {} ok, {} failed", summary.total(), summary.ok, summary.failed);
+            if summary.failed > 0 {
+                return Err(format!("{} file(s) failed", summary.failed).into());
+            }
+        }
+        Commands::Inspect { file, json } => {
+            let ihdr = match stream::index_if_large(file)? {
+                Some(index) => index.ihdr.ok_or("No readable IHDR chunk")?,
+                None => {
+                    let png = load_file(file, cli.timeout())?;
+                    if png.chunk_by_type("IHDR").is_none() {
+                        return Err(chunk_not_found("IHDR").into());
+                    }
+                    ihdr::Ihdr::read(&png).map_err(|reason| PngmeError::InvalidChunkData { chunk_type: "IHDR".to_string(), reason })?
+                }
+            };
+
+            if cli.json(*json) {
+                println!("{}", serde_json::to_string_pretty(&ihdr)?);
+            } else {
+                for line in ihdr.field_lines() {
+                    println!("{line}");
+                }
+            }
+        }
+        Commands::List { file, json } => {
+            let show_header = !cli.quiet && !cli.json(*json);
+            let (rows, header) = match stream::index_if_large(file)? {
+                Some(index) => {
+                    large_file_note(file);
+                    (list::index_rows(&index), show_header.then(|| print::index_header_line(file, &index)))
+                }
+                None => {
+                    let contents = read_file(file, cli.timeout())?;
+                    let png = parse_file(file, &contents)?;
+                    (list::rows(&png), show_header.then(|| image_header(file, &png)))
+                }
+            };
+
+            if let Some(header) = header {
+                println!("{header}");
+            }
+            if cli.json(*json) {
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            } else {
+                for line in list::table(&rows, cli.size_style()) {
+                    println!("{line}");
+                }
+            }
+        }
+        Commands::Scan { file, recursive, max_depth, hidden, exclude, follow_symlinks, pngme, json } => {
+            if *recursive {
+                let options = walk::WalkOptions { max_depth: *max_depth, hidden: *hidden, follow_symlinks: *follow_symlinks, exclude: exclude.clone() };
+                return scan_tree(file, &options, *pngme, *json, cli);
+            }
+            let contents = read_file(file, cli.timeout())?;
+            let png = parse_file(file, &contents)?;
+            let report = scan::scan(&png);
+
+            if cli.json(*json) {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            for finding in &report.findings {
+                println!("warning: {}", finding.line(cli.size_style()));
+            }
+            if *pngme {
+                for payload in &report.payloads {
+                    println!("{}", payload.line());
+                }
+            }
+            println!("{} finding(s), {} pngme payload(s) in {} chunks", report.findings.len(), report.payloads.len(), png.chunk_count());
+        }
+        Commands::Stats { file, json } => {
+            let summary = match stream::index_if_large(file)? {
+                Some(index) => {
+                    large_file_note(file);
+                    index.summary()
+                }
+                None => load_file(file, cli.timeout())?.summary(),
+            };
+
+            if cli.json(*json) {
+                println!("{}", summary_json(&summary)?);
+            } else {
+                println!("Size: {}, {} chunk(s), {} private", format_size(summary.total_size, cli.size_style()), summary.chunk_count, summary.private_chunks);
+                for (chunk_type, stats) in &summary.types {
+                    println!("{chunk_type}: {} chunk(s), {}", stats.count, format_size(stats.bytes, cli.size_style()));
+                }
+                for issue in &summary.issues {
+                    let level = if issue.is_warning() { "warning" } else { "error" };
+                    println!("{level}: {issue}");
+                }
+            }
+        }
+        Commands::Print { file, offsets, json, hex } => {
+            // A hex dump needs the chunk data, so large files are loaded whole for it.
+            if let Some(index) = if *hex { None } else { stream::index_if_large(file)? } {
+                large_file_note(file);
+                if cli.json(*json) {
+                    println!("{}", serde_json::to_string_pretty(&print::index_listing(&index))?);
+                    return Ok(());
+                }
+                if !cli.quiet {
+                    println!("{}", print::index_header_line(file, &index));
+                }
+                for line in print::index_lines(&index, *offsets, cli.size_style()) {
+                    println!("{line}");
+                }
+                return Ok(());
+            }
+
+            let png = load_file(file, cli.timeout())?;
+
+            if cli.json(*json) {
+                println!("{}", serde_json::to_string_pretty(&print::listing(&png))?);
+            } else {
+                if !cli.quiet {
+                    println!("{}", image_header(file, &png));
+                }
+                let lines = match hex {
+                    true => print::hex_lines(&png, *offsets, cli.size_style()),
+                    false => print::chunk_lines(&png, cli.preview_bytes, *offsets, cli.size_style()),
+                };
+                for line in lines {
+                    println!("{line}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the command line, exiting with the error's code when it fails.
+pub fn main() {
+    let cli = Cli::parse();
+
+    if let Err(error) = run(&cli) {
+        let error = PngmeError::from_boxed(error);
+        match cli.error_format() {
+            ErrorFormat::Human => eprintln!("Error: {error}"),
+            ErrorFormat::Json => {
+                let report = ErrorReport::new(&error, cli.command.file(), cli.command.chunk_type());
+                eprintln!("{}", serde_json::to_string(&report).expect("Error report should serialize"));
+            }
+        }
+        process::exit(error.exit_code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use clap::Parser;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_bytes_is_global() {
+        let parse = |args: &[&str]| Cli::parse_from(std::iter::once("pngme").chain(args.iter().copied()));
+
+        assert_eq!(parse(&["print", "image.png"]).size_style(), size::SizeStyle::Human);
+        assert_eq!(parse(&["print", "image.png", "--bytes"]).size_style(), size::SizeStyle::Bytes);
+        assert_eq!(parse(&["--bytes", "stats", "image.png"]).size_style(), size::SizeStyle::Bytes);
+        assert_eq!(parse(&["encode", "image.png", "ruSt", "hi", "--bytes"]).size_style(), size::SizeStyle::Bytes);
+        assert!(!parse(&["encode", "image.png", "ruSt", "--escaped", "hi"]).bytes);
+    }
+
+    #[test]
+    fn test_json_format() {
+        assert!(Cli::parse_from(["pngme", "list", "FILE", "--format", "json"]).json(false));
+        assert!(Cli::parse_from(["pngme", "--format", "json", "list", "FILE"]).json(false));
+        assert!(!Cli::parse_from(["pngme", "list", "FILE"]).json(false));
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_operation_cli() {
+        let words = |operation: &str| watch::split_operation(operation).unwrap();
+        let file = |cli: Cli| cli.command.file().unwrap().clone();
+        let png = Path::new("icons/a.png");
+
+        assert_eq!(file(operation_cli(&words("clean --include-raw tEXt"), png).unwrap()), png);
+        assert_eq!(file(operation_cli(&words("text set Comment 'build 42'"), png).unwrap()), png);
+        assert_eq!(file(operation_cli(&words("encode {} buIl --input-file build.json"), png).unwrap()), png);
+        assert!(operation_cli(&words("text set"), png).err().unwrap().to_string().contains("<KEYWORD>"));
+        assert!(operation_cli(&words("watch ruSt --input-file x"), png).err().unwrap().to_string().contains("another watch"));
+        assert!(operation_cli(&[], png).is_err());
+        assert!(Cli::try_parse_from(["pngme", "watch", "DIR", "ruSt", "--on-change", "clean"]).is_err());
+        assert!(Cli::try_parse_from(["pngme", "watch", "DIR"]).is_err());
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_refresh_chunk_replaces_previous_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hello".to_vec()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+        fs::write(&path, png.as_bytes()).unwrap();
+        let input = dir.path().join("version.json");
+        let cli = Cli::parse_from(["pngme", "watch", "FILE", "ruSt", "--input-file", "version.json"]);
+
+        fs::write(&input, r#"{"version":1}"#).unwrap();
+        assert_eq!(refresh_chunk(&path, "ruSt", &input, &cli).unwrap(), 13);
+        fs::write(&input, r#"{"version":2}"#).unwrap();
+        refresh_chunk(&path, "ruSt", &input, &cli).unwrap();
+
+        let png = load_file(&path, None).unwrap();
+        let chunks: Vec<&Chunk> = png.chunks_by_type("ruSt").collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(codec::open(chunks[0].data()).unwrap(), br#"{"version":2}"#);
+    }
+
+    #[test]
+    fn test_image_header_degrades_on_bad_ihdr() {
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 5]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hello".to_vec()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+
+        assert_eq!(image_header(Path::new("broken.png"), &png), "broken.png — 3 chunks");
+    }
+}
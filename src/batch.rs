@@ -0,0 +1,277 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::error::PngmeError;
+use crate::glob;
+use crate::json_path::JsonPath;
+use crate::output;
+use crate::Result;
+
+/// Reads a list of paths, one per line, ignoring blank lines and `#` comments.
+///
+/// Returns each path with its 1-based line number so problems can be reported per line.
+pub(crate) fn read_manifest(reader: impl BufRead) -> Result<Vec<(usize, PathBuf)>> {
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let entry = line.trim();
+        if entry.is_empty() || entry.starts_with('#') {
+            continue;
+        }
+        entries.push((index + 1, PathBuf::from(entry)));
+    }
+    Ok(entries)
+}
+
+/// A manifest entry naming a file that doesn't exist.
+#[derive(Debug, PartialEq)]
+pub(crate) struct MissingEntry {
+    pub(crate) manifest: PathBuf,
+    pub(crate) line: usize,
+    pub(crate) path: PathBuf,
+}
+
+impl fmt::Display for MissingEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} line {}: {} does not exist", self.manifest.display(), self.line, self.path.display())
+    }
+}
+
+/// Files to process: the paths given on the command line, with patterns expanded, followed by
+/// the manifest entries.
+///
+/// Manifest entries that don't exist are returned separately.
+pub(crate) fn collect_files(paths: &[PathBuf], files_from: Option<&Path>) -> Result<(Vec<PathBuf>, Vec<MissingEntry>)> {
+    let mut files = Vec::new();
+    for path in paths {
+        if glob::is_pattern(path) {
+            files.extend(glob::expand(path)?);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    let mut missing = Vec::new();
+
+    if let Some(manifest) = files_from {
+        let entries = if output::is_stdio(manifest) {
+            read_manifest(io::stdin().lock())?
+        } else {
+            read_manifest(io::BufReader::new(fs::File::open(manifest)?))?
+        };
+
+        for (line, path) in entries {
+            if path.exists() {
+                files.push(path);
+            } else {
+                missing.push(MissingEntry { manifest: manifest.to_path_buf(), line, path });
+            }
+        }
+    }
+
+    Ok((files, missing))
+}
+
+/// Outcome of running a command over several files.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct BatchSummary {
+    pub(crate) ok: usize,
+    pub(crate) failed: usize,
+}
+
+impl BatchSummary {
+    pub(crate) fn total(&self) -> usize {
+        self.ok + self.failed
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FileStatus {
+    Ok,
+    Failed,
+}
+
+/// Events written by `--progress json`, one JSON object per line.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum ProgressEvent {
+    Start {
+        files: usize,
+    },
+    File {
+        #[serde(flatten)]
+        path: JsonPath,
+        status: FileStatus,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bytes: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Done {
+        ok: usize,
+        failed: usize,
+    },
+}
+
+impl ProgressEvent {
+    fn file(path: &Path, error: Option<String>) -> ProgressEvent {
+        ProgressEvent::File {
+            path: path.to_path_buf().into(),
+            status: if error.is_some() { FileStatus::Failed } else { FileStatus::Ok },
+            bytes: fs::metadata(path).ok().map(|metadata| metadata.len()),
+            error,
+        }
+    }
+
+    fn done(summary: &BatchSummary) -> ProgressEvent {
+        ProgressEvent::Done { ok: summary.ok, failed: summary.failed }
+    }
+}
+
+/// Writes `event` as one line and flushes it, so readers see it as soon as it happens.
+fn emit(progress: &mut Option<&mut dyn Write>, event: &ProgressEvent) {
+    if let Some(writer) = progress {
+        let mut line = serde_json::to_vec(event).expect("Progress events should serialize");
+        line.push(b'\n');
+        // Progress is best effort, a closed stderr must not fail the batch.
+        let _ = writer.write_all(&line).and_then(|()| writer.flush());
+    }
+}
+
+/// Runs `operation` on every file without stopping at failures, and counts the outcomes.
+///
+/// `missing` manifest entries count as failures. Failures are reported on stderr, or as
+/// events on `progress` when it is set.
+pub(crate) fn run_batch(
+    files: &[PathBuf],
+    missing: &[MissingEntry],
+    mut progress: Option<&mut dyn Write>,
+    mut operation: impl FnMut(&Path) -> Result<()>,
+) -> BatchSummary {
+    let mut summary = BatchSummary { ok: 0, failed: missing.len() };
+    emit(&mut progress, &ProgressEvent::Start { files: files.len() + missing.len() });
+
+    for entry in missing {
+        match progress {
+            Some(_) => emit(&mut progress, &ProgressEvent::file(&entry.path, Some(entry.to_string()))),
+            None => eprintln!("Error: {entry}"),
+        }
+    }
+
+    for file in files {
+        match operation(file) {
+            Ok(()) => {
+                summary.ok += 1;
+                emit(&mut progress, &ProgressEvent::file(file, None));
+            }
+            Err(error) => {
+                summary.failed += 1;
+                let error = PngmeError::from_boxed(error);
+                let message = match error.path() {
+                    Some(_) => error.to_string(),
+                    None => format!("{}: {error}", file.display()),
+                };
+                match progress {
+                    Some(_) => emit(&mut progress, &ProgressEvent::file(file, Some(message))),
+                    None => eprintln!("Error: {message}"),
+                }
+            }
+        }
+    }
+
+    emit(&mut progress, &ProgressEvent::done(&summary));
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::png::Png;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_read_manifest() {
+        let manifest = "# assets\na.png\n\n  b.png  \n# c.png\nd.png\n";
+
+        let entries = read_manifest(manifest.as_bytes()).unwrap();
+
+        assert_eq!(entries, vec![
+            (2, PathBuf::from("a.png")),
+            (4, PathBuf::from("b.png")),
+            (6, PathBuf::from("d.png")),
+        ]);
+    }
+
+    #[test]
+    fn test_manifest_summary_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.png");
+        let not_png = dir.path().join("notes.txt");
+        let missing = dir.path().join("missing.png");
+        let direct = dir.path().join("direct.png");
+
+        let png = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![])]);
+        fs::write(&good, png.as_bytes()).unwrap();
+        fs::write(&direct, png.as_bytes()).unwrap();
+        fs::write(&not_png, b"not a png").unwrap();
+
+        let manifest = dir.path().join("files.txt");
+        let lines = format!("# inputs\n{}\n{}\n\n{}\n", good.display(), missing.display(), not_png.display());
+        fs::write(&manifest, lines).unwrap();
+
+        let (files, missing) = collect_files(std::slice::from_ref(&direct), Some(&manifest)).unwrap();
+
+        assert_eq!(files, vec![direct, good, not_png]);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].line, 3);
+        assert!(missing[0].to_string().contains("line 3"));
+
+        let summary = run_batch(&files, &missing, None, |file| {
+            Png::try_from(fs::read(file)?.as_ref())?;
+            Ok(())
+        });
+
+        assert_eq!(summary, BatchSummary { ok: 2, failed: 2 });
+        assert_eq!(summary.total(), 4);
+    }
+
+    #[test]
+    fn test_progress_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.png");
+        let bad = dir.path().join("bad.png");
+        let png = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![])]);
+        fs::write(&good, png.as_bytes()).unwrap();
+        fs::write(&bad, b"not a png").unwrap();
+        let missing = vec![MissingEntry { manifest: PathBuf::from("files.txt"), line: 2, path: dir.path().join("gone.png") }];
+
+        let mut events = Vec::new();
+        let summary = run_batch(&[good.clone(), bad], &missing, Some(&mut events), |file| {
+            Png::try_from(fs::read(file)?.as_ref())?;
+            Ok(())
+        });
+
+        let events: Vec<serde_json::Value> = String::from_utf8(events)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(summary, BatchSummary { ok: 1, failed: 2 });
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0], serde_json::json!({ "event": "start", "files": 3 }));
+        assert_eq!(events[1]["status"], "failed");
+        assert_eq!(events[2]["path"], good.display().to_string());
+        assert_eq!(events[2]["status"], "ok");
+        assert_eq!(events[2]["bytes"], png.as_bytes().len());
+        assert_eq!(events[3]["status"], "failed");
+        assert!(events[3]["error"].is_string());
+        assert_eq!(events[4], serde_json::json!({ "event": "done", "ok": 1, "failed": 2 }));
+    }
+}
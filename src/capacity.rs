@@ -0,0 +1,73 @@
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::encode::Mode;
+use crate::envelope;
+use crate::lsb;
+use crate::png::Png;
+use crate::size::{format_size, SizeStyle};
+
+/// How much content a plain `pngme encode`, with the envelope but no checksum, compression
+/// or encryption, can store in one mode.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Capacity {
+    pub(crate) mode: Mode,
+    /// `None` when the mode can't be used on the file.
+    pub(crate) bytes: Option<u64>,
+    /// Whether larger content still fits, split across several chunks.
+    pub(crate) splits: bool,
+    /// Why the mode can't be used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reason: Option<String>,
+}
+
+/// The capacity of `png` in `mode`.
+///
+/// A chunk holds up to 2 GiB whatever the image; the low bits of the samples hold one bit per
+/// 8 or 16-bit sample, less the header `encode --mode lsb` stores before the payload.
+pub(crate) fn capacity(png: &Png, mode: Mode) -> Capacity {
+    let content = |stored: usize| (stored.saturating_sub(envelope::HEADER_LEN)) as u64;
+    match mode {
+        Mode::Chunk => Capacity { mode, bytes: Some(content(Chunk::MAX_DATA_LEN)), splits: true, reason: None },
+        Mode::Lsb => match lsb::capacity(png) {
+            Ok(stored) => Capacity { mode, bytes: Some(content(stored)), splits: false, reason: None },
+            Err(reason) => Capacity { mode, bytes: None, splits: false, reason: Some(reason) },
+        },
+    }
+}
+
+/// `chunk: 2.0 GiB per chunk, more is split across chunks`, or `lsb: unavailable, <reason>`.
+pub(crate) fn line(capacity: &Capacity, style: SizeStyle) -> String {
+    let mode = match capacity.mode {
+        Mode::Chunk => "chunk",
+        Mode::Lsb => "lsb",
+    };
+    match (capacity.bytes, &capacity.reason) {
+        (Some(bytes), _) if capacity.splits => format!("{mode}: {} per chunk, more is split across chunks", format_size(bytes, style)),
+        (Some(bytes), _) => format!("{mode}: {}", format_size(bytes, style)),
+        (None, reason) => format!("{mode}: unavailable, {}", reason.as_deref().unwrap_or("unknown reason")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_capacity() {
+        let chunk = |chunk_type: &str, data: Vec<u8>| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data);
+        // 100x10 rgb: 3000 low bits, 375 bytes.
+        let ihdr = [100u32.to_be_bytes(), 10u32.to_be_bytes()].concat().into_iter().chain([8, 2, 0, 0, 0]).collect();
+        let png = Png::from_chunks(vec![chunk("IHDR", ihdr), chunk("IEND", vec![])]);
+
+        let lsb = capacity(&png, Mode::Lsb);
+        assert_eq!(lsb.bytes, Some(375 - 16 - envelope::HEADER_LEN as u64));
+        assert_eq!(line(&lsb, SizeStyle::Bytes), "lsb: 352 bytes");
+        assert_eq!(line(&capacity(&png, Mode::Chunk), SizeStyle::Human), "chunk: 2.0 GiB per chunk, more is split across chunks");
+
+        let png = Png::from_chunks(vec![chunk("IHDR", vec![0; 13])]);
+        assert!(line(&capacity(&png, Mode::Lsb), SizeStyle::Human).starts_with("lsb: unavailable, LSB mode needs"));
+    }
+}
@@ -1,85 +1,335 @@
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
 use std::{fmt, string::FromUtf8Error};
 
 use crate::chunk_type::ChunkType;
-
-pub(crate) struct Chunk {
+use crate::error::PngmeError;
+
+/// Standard chunk types whose data has a fixed size.
+const FIXED_DATA_LENGTHS: [(&str, usize); 7] = [
+    ("IHDR", 13), ("tIME", 7), ("gAMA", 4), ("pHYs", 9), ("sRGB", 1), ("cHRM", 32), ("IEND", 0),
+];
+
+/// CRC algorithm used by PNG chunks, computed over the chunk type and data.
+pub(crate) const CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+/// A png chunk: its type, its data and the CRC of both.
+///
+/// A chunk parsed from a buffer borrows its data from it rather than copying it, so listing
+/// the chunks of a large file costs no more than the file itself. `into_owned` detaches it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk<'a> {
     chunk_type: ChunkType,
-    chunk_data: Vec<u8>,
+    chunk_data: Cow<'a, [u8]>,
+    /// Computed once when the chunk is built, since neither field changes afterwards.
+    crc: u32,
 }
 
-impl Chunk {
-    pub(crate) fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        let chunk = Chunk{ chunk_type: chunk_type, chunk_data: data };
-        return chunk;
+impl<'a> Chunk<'a> {
+    /// Bytes taken by the length, chunk type and CRC fields around the data.
+    pub(crate) const METADATA_LEN: usize = 12;
+
+    /// Largest data length the spec allows, 2^31 - 1.
+    pub(crate) const MAX_DATA_LEN: usize = i32::MAX as usize;
+
+    /// Builds a chunk without checking its data, for private chunks with arbitrary payloads.
+    ///
+    /// `data` is a `Vec<u8>` for an owned chunk or a `&[u8]` for one that borrows it.
+    pub fn new(chunk_type: ChunkType, data: impl Into<Cow<'a, [u8]>>) -> Chunk<'a> {
+        let data = data.into();
+        debug_assert!(data.len() <= Self::MAX_DATA_LEN, "Chunk data is longer than the spec allows");
+        let mut digest = CRC32.digest();
+        digest.update(&chunk_type.bytes());
+        digest.update(&data);
+        Chunk{ chunk_type, chunk_data: data, crc: digest.finalize() }
+    }
+
+    /// Builds a chunk, refusing data that is too long to write or that doesn't have the size
+    /// a standard chunk type requires.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use pngme::{Chunk, ChunkType, PngmeError};
+    ///
+    /// let chunk = Chunk::try_new(ChunkType::from_str("ruSt").unwrap(), b"hello".as_slice()).unwrap();
+    /// assert_eq!(chunk.data(), b"hello");
+    ///
+    /// let error = Chunk::try_new(ChunkType::from_str("IEND").unwrap(), b"data".as_slice()).unwrap_err();
+    /// assert!(matches!(error, PngmeError::InvalidChunkData { .. }));
+    /// ```
+    pub fn try_new(chunk_type: ChunkType, data: impl Into<Cow<'a, [u8]>>) -> Result<Chunk<'a>, PngmeError> {
+        let data = data.into();
+        check_data_len(&chunk_type, data.len())
+            .map_err(|reason| PngmeError::InvalidChunkData { chunk_type: chunk_type.to_string(), reason })?;
+        Ok(Chunk::new(chunk_type, data))
     }
 
     fn length(&self) -> u32 {
         self.chunk_data.len().try_into().expect("Length is too large to fit in a u32")
     }
 
-    pub(crate) fn chunk_type(&self) -> &ChunkType {
+    /// Size of the chunk once serialized, metadata fields included.
+    pub(crate) fn serialized_len(&self) -> u64 {
+        (Self::METADATA_LEN + self.chunk_data.len()) as u64
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
         &self.chunk_type
     }
     
-    fn data(&self) -> &[u8] {
+    pub fn data(&self) -> &[u8] {
         &self.chunk_data
     }
 
-    fn crc(&self) -> u32 {
-        const CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
-        let type_bytes = self.chunk_type().bytes();
-        let all_bytes = [type_bytes.as_slice(), self.data()].concat();
-        CRC32.checksum(all_bytes.as_slice())
+    pub fn crc(&self) -> u32 {
+        self.crc
     }
 
-    pub(crate) fn data_as_string(&self) -> Result<String, FromUtf8Error> {
+    pub fn data_as_string(&self) -> Result<String, FromUtf8Error> {
         String::from_utf8(self.data().to_vec())
     }
 
-    pub(crate) fn as_bytes(&self) -> Vec<u8> {
-         let mut bytes_vec = self.length().to_be_bytes().to_vec();
-         bytes_vec.extend_from_slice(self.chunk_type().bytes().as_slice());
-         bytes_vec.extend_from_slice(self.data());
-         bytes_vec.extend_from_slice(self.crc().to_be_bytes().as_slice());
+    /// The chunk with its data copied if it was borrowed, so it outlives the parsed buffer.
+    pub fn into_owned(self) -> Chunk<'static> {
+        Chunk { chunk_type: self.chunk_type, chunk_data: Cow::Owned(self.chunk_data.into_owned()), crc: self.crc }
+    }
+
+    /// Splits the data of a text chunk (tEXt, zTXt or iTXt), or the profile name of an iCCP
+    /// chunk, at the NUL that ends its keyword, returning the keyword and whatever follows.
+    pub(crate) fn keyword_and_rest(&self) -> Result<(&[u8], &[u8]), String> {
+        let data = self.data();
+        let end = data.iter().position(|&byte| byte == 0).ok_or("no NUL separator after the keyword")?;
+        if !(1..=79).contains(&end) {
+            return Err(format!("keyword is {end} bytes long, it should be 1 to 79"));
+        }
+        Ok((&data[..end], &data[end + 1..]))
+    }
+
+    /// Parses the chunk at the front of `buf` and returns it with the number of bytes it took.
+    ///
+    /// Anything after the chunk is left for the caller, which advances by the consumed count.
+    pub fn parse(buf: &'a [u8]) -> Result<(Chunk<'a>, usize), String> {
+        Self::parse_inner(buf, true)
+    }
+
+    /// Like `parse`, but accepts a stored CRC that doesn't match the data.
+    ///
+    /// The stored value is dropped, so serializing the chunk again writes the correct CRC.
+    pub(crate) fn parse_ignoring_crc(buf: &'a [u8]) -> Result<(Chunk<'a>, usize), String> {
+        Self::parse_inner(buf, false)
+    }
+
+    /// The data length declared by the chunk at the front of `buf`, read without parsing it.
+    pub(crate) fn declared_data_len(buf: &[u8]) -> Option<u64> {
+        let length: [u8; 4] = buf.get(..4)?.try_into().ok()?;
+        Some(u64::from(u32::from_be_bytes(length)))
+    }
+
+    fn parse_inner(buf: &'a [u8], check_crc: bool) -> Result<(Chunk<'a>, usize), String> {
+        if buf.len() < Self::METADATA_LEN {
+            return Err(format!("Chunk needs at least {} bytes, got {}", Self::METADATA_LEN, buf.len()));
+        }
+
+        let data_len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        let chunk_type = ChunkType::try_from([buf[4], buf[5], buf[6], buf[7]])?;
+
+        // A length near u32::MAX overflows usize on 32-bit targets.
+        let consumed = data_len
+            .checked_add(Self::METADATA_LEN)
+            .ok_or_else(|| format!("{chunk_type} chunk length {data_len} is too large"))?;
+        if buf.len() < consumed {
+            return Err(format!("{chunk_type} chunk is truncated: expected {consumed} bytes, got {}", buf.len()));
+        }
+        let end_of_data_index = 8 + data_len;
+
+        // Type and data are contiguous in `buf`, so the CRC is computed in place and the data
+        // is borrowed rather than copied.
+        let crc = CRC32.checksum(&buf[4..end_of_data_index]);
+        let crc_bytes = &buf[end_of_data_index..consumed];
+        let stored_crc = u32::from_be_bytes([crc_bytes[0], crc_bytes[1], crc_bytes[2], crc_bytes[3]]);
+
+        if check_crc && crc != stored_crc {
+            return Err("Crc doesn't match".to_string());
+        }
+
+        let new_chunk = Chunk{ chunk_type, chunk_data: Cow::Borrowed(&buf[8..end_of_data_index]), crc };
+        Ok((new_chunk, consumed))
+    }
+
+    /// Reads the data and CRC of the chunk whose length and type fields were `header`.
+    ///
+    /// Data is read as it arrives rather than allocated from the declared length, so a bogus
+    /// length can't make it reserve gigabytes up front.
+    fn read_body(reader: &mut impl Read, header: [u8; 8]) -> crate::Result<Chunk<'static>> {
+        let data_len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let chunk_type = ChunkType::try_from([header[4], header[5], header[6], header[7]])?;
+
+        let mut data = Vec::new();
+        reader.take(u64::from(data_len)).read_to_end(&mut data)?;
+        let mut stored_crc = [0; 4];
+        if data.len() != data_len as usize || read_full(reader, &mut stored_crc)? != 4 {
+            return Err(format!("{chunk_type} chunk is truncated: expected {data_len} bytes of data and a CRC").into());
+        }
+
+        let mut digest = CRC32.digest();
+        digest.update(&header[4..]);
+        digest.update(&data);
+        let crc = digest.finalize();
+        if crc != u32::from_be_bytes(stored_crc) {
+            return Err("Crc doesn't match".into());
+        }
+
+        Ok(Chunk { chunk_type, chunk_data: Cow::Owned(data), crc })
+    }
 
-         return bytes_vec;
+    /// The chunk as it is written in a file: length, type, data and CRC.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.serialized_len() as usize);
+        self.write_to(&mut bytes).expect("Writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Writes the serialized chunk to `out`.
+    pub(crate) fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.length().to_be_bytes())?;
+        out.write_all(&self.chunk_type().bytes())?;
+        out.write_all(self.data())?;
+        out.write_all(&self.crc().to_be_bytes())
     }
 
 }
 
-impl TryFrom<&[u8]> for Chunk {
-    type Error = String;
+/// Reads the length and type fields of the next chunk, or `None` when `reader` is at its end.
+fn read_header(reader: &mut impl Read) -> crate::Result<Option<[u8; 8]>> {
+    let mut header = [0; 8];
+    match read_full(reader, &mut header)? {
+        0 => Ok(None),
+        8 => Ok(Some(header)),
+        len => Err(format!("Chunk needs at least {} bytes, got {len}", Chunk::METADATA_LEN).into()),
+    }
+}
 
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+/// Walks the chunks of a stream one at a time, stopping after IEND.
+///
+/// Chunks of the types passed to `skipping` are read past without being loaded or checked, and
+/// aren't yielded: listing the metadata of a large image then costs almost nothing for its
+/// IDAT chunks. The first error ends the iteration.
+pub struct ChunkReader<R> {
+    reader: R,
+    skipped: Vec<[u8; 4]>,
+    done: bool,
+}
 
-        let data_len: u32 = u32::from_be_bytes(value[..4].try_into().expect("Chunk length slice should be of length 4"));
+impl<R: Read> ChunkReader<R> {
+    /// Reads chunks from `reader`, which must be positioned at the start of a chunk, e.g. right
+    /// after the png signature.
+    pub fn new(reader: R) -> ChunkReader<R> {
+        ChunkReader { reader, skipped: Vec::new(), done: false }
+    }
 
-        let chunk_type_bytes: [u8; 4] = value[4..8].try_into().expect("Chunk type slice should be of length 4");
+    /// Reads the png signature from `reader`, then its chunks.
+    pub fn from_png(mut reader: R) -> crate::Result<ChunkReader<R>> {
+        let mut signature = [0; 8];
+        if read_full(&mut reader, &mut signature)? != 8 || signature != crate::png::Png::STANDARD_HEADER {
+            return Err("Data header should match the standard PNG header".into());
+        }
+        Ok(ChunkReader::new(reader))
+    }
 
-        let chunk_type = match ChunkType::try_from(chunk_type_bytes) {
-            Ok(chunk_type) => chunk_type,
-            Err(error) => panic!("{error:?}")
-        };
+    /// Skips chunks of `chunk_type` instead of yielding them.
+    pub fn skipping(mut self, chunk_type: &ChunkType) -> ChunkReader<R> {
+        self.skipped.push(chunk_type.bytes());
+        self
+    }
 
-        let end_of_data_index:usize = usize::try_from(8+data_len).unwrap();
+    /// The underlying reader, positioned after the last chunk read.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
 
-        let value_vec = value[8..end_of_data_index].to_vec();
+    fn read_next(&mut self) -> crate::Result<Option<Chunk<'static>>> {
+        while let Some(header) = read_header(&mut self.reader)? {
+            let chunk_type = [header[4], header[5], header[6], header[7]];
+            if !self.skipped.contains(&chunk_type) {
+                let chunk = Chunk::read_body(&mut self.reader, header)?;
+                self.done = chunk_type == *b"IEND";
+                return Ok(Some(chunk));
+            }
+
+            let len = u64::from(u32::from_be_bytes([header[0], header[1], header[2], header[3]])) + 4;
+            if io::copy(&mut (&mut self.reader).take(len), &mut io::sink())? != len {
+                return Err(format!("{} chunk is truncated", String::from_utf8_lossy(&chunk_type)).into());
+            }
+            if chunk_type == *b"IEND" {
+                break;
+            }
+        }
+        self.done = true;
+        Ok(None)
+    }
+}
 
-        let new_chunk = Chunk{ chunk_type: chunk_type, chunk_data: value_vec };
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = crate::Result<Chunk<'static>>;
 
-        let crc = u32::from_be_bytes(value[end_of_data_index..].try_into().expect("Chunk crc slice should be of length 4"));
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.read_next();
+        if next.is_err() {
+            self.done = true;
+        }
+        next.transpose()
+    }
+}
 
-        if new_chunk.crc() != crc {
-            return Err("Crc doesn't match".to_string());
+/// Fills as much of `buf` as `reader` has left, returning how many bytes that was.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(len) => filled += len,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
         }
+    }
+    Ok(filled)
+}
 
-        return Ok(new_chunk);
+fn check_data_len(chunk_type: &ChunkType, len: usize) -> Result<(), String> {
+    if len > Chunk::MAX_DATA_LEN {
+        return Err(format!("{len} bytes of data, over the {} bytes limit", Chunk::MAX_DATA_LEN));
+    }
+
+    let expected = FIXED_DATA_LENGTHS
+        .iter()
+        .find(|(name, _)| chunk_type.bytes() == name.as_bytes())
+        .map(|&(_, expected)| expected);
+    match expected {
+        Some(expected) if expected != len => Err(format!("{chunk_type} data must be {expected} bytes long, got {len}")),
+        _ => Ok(()),
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Chunk<'a> {
+    type Error = String;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        let (chunk, consumed) = Chunk::parse(value)?;
+
+        if consumed != value.len() {
+            return Err(format!("Chunk is {consumed} bytes long but {} bytes were given", value.len()));
+        }
+
+        Ok(chunk)
     }
 }
 
-impl fmt::Display for Chunk {
+impl fmt::Display for Chunk<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", &self.data_as_string().unwrap())
+        write!(f, "{}", String::from_utf8_lossy(self.data()))
     }
 }
 
@@ -87,9 +337,31 @@ impl fmt::Display for Chunk {
 mod tests {
     use super::*;
     use crate::chunk_type::ChunkType;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
     use std::str::FromStr;
 
-    fn testing_chunk() -> Chunk {
+    impl Arbitrary for Chunk<'static> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Chunk<'static>>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            (any::<ChunkType>(), vec(any::<u8>(), 0..256)).prop_map(|(chunk_type, data)| Chunk::new(chunk_type, data)).boxed()
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_new_chunk_crc_validates(chunk in any::<Chunk>()) {
+            let bytes = chunk.as_bytes();
+            let parsed = Chunk::try_from(bytes.as_slice()).unwrap();
+
+            prop_assert_eq!(parsed.crc(), CRC32.checksum(&bytes[4..bytes.len() - 4]));
+            prop_assert_eq!(parsed, chunk);
+        }
+    }
+
+    fn testing_chunk() -> Chunk<'static> {
         let data_length: u32 = 42;
         let chunk_type = "RuSt".as_bytes();
         let message_bytes = "This is where your secret message will be!".as_bytes();
@@ -104,7 +376,7 @@ mod tests {
             .copied()
             .collect();
         
-        Chunk::try_from(chunk_data.as_ref()).unwrap()
+        Chunk::try_from(chunk_data.as_ref()).unwrap().into_owned()
     }
 
     #[test]
@@ -136,6 +408,12 @@ mod tests {
         assert_eq!(chunk_string, expected_chunk_string);
     }
 
+    #[test]
+    fn test_chunk_serialized_len() {
+        let chunk = testing_chunk();
+        assert_eq!(chunk.serialized_len(), chunk.as_bytes().len() as u64);
+    }
+
     #[test]
     fn test_chunk_crc() {
         let chunk = testing_chunk();
@@ -190,6 +468,80 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_reserved_bit_chunk_from_bytes() {
+        let chunk = Chunk::new(ChunkType::from_str("Rust").unwrap(), b"lowercase reserved bit".to_vec());
+
+        let bytes = chunk.as_bytes();
+        let parsed = Chunk::try_from(bytes.as_ref()).unwrap();
+
+        assert!(parsed.chunk_type().has_invalid_reserved_bit());
+        assert_eq!(parsed.data(), b"lowercase reserved bit");
+    }
+
+    #[test]
+    fn test_non_alphabetic_chunk_type_from_bytes() {
+        let mut bytes = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"data".to_vec()).as_bytes();
+        bytes[6] = b'1';
+
+        assert!(Chunk::try_from(bytes.as_ref()).is_err());
+    }
+
+    #[test]
+    fn test_parse_back_to_back_chunks() {
+        let chunks = [
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"I am the first chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("miDl").unwrap(), vec![]),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), b"I am the last chunk".to_vec()),
+        ];
+        let mut buf: Vec<u8> = chunks.iter().flat_map(|chunk| chunk.as_bytes()).collect();
+        buf.extend_from_slice(b"garbage");
+
+        let mut pos = 0;
+        let mut offsets = Vec::new();
+        for expected in &chunks {
+            let (chunk, consumed) = Chunk::parse(&buf[pos..]).unwrap();
+            assert_eq!(chunk.chunk_type(), expected.chunk_type());
+            assert_eq!(chunk.data(), expected.data());
+            pos += consumed;
+            offsets.push(pos);
+        }
+
+        assert_eq!(offsets, vec![32, 44, 75]);
+        assert_eq!(&buf[pos..], b"garbage");
+        assert!(Chunk::parse(&buf[pos..]).is_err());
+    }
+
+    #[test]
+    fn test_parse_truncated_chunk() {
+        let bytes = testing_chunk().as_bytes();
+
+        assert!(Chunk::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Chunk::parse(&bytes[..3]).is_err());
+    }
+
+    #[test]
+    fn test_parse_ignoring_crc() {
+        let expected = testing_chunk().as_bytes();
+        let mut bytes = expected.clone();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        assert!(Chunk::parse(&bytes).is_err());
+
+        let (chunk, consumed) = Chunk::parse_ignoring_crc(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(chunk.as_bytes(), expected);
+    }
+
+    #[test]
+    fn test_try_from_rejects_trailing_bytes() {
+        let mut bytes = testing_chunk().as_bytes();
+        bytes.push(0);
+
+        assert!(Chunk::try_from(bytes.as_ref()).is_err());
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -210,4 +562,95 @@ mod tests {
         
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_try_new_standard_sizes() {
+        for (chunk_type, len) in FIXED_DATA_LENGTHS {
+            let chunk_type = ChunkType::from_str(chunk_type).unwrap();
+
+            assert!(Chunk::try_new(chunk_type.clone(), vec![0; len]).is_ok());
+            let error = Chunk::try_new(chunk_type.clone(), vec![0; len + 1]).unwrap_err();
+            assert_eq!(error.to_string(), format!("Invalid {chunk_type} chunk: {chunk_type} data must be {len} bytes long, got {}", len + 1));
+        }
+    }
+
+    #[test]
+    fn test_try_new_private_chunk() {
+        let chunk = Chunk::try_new(ChunkType::from_str("ruSt").unwrap(), vec![0; 5]).unwrap();
+
+        assert_eq!(chunk.length(), 5);
+    }
+
+    #[test]
+    fn test_keyword_and_rest() {
+        let text = |data: &[u8]| Chunk::new(ChunkType::from_str("tEXt").unwrap(), data.to_vec());
+
+        assert_eq!(text(b"Author\0Alice").keyword_and_rest(), Ok((b"Author".as_slice(), b"Alice".as_slice())));
+        assert_eq!(text(b"Comment\0").keyword_and_rest(), Ok((b"Comment".as_slice(), b"".as_slice())));
+        assert_eq!(text(b"a\0b\0c").keyword_and_rest(), Ok((b"a".as_slice(), b"b\0c".as_slice())));
+        assert!(text(b"Author").keyword_and_rest().is_err());
+        assert!(text(b"\0Alice").keyword_and_rest().is_err());
+        assert!(text(&[b"x".repeat(80), b"\0".to_vec()].concat()).keyword_and_rest().is_err());
+    }
+
+    #[test]
+    fn test_oversized_data() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+
+        assert!(check_data_len(&chunk_type, Chunk::MAX_DATA_LEN).is_ok());
+        assert!(check_data_len(&chunk_type, Chunk::MAX_DATA_LEN + 1).is_err());
+    }
+
+    fn chunk_stream(types: &[&str]) -> Vec<u8> {
+        types
+            .iter()
+            .flat_map(|chunk_type| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), chunk_type.as_bytes().repeat(10)).as_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_reader() {
+        let mut bytes = chunk_stream(&["IHDR", "tEXt", "IDAT", "IDAT", "IEND"]);
+        bytes.extend_from_slice(b"trailing");
+
+        let mut reader = ChunkReader::new(bytes.as_slice());
+        let types: Vec<String> = reader.by_ref().map(|chunk| chunk.unwrap().chunk_type().to_string()).collect();
+
+        assert_eq!(types, ["IHDR", "tEXt", "IDAT", "IDAT", "IEND"]);
+        assert_eq!(reader.into_inner(), b"trailing");
+    }
+
+    #[test]
+    fn test_chunk_reader_skips_types() {
+        let bytes = chunk_stream(&["IHDR", "IDAT", "tEXt", "IDAT", "IEND"]);
+
+        let reader = ChunkReader::new(bytes.as_slice()).skipping(&ChunkType::from_str("IDAT").unwrap());
+        let chunks: Vec<Chunk> = reader.collect::<crate::Result<_>>().unwrap();
+
+        let types: Vec<String> = chunks.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "tEXt", "IEND"]);
+        assert_eq!(chunks[1].data(), b"tEXt".repeat(10));
+    }
+
+    #[test]
+    fn test_chunk_reader_stops_at_first_error() {
+        let mut bytes = chunk_stream(&["IHDR", "tEXt"]);
+        bytes.truncate(bytes.len() - 3);
+
+        let mut reader = ChunkReader::new(bytes.as_slice());
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().unwrap_err().to_string().contains("tEXt chunk is truncated"));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_chunk_reader_truncated_skipped_chunk() {
+        let bytes = chunk_stream(&["IDAT"]);
+
+        let mut reader = ChunkReader::new(&bytes[..bytes.len() - 1]).skipping(&ChunkType::from_str("IDAT").unwrap());
+
+        assert!(reader.next().unwrap().is_err());
+        assert!(ChunkReader::from_png(&b"GIF89a"[..]).is_err());
+    }
 }
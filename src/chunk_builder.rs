@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::PngmeError;
+use crate::text::{self, TextEntry, TextKind};
+use crate::Result;
+
+/// Builds well-formed chunks step by step.
+///
+/// ```
+/// use pngme::ChunkBuilder;
+///
+/// # fn main() -> pngme::Result<()> {
+/// let private = ChunkBuilder::new().chunk_type("ruSt").data(b"hidden message").build()?;
+/// // A zTXt chunk, its keyword, NUL separator and compression method laid out in the data.
+/// let comment = ChunkBuilder::new().text("Comment", "made with pngme").compressed().build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ChunkBuilder {
+    chunk_type: Option<String>,
+    data: Option<Vec<u8>>,
+    /// Keyword and text.
+    text: Option<(String, String)>,
+    compressed: bool,
+}
+
+impl ChunkBuilder {
+    pub fn new() -> ChunkBuilder {
+        ChunkBuilder::default()
+    }
+
+    /// The four-letter type. Text chunks default to tEXt, or zTXt when compressed.
+    pub fn chunk_type(mut self, chunk_type: &str) -> ChunkBuilder {
+        self.chunk_type = Some(chunk_type.to_string());
+        self
+    }
+
+    /// The raw data of the chunk, stored as given.
+    pub fn data(mut self, data: impl Into<Vec<u8>>) -> ChunkBuilder {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Text stored under `keyword` in a tEXt, zTXt or iTXt chunk, which lays out the keyword,
+    /// NUL separators and header fields of its type.
+    pub fn text(mut self, keyword: &str, text: &str) -> ChunkBuilder {
+        self.text = Some((keyword.to_string(), text.to_string()));
+        self
+    }
+
+    /// Deflates the text, in a zTXt chunk or a compressed iTXt one, or the raw data of any other
+    /// chunk type.
+    pub fn compressed(mut self) -> ChunkBuilder {
+        self.compressed = true;
+        self
+    }
+
+    /// The chunk, or why it wouldn't be well-formed: a missing or invalid type, data of the
+    /// wrong size for a standard type, or text its chunk type can't store.
+    pub fn build(self) -> Result<Chunk<'static>> {
+        if let Some((keyword, text)) = &self.text {
+            if self.data.is_some() {
+                return Err("a chunk takes either data or text, not both".into());
+            }
+            let entry = TextEntry::new(self.text_kind()?, keyword, text);
+            let chunk = if entry.kind == TextKind::International && self.compressed { entry.to_compressed_chunk() } else { entry.to_chunk() };
+            return Ok(chunk?);
+        }
+
+        let name = self.chunk_type.as_deref().ok_or("a chunk without text needs a chunk type")?;
+        let chunk_type = ChunkType::from_str(name).map_err(|reason| PngmeError::InvalidChunkType { reason: reason.to_string() })?;
+        if !chunk_type.is_valid() {
+            let reason = format!("{chunk_type} has an invalid reserved bit (third letter must be uppercase)");
+            return Err(PngmeError::InvalidChunkType { reason }.into());
+        }
+        let data = self.data.unwrap_or_default();
+        let data = if self.compressed { text::deflate(&data) } else { data };
+        Ok(Chunk::try_new(chunk_type, data)?)
+    }
+
+    fn text_kind(&self) -> Result<TextKind> {
+        let kinds = [TextKind::Text, TextKind::Compressed, TextKind::International];
+        match self.chunk_type.as_deref() {
+            None if self.compressed => Ok(TextKind::Compressed),
+            None => Ok(TextKind::Text),
+            Some("tEXt") if self.compressed => Err("tEXt text can't be compressed, use zTXt or iTXt".into()),
+            Some(name) => kinds
+                .into_iter()
+                .find(|kind| kind.chunk_type() == name)
+                .ok_or_else(|| format!("{name} can't store text, use tEXt, zTXt or iTXt").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_of(chunk: &Chunk) -> (String, TextKind, String) {
+        let entry = TextEntry::parse(chunk).unwrap();
+        (entry.keyword, entry.kind, entry.text)
+    }
+
+    #[test]
+    fn test_build_data() {
+        let chunk = ChunkBuilder::new().chunk_type("ruSt").data(b"hidden".as_slice()).build().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "ruSt");
+        assert_eq!(chunk.data(), b"hidden");
+
+        let compressed = ChunkBuilder::new().chunk_type("ruSt").data(vec![7; 1000]).compressed().build().unwrap();
+        assert_eq!(text::inflate(0, compressed.data()).unwrap(), vec![7; 1000]);
+
+        assert!(ChunkBuilder::new().data(b"x".as_slice()).build().is_err());
+        assert!(ChunkBuilder::new().chunk_type("rust").build().unwrap_err().to_string().contains("reserved bit"));
+        assert!(ChunkBuilder::new().chunk_type("IHDR").data(vec![0; 12]).build().unwrap_err().to_string().contains("13 bytes"));
+    }
+
+    #[test]
+    fn test_build_text() {
+        let chunk = ChunkBuilder::new().text("Title", "Sunset").build().unwrap();
+        assert_eq!(chunk.data(), b"Title\0Sunset");
+        assert_eq!(text_of(&chunk), ("Title".to_string(), TextKind::Text, "Sunset".to_string()));
+
+        let chunk = ChunkBuilder::new().text("Title", "Sunset").compressed().build().unwrap();
+        assert_eq!(text_of(&chunk), ("Title".to_string(), TextKind::Compressed, "Sunset".to_string()));
+
+        let chunk = ChunkBuilder::new().chunk_type("iTXt").text("Title", "Coucher de soleil ☀").compressed().build().unwrap();
+        assert_eq!(&chunk.data()[6..8], [1, 0]);
+        assert_eq!(text_of(&chunk), ("Title".to_string(), TextKind::International, "Coucher de soleil ☀".to_string()));
+
+        assert!(ChunkBuilder::new().text("Title", "☀").build().unwrap_err().to_string().contains("iTXt"));
+        assert!(ChunkBuilder::new().chunk_type("tEXt").text("Title", "x").compressed().build().is_err());
+        assert!(ChunkBuilder::new().chunk_type("ruSt").text("Title", "x").build().is_err());
+        assert!(ChunkBuilder::new().text("Title", "x").data(b"x".as_slice()).build().is_err());
+    }
+}
@@ -1,15 +1,18 @@
 use std::{fmt, str::FromStr};
 
-pub(crate) struct ChunkType {
+/// The four-letter type of a chunk, e.g. `IHDR` or `ruSt`.
+#[derive(Clone)]
+pub struct ChunkType {
     chunk_type: [u8; 4],
 }
 
 impl ChunkType {
-    pub(crate) fn bytes(&self) -> [u8; 4] {
+    pub fn bytes(&self) -> [u8; 4] {
         self.chunk_type
     }
 
-    fn is_valid(&self) -> bool {
+    /// Whether every byte is a letter and the reserved bit is unset.
+    pub fn is_valid(&self) -> bool {
         for chunk_byte in self.chunk_type {
             if !chunk_byte.is_ascii_alphabetic() {
                 return false;
@@ -17,18 +20,25 @@ impl ChunkType {
         }
         self.is_reserved_bit_valid()
     }
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         self.chunk_type[0].is_ascii_uppercase()
     }
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         self.chunk_type[1].is_ascii_uppercase()
     }
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         self.chunk_type[2].is_ascii_uppercase()
     }
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         self.chunk_type[3].is_ascii_lowercase()
     }
+
+    /// Whether the third letter is lowercase, which the current spec reserves.
+    ///
+    /// Such types are still syntactically valid and are accepted when parsing.
+    pub fn has_invalid_reserved_bit(&self) -> bool {
+        !self.is_reserved_bit_valid()
+    }
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
@@ -40,7 +50,7 @@ impl TryFrom<[u8; 4]> for ChunkType {
                 return Err("ChunkType can only contain ascii alphabetical (A-Z and a-z)");
             }
         }
-        return Ok(ChunkType{ chunk_type: value });
+        Ok(ChunkType{ chunk_type: value })
     }
 }
 
@@ -54,20 +64,24 @@ impl FromStr for ChunkType {
 
         let chunk_bytes = s.as_bytes();
 
-        return ChunkType::try_from([chunk_bytes[0],chunk_bytes[1],chunk_bytes[2],chunk_bytes[3]]);
+        ChunkType::try_from([chunk_bytes[0],chunk_bytes[1],chunk_bytes[2],chunk_bytes[3]])
     }
 }
 
 impl PartialEq for ChunkType {
     fn eq(&self, other: &ChunkType) -> bool {
-        return self.bytes() == other.bytes();
+        self.bytes() == other.bytes()
     }
 }
 
 
 impl fmt::Display for ChunkType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", std::str::from_utf8(&self.chunk_type).unwrap())
+        // The bytes are ASCII letters, checked when the type was built.
+        for &byte in &self.chunk_type {
+            write!(f, "{}", byte as char)?;
+        }
+        Ok(())
     }
 }
 
@@ -82,9 +96,29 @@ impl fmt::Debug for ChunkType {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
     use std::convert::TryFrom;
     use std::str::FromStr;
 
+    /// Any four letters, reserved bit included, since parsing accepts them all.
+    impl Arbitrary for ChunkType {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<ChunkType>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            prop::array::uniform4(prop_oneof![b'A'..=b'Z', b'a'..=b'z'])
+                .prop_map(|bytes| ChunkType::try_from(bytes).unwrap())
+                .boxed()
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_chunk_type_string_round_trip(chunk_type in any::<ChunkType>()) {
+            prop_assert_eq!(ChunkType::from_str(&chunk_type.to_string()).unwrap(), chunk_type);
+        }
+    }
+
     #[test]
     pub fn test_chunk_type_from_bytes() {
         let expected = [82, 117, 83, 116];
@@ -148,6 +182,16 @@ mod tests {
         assert!(!chunk.is_safe_to_copy());
     }
 
+    #[test]
+    pub fn test_chunk_type_has_invalid_reserved_bit() {
+        let chunk = ChunkType::from_str("Rust").unwrap();
+        assert!(chunk.has_invalid_reserved_bit());
+        assert!(!chunk.is_valid());
+
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert!(!chunk.has_invalid_reserved_bit());
+    }
+
     #[test]
     pub fn test_valid_chunk_is_valid() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -0,0 +1,96 @@
+use crate::envelope::EnvelopeHeader;
+use crate::png::Png;
+
+#[derive(Debug)]
+pub(crate) struct RemovedChunk {
+    pub(crate) chunk_type: String,
+    pub(crate) size: usize,
+    pub(crate) compressed: bool,
+    pub(crate) encrypted: bool,
+}
+
+/// Removes every chunk carrying a pngme envelope, plus every chunk whose type is listed in
+/// `include_raw` (for payloads written with `--raw-payload`).
+pub(crate) fn clean_chunks(png: &mut Png, include_raw: &[String]) -> Vec<RemovedChunk> {
+    let to_remove: Vec<usize> = png
+        .chunks()
+        .enumerate()
+        .filter(|(_, chunk)| {
+            EnvelopeHeader::parse(chunk.data()).is_some() || include_raw.contains(&chunk.chunk_type().to_string())
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut removed: Vec<RemovedChunk> = to_remove
+        .iter()
+        .rev()
+        .map(|&index| {
+            let chunk = png.remove_chunk_at_unchecked(index);
+            let header = EnvelopeHeader::parse(chunk.data());
+            RemovedChunk {
+                chunk_type: chunk.chunk_type().to_string(),
+                size: chunk.data().len(),
+                compressed: header.is_some_and(|header| header.is_compressed()),
+                encrypted: header.is_some_and(|header| header.is_encrypted()),
+            }
+        })
+        .collect();
+
+    removed.reverse();
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::envelope::{self, FLAG_COMPRESSED, FLAG_ENCRYPTED};
+    use std::str::FromStr;
+
+    fn chunk_from_bytes(chunk_type: &str, data: Vec<u8>) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn testing_png() -> Png<'static> {
+        Png::from_chunks(vec![
+            chunk_from_bytes("IHDR", b"header".to_vec()),
+            chunk_from_bytes("IDAT", b"pixels".to_vec()),
+            chunk_from_bytes("tEXt", b"Comment\0hello".to_vec()),
+            chunk_from_bytes("IEND", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_clean_restores_original() {
+        let original = testing_png().as_bytes();
+
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_bytes("ruSt", envelope::wrap(EnvelopeHeader::new(FLAG_COMPRESSED), b"compressed")));
+        png.append_chunk(chunk_from_bytes("teSt", envelope::wrap(EnvelopeHeader::new(FLAG_ENCRYPTED), b"encrypted")));
+        png.append_chunk(chunk_from_bytes("raWp", b"raw payload".to_vec()));
+
+        let removed = clean_chunks(&mut png, &["raWp".to_string()]);
+
+        assert_eq!(removed.len(), 3);
+        assert_eq!(removed[0].chunk_type, "ruSt");
+        assert!(removed[0].compressed);
+        assert!(!removed[0].encrypted);
+        assert_eq!(removed[1].chunk_type, "teSt");
+        assert!(removed[1].encrypted);
+        assert_eq!(removed[2].chunk_type, "raWp");
+        assert_eq!(removed[2].size, 11);
+        assert_eq!(png.as_bytes(), original);
+    }
+
+    #[test]
+    fn test_clean_keeps_raw_payload_by_default() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_bytes("raWp", b"raw payload".to_vec()));
+
+        let removed = clean_chunks(&mut png, &[]);
+
+        assert!(removed.is_empty());
+        assert!(png.chunk_by_type("raWp").is_some());
+    }
+}
@@ -1,12 +1,148 @@
-use clap::Parser;
-use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Parser, ValueEnum};
 
 use crate::commands::Commands;
+use crate::order::ChunkOrder;
+use crate::output::{parse_backup_suffix, WriteOptions};
+use crate::preview::DEFAULT_PREVIEW_BYTES;
+use crate::size::SizeStyle;
+
+/// How failures are reported on stderr.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub(crate) enum ErrorFormat {
+    #[default]
+    Human,
+    /// A single JSON object with a stable `code` field
+    Json,
+}
+
+/// How command results are printed on stdout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub(crate) enum OutputFormat {
+    #[default]
+    Human,
+    /// JSON, as `--json` gives on the commands that have it; decode prints payloads in base64
+    Json,
+}
+
+/// Machine-readable progress reporting for multi-file runs.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub(crate) enum ProgressFormat {
+    /// Newline-delimited JSON events on stderr
+    Json,
+}
 
-#[derive(Parser)]
+#[derive(Clone, Parser)]
 #[command(version, about, long_about = None)]
+#[command(after_help = "FILE can also be a pattern, like 'icons/*.png' or '{a,b}.png', to run the command on every matching file")]
 pub(crate) struct Cli {
-    
+
     #[command(subcommand)]
-    pub(crate) command: Commands
+    pub(crate) command: Commands,
+
+    /// Number of data bytes shown in chunk previews (0 disables previews)
+    #[arg(long, global = true, value_name = "N", default_value_t = DEFAULT_PREVIEW_BYTES)]
+    pub(crate) preview_bytes: usize,
+
+    /// Print sizes as exact byte counts instead of KiB, MiB, ...
+    #[arg(long, global = true)]
+    pub(crate) bytes: bool,
+
+    /// Don't print the image description line before decode, list and print output
+    #[arg(short, long, global = true)]
+    pub(crate) quiet: bool,
+
+    /// Check serialized output against the in-memory model before every write
+    #[arg(long, global = true)]
+    pub(crate) paranoid: bool,
+
+    /// Exit with a distinct code instead of 0 when a command has nothing to change
+    #[arg(long, global = true)]
+    pub(crate) fail_unchanged: bool,
+
+    /// Do everything up to writing the result, then discard it silently and exit as a real run would
+    #[arg(long, global = true)]
+    pub(crate) check_only: bool,
+
+    /// Read every written file back and restore the original if it doesn't match what was meant
+    #[arg(long, global = true)]
+    pub(crate) verify_roundtrip: bool,
+
+    /// Refuse to write an output path that is a symbolic link instead of writing to its target
+    #[arg(long, global = true)]
+    pub(crate) no_follow_symlinks: bool,
+
+    /// Copy every file about to be overwritten to FILE.bak, or FILE followed by SUFFIX, first
+    #[arg(long, global = true, value_name = "SUFFIX", num_args = 0..=1, require_equals = true, default_missing_value = ".bak", value_parser = parse_backup_suffix)]
+    pub(crate) backup: Option<String>,
+
+    /// What to do when a modified png would be written with IHDR not first, PLTE after IDAT,
+    /// split IDAT chunks or chunks after IEND
+    #[arg(long, global = true, value_name = "POLICY", default_value = "reject")]
+    pub(crate) chunk_order: ChunkOrder,
+
+    /// Give up on reading an input after this many seconds
+    #[arg(long, global = true, value_name = "SECONDS")]
+    pub(crate) timeout: Option<u64>,
+
+    /// Don't lock files while modifying them in place
+    #[arg(long, global = true)]
+    pub(crate) no_lock: bool,
+
+    /// Give up after this many seconds when another pngme holds the lock on a file
+    #[arg(long, global = true, value_name = "SECONDS")]
+    pub(crate) lock_timeout: Option<u64>,
+
+    /// Report progress of multi-file runs on stderr; also makes error messages JSON
+    #[arg(long, global = true, value_enum, value_name = "FORMAT")]
+    pub(crate) progress: Option<ProgressFormat>,
+
+    /// Format of error messages
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Human)]
+    pub(crate) errors: ErrorFormat,
+
+    /// Format of command output
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    pub(crate) format: OutputFormat
+}
+
+impl Cli {
+    /// Errors are JSON when asked for, or when progress events already are.
+    pub(crate) fn error_format(&self) -> ErrorFormat {
+        match self.progress {
+            Some(ProgressFormat::Json) => ErrorFormat::Json,
+            None => self.errors,
+        }
+    }
+
+    /// Whether to print JSON, asked for with the command's own `--json` or `--format json`.
+    pub(crate) fn json(&self, flag: bool) -> bool {
+        flag || self.format == OutputFormat::Json
+    }
+
+    pub(crate) fn size_style(&self) -> SizeStyle {
+        if self.bytes { SizeStyle::Bytes } else { SizeStyle::Human }
+    }
+
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    pub(crate) fn lock_timeout(&self) -> Option<Duration> {
+        self.lock_timeout.map(Duration::from_secs)
+    }
+
+    pub(crate) fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            paranoid: self.paranoid,
+            fail_unchanged: self.fail_unchanged,
+            check_only: self.check_only,
+            verify_roundtrip: self.verify_roundtrip,
+            force: self.command.force(),
+            no_follow_symlinks: self.no_follow_symlinks,
+            chunk_order: self.chunk_order,
+            backup: self.backup.clone(),
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,524 @@
+use std::io::{Read, Write};
+
+use clap::ValueEnum;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::chunk::CRC32;
+use crate::crypto::{SigningKey, VerifyingKey, SIGNATURE_LEN};
+use crate::envelope::{self, EnvelopeHeader, FLAG_CHECKSUM, FLAG_COMPRESSED, FLAG_ENCRYPTED, FLAG_HMAC, FLAG_SIGNED};
+use crate::Result;
+
+/// One reversible transformation of a pngme payload.
+///
+/// Each codec owns one envelope flag, so a payload records which stages it went through.
+pub(crate) trait Codec: Sync {
+    fn flag(&self) -> u8;
+
+    fn name(&self) -> &'static str;
+
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>>;
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Appends a CRC32 of the data, checked when decoding.
+pub(crate) struct Checksum;
+
+impl Codec for Checksum {
+    fn flag(&self) -> u8 {
+        FLAG_CHECKSUM
+    }
+
+    fn name(&self) -> &'static str {
+        "checksum"
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut encoded = data.to_vec();
+        encoded.extend_from_slice(&CRC32.checksum(data).to_be_bytes());
+        Ok(encoded)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < 4 {
+            return Err("Payload is too short to hold its checksum".into());
+        }
+        let (payload, stored) = data.split_at(data.len() - 4);
+        let stored = u32::from_be_bytes(stored.try_into().unwrap());
+        let computed = CRC32.checksum(payload);
+        if stored != computed {
+            return Err(format!("Payload checksum mismatch: stored {stored:08x}, computed {computed:08x}").into());
+        }
+        Ok(payload.to_vec())
+    }
+}
+
+/// Compressed payloads larger than this once decompressed are refused, so a small chunk
+/// can't expand into all of memory.
+pub(crate) const MAX_DECOMPRESSED_LEN: u64 = 1 << 30;
+
+/// Compresses the data, prefixed with one byte naming the algorithm so decoding doesn't need
+/// to be told which one was used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub(crate) enum Compression {
+    #[default]
+    Zlib,
+    Zstd,
+}
+
+impl Compression {
+    fn id(self) -> u8 {
+        match self {
+            Compression::Zlib => 0,
+            Compression::Zstd => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Compression> {
+        [Compression::Zlib, Compression::Zstd].into_iter().find(|compression| compression.id() == id)
+    }
+}
+
+/// Reads `reader` to the end, failing past `limit` bytes.
+pub(crate) fn read_limited(reader: impl Read, limit: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        return Err(format!("Payload decompresses to more than {limit} bytes").into());
+    }
+    Ok(data)
+}
+
+impl Codec for Compression {
+    fn flag(&self) -> u8 {
+        FLAG_COMPRESSED
+    }
+
+    fn name(&self) -> &'static str {
+        "compression"
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut encoded = vec![self.id()];
+        match self {
+            Compression::Zlib => {
+                let mut encoder = flate2::write::ZlibEncoder::new(encoded, flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoded = encoder.finish()?;
+            }
+            Compression::Zstd => encoded.extend(zstd::encode_all(data, 0)?),
+        }
+        Ok(encoded)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let (&id, compressed) = data.split_first().ok_or("Payload is too short to hold its compression algorithm")?;
+        match Compression::from_id(id) {
+            Some(Compression::Zlib) => read_limited(flate2::read::ZlibDecoder::new(compressed), MAX_DECOMPRESSED_LEN),
+            Some(Compression::Zstd) => read_limited(zstd::Decoder::new(compressed)?, MAX_DECOMPRESSED_LEN),
+            None => Err(format!("Unknown compression algorithm {id}").into()),
+        }
+    }
+}
+
+/// Encrypts the data with AES-256-GCM, under a key derived from a passphrase with Argon2id.
+///
+/// The data starts with one byte naming the scheme, then the random salt and nonce it was
+/// encrypted with. The GCM tag at the end makes a wrong passphrase or modified data fail to
+/// decrypt instead of producing garbage.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Encryption<'a>(pub(crate) Option<&'a str>);
+
+/// Scheme 0: Argon2id with OWASP's parameters (19 MiB, 2 passes, 1 lane), then AES-256-GCM.
+const ENCRYPTION_SCHEME: u8 = 0;
+
+const SALT_LEN: usize = 16;
+
+impl Encryption<'_> {
+    fn key(&self, salt: &[u8]) -> Result<LessSafeKey> {
+        let passphrase = self.0.ok_or("no passphrase was given, decode with --decrypt")?;
+        let key = argon2::hash_raw(passphrase.as_bytes(), salt, &argon2::Config::owasp2())?;
+        let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| "can't build the AES key")?;
+        Ok(LessSafeKey::new(key))
+    }
+}
+
+impl Codec for Encryption<'_> {
+    fn flag(&self) -> u8 {
+        FLAG_ENCRYPTED
+    }
+
+    fn name(&self) -> &'static str {
+        "encryption"
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let random = SystemRandom::new();
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        random.fill(&mut salt).and_then(|()| random.fill(&mut nonce)).map_err(|_| "can't generate a random salt")?;
+
+        let mut ciphertext = data.to_vec();
+        self.key(&salt)?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut ciphertext)
+            .map_err(|_| "encryption failed")?;
+        Ok([&[ENCRYPTION_SCHEME][..], &salt, &nonce, &ciphertext].concat())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let (&scheme, rest) = data.split_first().ok_or("Payload is too short to hold its encryption scheme")?;
+        if scheme != ENCRYPTION_SCHEME {
+            return Err(format!("Unknown encryption scheme {scheme}").into());
+        }
+        if rest.len() < SALT_LEN + NONCE_LEN + AES_256_GCM.tag_len() {
+            return Err("Payload is too short to hold its salt, nonce and tag".into());
+        }
+        let (salt, rest) = rest.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "invalid nonce")?;
+
+        let mut plaintext = ciphertext.to_vec();
+        let len = self
+            .key(salt)?
+            .open_in_place(nonce, Aad::empty(), &mut plaintext)
+            .map_err(|_| "wrong passphrase, or the payload was modified")?
+            .len();
+        plaintext.truncate(len);
+        Ok(plaintext)
+    }
+}
+
+/// Appends an HMAC-SHA256 tag of the data keyed by a passphrase, which decoding requires and
+/// checks: unlike the checksum, the tag can't be recomputed after tampering without the
+/// passphrase.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Hmac<'a>(pub(crate) Option<&'a str>);
+
+const HMAC_TAG_LEN: usize = 32;
+
+impl Hmac<'_> {
+    fn key(&self) -> Result<hmac::Key> {
+        let passphrase = self.0.ok_or("payload carries an HMAC tag, decode with --hmac to check it")?;
+        Ok(hmac::Key::new(hmac::HMAC_SHA256, passphrase.as_bytes()))
+    }
+}
+
+impl Codec for Hmac<'_> {
+    fn flag(&self) -> u8 {
+        FLAG_HMAC
+    }
+
+    fn name(&self) -> &'static str {
+        "hmac"
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok([data, hmac::sign(&self.key()?, data).as_ref()].concat())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < HMAC_TAG_LEN {
+            return Err("Payload is too short to hold its HMAC tag".into());
+        }
+        let (payload, tag) = data.split_at(data.len() - HMAC_TAG_LEN);
+        hmac::verify(&self.key()?, payload, tag).map_err(|_| "HMAC tag mismatch: the payload was modified, or the passphrase is wrong")?;
+        Ok(payload.to_vec())
+    }
+}
+
+/// Appends an ed25519 signature of the data, made with `key` and checked against `public_key`
+/// when decoding.
+///
+/// Decoding without a public key drops the signature unchecked, like any other reader that
+/// doesn't care who wrote the payload; `decode --verify` makes it required.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Signature<'a> {
+    pub(crate) key: Option<&'a SigningKey>,
+    pub(crate) public_key: Option<&'a VerifyingKey>,
+}
+
+impl Codec for Signature<'_> {
+    fn flag(&self) -> u8 {
+        FLAG_SIGNED
+    }
+
+    fn name(&self) -> &'static str {
+        "signature"
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let key = self.key.ok_or("no signing key was given, encode with --sign")?;
+        Ok([data, &key.sign(data)].concat())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < SIGNATURE_LEN {
+            return Err("Payload is too short to hold its signature".into());
+        }
+        let (payload, signature) = data.split_at(data.len() - SIGNATURE_LEN);
+        if let Some(public_key) = self.public_key {
+            public_key.verify(payload, signature)?;
+        }
+        Ok(payload.to_vec())
+    }
+}
+
+/// Registered codecs, in the order they are applied when encoding: compression, then
+/// encryption, then integrity, then authenticity. Decoding runs them in reverse.
+///
+/// Adding a codec means implementing `Codec` and inserting it here at its stage. Codecs
+/// with settings are registered with their defaults and configured by `Settings`.
+static STAGES: &[&dyn Codec] =
+    &[&Compression::Zlib, &Encryption(None), &Checksum, &Hmac(None), &Signature { key: None, public_key: None }];
+
+/// The configured codecs for the stages that take settings.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Settings<'a> {
+    /// The algorithm used when compressing; decompression reads it from the payload.
+    pub(crate) compression: Compression,
+    pub(crate) encryption: Encryption<'a>,
+    pub(crate) hmac: Hmac<'a>,
+    pub(crate) signature: Signature<'a>,
+}
+
+impl Settings<'_> {
+    /// The codec to run for `stage`: the configured one if it takes settings, else itself.
+    fn codec<'s>(&'s self, stage: &'s dyn Codec) -> &'s dyn Codec {
+        match stage.flag() {
+            FLAG_COMPRESSED => &self.compression,
+            FLAG_ENCRYPTED => &self.encryption,
+            FLAG_HMAC => &self.hmac,
+            FLAG_SIGNED => &self.signature,
+            _ => stage,
+        }
+    }
+}
+
+fn unsupported_flags(flags: u8) -> u8 {
+    STAGES.iter().fold(flags, |flags, codec| flags & !codec.flag())
+}
+
+/// `compressed, encrypted` for known flags without a codec, the raw bits otherwise.
+fn describe_flags(flags: u8) -> String {
+    let names = envelope::flag_names(flags);
+    if names.is_empty() {
+        format!("{flags:#04x}")
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Runs `payload` through the stages selected by `flags` and wraps the result in an envelope
+/// recording them.
+pub(crate) fn seal(payload: &[u8], flags: u8, settings: Settings) -> Result<Vec<u8>> {
+    if unsupported_flags(flags) != 0 {
+        return Err(format!("This pngme can't write {} payloads", describe_flags(unsupported_flags(flags))).into());
+    }
+
+    let mut data = payload.to_vec();
+    for codec in STAGES.iter().filter(|codec| flags & codec.flag() != 0) {
+        data = settings.codec(*codec).encode(&data)?;
+    }
+    Ok(envelope::wrap(EnvelopeHeader::new(flags), &data))
+}
+
+/// `open_with` default settings, for payloads that aren't encrypted.
+pub(crate) fn open(data: &[u8]) -> Result<Vec<u8>> {
+    open_with(data, Settings::default())
+}
+
+/// Reverses the stages recorded in the envelope of `data`; data without an envelope is
+/// returned as is, unless a public key in `settings` requires a signature.
+pub(crate) fn open_with(data: &[u8], settings: Settings) -> Result<Vec<u8>> {
+    let unsigned = || -> Result<Vec<u8>> { Err("Payload isn't signed, so it can't be verified".into()) };
+    let Some((header, body)) = envelope::read(data)? else {
+        return if settings.signature.public_key.is_some() { unsigned() } else { Ok(data.to_vec()) };
+    };
+    if settings.signature.public_key.is_some() && header.flags & FLAG_SIGNED == 0 {
+        return unsigned();
+    }
+    if unsupported_flags(header.flags) != 0 {
+        return Err(format!("Payload is {}, which this pngme can't decode", describe_flags(unsupported_flags(header.flags))).into());
+    }
+
+    let mut payload = body.to_vec();
+    for codec in STAGES.iter().rev().filter(|codec| header.flags & codec.flag() != 0) {
+        payload = settings.codec(*codec).decode(&payload).map_err(|error| format!("{} stage: {error}", codec.name()))?;
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::FLAG_ENCRYPTED;
+
+    /// Deterministic pseudo-random payloads (xorshift), so failures are reproducible.
+    fn random_payloads() -> Vec<Vec<u8>> {
+        let mut state: u32 = 0x2545_f491;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        (0..64).map(|_| (0..next() % 2048).map(|_| next() as u8).collect()).collect()
+    }
+
+    /// Every combination of the registered stages.
+    fn stage_combinations() -> Vec<u8> {
+        (0..1u32 << STAGES.len())
+            .map(|mask| {
+                STAGES.iter().enumerate().filter(|(i, _)| mask & 1 << i != 0).fold(0, |flags, (_, codec)| flags | codec.flag())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_checksum_codec() {
+        let encoded = Checksum.encode(b"hello").unwrap();
+
+        assert_eq!(encoded.len(), 9);
+        assert_eq!(Checksum.decode(&encoded).unwrap(), b"hello");
+
+        let mut corrupted = encoded.clone();
+        corrupted[0] ^= 1;
+        assert!(Checksum.decode(&corrupted).is_err());
+        assert!(Checksum.decode(&encoded[..3]).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_every_combination() {
+        let payloads = random_payloads();
+        let key = SigningKey::parse(&[7; 32]).unwrap();
+        let public_key = key.verifying_key();
+        for flags in stage_combinations() {
+            // Key derivation is slow on purpose, so encrypted combinations only get a few.
+            let payloads = if flags & FLAG_ENCRYPTED != 0 { &payloads[..2] } else { &payloads[..] };
+            for payload in payloads {
+                for compression in [Compression::Zlib, Compression::Zstd] {
+                    // A public key requires a signature, so it is only given for signed combinations.
+                    let signature = Signature { key: Some(&key), public_key: (flags & FLAG_SIGNED != 0).then_some(&public_key) };
+                    let settings =
+                        Settings { compression, encryption: Encryption(Some("correct horse")), hmac: Hmac(Some("tag key")), signature };
+                    let sealed = seal(payload, flags, settings).unwrap();
+
+                    assert_eq!(EnvelopeHeader::parse(&sealed).unwrap().flags, flags);
+                    assert_eq!(&open_with(&sealed, settings).unwrap(), payload, "flags {flags:#04x}, {compression:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compression_codec() {
+        let text = b"pngme ".repeat(1000);
+
+        for compression in [Compression::Zlib, Compression::Zstd] {
+            let encoded = compression.encode(&text).unwrap();
+
+            assert_eq!(encoded[0], compression.id());
+            assert!(encoded.len() < text.len() / 10, "{compression:?}");
+            // Decoding reads the algorithm from the data, whichever codec instance runs it.
+            assert_eq!(Compression::Zlib.decode(&encoded).unwrap(), text);
+        }
+        assert!(Compression::Zlib.decode(&[]).is_err());
+        assert!(Compression::Zlib.decode(&[7, 1, 2, 3]).unwrap_err().to_string().contains("algorithm 7"));
+        assert!(Compression::Zlib.decode(&[0, 1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_encryption_codec() {
+        let encryption = Encryption(Some("correct horse"));
+        let encoded = encryption.encode(b"secret").unwrap();
+
+        assert_eq!(encoded.len(), 1 + SALT_LEN + NONCE_LEN + 6 + AES_256_GCM.tag_len());
+        assert!(!encoded.windows(6).any(|window| window == b"secret"));
+        assert_eq!(encryption.decode(&encoded).unwrap(), b"secret");
+        // A fresh salt and nonce every time.
+        assert_ne!(encryption.encode(b"secret").unwrap(), encoded);
+
+        let wrong = Encryption(Some("battery staple")).decode(&encoded).unwrap_err();
+        assert!(wrong.to_string().contains("wrong passphrase"), "{wrong}");
+        let mut tampered = encoded.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(encryption.decode(&tampered).is_err());
+        assert!(Encryption(None).decode(&encoded).unwrap_err().to_string().contains("--decrypt"));
+        assert!(encryption.decode(&encoded[..20]).is_err());
+    }
+
+    #[test]
+    fn test_hmac_codec() {
+        let hmac = Hmac(Some("tag key"));
+        let encoded = hmac.encode(b"hello").unwrap();
+
+        assert_eq!(encoded.len(), 5 + HMAC_TAG_LEN);
+        assert_eq!(hmac.decode(&encoded).unwrap(), b"hello");
+        assert!(Hmac(Some("other key")).decode(&encoded).unwrap_err().to_string().contains("HMAC tag mismatch"));
+        let mut tampered = encoded.clone();
+        tampered[1] ^= 1;
+        assert!(hmac.decode(&tampered).is_err());
+        assert!(Hmac(None).decode(&encoded).unwrap_err().to_string().contains("--hmac"));
+        assert!(hmac.decode(&encoded[..10]).is_err());
+    }
+
+    #[test]
+    fn test_signature_codec() {
+        let key = SigningKey::parse(&[7; 32]).unwrap();
+        let public_key = key.verifying_key();
+        let other = SigningKey::parse(&[8; 32]).unwrap().verifying_key();
+        let signing = Signature { key: Some(&key), public_key: None };
+        let encoded = signing.encode(b"hello").unwrap();
+
+        assert_eq!(encoded.len(), 5 + SIGNATURE_LEN);
+        assert_eq!(Signature { key: None, public_key: Some(&public_key) }.decode(&encoded).unwrap(), b"hello");
+        assert_eq!(Signature::default().decode(&encoded).unwrap(), b"hello");
+        assert!(Signature { key: None, public_key: Some(&other) }.decode(&encoded).is_err());
+        let mut tampered = encoded.clone();
+        tampered[0] ^= 1;
+        assert!(Signature { key: None, public_key: Some(&public_key) }.decode(&tampered).is_err());
+        assert!(Signature::default().encode(b"hello").unwrap_err().to_string().contains("--sign"));
+
+        let verifying = Settings { signature: Signature { key: None, public_key: Some(&public_key) }, ..Settings::default() };
+        assert!(open_with(&seal(b"hello", FLAG_CHECKSUM, Settings::default()).unwrap(), verifying).unwrap_err().to_string().contains("isn't signed"));
+        assert!(open_with(b"raw", verifying).is_err());
+    }
+
+    #[test]
+    fn test_decompression_limit() {
+        let bomb = Compression::Zlib.encode(&[0; 1 << 20]).unwrap();
+        let decoder = || flate2::read::ZlibDecoder::new(&bomb[1..]);
+
+        assert_eq!(read_limited(decoder(), 1 << 20).unwrap().len(), 1 << 20);
+        assert!(read_limited(decoder(), 1000).unwrap_err().to_string().contains("more than 1000 bytes"));
+    }
+
+    #[test]
+    fn test_open_raw_data() {
+        assert_eq!(open(b"not an envelope").unwrap(), b"not an envelope");
+    }
+
+    #[test]
+    fn test_open_v1_checksum_payload() {
+        let v1 = [0x70, 0x6e, 0x67, 0x6d, 0x65, 0x01, 0x04, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x36, 0x10, 0xa6, 0x86];
+
+        assert_eq!(open(&v1).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_open_newer_version() {
+        let wrapped = envelope::wrap(EnvelopeHeader { version: envelope::VERSION + 1, flags: 0 }, b"hello");
+
+        assert!(open(&wrapped).unwrap_err().to_string().contains("newer pngme"));
+    }
+
+    #[test]
+    fn test_unsupported_stages() {
+        assert!(seal(b"hello", 0b1000_0000, Settings::default()).is_err());
+
+        let wrapped = envelope::wrap(EnvelopeHeader::new(0b1000_0000), b"hello");
+        assert!(open(&wrapped).unwrap_err().to_string().contains("0x80"));
+    }
+}
@@ -1,35 +1,1017 @@
 use std::path::PathBuf;
 
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 
-#[derive(Subcommand)]
+use crate::codec::Compression;
+use crate::encode::{parse_position, Mode, Position};
+use crate::filter::Filter;
+use crate::merge::{parse_type_policy, ConflictPolicy};
+use crate::phys::parse_dpi;
+use crate::selector::ChunkSelector;
+use crate::stamp::parse_key_value;
+use crate::text::TextKind;
+use crate::time::{parse_timestamp, LastModified};
+
+#[derive(Clone, Subcommand)]
 pub(crate) enum Commands {
     /// Encode chunk in png
     Encode {
+        /// Png file, `-` to read stdin and write the result to stdout
         file: PathBuf,
 
         chunk_type: String,
         
         /// String to encode into png chunk
-        content: String
+        #[arg(required_unless_present_any = ["escaped", "input_file"])]
+        content: Option<String>,
+
+        /// Bytes to encode, with \xNN, \n, \t, \0 and \\ escapes, instead of CONTENT
+        #[arg(long, value_name = "ESCAPED", conflicts_with = "content")]
+        escaped: Option<String>,
+
+        /// Encode the contents of this file, byte for byte, instead of CONTENT ("-" for stdin)
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["content", "escaped"])]
+        input_file: Option<PathBuf>,
+
+        /// CONTENT, or the --input-file contents, is base64 text to decode before encoding
+        #[arg(long, conflicts_with = "escaped")]
+        base64: bool,
+
+        /// Store the content as is, without the pngme envelope header
+        #[arg(long)]
+        raw_payload: bool,
+
+        /// Split the payload across chunks of at most this many bytes, which decode joins back;
+        /// payloads too large for a single chunk are always split
+        #[arg(long, value_name = "BYTES", conflicts_with = "raw_payload")]
+        split_size: Option<usize>,
+
+        /// Fail instead of warning when the content has NULs, control characters, or characters
+        /// a tEXt chunk can't hold
+        #[arg(long)]
+        strict_text: bool,
+
+        /// Append a checksum of the content that decode verifies
+        #[arg(long, conflicts_with = "raw_payload")]
+        checksum: bool,
+
+        /// Compress the content before storing it, with zlib unless `--compress=zstd` is given;
+        /// decode decompresses it
+        #[arg(long, value_name = "ALGORITHM", num_args = 0..=1, require_equals = true, default_missing_value = "zlib", conflicts_with = "raw_payload")]
+        compress: Option<Compression>,
+
+        /// Encrypt the content with a passphrase, prompted for or read from PNGME_PASSPHRASE
+        #[arg(long, conflicts_with = "raw_payload")]
+        encrypt: bool,
+
+        /// Append an HMAC-SHA256 tag of the payload keyed by this passphrase; decode then needs
+        /// the same --hmac and refuses modified payloads
+        #[arg(long, value_name = "PASSPHRASE", conflicts_with = "raw_payload")]
+        hmac: Option<String>,
+
+        /// Sign the payload with this ed25519 private key, a PEM PKCS#8 file or a raw 32-byte
+        /// seed; decode --verify checks the signature
+        #[arg(long, value_name = "KEY_FILE", conflicts_with = "raw_payload")]
+        sign: Option<PathBuf>,
+
+        /// Allow chunk types with an invalid reserved bit, and writing to Apple CgBI pngs
+        #[arg(long)]
+        force: bool,
+
+        /// Encode into files that have no IDAT chunk, and so no image
+        #[arg(long)]
+        allow_no_image: bool,
+
+        /// Where to insert the chunk: before-iend, after-ihdr, or `index N` for a chunk index
+        #[arg(long, value_name = "POSITION", value_parser = parse_position, default_value = "before-iend")]
+        position: Position,
+
+        /// Fail without writing if the resulting file would be larger than this many bytes
+        #[arg(long, value_name = "BYTES")]
+        max_output_size: Option<u64>,
+
+        /// Report the resulting file size without writing the file
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Also encode into the files listed in this manifest, one path per line ("-" for stdin)
+        #[arg(long, value_name = "PATH")]
+        files_from: Option<PathBuf>,
+
+        /// Only write the files if every one of them can be encoded
+        #[arg(long)]
+        all_or_nothing: bool,
+
+        /// Shell command the content is piped through before it is stored
+        #[arg(long, value_name = "COMMAND")]
+        pipe_through: Option<String>,
+
+        /// Write the result to this file instead of modifying FILE
+        #[arg(short, long, conflicts_with = "files_from")]
+        output: Option<PathBuf>,
+
+        /// Store the payload in a chunk, or hide it in the low bits of the image samples and
+        /// re-compress the image data; CHUNK_TYPE then only tags the payload for decode
+        #[arg(long, value_enum, default_value_t = Mode::Chunk, conflicts_with_all = ["split_size", "position", "dry_run", "files_from", "all_or_nothing", "allow_no_image", "max_output_size"])]
+        mode: Mode
     },
 
     /// Decode chunk in png
     Decode {
+        /// Png file, `-` for stdin
         file: PathBuf,
 
-        chunk_type: String
+        /// Chunk type, optionally with an occurrence: `ruSt[2]` for the third, `ruSt[*]` for all
+        #[arg(required_unless_present = "nth")]
+        chunk_type: Option<ChunkSelector>,
+
+        /// Select the chunk by its position in the file instead of by type
+        #[arg(long, value_name = "INDEX")]
+        nth: Option<usize>,
+
+        /// Decode every chunk of the type, like `TYPE[*]`
+        #[arg(long, requires = "chunk_type", conflicts_with_all = ["nth", "index"])]
+        all: bool,
+
+        /// Decode the chunk at this 0-based position among chunks of the type, like `TYPE[N]`
+        #[arg(long, value_name = "N", requires = "chunk_type", conflicts_with = "nth")]
+        index: Option<usize>,
+
+        /// Decrypt encrypted payloads with a passphrase, prompted for or read from
+        /// PNGME_PASSPHRASE
+        #[arg(long)]
+        decrypt: bool,
+
+        /// Passphrase to check the HMAC tag of payloads encoded with --hmac
+        #[arg(long, value_name = "PASSPHRASE")]
+        hmac: Option<String>,
+
+        /// Refuse payloads that aren't signed by the private key matching this ed25519 public
+        /// key, a PEM file or 32 raw bytes
+        #[arg(long = "verify", value_name = "PUBLIC_KEY_FILE")]
+        verify_key: Option<PathBuf>,
+
+        /// Shell command the decoded payload is piped through before it is printed
+        #[arg(long, value_name = "COMMAND")]
+        pipe_through: Option<String>,
+
+        /// Write the payload byte for byte to this file ("-" for stdout) instead of printing it
+        /// as text
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Print or write the payload as base64 text, safe for binary data in shells and JSON
+        #[arg(long)]
+        base64: bool,
+
+        /// Read the payload from a chunk, or from the low bits of the image samples where
+        /// `encode --mode lsb` hid it
+        #[arg(long, value_enum, default_value_t = Mode::Chunk, conflicts_with_all = ["nth", "all", "index"])]
+        mode: Mode
     },
 
     /// Remove chunk from png
     Remove {
+        /// Png file, `-` to read stdin and write the result to stdout
+        file: PathBuf,
+
+        /// Chunk type, optionally with an occurrence: `ruSt[2]` for the third, `ruSt[*]` for all
+        #[arg(required_unless_present = "matching", conflicts_with = "matching")]
+        chunk_type: Option<ChunkSelector>,
+
+        /// Remove every chunk of the type, like `TYPE[*]`
+        #[arg(long, requires = "chunk_type")]
+        all: bool,
+
+        /// Remove every chunk matching this expression, e.g. 'private && size>1024'
+        ///
+        /// Fields: type, size, index, critical, private, safe_to_copy. Operators: && || ! == != < <= > >= and parentheses.
+        #[arg(long, value_name = "EXPRESSION")]
+        matching: Option<Filter>,
+
+        /// List the chunks that would be removed without writing the file
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Modify the file even if it is an Apple CgBI png
+        #[arg(long)]
+        force: bool
+    },
+
+    /// Change the type of a chunk, keeping its data, e.g. to rename a private chunk
+    Retype {
+        /// Png file, `-` to read stdin and write the result to stdout
+        file: PathBuf,
+
+        /// Chunk type to change, optionally with an occurrence: `ruSt[2]` for the third, `ruSt[*]` for all
+        old_type: ChunkSelector,
+
+        /// Chunk type the chunk gets
+        new_type: String,
+
+        /// Retype every chunk of the type, like `TYPE[*]`
+        #[arg(long)]
+        all: bool,
+
+        /// Allow a new type with an invalid reserved bit, and modifying Apple CgBI pngs
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Copy an ancillary chunk, e.g. an ICC profile or private metadata, into another png
+    CopyChunk {
+        /// Png file to copy the chunk from, `-` for stdin
+        source: PathBuf,
+
+        /// Png file to copy the chunk into, `-` to read stdin and write the result to stdout
+        #[arg(value_name = "DESTINATION")]
+        file: PathBuf,
+
+        /// Chunk type, optionally with an occurrence: `ruSt[2]` for the third, `ruSt[*]` for all
+        chunk_type: ChunkSelector,
+
+        /// Copy every chunk of the type, like `TYPE[*]`
+        #[arg(long)]
+        all: bool,
+
+        /// Copy chunks that aren't safe to copy, and modify Apple CgBI pngs
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Extract chunk data into files
+    Extract {
+        file: PathBuf,
+
+        /// Chunk type, optionally with an occurrence: `ruSt[2]` for the third, `ruSt[*]` for all
+        #[arg(required_unless_present = "nth")]
+        chunk_type: Option<ChunkSelector>,
+
+        /// Select the chunk by its position in the file instead of by type
+        #[arg(long, value_name = "INDEX", conflicts_with = "all")]
+        nth: Option<usize>,
+
+        /// Extract every chunk of that type instead of only the first one
+        #[arg(long)]
+        all: bool,
+
+        /// Directory to write the extracted files into
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+
+        /// Overwrite existing files
+        #[arg(long)]
+        force: bool,
+
+        /// Print the list of written files as JSON
+        #[arg(long)]
+        json: bool
+    },
+
+    /// Remove every chunk written by pngme
+    Clean {
+        file: PathBuf,
+
+        /// List the chunks that would be removed without writing the file
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Also remove chunks of this type even without the pngme envelope
+        #[arg(long, value_name = "TYPE")]
+        include_raw: Vec<String>,
+
+        /// Modify the file even if it is an Apple CgBI png
+        #[arg(long)]
+        force: bool
+    },
+
+    /// Write or replace the pngme provenance record
+    Stamp {
+        file: PathBuf,
+
+        /// Extra key=value pair to record (git sha, build id, ...)
+        #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        pairs: Vec<(String, String)>,
+
+        /// Leave the timestamp out so repeated stamps produce identical files
+        #[arg(long)]
+        deterministic: bool,
+
+        /// Print the existing record instead of writing one
+        #[arg(long, conflicts_with_all = ["pairs", "deterministic"])]
+        show: bool,
+
+        /// Print the record as JSON (with --show)
+        #[arg(long, requires = "show")]
+        json: bool,
+
+        /// Modify the file even if it is an Apple CgBI png
+        #[arg(long)]
+        force: bool
+    },
+
+    /// List stored and recomputed CRC of every chunk
+    Crc {
+        /// Png file, `-` for stdin
+        file: PathBuf,
+
+        /// Stop at the first mismatching CRC
+        #[arg(long)]
+        fail_fast: bool
+    },
+
+    /// Parse and re-serialize a png, optionally normalizing it
+    Cat {
+        /// Input file, `-` for stdin
+        file: PathBuf,
+
+        /// Output file, `-` for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
+
+        /// Drop any data after the IEND chunk
+        #[arg(long)]
+        strip_trailing: bool,
+
+        /// Accept chunks with a wrong CRC and write the correct one
+        #[arg(long)]
+        fix_crc: bool,
+
+        /// Move chunks to the positions required by the PNG spec
+        #[arg(long)]
+        reorder_spec: bool
+    },
+
+    /// Combine the ancillary chunks of several pngs into a copy of the first one
+    Merge {
+        /// Png whose image and chunks the result starts from, `-` for stdin
+        #[arg(value_name = "BASE")]
+        file: PathBuf,
+
+        /// Pngs whose ancillary chunks are merged in, in order
+        #[arg(value_name = "OVERLAY", required = true)]
+        overlays: Vec<PathBuf>,
+
+        /// Output file, `-` for stdout
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Which chunks to keep when files have different chunks of the same type
+        #[arg(long, value_name = "POLICY", default_value = "first-wins")]
+        on_conflict: ConflictPolicy,
+
+        /// Policy for one chunk type, overriding --on-conflict, e.g. `tEXt=last-wins`
+        #[arg(long = "policy", value_name = "TYPE=POLICY", value_parser = parse_type_policy)]
+        policies: Vec<(String, ConflictPolicy)>,
+
+        /// Merge chunks that aren't safe to copy, and write Apple CgBI pngs
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Cut the file right after IEND, dropping anything appended to it
+    Truncate {
+        file: PathBuf,
+
+        /// Save the removed bytes to this file
+        #[arg(long, value_name = "PATH")]
+        save_trailing: Option<PathBuf>,
+
+        /// Modify the file even if it is an Apple CgBI png
+        #[arg(long)]
+        force: bool
+    },
+
+    /// Write every chunk to its own file, with a manifest to reassemble them
+    Explode {
+        file: PathBuf,
+
+        /// Directory the chunk files and manifest.json are written to
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+
+        /// Overwrite existing files
+        #[arg(long)]
+        force: bool
+    },
+
+    /// Rebuild a png from a directory written by explode
+    Assemble {
+        dir: PathBuf,
+
+        /// Output file, `-` for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
+
+        /// Rebuild length and CRC fields of edited chunk files instead of rejecting them
+        #[arg(long)]
+        recompute_crc: bool
+    },
+
+    /// Keep a chunk in sync with a payload file, re-encoding it every time the file changes, or
+    /// run an operation on every png created or modified in a directory
+    #[cfg(feature = "watch")]
+    Watch {
+        /// Png file to update, or the directory to watch with --on-change
+        file: PathBuf,
+
+        #[arg(required_unless_present = "on_change")]
+        chunk_type: Option<String>,
+
+        /// File whose content is encoded into the chunk
+        #[arg(long, value_name = "PATH", required_unless_present = "on_change")]
+        input_file: Option<PathBuf>,
+
+        /// pngme command run on each png created or modified in the directory FILE, like "clean"
+        /// or "encode buIl --input-file build.json"; {} stands for the png, which otherwise
+        /// follows the command name
+        #[arg(long, value_name = "OPERATION", conflicts_with_all = ["chunk_type", "input_file"])]
+        on_change: Option<String>,
+
+        /// How long the payload file must stay unchanged before it is encoded
+        #[arg(long, value_name = "MILLISECONDS", default_value_t = 200)]
+        debounce: u64,
+
+        /// Modify the file even if it is an Apple CgBI png
+        #[arg(long, conflicts_with = "on_change")]
+        force: bool
+    },
+
+    /// Edit the image header
+    Ihdr {
+        #[command(subcommand)]
+        action: IhdrAction
+    },
+
+    /// Read and edit tEXt, zTXt and iTXt chunks by keyword
+    Text {
+        #[command(subcommand)]
+        action: TextAction
+    },
+
+    /// Read and set the last modification time stored in the tIME chunk
+    Time {
+        #[command(subcommand)]
+        action: TimeAction
+    },
+
+    /// Read and set the resolution stored in the pHYs chunk
+    Dpi {
+        #[command(subcommand)]
+        action: DpiAction
+    },
+
+    /// Read, replace or remove the EXIF metadata stored in the eXIf chunk
+    Exif {
+        #[command(subcommand)]
+        action: ExifAction
+    },
+
+    /// Extract or embed the ICC color profile stored in the iCCP chunk
+    Icc {
+        #[command(subcommand)]
+        action: IccAction
+    },
+
+    /// Read or replace the XMP metadata packet
+    Xmp {
+        #[command(subcommand)]
+        action: XmpAction
+    },
+
+    /// List the PLTE colors as hex RGB, with swatches on a color terminal
+    Palette {
+        /// Png file, `-` for stdin
+        file: PathBuf,
+
+        /// Print the palette entries as JSON
+        #[arg(long)]
+        json: bool
+    },
+
+    /// List the frames of an animated png, with their delays and fdAT sequence numbers
+    Frames {
+        /// Png file, `-` for stdin
+        file: PathBuf,
+
+        /// Print the animation as JSON
+        #[arg(long)]
+        json: bool
+    },
+
+    /// Report how many bytes of content encode can store in the file, in each mode
+    Capacity {
+        /// Png file, `-` for stdin
+        file: PathBuf,
+
+        /// Only report this mode
+        #[arg(long, value_enum)]
+        mode: Option<Mode>,
+
+        /// Print the capacities as JSON
+        #[arg(long)]
+        json: bool
+    },
+
+    /// Check the signature, every CRC and the required chunks, reporting all problems
+    Verify {
+        /// Png file, `-` for stdin
+        file: PathBuf,
+
+        /// Also inflate the IDAT stream and check its size against the IHDR, which catches
+        /// missing or reordered IDAT chunks that still have valid CRCs
+        #[arg(long)]
+        image_data: bool
+    },
+
+    /// Fix wrong CRCs, drop a truncated last chunk and add a missing IEND
+    Repair {
+        /// Png file, `-` to read stdin and write the result to stdout
+        file: PathBuf,
+
+        /// Modify the file even if it is an Apple CgBI png
+        #[arg(long)]
+        force: bool
+    },
+
+    /// Check png structure
+    Check {
+        #[arg(required_unless_present = "files_from")]
+        files: Vec<PathBuf>,
+
+        /// Also check the files listed in this manifest, one path per line ("-" for stdin)
+        #[arg(long, value_name = "PATH")]
+        files_from: Option<PathBuf>,
+
+        /// Keep the first occurrence of chunks that must be unique and drop the rest
+        #[arg(long)]
+        fix_duplicates: bool,
+
+        /// Modify the file even if it is an Apple CgBI png
+        #[arg(long)]
+        force: bool
+    },
+
+    /// Show the image header fields: size, bit depth, color type and interlacing
+    Inspect {
+        /// Png file, `-` for stdin
+        file: PathBuf,
+
+        /// Print the fields as JSON
+        #[arg(long)]
+        json: bool
+    },
+
+    /// List chunks in a table: type, length, CRC, offset and property flags
+    List {
+        /// Png file, `-` for stdin
+        file: PathBuf,
+
+        /// Print the rows as JSON
+        #[arg(long)]
+        json: bool
+    },
+
+    /// Look for data hidden in a png
+    Scan {
+        /// Png file, `-` for stdin, or the directory to scan with --recursive
         file: PathBuf,
 
-        chunk_type: String
+        /// Scan every png in the directory tree FILE, recognized by its signature whatever its
+        /// extension, and report on all of them together
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Descend at most this many directory levels below FILE
+        #[arg(long, value_name = "N", requires = "recursive")]
+        max_depth: Option<usize>,
+
+        /// Also scan files and directories whose name starts with a dot
+        #[arg(long, requires = "recursive")]
+        hidden: bool,
+
+        /// Skip files and directories whose name matches this pattern, like '*.bak' (repeatable)
+        #[arg(long, value_name = "PATTERN", requires = "recursive")]
+        exclude: Vec<String>,
+
+        /// Descend into symbolic links to directories
+        #[arg(long, requires = "recursive")]
+        follow_symlinks: bool,
+
+        /// List every pngme payload with its envelope format version
+        #[arg(long)]
+        pngme: bool,
+
+        /// Print the findings and pngme payloads as JSON
+        #[arg(long)]
+        json: bool
+    },
+
+    /// Show chunk statistics and structural issues
+    Stats {
+        /// Png file, `-` for stdin
+        file: PathBuf,
+
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool
     },
 
     /// Print png
     Print {
-        file: PathBuf
+        /// Png file, `-` for stdin
+        file: PathBuf,
+
+        /// Show the byte range of every chunk in the file
+        #[arg(long)]
+        offsets: bool,
+
+        /// Print the image header fields and the chunk list, with byte ranges, as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Dump the full data of every chunk as offset, hex and ASCII columns
+        #[arg(long, conflicts_with = "json")]
+        hex: bool
     },
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Subcommand)]
+pub(crate) enum IhdrAction {
+    /// Change header fields in place
+    Set(IhdrSet),
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct IhdrSet {
+    pub(crate) file: PathBuf,
+
+    /// 1 for Adam7 interlacing, 0 for none
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=1))]
+    pub(crate) interlace: Option<u8>,
+
+    /// Bits per sample; only accepted if the pixel data keeps its size
+    #[arg(long)]
+    pub(crate) bit_depth: Option<u8>,
+
+    /// PNG color type code (0, 2, 3, 4 or 6); only accepted if the pixel data keeps its size
+    #[arg(long)]
+    pub(crate) color_type: Option<u8>,
+
+    /// New width, requires --unsafe
+    #[arg(long)]
+    pub(crate) width: Option<u32>,
+
+    /// New height, requires --unsafe
+    #[arg(long)]
+    pub(crate) height: Option<u32>,
+
+    /// Allow width and height changes even though the pixel data won't match them
+    #[arg(long = "unsafe")]
+    pub(crate) allow_unsafe: bool,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Clone, Subcommand)]
+pub(crate) enum TextAction {
+    /// Store text under a keyword, replacing any tEXt, zTXt or iTXt chunk that already has it
+    Set(TextSet),
+    /// Print the text stored under a keyword
+    Get(TextKey),
+    /// Remove the text chunks with a keyword
+    Del(TextDel),
+    /// Rewrite the text chunks with a keyword as another text chunk type
+    Convert(TextConvert),
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct TextSet {
+    /// Png file, `-` to read stdin and write the result to stdout
+    pub(crate) file: PathBuf,
+
+    /// Keyword such as Title, Author or Comment: 1 to 79 Latin-1 characters
+    pub(crate) keyword: String,
+
+    /// Text to store, Latin-1 unless it goes in an iTXt chunk
+    pub(crate) text: String,
+
+    /// Chunk type to store the text in; defaults to the type of the chunk being replaced, or tEXt
+    #[arg(long = "type", value_name = "TYPE")]
+    pub(crate) kind: Option<TextKind>,
+
+    /// Language tag of the text, e.g. fr-CA, for iTXt chunks
+    #[arg(long, value_name = "TAG")]
+    pub(crate) language: Option<String>,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct TextKey {
+    /// Png file, `-` for stdin
+    pub(crate) file: PathBuf,
+
+    pub(crate) keyword: String,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct TextDel {
+    /// Png file, `-` to read stdin and write the result to stdout
+    pub(crate) file: PathBuf,
+
+    pub(crate) keyword: String,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct TextConvert {
+    /// Png file, `-` to read stdin and write the result to stdout
+    pub(crate) file: PathBuf,
+
+    pub(crate) keyword: String,
+
+    /// Chunk type to convert to
+    #[arg(long, value_name = "TYPE")]
+    pub(crate) to: TextKind,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Clone, Subcommand)]
+pub(crate) enum TimeAction {
+    /// Print the modification time as an RFC 3339 timestamp
+    Get(TimeGet),
+    /// Store a modification time
+    Set(TimeSet),
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct TimeGet {
+    /// Png file, `-` for stdin
+    pub(crate) file: PathBuf,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct TimeSet {
+    /// Png file, `-` to read stdin and write the result to stdout
+    pub(crate) file: PathBuf,
+
+    /// RFC 3339 timestamp, e.g. 2024-05-01T12:00:00Z, stored in UTC to the second; defaults
+    /// to now
+    #[arg(value_parser = parse_timestamp)]
+    pub(crate) timestamp: Option<LastModified>,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Clone, Subcommand)]
+pub(crate) enum DpiAction {
+    /// Print the resolution in dots per inch
+    Get(DpiGet),
+    /// Store a resolution, the same on both axes
+    Set(DpiSet),
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct DpiGet {
+    /// Png file, `-` for stdin
+    pub(crate) file: PathBuf,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct DpiSet {
+    /// Png file, `-` to read stdin and write the result to stdout
+    pub(crate) file: PathBuf,
+
+    /// Dots per inch, stored rounded to whole pixels per meter
+    #[arg(value_parser = parse_dpi)]
+    pub(crate) dpi: f64,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Clone, Subcommand)]
+pub(crate) enum ExifAction {
+    /// Print the camera, date and GPS position tags
+    Get(ExifFile),
+    /// Store an EXIF block, raw TIFF data or as copied from a JPEG APP1 segment
+    Set(ExifSet),
+    /// Remove the eXIf chunk and every tag in it
+    Strip(ExifStrip),
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct ExifFile {
+    /// Png file, `-` for stdin
+    pub(crate) file: PathBuf,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct ExifStrip {
+    /// Png file, `-` to read stdin and write the result to stdout
+    pub(crate) file: PathBuf,
+
+    /// List the chunks that would be removed without writing the file
+    #[arg(long)]
+    pub(crate) dry_run: bool,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct ExifSet {
+    /// Png file, `-` to read stdin and write the result to stdout
+    pub(crate) file: PathBuf,
+
+    /// File holding the EXIF data, `-` for stdin
+    pub(crate) exif: PathBuf,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Clone, Subcommand)]
+pub(crate) enum IccAction {
+    /// Write the decompressed profile to a file
+    Extract(IccExtract),
+    /// Store a profile, in place of the current one
+    Embed(IccEmbed),
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct IccExtract {
+    /// Png file, `-` for stdin
+    pub(crate) file: PathBuf,
+
+    /// Output file, `-` for stdout
+    #[arg(short, long, default_value = "-")]
+    pub(crate) output: PathBuf,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct IccEmbed {
+    /// Png file, `-` to read stdin and write the result to stdout
+    pub(crate) file: PathBuf,
+
+    /// ICC profile file, `-` for stdin
+    pub(crate) profile: PathBuf,
+
+    /// Profile name stored with it, 1 to 79 Latin-1 characters
+    #[arg(long, default_value = "ICC profile")]
+    pub(crate) name: String,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Clone, Subcommand)]
+pub(crate) enum XmpAction {
+    /// Print the XMP packet
+    Get(XmpGet),
+    /// Store an XMP packet, in place of the current one
+    Set(XmpSet),
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct XmpGet {
+    /// Png file, `-` for stdin
+    pub(crate) file: PathBuf,
+}
+
+#[derive(Args, Clone)]
+pub(crate) struct XmpSet {
+    /// Png file, `-` to read stdin and write the result to stdout
+    pub(crate) file: PathBuf,
+
+    /// File holding the XMP packet, stdin when left out or `-`
+    pub(crate) xmp: Option<PathBuf>,
+
+    /// Modify the file even if it is an Apple CgBI png
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+/// The FILE of a command: `file` and `file_mut` only differ in how they borrow it.
+macro_rules! command_file {
+    ($command:expr, $first:ident, $($borrow:tt)+) => {
+        match $command {
+            Commands::Encode { file, .. }
+            | Commands::Decode { file, .. }
+            | Commands::Remove { file, .. }
+            | Commands::Retype { file, .. }
+            | Commands::CopyChunk { file, .. }
+            | Commands::Extract { file, .. }
+            | Commands::Clean { file, .. }
+            | Commands::Stamp { file, .. }
+            | Commands::Crc { file, .. }
+            | Commands::Cat { file, .. }
+            | Commands::Merge { file, .. }
+            | Commands::Truncate { file, .. }
+            | Commands::Explode { file, .. }
+            | Commands::Assemble { dir: file, .. }
+            | Commands::Inspect { file, .. }
+            | Commands::List { file, .. }
+            | Commands::Scan { file, .. }
+            | Commands::Stats { file, .. }
+            | Commands::Print { file, .. }
+            | Commands::Palette { file, .. }
+            | Commands::Frames { file, .. }
+            | Commands::Capacity { file, .. }
+            | Commands::Verify { file, .. }
+            | Commands::Repair { file, .. } => file,
+            #[cfg(feature = "watch")]
+            Commands::Watch { file, .. } => file,
+            Commands::Ihdr { action: IhdrAction::Set(set) } => $($borrow)+ set.file,
+            Commands::Text { action: TextAction::Set(set) } => $($borrow)+ set.file,
+            Commands::Text { action: TextAction::Get(key) } => $($borrow)+ key.file,
+            Commands::Text { action: TextAction::Del(del) } => $($borrow)+ del.file,
+            Commands::Text { action: TextAction::Convert(convert) } => $($borrow)+ convert.file,
+            Commands::Time { action: TimeAction::Get(get) } => $($borrow)+ get.file,
+            Commands::Time { action: TimeAction::Set(set) } => $($borrow)+ set.file,
+            Commands::Dpi { action: DpiAction::Get(get) } => $($borrow)+ get.file,
+            Commands::Dpi { action: DpiAction::Set(set) } => $($borrow)+ set.file,
+            Commands::Exif { action: ExifAction::Get(get) } => $($borrow)+ get.file,
+            Commands::Exif { action: ExifAction::Strip(strip) } => $($borrow)+ strip.file,
+            Commands::Exif { action: ExifAction::Set(set) } => $($borrow)+ set.file,
+            Commands::Icc { action: IccAction::Extract(extract) } => $($borrow)+ extract.file,
+            Commands::Icc { action: IccAction::Embed(embed) } => $($borrow)+ embed.file,
+            Commands::Xmp { action: XmpAction::Get(get) } => $($borrow)+ get.file,
+            Commands::Xmp { action: XmpAction::Set(set) } => $($borrow)+ set.file,
+            Commands::Check { files, .. } => return files.$first(),
+        }
+    };
+}
+
+impl Commands {
+    /// The png file the command works on.
+    pub(crate) fn file(&self) -> Option<&PathBuf> {
+        let file = command_file!(self, first, &);
+        Some(file)
+    }
+
+    pub(crate) fn file_mut(&mut self) -> Option<&mut PathBuf> {
+        let file = command_file!(self, first_mut, &mut);
+        Some(file)
+    }
+
+    /// Whether `--force` was given to a command that modifies the file in place.
+    pub(crate) fn force(&self) -> bool {
+        match self {
+            Commands::Encode { force, .. }
+            | Commands::Remove { force, .. }
+            | Commands::Retype { force, .. }
+            | Commands::CopyChunk { force, .. }
+            | Commands::Merge { force, .. }
+            | Commands::Clean { force, .. }
+            | Commands::Stamp { force, .. }
+            | Commands::Truncate { force, .. }
+            | Commands::Repair { force, .. }
+            | Commands::Check { force, .. } => *force,
+            #[cfg(feature = "watch")]
+            Commands::Watch { force, .. } => *force,
+            Commands::Ihdr { action: IhdrAction::Set(IhdrSet { force, .. }) }
+            | Commands::Text { action: TextAction::Set(TextSet { force, .. }) }
+            | Commands::Text { action: TextAction::Del(TextDel { force, .. }) }
+            | Commands::Text { action: TextAction::Convert(TextConvert { force, .. }) }
+            | Commands::Time { action: TimeAction::Set(TimeSet { force, .. }) }
+            | Commands::Dpi { action: DpiAction::Set(DpiSet { force, .. }) }
+            | Commands::Exif { action: ExifAction::Set(ExifSet { force, .. }) }
+            | Commands::Exif { action: ExifAction::Strip(ExifStrip { force, .. }) }
+            | Commands::Xmp { action: XmpAction::Set(XmpSet { force, .. }) }
+            | Commands::Icc { action: IccAction::Embed(IccEmbed { force, .. }) } => *force,
+            _ => false,
+        }
+    }
+
+    /// The chunk type argument, for commands that take one.
+    pub(crate) fn chunk_type(&self) -> Option<&str> {
+        match self {
+            Commands::Encode { chunk_type, .. } => Some(chunk_type),
+            #[cfg(feature = "watch")]
+            Commands::Watch { chunk_type, .. } => chunk_type.as_deref(),
+            Commands::Remove { chunk_type, .. } | Commands::Decode { chunk_type, .. } | Commands::Extract { chunk_type, .. } => {
+                chunk_type.as_ref().map(|selector| selector.chunk_type.as_str())
+            }
+            Commands::Retype { old_type: selector, .. } | Commands::CopyChunk { chunk_type: selector, .. } => Some(&selector.chunk_type),
+            _ => None,
+        }
+    }
+}
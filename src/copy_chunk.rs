@@ -0,0 +1,100 @@
+use crate::error::PngmeError;
+use crate::order;
+use crate::png::Png;
+use crate::selector::ChunkSelector;
+use crate::validate::UNIQUE_CHUNK_TYPES;
+use crate::Result;
+
+/// Copies the chunks `selector` picks from `source` into `destination`, where the placement
+/// rules want them, and returns how many were copied. A chunk of a type the spec allows only
+/// once replaces the one `destination` already has.
+///
+/// Critical chunks describe the image they come with and are never copied. Ancillary chunks
+/// that aren't safe to copy depend on that image too, so they are only copied with `force`.
+pub(crate) fn copy_chunks(source: &Png, destination: &mut Png<'static>, selector: &ChunkSelector, force: bool) -> Result<usize> {
+    let matches = selector.matches(source);
+    if matches.is_empty() {
+        return Err(PngmeError::ChunkNotFound { chunk_type: selector.to_string() }.into());
+    }
+
+    let chunk_type = source[matches[0].1].chunk_type();
+    if chunk_type.is_critical() {
+        return Err(format!("{chunk_type} is a critical chunk, only ancillary chunks can be copied to another image").into());
+    }
+    if !chunk_type.is_safe_to_copy() && !force {
+        return Err(format!(
+            "{chunk_type} is not safe to copy, it may not match the image it is copied to; use --force to copy it anyway"
+        ).into());
+    }
+
+    let unique = UNIQUE_CHUNK_TYPES.contains(&chunk_type.to_string().as_str());
+    for &(_, index) in &matches {
+        let chunk = source[index].clone().into_owned();
+        match destination.chunks().position(|existing| existing.chunk_type() == chunk_type) {
+            Some(existing) if unique => drop(destination.replace_chunk_unchecked(existing, chunk)),
+            _ => order::insert_in_place(destination, chunk),
+        }
+    }
+    Ok(matches.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png(extra: Vec<Chunk<'static>>) -> Png<'static> {
+        let mut chunks = vec![chunk("IHDR", &[0; 13])];
+        chunks.extend(extra);
+        chunks.extend([chunk("IDAT", &[0; 8]), chunk("IEND", b"")]);
+        Png::from_chunks(chunks)
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_copy_chunks() {
+        let source = testing_png(vec![chunk("tEXt", b"Title\0One"), chunk("tEXt", b"Title\0Two"), chunk("ruSt", b"hidden")]);
+        let mut destination = testing_png(vec![]);
+        let selector = |s: &str| ChunkSelector::from_str(s).unwrap();
+
+        assert_eq!(copy_chunks(&source, &mut destination, &selector("tEXt[*]"), false).unwrap(), 2);
+        assert_eq!(copy_chunks(&source, &mut destination, &selector("ruSt"), false).unwrap(), 1);
+        assert_eq!(types(&destination), ["IHDR", "IDAT", "tEXt", "tEXt", "ruSt", "IEND"]);
+        assert_eq!(destination[4].data(), b"hidden");
+    }
+
+    #[test]
+    fn test_copy_unique_chunk_replaces_it() {
+        let phys = |dpi: u8| chunk("pHYs", &[0, 0, 0, dpi, 0, 0, 0, dpi, 1]);
+        let mut destination = testing_png(vec![phys(72)]);
+        let selector = ChunkSelector::from_str("pHYs").unwrap();
+
+        copy_chunks(&testing_png(vec![phys(96)]), &mut destination, &selector, false).unwrap();
+        assert_eq!(types(&destination), ["IHDR", "pHYs", "IDAT", "IEND"]);
+        assert_eq!(destination[1].data(), phys(96).data());
+    }
+
+    #[test]
+    fn test_copy_chunks_respects_safe_to_copy() {
+        let source = testing_png(vec![chunk("iCCP", b"Profile\0\0data"), chunk("PLTE", &[0; 3])]);
+        let mut destination = testing_png(vec![chunk("PLTE", &[0; 3])]);
+        let selector = |s: &str| ChunkSelector::from_str(s).unwrap();
+
+        assert!(copy_chunks(&source, &mut destination, &selector("iCCP"), false).unwrap_err().to_string().contains("--force"));
+        assert!(copy_chunks(&source, &mut destination, &selector("PLTE"), true).unwrap_err().to_string().contains("critical"));
+        assert!(copy_chunks(&source, &mut destination, &selector("noNe"), true).is_err());
+        assert!(!destination.is_modified());
+
+        copy_chunks(&source, &mut destination, &selector("iCCP"), true).unwrap();
+        assert_eq!(types(&destination), ["IHDR", "iCCP", "PLTE", "IDAT", "IEND"]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::io::{self, Read};
+
+use crate::chunk::CRC32;
+use crate::png::Png;
+use crate::Result;
+
+/// Stored and recomputed CRC of one chunk.
+#[derive(Debug, PartialEq)]
+pub(crate) struct CrcEntry {
+    pub(crate) index: usize,
+    pub(crate) chunk_type: String,
+    pub(crate) stored: u32,
+    pub(crate) computed: u32,
+}
+
+impl CrcEntry {
+    pub(crate) fn is_match(&self) -> bool {
+        self.stored == self.computed
+    }
+
+    /// Tab separated row: index, type, stored CRC, computed CRC, match status.
+    pub(crate) fn row(&self) -> String {
+        let status = if self.is_match() { "ok" } else { "mismatch" };
+        format!("{}\t{}\t{:08x}\t{:08x}\t{status}", self.index, self.chunk_type, self.stored, self.computed)
+    }
+}
+
+/// Fills `buf`, returning false on a clean end of input before the first byte.
+fn read_header(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err("Chunk header is truncated".into()),
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(true)
+}
+
+/// Reads every chunk's CRC from `reader` without keeping chunk data around.
+///
+/// Unlike `Png::try_from`, a CRC mismatch doesn't stop the reader: it is recorded and the next
+/// chunk is read, unless `fail_fast` is set.
+pub(crate) fn read_crcs(mut reader: impl Read, fail_fast: bool) -> Result<Vec<CrcEntry>> {
+    let mut signature = [0; 8];
+    reader.read_exact(&mut signature)?;
+    if signature != Png::STANDARD_HEADER {
+        return Err("Data header should match the standard PNG header".into());
+    }
+
+    let mut entries = Vec::new();
+    let mut header = [0; 8];
+    let mut buf = [0; 8192];
+
+    while read_header(&mut reader, &mut header)? {
+        let mut remaining = u32::from_be_bytes(header[..4].try_into().expect("Chunk length slice should be of length 4")) as usize;
+
+        let mut digest = CRC32.digest();
+        digest.update(&header[4..]);
+        while remaining > 0 {
+            let len = remaining.min(buf.len());
+            reader.read_exact(&mut buf[..len])?;
+            digest.update(&buf[..len]);
+            remaining -= len;
+        }
+
+        let mut stored = [0; 4];
+        reader.read_exact(&mut stored)?;
+
+        let entry = CrcEntry {
+            index: entries.len(),
+            chunk_type: String::from_utf8_lossy(&header[4..]).into_owned(),
+            stored: u32::from_be_bytes(stored),
+            computed: digest.finalize(),
+        };
+        let stop = fail_fast && !entry.is_match();
+        entries.push(entry);
+        if stop {
+            break;
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"I am the first chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("miDl").unwrap(), b"I am another chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), b"I am the last chunk".to_vec()),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_read_crcs() {
+        let entries = read_crcs(testing_bytes().as_slice(), false).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(CrcEntry::is_match));
+        assert_eq!(entries[1].chunk_type, "miDl");
+    }
+
+    #[test]
+    fn test_read_crcs_corrupt_middle_chunk() {
+        let mut bytes = testing_bytes();
+        // Signature (8) + first chunk (32) + second chunk length and type (8) + 2 data bytes
+        bytes[50] ^= 0xff;
+
+        let entries = read_crcs(bytes.as_slice(), false).unwrap();
+        let mismatches: Vec<&CrcEntry> = entries.iter().filter(|entry| !entry.is_match()).collect();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].index, 1);
+        assert!(mismatches[0].row().ends_with("\tmismatch"));
+    }
+
+    #[test]
+    fn test_read_crcs_fail_fast() {
+        let mut bytes = testing_bytes();
+        bytes[50] ^= 0xff;
+
+        let entries = read_crcs(bytes.as_slice(), true).unwrap();
+
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn test_read_crcs_truncated() {
+        let bytes = testing_bytes();
+
+        assert!(read_crcs(&bytes[..bytes.len() - 2], false).is_err());
+    }
+}
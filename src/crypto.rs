@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::signature::{self, Ed25519KeyPair, UnparsedPublicKey};
+
+use crate::Result;
+
+/// Length of an ed25519 signature.
+pub(crate) const SIGNATURE_LEN: usize = 64;
+
+/// Length of a raw ed25519 seed or public key.
+const KEY_LEN: usize = 32;
+
+/// DER prefix of an ed25519 SubjectPublicKeyInfo, which the 32 key bytes follow.
+const SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// The DER bytes of the `-----BEGIN <label>-----` block of `contents`, if it is PEM text.
+fn pem_block(contents: &[u8], label: &str) -> Option<std::result::Result<Vec<u8>, String>> {
+    let text = std::str::from_utf8(contents).ok()?;
+    let begin = format!("-----BEGIN {label}-----");
+    let start = text.find(&begin)? + begin.len();
+    let Some(end) = text[start..].find(&format!("-----END {label}-----")) else {
+        return Some(Err(format!("PEM {label} block has no END line")));
+    };
+    let digits: String = text[start..start + end].split_ascii_whitespace().collect();
+    Some(BASE64.decode(digits).map_err(|error| error.to_string()))
+}
+
+/// An ed25519 private key, for `encode --sign`.
+#[derive(Debug)]
+pub(crate) struct SigningKey(Ed25519KeyPair);
+
+impl SigningKey {
+    /// Reads a PEM `PRIVATE KEY` (PKCS#8, as `openssl genpkey -algorithm ed25519` writes it)
+    /// or a raw 32-byte seed.
+    pub(crate) fn load(path: &Path) -> Result<SigningKey> {
+        let contents = fs::read(path)?;
+        SigningKey::parse(&contents).map_err(|reason| format!("Can't load the signing key {}: {reason}", path.display()).into())
+    }
+
+    pub(crate) fn parse(contents: &[u8]) -> std::result::Result<SigningKey, String> {
+        let key_pair = match pem_block(contents, "PRIVATE KEY") {
+            Some(der) => Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der?),
+            None if contents.len() == KEY_LEN => Ed25519KeyPair::from_seed_unchecked(contents),
+            None => return Err(format!("expected a PEM PRIVATE KEY block or a raw {KEY_LEN}-byte seed, got {} bytes", contents.len())),
+        };
+        key_pair.map(SigningKey).map_err(|error| format!("not an ed25519 key: {error}"))
+    }
+
+    pub(crate) fn sign(&self, data: &[u8]) -> [u8; SIGNATURE_LEN] {
+        self.0.sign(data).as_ref().try_into().expect("ed25519 signatures are 64 bytes long")
+    }
+
+    /// The public key matching this one, which only tests need: users derive it with openssl.
+    #[cfg(test)]
+    pub(crate) fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(signature::KeyPair::public_key(&self.0).as_ref().try_into().expect("ed25519 public keys are 32 bytes long"))
+    }
+}
+
+/// An ed25519 public key, for `decode --verify`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct VerifyingKey([u8; KEY_LEN]);
+
+impl VerifyingKey {
+    /// Reads a PEM `PUBLIC KEY` (SubjectPublicKeyInfo, as `openssl pkey -pubout` writes it)
+    /// or a raw 32-byte key.
+    pub(crate) fn load(path: &Path) -> Result<VerifyingKey> {
+        let contents = fs::read(path)?;
+        VerifyingKey::parse(&contents).map_err(|reason| format!("Can't load the public key {}: {reason}", path.display()).into())
+    }
+
+    fn parse(contents: &[u8]) -> std::result::Result<VerifyingKey, String> {
+        let raw = match pem_block(contents, "PUBLIC KEY") {
+            Some(der) => {
+                let der = der?;
+                der.strip_prefix(SPKI_PREFIX.as_slice()).ok_or("not an ed25519 public key")?.to_vec()
+            }
+            None => contents.to_vec(),
+        };
+        raw.as_slice()
+            .try_into()
+            .map(VerifyingKey)
+            .map_err(|_| format!("expected a PEM PUBLIC KEY block or a raw {KEY_LEN}-byte key, got {} bytes", raw.len()))
+    }
+
+    /// Checks that `signature` was made over `data` by the matching private key.
+    pub(crate) fn verify(&self, data: &[u8], signature: &[u8]) -> std::result::Result<(), String> {
+        UnparsedPublicKey::new(&signature::ED25519, &self.0)
+            .verify(data, signature)
+            .map_err(|_| "signature doesn't match the public key: the payload was modified, or signed with another key".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+
+    fn pem(label: &str, der: &[u8]) -> Vec<u8> {
+        format!("-----BEGIN {label}-----\n{}\n-----END {label}-----\n", BASE64.encode(der)).into_bytes()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key = SigningKey::parse(&pem("PRIVATE KEY", pkcs8.as_ref())).unwrap();
+        let public = key.verifying_key();
+
+        let signature = key.sign(b"payload");
+        assert_eq!(public.verify(b"payload", &signature), Ok(()));
+        assert!(public.verify(b"Payload", &signature).unwrap_err().contains("modified"));
+
+        let spki = [SPKI_PREFIX.as_slice(), &public.0].concat();
+        assert_eq!(VerifyingKey::parse(&pem("PUBLIC KEY", &spki)).unwrap(), public);
+        assert_eq!(VerifyingKey::parse(&public.0).unwrap(), public);
+    }
+
+    #[test]
+    fn test_raw_seed() {
+        let key = SigningKey::parse(&[7; KEY_LEN]).unwrap();
+        let again = SigningKey::parse(&[7; KEY_LEN]).unwrap();
+
+        assert_eq!(key.verifying_key(), again.verifying_key());
+        assert!(SigningKey::parse(&[7; 31]).unwrap_err().contains("31 bytes"));
+        assert!(VerifyingKey::parse(&pem("PUBLIC KEY", &[0; 44])).is_err());
+    }
+}
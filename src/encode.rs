@@ -0,0 +1,286 @@
+use std::fmt;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::codec::{self, Settings};
+use crate::error::PngmeError;
+use crate::png::Png;
+use crate::split;
+use crate::Result;
+
+/// Exit code used when an encode would push the file over `--max-output-size`.
+pub(crate) const SIZE_BUDGET_EXIT_CODE: i32 = 3;
+
+/// The encoded file would be larger than the configured budget.
+#[derive(Debug, PartialEq)]
+pub struct SizeBudgetError {
+    pub(crate) current: u64,
+    pub(crate) payload: u64,
+    pub(crate) projected: u64,
+    pub(crate) limit: u64,
+}
+
+impl fmt::Display for SizeBudgetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Output would be {} bytes, over the {} bytes limit (current size {} bytes, payload {} bytes)",
+            self.projected, self.limit, self.current, self.payload
+        )
+    }
+}
+
+impl std::error::Error for SizeBudgetError {}
+
+/// Where `pngme encode` stores the payload and `pngme decode` looks for it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Mode {
+    /// In an ancillary chunk of its own.
+    #[default]
+    Chunk,
+    /// In the least significant bits of the image samples, which survives tools that strip
+    /// unknown chunks.
+    Lsb,
+}
+
+/// Where `pngme encode` puts the new chunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum Position {
+    /// Before IEND, so readers that stop there still see the chunks, and the file stays valid.
+    #[default]
+    BeforeIend,
+    /// Right after IHDR, or at the start when there is none.
+    AfterIhdr,
+    /// So that the first chunk ends up at this index.
+    Index(usize),
+}
+
+impl Position {
+    /// Where in `png` the first inserted chunk goes.
+    pub(crate) fn index(self, png: &Png) -> std::result::Result<usize, String> {
+        let position_of = |chunk_type: &[u8; 4]| png.chunks().position(|chunk| chunk.chunk_type().bytes() == *chunk_type);
+        match self {
+            Position::BeforeIend => Ok(position_of(b"IEND").unwrap_or(png.chunk_count())),
+            Position::AfterIhdr => Ok(position_of(b"IHDR").map_or(0, |index| index + 1)),
+            Position::Index(index) if index > png.chunk_count() => {
+                Err(format!("Chunk index {index} is out of range, the file has {} chunks", png.chunk_count()))
+            }
+            Position::Index(index) => Ok(index),
+        }
+    }
+
+    /// Inserts `chunks`, in order, at the position in `png`.
+    pub(crate) fn insert<'a>(self, png: &mut Png<'a>, chunks: &[Chunk<'a>]) -> std::result::Result<(), String> {
+        let index = self.index(png)?;
+        for (offset, chunk) in chunks.iter().enumerate() {
+            png.insert_chunk_at_unchecked(index + offset, chunk.clone());
+        }
+        Ok(())
+    }
+}
+
+/// Parses `--position`: `before-iend`, `after-ihdr`, or `index N` (also `index=N`).
+pub(crate) fn parse_position(s: &str) -> std::result::Result<Position, String> {
+    match s {
+        "before-iend" => Ok(Position::BeforeIend),
+        "after-ihdr" => Ok(Position::AfterIhdr),
+        _ => s
+            .strip_prefix("index")
+            .map(|rest| rest.trim_start_matches(['=', ' ']))
+            .and_then(|index| index.parse().ok())
+            .map(Position::Index)
+            .ok_or_else(|| format!("expected before-iend, after-ihdr or index N, got {s:?}")),
+    }
+}
+
+/// Size of `png` once `chunks` have been added.
+pub(crate) fn projected_len(png: &Png, chunks: &[Chunk]) -> u64 {
+    png.serialized_len() + chunks.iter().map(Chunk::serialized_len).sum::<u64>()
+}
+
+/// Fails when adding `chunks` to `png` would produce a file larger than `limit` bytes.
+pub(crate) fn check_size_budget(png: &Png, chunks: &[Chunk], limit: u64) -> std::result::Result<u64, SizeBudgetError> {
+    let projected = projected_len(png, chunks);
+
+    if projected > limit {
+        return Err(SizeBudgetError {
+            current: png.serialized_len(),
+            payload: chunks.iter().map(|chunk| chunk.data().len() as u64).sum(),
+            projected,
+            limit,
+        });
+    }
+
+    Ok(projected)
+}
+
+/// Fails when `png` has no IDAT chunk: data hidden in a file no viewer opens doesn't stay
+/// hidden for long.
+pub(crate) fn check_has_image(png: &Png) -> Result<()> {
+    if !png.has_image_data() {
+        return Err("File has no IDAT chunk, so no viewer will open it; use --allow-no-image to encode anyway".into());
+    }
+    Ok(())
+}
+
+/// Builds the chunk written by `pngme encode`.
+///
+/// Unless `raw_payload` is set, the content goes through the codec stages selected by
+/// `flags` and is wrapped in an envelope. Chunk types with a lowercase third letter are refused
+/// unless `force` is set, so pngme doesn't create new chunks the spec reserves.
+pub(crate) fn build_chunk(chunk_type: &str, content: &[u8], raw_payload: bool, flags: u8, settings: Settings, force: bool) -> Result<Chunk<'static>> {
+    let chunk_type = checked_chunk_type(chunk_type, force)?;
+
+    let data = if raw_payload {
+        content.to_vec()
+    } else {
+        codec::seal(content, flags, settings)?
+    };
+
+    Ok(Chunk::try_new(chunk_type, data)?)
+}
+
+/// Like `build_chunk` for an enveloped payload, but one sealed to more than `max_chunk_size`
+/// bytes, or more than a chunk can hold, is split across several chunks of the type.
+pub(crate) fn build_split_chunks(chunk_type: &str, content: &[u8], flags: u8, settings: Settings, force: bool, max_chunk_size: usize) -> Result<Vec<Chunk<'static>>> {
+    let chunk_type = checked_chunk_type(chunk_type, force)?;
+    let sealed = codec::seal(content, flags, settings)?;
+
+    split::split(&sealed, max_chunk_size.min(Chunk::MAX_DATA_LEN))?
+        .into_iter()
+        .map(|data| Ok(Chunk::try_new(chunk_type.clone(), data)?))
+        .collect()
+}
+
+pub(crate) fn checked_chunk_type(chunk_type: &str, force: bool) -> Result<ChunkType> {
+    let chunk_type = ChunkType::from_str(chunk_type)
+        .map_err(|reason| PngmeError::InvalidChunkType { reason: reason.to_string() })?;
+
+    if chunk_type.has_invalid_reserved_bit() && !force {
+        return Err(format!(
+            "{chunk_type} has an invalid reserved bit (third letter must be uppercase), use --force to write it anyway"
+        ).into());
+    }
+    Ok(chunk_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_chunk() {
+        let chunk = build_chunk("ruSt", b"hello", false, 0, Settings::default(), false).unwrap();
+
+        assert_eq!(chunk.chunk_type().to_string(), "ruSt");
+        assert_eq!(codec::open(chunk.data()).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_build_raw_chunk() {
+        let chunk = build_chunk("ruSt", b"hello", true, 0, Settings::default(), false).unwrap();
+
+        assert_eq!(chunk.data(), b"hello");
+    }
+
+    #[test]
+    fn test_build_chunk_invalid_reserved_bit() {
+        assert!(build_chunk("rust", b"hello", false, 0, Settings::default(), false).is_err());
+
+        let chunk = build_chunk("rust", b"hello", false, 0, Settings::default(), true).unwrap();
+        assert!(chunk.chunk_type().has_invalid_reserved_bit());
+    }
+
+    #[test]
+    fn test_projected_len_matches_output() {
+        let mut png = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13])]);
+        let chunk = build_chunk("ruSt", b"hello", false, 0, Settings::default(), false).unwrap();
+
+        let projected = projected_len(&png, std::slice::from_ref(&chunk));
+        png.append_chunk(chunk);
+
+        assert_eq!(projected, png.as_bytes().len() as u64);
+    }
+
+    #[test]
+    fn test_position_insert() {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![]);
+        let testing_png = || Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")]);
+        let types = |position: Position| {
+            let mut png = testing_png();
+            position.insert(&mut png, &[chunk("ruSt"), chunk("ruSu")]).unwrap();
+            png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(types(Position::BeforeIend), ["IHDR", "IDAT", "ruSt", "ruSu", "IEND"]);
+        assert_eq!(types(Position::AfterIhdr), ["IHDR", "ruSt", "ruSu", "IDAT", "IEND"]);
+        assert_eq!(types(Position::Index(3)), ["IHDR", "IDAT", "IEND", "ruSt", "ruSu"]);
+        assert_eq!(
+            Position::Index(4).insert(&mut testing_png(), &[chunk("ruSt")]),
+            Err("Chunk index 4 is out of range, the file has 3 chunks".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_position() {
+        assert_eq!(parse_position("before-iend"), Ok(Position::BeforeIend));
+        assert_eq!(parse_position("after-ihdr"), Ok(Position::AfterIhdr));
+        assert_eq!(parse_position("index 2"), Ok(Position::Index(2)));
+        assert_eq!(parse_position("index=0"), Ok(Position::Index(0)));
+        assert!(parse_position("index").is_err());
+        assert!(parse_position("end").is_err());
+    }
+
+    #[test]
+    fn test_check_size_budget() {
+        let png = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13])]);
+        let chunk = build_chunk("ruSt", b"hello", true, 0, Settings::default(), false).unwrap();
+        let chunks = std::slice::from_ref(&chunk);
+
+        // 8 byte signature + 25 byte IHDR + 17 byte ruSt chunk
+        assert_eq!(check_size_budget(&png, chunks, 50), Ok(50));
+        assert_eq!(
+            check_size_budget(&png, chunks, 49),
+            Err(SizeBudgetError { current: 33, payload: 5, projected: 50, limit: 49 })
+        );
+    }
+
+    #[test]
+    fn test_build_chunk_non_alphabetic() {
+        assert!(build_chunk("ru1t", b"hello", false, 0, Settings::default(), true).is_err());
+    }
+
+    #[test]
+    fn test_build_chunk_with_checksum() {
+        let chunk = build_chunk("ruSt", b"hello", false, crate::envelope::FLAG_CHECKSUM, Settings::default(), false).unwrap();
+
+        assert_eq!(chunk.data().len(), 7 + 5 + 4);
+        assert_eq!(codec::open(chunk.data()).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_build_split_chunks() {
+        let content = vec![b'x'; 100];
+
+        let chunks = build_split_chunks("ruSt", &content, 0, Settings::default(), false, 50).unwrap();
+
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|chunk| chunk.data().len() <= 50));
+        let png = Png::from_chunks(chunks);
+        assert_eq!(codec::open(&split::join(&png, "ruSt").unwrap()).unwrap(), content);
+
+        assert_eq!(build_split_chunks("ruSt", &content, 0, Settings::default(), false, 200).unwrap().len(), 1);
+        assert!(build_split_chunks("rust", &content, 0, Settings::default(), false, 50).is_err());
+    }
+
+    #[test]
+    fn test_build_chunk_checks_standard_sizes() {
+        assert!(build_chunk("gAMA", &[0, 0, 177, 143], true, 0, Settings::default(), false).is_ok());
+        assert!(build_chunk("gAMA", b"hello", true, 0, Settings::default(), false).is_err());
+    }
+}
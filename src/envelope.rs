@@ -0,0 +1,201 @@
+use std::fmt;
+
+/// Marker at the start of every payload written by pngme.
+pub(crate) const MAGIC: [u8; 5] = *b"pngme";
+
+/// Envelope format written by this version of pngme.
+pub(crate) const VERSION: u8 = 1;
+
+pub(crate) const FLAG_COMPRESSED: u8 = 0b0000_0001;
+pub(crate) const FLAG_ENCRYPTED: u8 = 0b0000_0010;
+/// The payload ends with a CRC32 of its content.
+pub(crate) const FLAG_CHECKSUM: u8 = 0b0000_0100;
+/// The payload is one part of a payload split across several chunks, see `split`.
+pub(crate) const FLAG_PART: u8 = 0b0000_1000;
+/// The payload ends with an ed25519 signature, see `crypto`.
+pub(crate) const FLAG_SIGNED: u8 = 0b0001_0000;
+/// The payload ends with an HMAC-SHA256 tag keyed by a passphrase.
+pub(crate) const FLAG_HMAC: u8 = 0b0010_0000;
+
+/// Names of the envelope flags, in bit order.
+const FLAG_NAMES: [(u8, &str); 6] = [
+    (FLAG_COMPRESSED, "compressed"),
+    (FLAG_ENCRYPTED, "encrypted"),
+    (FLAG_CHECKSUM, "checksum"),
+    (FLAG_PART, "part"),
+    (FLAG_SIGNED, "signed"),
+    (FLAG_HMAC, "hmac"),
+];
+
+/// The names of the known flags set in `flags`, in bit order.
+pub(crate) fn flag_names(flags: u8) -> Vec<&'static str> {
+    FLAG_NAMES.iter().filter(|(flag, _)| flags & flag != 0).map(|&(_, name)| name).collect()
+}
+
+pub(crate) const HEADER_LEN: usize = MAGIC.len() + 2;
+
+/// Header prepended to pngme payloads: magic bytes, format version and flags.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct EnvelopeHeader {
+    pub(crate) version: u8,
+    pub(crate) flags: u8,
+}
+
+impl EnvelopeHeader {
+    pub(crate) fn new(flags: u8) -> EnvelopeHeader {
+        EnvelopeHeader { version: VERSION, flags }
+    }
+
+    /// Reads the header at the start of `data`, whatever its version.
+    pub(crate) fn parse(data: &[u8]) -> Option<EnvelopeHeader> {
+        if data.len() < HEADER_LEN || data[..MAGIC.len()] != MAGIC {
+            return None;
+        }
+
+        Some(EnvelopeHeader { version: data[MAGIC.len()], flags: data[MAGIC.len() + 1] })
+    }
+
+    /// Whether this build knows how to read the envelope's format version.
+    pub(crate) fn is_supported(&self) -> bool {
+        (1..=VERSION).contains(&self.version)
+    }
+
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.flags & FLAG_COMPRESSED != 0
+    }
+
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.flags & FLAG_ENCRYPTED != 0
+    }
+
+    pub(crate) fn as_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[..MAGIC.len()].copy_from_slice(&MAGIC);
+        bytes[MAGIC.len()] = self.version;
+        bytes[MAGIC.len() + 1] = self.flags;
+        bytes
+    }
+}
+
+/// An envelope whose format version this build can't read.
+#[derive(Debug, PartialEq)]
+pub(crate) struct UnsupportedVersion {
+    pub(crate) version: u8,
+}
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.version > VERSION {
+            write!(f, "payload was written by a newer pngme (format v{}); please upgrade", self.version)
+        } else {
+            write!(f, "payload has an unknown format version v{}", self.version)
+        }
+    }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+/// Reads the envelope at the start of `data` with the parser for its format version, and
+/// returns its header with the bytes it wraps.
+///
+/// Returns `Ok(None)` for data that isn't an envelope. Every format version ever written
+/// keeps its parser here, so old payloads stay readable.
+pub(crate) fn read(data: &[u8]) -> Result<Option<(EnvelopeHeader, &[u8])>, UnsupportedVersion> {
+    let Some(header) = EnvelopeHeader::parse(data) else {
+        return Ok(None);
+    };
+
+    match header.version {
+        1 => Ok(Some(read_v1(data))),
+        version => Err(UnsupportedVersion { version }),
+    }
+}
+
+/// v1: magic, version byte, flags byte, then the payload as transformed by the flagged codecs.
+fn read_v1(data: &[u8]) -> (EnvelopeHeader, &[u8]) {
+    (EnvelopeHeader { version: 1, flags: data[MAGIC.len() + 1] }, &data[HEADER_LEN..])
+}
+
+/// Prepends an envelope header to `payload`.
+pub(crate) fn wrap(header: EnvelopeHeader, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = header.as_bytes().to_vec();
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/// Returns the payload inside an envelope, or `data` itself when it isn't one.
+pub(crate) fn unwrap(data: &[u8]) -> &[u8] {
+    match EnvelopeHeader::parse(data) {
+        Some(_) => &data[HEADER_LEN..],
+        None => data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_unwrap() {
+        let wrapped = wrap(EnvelopeHeader::new(0), b"secret");
+
+        assert_eq!(&wrapped[..5], b"pngme");
+        assert_eq!(unwrap(&wrapped), b"secret");
+    }
+
+    #[test]
+    fn test_unwrap_raw_data() {
+        assert_eq!(unwrap(b"not an envelope"), b"not an envelope");
+        assert_eq!(unwrap(b"pngme"), b"pngme");
+    }
+
+    #[test]
+    fn test_parse_header_flags() {
+        let wrapped = wrap(EnvelopeHeader::new(FLAG_ENCRYPTED), b"secret");
+        let header = EnvelopeHeader::parse(&wrapped).unwrap();
+
+        assert_eq!(header.version, VERSION);
+        assert!(header.is_encrypted());
+        assert!(!header.is_compressed());
+    }
+
+    /// Payloads as written by pngme releases using format v1. Never change these: they make
+    /// sure old files stay readable.
+    const V1_PLAIN: [u8; 12] = [0x70, 0x6e, 0x67, 0x6d, 0x65, 0x01, 0x00, 0x68, 0x65, 0x6c, 0x6c, 0x6f];
+    const V1_CHECKSUM: [u8; 16] = [
+        0x70, 0x6e, 0x67, 0x6d, 0x65, 0x01, 0x04, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x36, 0x10, 0xa6, 0x86,
+    ];
+
+    #[test]
+    fn test_read_v1_fixtures() {
+        let (header, body) = read(&V1_PLAIN).unwrap().unwrap();
+        assert_eq!(header, EnvelopeHeader { version: 1, flags: 0 });
+        assert_eq!(body, b"hello");
+
+        let (header, body) = read(&V1_CHECKSUM).unwrap().unwrap();
+        assert_eq!(header.flags, FLAG_CHECKSUM);
+        assert_eq!(body, b"hello\x36\x10\xa6\x86");
+    }
+
+    #[test]
+    fn test_read_newer_version() {
+        let wrapped = wrap(EnvelopeHeader { version: 3, flags: 0 }, b"secret");
+
+        let error = read(&wrapped).unwrap_err();
+        assert_eq!(error, UnsupportedVersion { version: 3 });
+        assert_eq!(error.to_string(), "payload was written by a newer pngme (format v3); please upgrade");
+        assert!(!EnvelopeHeader::parse(&wrapped).unwrap().is_supported());
+    }
+
+    #[test]
+    fn test_read_raw_data() {
+        assert_eq!(read(b"not an envelope"), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_header_any_version() {
+        let wrapped = wrap(EnvelopeHeader { version: 9, flags: 0 }, b"secret");
+
+        assert_eq!(EnvelopeHeader::parse(&wrapped).unwrap().version, 9);
+    }
+}
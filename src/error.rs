@@ -0,0 +1,265 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::encode::{SizeBudgetError, SIZE_BUDGET_EXIT_CODE};
+use crate::json_path::JsonPath;
+use crate::lock::{LockTimeout, LOCK_TIMEOUT_EXIT_CODE};
+use crate::output::{RoundtripMismatch, SerializationMismatch, Unchanged, UNCHANGED_EXIT_CODE};
+use crate::timeout::{TimedOut, TIMEOUT_EXIT_CODE};
+use crate::Error;
+
+/// Errors reported by the command line, and by `Png::parse_untrusted`.
+///
+/// The variant names, as returned by `code`, are part of the `--errors json` output and must
+/// stay stable across releases.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PngmeError {
+    Io { path: Option<PathBuf>, source: io::Error },
+    InvalidPng { path: Option<PathBuf>, reason: String },
+    ChunkNotFound { chunk_type: String },
+    InvalidChunkType { reason: String },
+    /// Chunk data that can't be written legally, or doesn't fit the size its type requires.
+    InvalidChunkData { chunk_type: String, reason: String },
+    /// An in-place edit of an Apple CgBI png without `--force`.
+    AppleCgbi { path: PathBuf },
+    SizeBudgetExceeded(SizeBudgetError),
+    Unchanged,
+    SerializationMismatch(SerializationMismatch),
+    RoundtripMismatch(RoundtripMismatch),
+    TimedOut(TimedOut),
+    LockTimeout(LockTimeout),
+    Other(String),
+}
+
+impl PngmeError {
+    /// Stable snake_case name of the variant.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PngmeError::Io { .. } => "io",
+            PngmeError::InvalidPng { .. } => "invalid_png",
+            PngmeError::ChunkNotFound { .. } => "chunk_not_found",
+            PngmeError::InvalidChunkType { .. } => "invalid_chunk_type",
+            PngmeError::InvalidChunkData { .. } => "invalid_chunk_data",
+            PngmeError::AppleCgbi { .. } => "apple_cgbi",
+            PngmeError::SizeBudgetExceeded(_) => "size_budget_exceeded",
+            PngmeError::Unchanged => "unchanged",
+            PngmeError::SerializationMismatch(_) => "serialization_mismatch",
+            PngmeError::RoundtripMismatch(_) => "roundtrip_mismatch",
+            PngmeError::TimedOut(_) => "timed_out",
+            PngmeError::LockTimeout(_) => "lock_timeout",
+            PngmeError::Other(_) => "other",
+        }
+    }
+
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            PngmeError::SizeBudgetExceeded(_) => SIZE_BUDGET_EXIT_CODE,
+            PngmeError::Unchanged => UNCHANGED_EXIT_CODE,
+            PngmeError::TimedOut(_) => TIMEOUT_EXIT_CODE,
+            PngmeError::LockTimeout(_) => LOCK_TIMEOUT_EXIT_CODE,
+            _ => 1,
+        }
+    }
+
+    /// The file the error is about, when it carries one.
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            PngmeError::Io { path, .. } | PngmeError::InvalidPng { path, .. } => path.as_ref(),
+            PngmeError::RoundtripMismatch(error) => Some(&error.path),
+            PngmeError::AppleCgbi { path } => Some(path),
+            PngmeError::LockTimeout(error) => Some(&error.path),
+            _ => None,
+        }
+    }
+
+    /// The chunk type the error is about, when it carries one.
+    pub fn chunk_type(&self) -> Option<&str> {
+        match self {
+            PngmeError::ChunkNotFound { chunk_type } | PngmeError::InvalidChunkData { chunk_type, .. } => Some(chunk_type),
+            _ => None,
+        }
+    }
+
+    /// Recovers the typed error behind a boxed one, falling back to `Other`.
+    pub(crate) fn from_boxed(error: Error) -> PngmeError {
+        let error = match error.downcast::<PngmeError>() {
+            Ok(error) => return *error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<SizeBudgetError>() {
+            Ok(error) => return PngmeError::SizeBudgetExceeded(*error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<SerializationMismatch>() {
+            Ok(error) => return PngmeError::SerializationMismatch(*error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<RoundtripMismatch>() {
+            Ok(error) => return PngmeError::RoundtripMismatch(*error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<TimedOut>() {
+            Ok(error) => return PngmeError::TimedOut(*error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<LockTimeout>() {
+            Ok(error) => return PngmeError::LockTimeout(*error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<io::Error>() {
+            Ok(error) => return PngmeError::Io { path: None, source: *error },
+            Err(error) => error,
+        };
+        if error.is::<Unchanged>() {
+            return PngmeError::Unchanged;
+        }
+
+        PngmeError::Other(error.to_string())
+    }
+}
+
+impl fmt::Display for PngmeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PngmeError::Io { path: Some(path), source } => write!(f, "{}: {source}", path.display()),
+            PngmeError::Io { path: None, source } => write!(f, "{source}"),
+            PngmeError::InvalidPng { path: Some(path), reason } => write!(f, "{} isn't a valid PNG: {reason}", path.display()),
+            PngmeError::InvalidPng { path: None, reason } => write!(f, "Not a valid PNG: {reason}"),
+            PngmeError::ChunkNotFound { chunk_type } => write!(f, "There are no chunk of type {chunk_type}"),
+            PngmeError::InvalidChunkType { reason } => write!(f, "Invalid chunk type: {reason}"),
+            PngmeError::InvalidChunkData { chunk_type, reason } => write!(f, "Invalid {chunk_type} chunk: {reason}"),
+            PngmeError::AppleCgbi { path } => write!(
+                f,
+                "{} is an Apple CgBI png (iOS-optimized, proprietary format), use --force to modify it anyway",
+                path.display()
+            ),
+            PngmeError::SizeBudgetExceeded(error) => write!(f, "{error}"),
+            PngmeError::Unchanged => write!(f, "{Unchanged}"),
+            PngmeError::SerializationMismatch(error) => write!(f, "{error}"),
+            PngmeError::RoundtripMismatch(error) => write!(f, "{error}"),
+            PngmeError::TimedOut(error) => write!(f, "{error}"),
+            PngmeError::LockTimeout(error) => write!(f, "{error}"),
+            PngmeError::Other(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for PngmeError {}
+
+/// JSON object written to stderr for a failure with `--errors json`.
+///
+/// `path` is a string and may be lossy; `path_base64` holds the exact bytes of paths that
+/// aren't valid UTF-8 (see `JsonPath`).
+#[derive(Debug, Serialize)]
+pub(crate) struct ErrorReport {
+    pub(crate) code: &'static str,
+    pub(crate) message: String,
+    #[serde(flatten)]
+    pub(crate) path: Option<JsonPath>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) chunk_type: Option<String>,
+}
+
+impl ErrorReport {
+    /// Builds the report, using the command's file and chunk type when the error doesn't
+    /// carry its own.
+    pub(crate) fn new(error: &PngmeError, path: Option<&PathBuf>, chunk_type: Option<&str>) -> ErrorReport {
+        ErrorReport {
+            code: error.code(),
+            message: error.to_string(),
+            path: error.path().or(path).cloned().map(JsonPath::from),
+            chunk_type: error.chunk_type().or(chunk_type).map(str::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report_json(error: Error, path: Option<&PathBuf>, chunk_type: Option<&str>) -> serde_json::Value {
+        let error = PngmeError::from_boxed(error);
+        let json = serde_json::to_string(&ErrorReport::new(&error, path, chunk_type)).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_chunk_not_found_report() {
+        let path = PathBuf::from("img.png");
+        let error = PngmeError::ChunkNotFound { chunk_type: "ruSt".to_string() };
+
+        let json = report_json(error.into(), Some(&path), None);
+
+        assert_eq!(json["code"], "chunk_not_found");
+        assert_eq!(json["message"], "There are no chunk of type ruSt");
+        assert_eq!(json["path"], "img.png");
+        assert_eq!(json["chunk_type"], "ruSt");
+    }
+
+    #[test]
+    fn test_io_report() {
+        let error = PngmeError::Io {
+            path: Some(PathBuf::from("missing.png")),
+            source: io::Error::new(io::ErrorKind::NotFound, "not found"),
+        };
+
+        let json = report_json(error.into(), None, None);
+
+        assert_eq!(json["code"], "io");
+        assert_eq!(json["path"], "missing.png");
+        assert!(json.get("chunk_type").is_none());
+    }
+
+    #[test]
+    fn test_invalid_png_report() {
+        let error = PngmeError::InvalidPng { path: None, reason: "bad header".to_string() };
+
+        assert_eq!(report_json(error.into(), None, None)["code"], "invalid_png");
+    }
+
+    #[test]
+    fn test_size_budget_report() {
+        let error = SizeBudgetError { current: 1, payload: 2, projected: 3, limit: 2 };
+        let typed = PngmeError::from_boxed(error.into());
+
+        assert_eq!(typed.code(), "size_budget_exceeded");
+        assert_eq!(typed.exit_code(), SIZE_BUDGET_EXIT_CODE);
+    }
+
+    #[test]
+    fn test_unchanged_report() {
+        let typed = PngmeError::from_boxed(Unchanged.into());
+
+        assert_eq!(typed.code(), "unchanged");
+        assert_eq!(typed.exit_code(), UNCHANGED_EXIT_CODE);
+    }
+
+    #[test]
+    fn test_untyped_error_report() {
+        let json = report_json("something went wrong".into(), None, Some("teSt"));
+
+        assert_eq!(json["code"], "other");
+        assert_eq!(json["message"], "something went wrong");
+        assert_eq!(json["chunk_type"], "teSt");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_report() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+
+        let path = PathBuf::from(OsString::from_vec(b"img\xff.png".to_vec()));
+        let error = PngmeError::InvalidPng { path: Some(path), reason: "bad header".to_string() };
+
+        let json = report_json(error.into(), None, None);
+
+        assert_eq!(json["path"], "img\u{fffd}.png");
+        assert_eq!(json["path_base64"], "aW1n/y5wbmc=");
+        assert!(json["message"].as_str().unwrap().starts_with("img\u{fffd}.png"));
+    }
+}
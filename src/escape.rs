@@ -0,0 +1,85 @@
+/// Parses a byte string with `\xNN`, `\n`, `\t`, `\0` and `\\` escapes, as given to
+/// `encode --escaped`.
+///
+/// Hex digits may be upper or lowercase. Other characters stand for their UTF-8 bytes. Errors
+/// give the byte offset of the offending backslash.
+pub(crate) fn parse_escaped(input: &str) -> Result<Vec<u8>, String> {
+    let bytes = input.as_bytes();
+    let mut parsed = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        if bytes[pos] != b'\\' {
+            parsed.push(bytes[pos]);
+            pos += 1;
+            continue;
+        }
+
+        match bytes.get(pos + 1) {
+            Some(b'n') => parsed.push(b'\n'),
+            Some(b't') => parsed.push(b'\t'),
+            Some(b'0') => parsed.push(0),
+            Some(b'\\') => parsed.push(b'\\'),
+            Some(b'x') => {
+                let digits = bytes.get(pos + 2..pos + 4).and_then(|digits| std::str::from_utf8(digits).ok());
+                let byte = digits
+                    .filter(|digits| digits.bytes().all(|digit| digit.is_ascii_hexdigit()))
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| format!("\\x at offset {pos} must be followed by two hex digits"))?;
+                parsed.push(byte);
+                pos += 4;
+                continue;
+            }
+            Some(_) => {
+                let escape = input[pos + 1..].chars().next().expect("Offset should be on a char boundary");
+                return Err(format!("Unknown escape \\{escape} at offset {pos}"));
+            }
+            None => return Err(format!("Trailing backslash at offset {pos}")),
+        }
+        pos += 2;
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(parse_escaped("hello").unwrap(), b"hello");
+        assert_eq!(parse_escaped("").unwrap(), b"");
+        assert_eq!(parse_escaped("é").unwrap(), "é".as_bytes());
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(parse_escaped(r"\x00\x01hello\xff").unwrap(), b"\x00\x01hello\xff");
+        assert_eq!(parse_escaped(r"a\nb\tc\0d\\e").unwrap(), b"a\nb\tc\0d\\e");
+    }
+
+    #[test]
+    fn test_uppercase_hex() {
+        assert_eq!(parse_escaped(r"\xAB\xcD").unwrap(), [0xab, 0xcd]);
+    }
+
+    #[test]
+    fn test_trailing_backslash() {
+        assert_eq!(parse_escaped(r"abc\").unwrap_err(), "Trailing backslash at offset 3");
+    }
+
+    #[test]
+    fn test_short_hex_escape() {
+        assert_eq!(parse_escaped(r"ab\x1").unwrap_err(), "\\x at offset 2 must be followed by two hex digits");
+        assert!(parse_escaped(r"\x1g").is_err());
+        assert!(parse_escaped(r"\x").is_err());
+        assert!(parse_escaped(r"\x+1").is_err());
+    }
+
+    #[test]
+    fn test_unknown_escape() {
+        assert_eq!(parse_escaped(r"a\q").unwrap_err(), "Unknown escape \\q at offset 1");
+        assert_eq!(parse_escaped(r"\é").unwrap_err(), "Unknown escape \\é at offset 0");
+    }
+}
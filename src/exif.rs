@@ -0,0 +1,281 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+pub(crate) const EXIF_CHUNK_TYPE: &str = "eXIf";
+
+/// Prefix of the EXIF data in a JPEG APP1 segment, which the eXIf chunk leaves out.
+const JPEG_EXIF_PREFIX: &[u8] = b"Exif\0\0";
+
+const TAG_MAKE: u16 = 0x010f;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_GPS_LATITUDE_REF: u16 = 1;
+const TAG_GPS_LATITUDE: u16 = 2;
+const TAG_GPS_LONGITUDE_REF: u16 = 3;
+const TAG_GPS_LONGITUDE: u16 = 4;
+
+const TYPE_ASCII: u16 = 2;
+const TYPE_LONG: u16 = 4;
+const TYPE_RATIONAL: u16 = 5;
+/// A LONG holding the offset of a sub-IFD, used by some writers instead of LONG.
+const TYPE_IFD: u16 = 13;
+
+/// One entry of an image file directory: where its value is and how to read it.
+struct Entry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    /// Position of the value in the TIFF data, inline in the entry when it fits in 4 bytes.
+    value_pos: usize,
+}
+
+/// Just enough of a TIFF reader to walk the EXIF directories.
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn parse(data: &'a [u8]) -> Result<Tiff<'a>, String> {
+        let big_endian = match data.get(..4) {
+            Some(b"II*\0") => false,
+            Some(b"MM\0*") => true,
+            _ => return Err("EXIF data should start with a TIFF header, II*\\0 or MM\\0*".to_string()),
+        };
+        Ok(Tiff { data, big_endian })
+    }
+
+    fn bytes<const N: usize>(&self, pos: usize) -> Result<[u8; N], String> {
+        pos.checked_add(N)
+            .and_then(|end| self.data.get(pos..end))
+            .map(|bytes| bytes.try_into().expect("Slice should be of length N"))
+            .ok_or_else(|| format!("EXIF data is {} bytes long, it ends before offset {pos:#x} is read", self.data.len()))
+    }
+
+    fn u16(&self, pos: usize) -> Result<u16, String> {
+        let bytes = self.bytes(pos)?;
+        Ok(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, pos: usize) -> Result<u32, String> {
+        let bytes = self.bytes(pos)?;
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    /// The entries of the directory at `offset`.
+    fn entries(&self, offset: u32) -> Result<Vec<Entry>, String> {
+        let offset = offset as usize;
+        (0..usize::from(self.u16(offset)?))
+            .map(|index| {
+                let pos = offset + 2 + index * 12;
+                let (tag, field_type, count) = (self.u16(pos)?, self.u16(pos + 2)?, self.u32(pos + 4)?);
+                let size = match field_type {
+                    3 => 2,
+                    4 | 9 | 11 | 13 => 4,
+                    5 | 10 | 12 => 8,
+                    _ => 1,
+                };
+                let value_pos = if u64::from(count) * size <= 4 { pos + 8 } else { self.u32(pos + 8)? as usize };
+                Ok(Entry { tag, field_type, count, value_pos })
+            })
+            .collect()
+    }
+
+    fn ascii(&self, entry: &Entry) -> Result<String, String> {
+        let end = entry.value_pos.saturating_add(entry.count as usize);
+        let bytes = self.data.get(entry.value_pos..end).ok_or_else(|| format!("Tag {:#06x} points past the end of the EXIF data", entry.tag))?;
+        let text = bytes.split(|&byte| byte == 0).next().unwrap_or_default();
+        Ok(String::from_utf8_lossy(text).trim().to_string())
+    }
+
+    fn rationals(&self, entry: &Entry) -> Result<Vec<f64>, String> {
+        (0..entry.count as usize)
+            .map(|index| {
+                let pos = entry.value_pos + index * 8;
+                let (numerator, denominator) = (self.u32(pos)?, self.u32(pos + 4)?);
+                Ok(if denominator == 0 { 0.0 } else { f64::from(numerator) / f64::from(denominator) })
+            })
+            .collect()
+    }
+
+    /// The sub-directory `tag` points to in `entries`, if any.
+    fn sub_ifd(&self, entries: &[Entry], tag: u16) -> Result<Vec<Entry>, String> {
+        match entries.iter().find(|entry| entry.tag == tag && matches!(entry.field_type, TYPE_LONG | TYPE_IFD)) {
+            Some(entry) => self.entries(self.u32(entry.value_pos)?),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// The commonly wanted tags of an EXIF block: camera, date and location.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ExifSummary {
+    pub(crate) make: Option<String>,
+    pub(crate) model: Option<String>,
+    /// When the photo was taken, or else when the file was last changed, as EXIF writes it.
+    pub(crate) date: Option<String>,
+    /// Latitude and longitude in degrees, negative south and west.
+    pub(crate) gps: Option<(f64, f64)>,
+}
+
+impl ExifSummary {
+    /// Reads the summary from the TIFF data of an eXIf chunk.
+    pub(crate) fn parse(data: &[u8]) -> Result<ExifSummary, String> {
+        let tiff = Tiff::parse(data)?;
+        let ifd0 = tiff.entries(tiff.u32(4)?)?;
+        let exif = tiff.sub_ifd(&ifd0, TAG_EXIF_IFD)?;
+        let gps = tiff.sub_ifd(&ifd0, TAG_GPS_IFD)?;
+
+        let ascii = |entries: &[Entry], tag: u16| -> Result<Option<String>, String> {
+            match entries.iter().find(|entry| entry.tag == tag && entry.field_type == TYPE_ASCII) {
+                Some(entry) => Ok(Some(tiff.ascii(entry)?).filter(|text| !text.is_empty())),
+                None => Ok(None),
+            }
+        };
+        let coordinate = |tag: u16, ref_tag: u16, negative: &str| -> Result<Option<f64>, String> {
+            let Some(entry) = gps.iter().find(|entry| entry.tag == tag && entry.field_type == TYPE_RATIONAL && entry.count == 3) else {
+                return Ok(None);
+            };
+            let parts = tiff.rationals(entry)?;
+            let degrees = parts[0] + parts[1] / 60.0 + parts[2] / 3600.0;
+            Ok(Some(if ascii(&gps, ref_tag)?.as_deref() == Some(negative) { -degrees } else { degrees }))
+        };
+
+        let latitude = coordinate(TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF, "S")?;
+        let longitude = coordinate(TAG_GPS_LONGITUDE, TAG_GPS_LONGITUDE_REF, "W")?;
+        Ok(ExifSummary {
+            make: ascii(&ifd0, TAG_MAKE)?,
+            model: ascii(&ifd0, TAG_MODEL)?,
+            date: ascii(&exif, TAG_DATE_TIME_ORIGINAL)?.or(ascii(&ifd0, TAG_DATE_TIME)?),
+            gps: latitude.zip(longitude),
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == ExifSummary::default()
+    }
+}
+
+/// One `Name: value` line per tag found.
+impl fmt::Display for ExifSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines = Vec::new();
+        let camera: Vec<&str> = [&self.make, &self.model].into_iter().flatten().map(String::as_str).collect();
+        if !camera.is_empty() {
+            lines.push(format!("Camera: {}", camera.join(" ")));
+        }
+        if let Some(date) = &self.date {
+            lines.push(format!("Date: {date}"));
+        }
+        if let Some((latitude, longitude)) = self.gps {
+            let (north_south, east_west) = (if latitude < 0.0 { 'S' } else { 'N' }, if longitude < 0.0 { 'W' } else { 'E' });
+            lines.push(format!("GPS: {:.6} {north_south}, {:.6} {east_west}", latitude.abs(), longitude.abs()));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// The TIFF data to store in an eXIf chunk, from a raw EXIF block or one copied out of a JPEG
+/// APP1 segment, whose `Exif\0\0` prefix is dropped. Fails unless the data starts with a TIFF
+/// header whose first directory can be read.
+pub(crate) fn prepare(data: &[u8]) -> Result<Vec<u8>, String> {
+    let data = data.strip_prefix(JPEG_EXIF_PREFIX).unwrap_or(data);
+    ExifSummary::parse(data)?;
+    Ok(data.to_vec())
+}
+
+/// Writes `data` into `png`, replacing its eXIf chunk, or adding one before the image data.
+pub(crate) fn write(png: &mut Png, data: Vec<u8>) {
+    let chunk = Chunk::new(ChunkType::from_str(EXIF_CHUNK_TYPE).expect("eXIf is a valid chunk type"), data);
+    if png.chunk_by_type(EXIF_CHUNK_TYPE).is_some() {
+        png.replace_or_insert_chunk(chunk);
+    } else {
+        png.insert_before_first_of(&["IDAT", "IEND"], chunk);
+    }
+}
+
+/// Removes every eXIf chunk of `png`, returning how many there were.
+pub(crate) fn strip(png: &mut Png) -> usize {
+    png.remove_chunks_where(|chunk, _| chunk.chunk_type().to_string() == EXIF_CHUNK_TYPE).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian TIFF data with Make, Model, a GPS directory and the Exif directory.
+    fn testing_exif() -> Vec<u8> {
+        let entry = |tag: u16, field_type: u16, count: u32, value: u32| {
+            [tag.to_le_bytes().as_slice(), &field_type.to_le_bytes(), &count.to_le_bytes(), &value.to_le_bytes()].concat()
+        };
+        let rational = |numerator: u32, denominator: u32| [numerator.to_le_bytes(), denominator.to_le_bytes()].concat();
+
+        // Header at 0, IFD0 at 8 with 4 entries (8 + 2 + 48 + 4 = 62), then the values.
+        let mut data = b"II*\0".to_vec();
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&4u16.to_le_bytes());
+        data.extend(entry(TAG_MAKE, TYPE_ASCII, 6, 62));
+        data.extend(entry(TAG_MODEL, TYPE_ASCII, 4, u32::from_le_bytes(*b"X1\0\0")));
+        data.extend(entry(TAG_EXIF_IFD, TYPE_LONG, 1, 68));
+        data.extend(entry(TAG_GPS_IFD, TYPE_LONG, 1, 106));
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(b"Canon\0");
+        // Exif IFD at 68: one entry, then the 20 byte date at 86.
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend(entry(TAG_DATE_TIME_ORIGINAL, TYPE_ASCII, 20, 86));
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(b"2024:05:01 12:34:56\0");
+        // GPS IFD at 106: four entries, then the rationals at 106 + 2 + 48 + 4 = 160.
+        data.extend_from_slice(&4u16.to_le_bytes());
+        data.extend(entry(TAG_GPS_LATITUDE_REF, TYPE_ASCII, 2, u32::from(b'N')));
+        data.extend(entry(TAG_GPS_LATITUDE, TYPE_RATIONAL, 3, 160));
+        data.extend(entry(TAG_GPS_LONGITUDE_REF, TYPE_ASCII, 2, u32::from(b'W')));
+        data.extend(entry(TAG_GPS_LONGITUDE, TYPE_RATIONAL, 3, 184));
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend([rational(48, 1), rational(51, 1), rational(2952, 100)].concat());
+        data.extend([rational(2, 1), rational(17, 1), rational(40, 1)].concat());
+        data
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = ExifSummary::parse(&testing_exif()).unwrap();
+
+        assert_eq!(summary.make.as_deref(), Some("Canon"));
+        assert_eq!(summary.model.as_deref(), Some("X1"));
+        assert_eq!(summary.to_string(), "Camera: Canon X1\nDate: 2024:05:01 12:34:56\nGPS: 48.858200 N, 2.294444 W");
+    }
+
+    #[test]
+    fn test_prepare() {
+        let jpeg = [JPEG_EXIF_PREFIX, testing_exif().as_slice()].concat();
+
+        assert_eq!(prepare(&jpeg).unwrap(), testing_exif());
+        assert!(prepare(b"not exif").is_err());
+        assert!(prepare(&testing_exif()[..40]).unwrap_err().contains("ends before offset"));
+
+        let empty = [b"MM\0*".as_slice(), &[0, 0, 0, 8, 0, 0]].concat();
+        assert!(ExifSummary::parse(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_write_and_strip() {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![]);
+        let mut png = Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")]);
+
+        write(&mut png, testing_exif());
+        write(&mut png, testing_exif());
+
+        let types: Vec<String> = png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "eXIf", "IDAT", "IEND"]);
+        assert_eq!(strip(&mut png), 1);
+        assert_eq!(png.chunk_count(), 3);
+    }
+}
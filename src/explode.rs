@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::Result;
+
+/// Name of the file recording the chunk order in an exploded directory.
+pub(crate) const MANIFEST_FILE: &str = "manifest.json";
+
+const TRAILING_FILE: &str = "trailing.bin";
+
+/// Contents of `manifest.json`, enough to put the png back together.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ExplodeManifest {
+    /// The 8 byte png signature, in hex.
+    pub(crate) signature: String,
+    /// Chunk file names in file order.
+    pub(crate) chunks: Vec<String>,
+    /// File holding the data after IEND, if there is any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) trailing: Option<String>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Writes every chunk of `png` to `dir` as `NNN_TYPE.chunk`, serialized with its CRC, plus the
+/// manifest. Returns the paths written.
+pub(crate) fn explode(png: &Png, dir: &Path, force: bool) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<(String, Vec<u8>)> = png
+        .chunks()
+        .enumerate()
+        .map(|(index, chunk)| (format!("{index:03}_{}.chunk", chunk.chunk_type()), chunk.as_bytes()))
+        .collect();
+
+    let manifest = ExplodeManifest {
+        signature: hex(png.header()),
+        chunks: files.iter().map(|(name, _)| name.clone()).collect(),
+        trailing: (!png.trailing_data().is_empty()).then(|| TRAILING_FILE.to_string()),
+    };
+    if manifest.trailing.is_some() {
+        files.push((TRAILING_FILE.to_string(), png.trailing_data().to_vec()));
+    }
+    files.push((MANIFEST_FILE.to_string(), serde_json::to_vec_pretty(&manifest)?));
+
+    let files: Vec<(PathBuf, Vec<u8>)> = files.into_iter().map(|(name, bytes)| (dir.join(name), bytes)).collect();
+    if !force {
+        if let Some((existing, _)) = files.iter().find(|(path, _)| path.exists()) {
+            return Err(format!("{} already exists (use --force to overwrite)", existing.display()).into());
+        }
+    }
+
+    fs::create_dir_all(dir)?;
+    for (path, bytes) in &files {
+        fs::write(path, bytes)?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Reads one chunk file. With `recompute_crc` the length and CRC fields are ignored and
+/// rebuilt from the file size and data, so chunk data can be edited in place.
+fn read_chunk_file(path: &Path, recompute_crc: bool) -> Result<Chunk<'static>> {
+    let bytes = fs::read(path)?;
+    let invalid = |reason: String| format!("{}: {reason}", path.display());
+
+    if recompute_crc {
+        if bytes.len() < Chunk::METADATA_LEN {
+            return Err(invalid(format!("needs at least {} bytes", Chunk::METADATA_LEN)).into());
+        }
+        let type_bytes: [u8; 4] = bytes[4..8].try_into().unwrap();
+        let chunk_type = ChunkType::try_from(type_bytes).map_err(|reason| invalid(reason.to_string()))?;
+        return Ok(Chunk::new(chunk_type, bytes[8..bytes.len() - 4].to_vec()));
+    }
+
+    Chunk::try_from(bytes.as_slice()).map(Chunk::into_owned).map_err(|reason| invalid(reason).into())
+}
+
+/// Rebuilds a png from a directory written by `explode`.
+///
+/// Chunk files listed in the manifest but deleted from the directory are skipped; their
+/// names are returned so the caller can report them. Every remaining chunk's CRC is checked
+/// unless `recompute_crc` is set.
+pub(crate) fn assemble(dir: &Path, recompute_crc: bool) -> Result<(Png<'static>, Vec<String>)> {
+    let manifest: ExplodeManifest = serde_json::from_slice(&fs::read(dir.join(MANIFEST_FILE))?)?;
+    if manifest.signature != hex(&Png::STANDARD_HEADER) {
+        return Err(format!("Manifest signature {} isn't the png signature", manifest.signature).into());
+    }
+
+    let mut chunks = Vec::with_capacity(manifest.chunks.len());
+    let mut skipped = Vec::new();
+    for name in manifest.chunks {
+        let path = dir.join(&name);
+        if !path.exists() {
+            skipped.push(name);
+            continue;
+        }
+        chunks.push(read_chunk_file(&path, recompute_crc)?);
+    }
+
+    let mut png = Png::from_chunks(chunks);
+    if let Some(trailing) = manifest.trailing {
+        png.set_trailing_data(fs::read(dir.join(trailing))?);
+    }
+    Ok((png, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png<'static> {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            Chunk::new(ChunkType::from_str("teSt").unwrap(), b"hello".to_vec()),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+        png.set_trailing_data(b"appended".to_vec());
+        png
+    }
+
+    #[test]
+    fn test_explode_assemble_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = testing_png();
+
+        let written = explode(&png, dir.path(), false).unwrap();
+        let (assembled, skipped) = assemble(dir.path(), false).unwrap();
+
+        assert_eq!(written.len(), 6);
+        assert!(dir.path().join("001_teSt.chunk").exists());
+        assert!(skipped.is_empty());
+        assert_eq!(assembled.as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_explode_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        explode(&testing_png(), dir.path(), false).unwrap();
+
+        assert!(explode(&testing_png(), dir.path(), false).is_err());
+        assert!(explode(&testing_png(), dir.path(), true).is_ok());
+    }
+
+    #[test]
+    fn test_assemble_after_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        explode(&testing_png(), dir.path(), false).unwrap();
+        fs::remove_file(dir.path().join("001_teSt.chunk")).unwrap();
+
+        let (assembled, skipped) = assemble(dir.path(), false).unwrap();
+
+        assert_eq!(skipped, vec!["001_teSt.chunk"]);
+        assert_eq!(assembled.chunk_count(), 3);
+        assert!(assembled.chunk_by_type("teSt").is_none());
+    }
+
+    #[test]
+    fn test_assemble_edited_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        explode(&testing_png(), dir.path(), false).unwrap();
+        let path = dir.path().join("001_teSt.chunk");
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.splice(8..13, b"goodbye".iter().copied());
+        fs::write(&path, bytes).unwrap();
+
+        assert!(assemble(dir.path(), false).is_err());
+
+        let (assembled, _) = assemble(dir.path(), true).unwrap();
+        assert_eq!(assembled.chunk_by_type("teSt").unwrap().data(), b"goodbye");
+        assert!(Png::try_from(assembled.as_bytes().as_slice()).is_ok());
+    }
+}
@@ -0,0 +1,171 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::error::PngmeError;
+use crate::json_path::JsonPath;
+use crate::png::Png;
+use crate::selector::ChunkSelector;
+use crate::Result;
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ExtractedFile {
+    #[serde(flatten)]
+    pub(crate) path: JsonPath,
+    pub(crate) size: usize,
+}
+
+/// File name used for the `index`-th occurrence of `chunk_type`, e.g. `teSt_000.bin`.
+fn extracted_file_name(chunk_type: &str, index: usize) -> String {
+    format!("{chunk_type}_{index:03}.bin")
+}
+
+/// Writes the data of the selected chunks into `dir`, one file per chunk numbered after its
+/// occurrence.
+pub(crate) fn extract_chunks(png: &Png, selector: &ChunkSelector, dir: &Path, force: bool) -> Result<Vec<ExtractedFile>> {
+    let matches = selector.matches(png);
+    if matches.is_empty() {
+        return Err(PngmeError::ChunkNotFound { chunk_type: selector.to_string() }.into());
+    }
+
+    let named = matches
+        .into_iter()
+        .map(|(occurrence, index)| (extracted_file_name(&selector.chunk_type, occurrence), &png[index]))
+        .collect();
+
+    write_chunks(named, dir, force)
+}
+
+/// Writes the data of the chunk at `index` in the file, named after its type and that index.
+pub(crate) fn extract_nth(png: &Png, index: usize, dir: &Path, force: bool) -> Result<Vec<ExtractedFile>> {
+    let chunk = png.nth_chunk(index)?;
+    let name = extracted_file_name(&chunk.chunk_type().to_string(), index);
+
+    write_chunks(vec![(name, chunk)], dir, force)
+}
+
+/// Every destination is checked before anything is written, so a refused overwrite leaves the
+/// directory untouched.
+fn write_chunks(named: Vec<(String, &Chunk)>, dir: &Path, force: bool) -> Result<Vec<ExtractedFile>> {
+    let named: Vec<(PathBuf, &Chunk)> = named.into_iter().map(|(name, chunk)| (dir.join(name), chunk)).collect();
+
+    if !force {
+        if let Some((existing, _)) = named.iter().find(|(path, _)| path.exists()) {
+            return Err(format!("{} already exists (use --force to overwrite)", existing.display()).into());
+        }
+    }
+
+    fs::create_dir_all(dir)?;
+
+    let mut extracted = Vec::with_capacity(named.len());
+    for (path, chunk) in named {
+        fs::write(&path, chunk.data())?;
+        extracted.push(ExtractedFile { path: path.into(), size: chunk.data().len() });
+    }
+
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn selector(s: &str) -> ChunkSelector {
+        ChunkSelector::from_str(s).unwrap()
+    }
+
+    fn testing_png() -> Png<'static> {
+        Png::from_chunks(vec![
+            chunk_from_strings("FrSt", "I am the first chunk"),
+            chunk_from_strings("teSt", "first occurrence"),
+            chunk_from_strings("miDl", "I am another chunk"),
+            chunk_from_strings("teSt", "second occurrence"),
+            chunk_from_strings("teSt", "third occurrence"),
+            chunk_from_strings("LASt", "I am the last chunk"),
+        ])
+    }
+
+    #[test]
+    fn test_extract_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+
+        let extracted = extract_chunks(&testing_png(), &selector("teSt[*]"), &out, false).unwrap();
+
+        assert_eq!(extracted.len(), 3);
+        assert_eq!(fs::read_dir(&out).unwrap().count(), 3);
+
+        let expected = ["first occurrence", "second occurrence", "third occurrence"];
+        for (index, (file, data)) in extracted.iter().zip(expected).enumerate() {
+            assert_eq!(file.path.as_path(), out.join(format!("teSt_{index:03}.bin")));
+            assert_eq!(file.size, data.len());
+            assert_eq!(fs::read(file.path.as_path()).unwrap(), data.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_extract_first_only() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let extracted = extract_chunks(&testing_png(), &selector("teSt"), dir.path(), false).unwrap();
+
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(fs::read(extracted[0].path.as_path()).unwrap(), b"first occurrence");
+    }
+
+    #[test]
+    fn test_extract_occurrence() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let extracted = extract_chunks(&testing_png(), &selector("teSt[2]"), dir.path(), false).unwrap();
+
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(extracted[0].path.as_path(), dir.path().join("teSt_002.bin"));
+        assert_eq!(fs::read(extracted[0].path.as_path()).unwrap(), b"third occurrence");
+    }
+
+    #[test]
+    fn test_extract_no_match() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let error = extract_chunks(&testing_png(), &selector("noNe[*]"), dir.path(), false).unwrap_err();
+
+        assert_eq!(PngmeError::from_boxed(error).code(), "chunk_not_found");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_extract_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("teSt_001.bin"), b"keep me").unwrap();
+
+        let extracted = extract_chunks(&testing_png(), &selector("teSt[*]"), dir.path(), false);
+
+        assert!(extracted.is_err());
+        assert!(!dir.path().join("teSt_000.bin").exists());
+        assert_eq!(fs::read(dir.path().join("teSt_001.bin")).unwrap(), b"keep me");
+
+        let extracted = extract_chunks(&testing_png(), &selector("teSt[*]"), dir.path(), true).unwrap();
+        assert_eq!(extracted.len(), 3);
+        assert_eq!(fs::read(dir.path().join("teSt_001.bin")).unwrap(), b"second occurrence");
+    }
+
+    #[test]
+    fn test_extract_nth() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let extracted = extract_nth(&testing_png(), 3, dir.path(), false).unwrap();
+
+        assert_eq!(extracted[0].path.as_path(), dir.path().join("teSt_003.bin"));
+        assert_eq!(fs::read(extracted[0].path.as_path()).unwrap(), b"second occurrence");
+        assert!(extract_nth(&testing_png(), 6, dir.path(), false).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! A C API, built with `cargo build --release --no-default-features --features ffi`, for C,
+//! C++ and any runtime that can call into a shared library. The header, `include/pngme.h`, is
+//! generated by `build.rs`.
+//!
+//! Every function returns 0 on success and -1 on failure, when `pngme_last_error` describes
+//! what went wrong. Buffers returned through `out` belong to the caller, who hands them back
+//! to `pngme_free`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::codec::{self, Settings};
+use crate::encode::{self, Position};
+use crate::png::Png;
+use crate::Result;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs `operation` and stores its result in `out` and `out_len`, turning errors and panics
+/// into -1 so they don't unwind into C.
+fn call(out: *mut *mut u8, out_len: *mut usize, operation: impl FnOnce() -> Result<Vec<u8>>) -> c_int {
+    let result = match panic::catch_unwind(AssertUnwindSafe(operation)) {
+        Ok(result) => result,
+        Err(_) => Err("pngme panicked".into()),
+    };
+    let result = result.and_then(|bytes| {
+        if out.is_null() || out_len.is_null() {
+            return Err("out and out_len must not be NULL".into());
+        }
+        Ok(bytes)
+    });
+    match result {
+        Ok(bytes) => {
+            let bytes = bytes.into_boxed_slice();
+            // SAFETY: both pointers were checked for NULL, and the caller guarantees they are
+            // valid for writes.
+            unsafe {
+                *out_len = bytes.len();
+                *out = Box::into_raw(bytes).cast();
+            }
+            LAST_ERROR.with(|last| *last.borrow_mut() = None);
+            0
+        }
+        Err(error) => {
+            let message = CString::new(error.to_string().replace('\0', " ")).expect("NULs were replaced");
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+            -1
+        }
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must be NULL or valid for reads of `len` bytes, which stay unchanged during the call.
+unsafe fn bytes<'a>(bytes: *const u8, len: usize, name: &str) -> Result<&'a [u8]> {
+    if bytes.is_null() {
+        return if len == 0 { Ok(&[]) } else { Err(format!("{name} is NULL").into()) };
+    }
+    Ok(std::slice::from_raw_parts(bytes, len))
+}
+
+/// # Safety
+///
+/// `string` must be NULL or point to a NUL-terminated string.
+unsafe fn string<'a>(string: *const c_char, name: &str) -> Result<&'a str> {
+    if string.is_null() {
+        return Err(format!("{name} is NULL").into());
+    }
+    CStr::from_ptr(string).to_str().map_err(|_| format!("{name} isn't UTF-8").into())
+}
+
+/// Stores `payload` in a new `chunk_type` chunk before IEND of the png in `png`, in the
+/// envelope `pngme decode` reads, and returns the new file through `out` and `out_len`.
+///
+/// # Safety
+///
+/// `png` and `payload` must be valid for reads of `png_len` and `payload_len` bytes,
+/// `chunk_type` must be a NUL-terminated string, and `out` and `out_len` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_encode(
+    png: *const u8,
+    png_len: usize,
+    chunk_type: *const c_char,
+    payload: *const u8,
+    payload_len: usize,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    call(out, out_len, || {
+        let mut png = Png::try_from(bytes(png, png_len, "png")?)?;
+        let payload = bytes(payload, payload_len, "payload")?;
+        let chunk = encode::build_chunk(string(chunk_type, "chunk_type")?, payload, false, 0, Settings::default(), false)?;
+        Position::BeforeIend.insert(&mut png, &[chunk])?;
+        Ok(png.as_bytes())
+    })
+}
+
+/// Returns through `out` and `out_len` the payload of the first `chunk_type` chunk of the png
+/// in `png`, as stored by `pngme_encode` or `pngme encode` without encryption.
+///
+/// # Safety
+///
+/// `png` must be valid for reads of `png_len` bytes, `chunk_type` must be a NUL-terminated
+/// string, and `out` and `out_len` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_decode(
+    png: *const u8,
+    png_len: usize,
+    chunk_type: *const c_char,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    call(out, out_len, || {
+        let png = Png::try_from(bytes(png, png_len, "png")?)?;
+        let chunk_type = string(chunk_type, "chunk_type")?;
+        let chunk = png.chunk_by_type(chunk_type).ok_or_else(|| format!("No {chunk_type} chunk in the png"))?;
+        codec::open(chunk.data())
+    })
+}
+
+/// Frees a buffer returned by `pngme_encode` or `pngme_decode`. NULL is ignored.
+///
+/// # Safety
+///
+/// `buffer` and `len` must be a pair returned by this library and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_free(buffer: *mut u8, len: usize) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, len)));
+    }
+}
+
+/// The message of the last error on this thread, or NULL after a success. The string is
+/// owned by the library and valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn pngme_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_round_trip() {
+        let chunk = |chunk_type: &str, size: usize| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; size]);
+        let png = Png::from_chunks(vec![chunk("IHDR", 13), chunk("IDAT", 8), chunk("IEND", 0)]).as_bytes();
+        let (mut encoded, mut encoded_len) = (ptr::null_mut(), 0);
+        let (mut decoded, mut decoded_len) = (ptr::null_mut(), 0);
+
+        unsafe {
+            assert_eq!(pngme_encode(png.as_ptr(), png.len(), c"ruSt".as_ptr(), b"hidden".as_ptr(), 6, &mut encoded, &mut encoded_len), 0);
+            assert!(pngme_last_error().is_null());
+            assert_eq!(pngme_decode(encoded, encoded_len, c"ruSt".as_ptr(), &mut decoded, &mut decoded_len), 0);
+            assert_eq!(std::slice::from_raw_parts(decoded, decoded_len), b"hidden");
+
+            assert_eq!(pngme_decode(encoded, encoded_len, c"teSt".as_ptr(), &mut decoded, &mut decoded_len), -1);
+            assert_eq!(CStr::from_ptr(pngme_last_error()).to_str().unwrap(), "No teSt chunk in the png");
+            assert_eq!(pngme_decode(ptr::null(), 4, c"ruSt".as_ptr(), &mut decoded, &mut decoded_len), -1);
+            assert_eq!(CStr::from_ptr(pngme_last_error()).to_str().unwrap(), "png is NULL");
+
+            pngme_free(encoded, encoded_len);
+            pngme_free(decoded, decoded_len);
+            pngme_free(ptr::null_mut(), 0);
+        }
+    }
+}
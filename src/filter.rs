@@ -0,0 +1,351 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+
+/// A syntax or type error in a filter expression, at a 0-based character position.
+#[derive(Debug, PartialEq)]
+pub(crate) struct FilterError {
+    pub(crate) position: usize,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at position {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+fn error<T>(position: usize, message: impl Into<String>) -> Result<T, FilterError> {
+    Err(FilterError { position, message: message.into() })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(u64),
+    Str(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Op(Op),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn apply<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let start = pos;
+        let two: String = chars[pos..chars.len().min(pos + 2)].iter().collect();
+        let token = match chars[pos] {
+            c if c.is_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            '(' => Token::Open,
+            ')' => Token::Close,
+            _ if two == "&&" => Token::And,
+            _ if two == "||" => Token::Or,
+            _ if two == "==" => Token::Op(Op::Eq),
+            _ if two == "!=" => Token::Op(Op::Ne),
+            _ if two == "<=" => Token::Op(Op::Le),
+            _ if two == ">=" => Token::Op(Op::Ge),
+            '!' => Token::Not,
+            '<' => Token::Op(Op::Lt),
+            '>' => Token::Op(Op::Gt),
+            quote @ ('\'' | '"') => {
+                let end = chars[pos + 1..]
+                    .iter()
+                    .position(|&c| c == quote)
+                    .ok_or(FilterError { position: pos, message: "unterminated string".to_string() })?;
+                pos += end + 2;
+                tokens.push((start, Token::Str(chars[start + 1..pos - 1].iter().collect())));
+                continue;
+            }
+            c if c.is_ascii_digit() => {
+                while pos < chars.len() && chars[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+                let digits: String = chars[start..pos].iter().collect();
+                let number = digits.parse().or_else(|_| error(start, "number is too large"))?;
+                tokens.push((start, Token::Number(number)));
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                    pos += 1;
+                }
+                tokens.push((start, Token::Word(chars[start..pos].iter().collect())));
+                continue;
+            }
+            c => return error(pos, format!("unexpected character `{c}`")),
+        };
+        pos += match token {
+            Token::And | Token::Or | Token::Op(Op::Eq | Op::Ne | Op::Le | Op::Ge) => 2,
+            _ => 1,
+        };
+        tokens.push((start, token));
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flag {
+    Critical,
+    Private,
+    SafeToCopy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    Size,
+    Index,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Flag(Flag),
+    Number(Number, Op, u64),
+    Type(Op, String),
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Position reported for errors at the end of the input.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |&(position, _)| position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, token)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, FilterError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, FilterError> {
+        let position = self.position();
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return error(self.position(), "expected `)`");
+                }
+                self.next();
+                Ok(expr)
+            }
+            Some(Token::Word(word)) => match word.as_str() {
+                "critical" => Ok(Expr::Flag(Flag::Critical)),
+                "private" => Ok(Expr::Flag(Flag::Private)),
+                "safe_to_copy" => Ok(Expr::Flag(Flag::SafeToCopy)),
+                "size" => self.number_comparison(Number::Size),
+                "index" => self.number_comparison(Number::Index),
+                "type" => self.type_comparison(),
+                _ => error(position, format!("unknown field `{word}`")),
+            },
+            Some(_) => error(position, "expected a field or `(`"),
+            None => error(position, "unexpected end of expression"),
+        }
+    }
+
+    fn operator(&mut self) -> Result<Op, FilterError> {
+        let position = self.position();
+        match self.next() {
+            Some(Token::Op(op)) => Ok(op),
+            _ => error(position, "expected a comparison operator"),
+        }
+    }
+
+    fn number_comparison(&mut self, field: Number) -> Result<Expr, FilterError> {
+        let op = self.operator()?;
+        let position = self.position();
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(field, op, value)),
+            _ => error(position, "expected a number"),
+        }
+    }
+
+    fn type_comparison(&mut self) -> Result<Expr, FilterError> {
+        let position = self.position();
+        let op = self.operator()?;
+        if !matches!(op, Op::Eq | Op::Ne) {
+            return error(position, "type can only be compared with == or !=");
+        }
+        let position = self.position();
+        match self.next() {
+            Some(Token::Str(value) | Token::Word(value)) => Ok(Expr::Type(op, value)),
+            _ => error(position, "expected a chunk type"),
+        }
+    }
+}
+
+/// A parsed `--matching` expression, e.g. `private && size > 1024`.
+///
+/// Fields: `type` (compared with `==` or `!=` to a bare or quoted chunk type), `size` and
+/// `index` (compared to numbers), and the flags `critical`, `private` and `safe_to_copy`.
+/// `!` binds tighter than `&&`, which binds tighter than `||`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Filter(Expr);
+
+impl Filter {
+    /// Whether `chunk`, at `index` in the file, satisfies the expression.
+    pub(crate) fn matches(&self, chunk: &Chunk, index: usize) -> bool {
+        evaluate(&self.0, chunk, index)
+    }
+}
+
+fn evaluate(expr: &Expr, chunk: &Chunk, index: usize) -> bool {
+    let chunk_type = chunk.chunk_type();
+    match expr {
+        Expr::Or(left, right) => evaluate(left, chunk, index) || evaluate(right, chunk, index),
+        Expr::And(left, right) => evaluate(left, chunk, index) && evaluate(right, chunk, index),
+        Expr::Not(inner) => !evaluate(inner, chunk, index),
+        Expr::Flag(Flag::Critical) => chunk_type.is_critical(),
+        Expr::Flag(Flag::Private) => !chunk_type.is_public(),
+        Expr::Flag(Flag::SafeToCopy) => chunk_type.is_safe_to_copy(),
+        Expr::Number(Number::Size, op, value) => op.apply(chunk.data().len() as u64, *value),
+        Expr::Number(Number::Index, op, value) => op.apply(index as u64, *value),
+        Expr::Type(op, value) => op.apply(chunk_type.to_string().as_str(), value.as_str()),
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { tokens: tokenize(s)?, pos: 0, end: s.chars().count() };
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return error(parser.position(), "unexpected input after the expression");
+        }
+        Ok(Filter(expr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+
+    fn chunk(chunk_type: &str, size: usize) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; size])
+    }
+
+    fn matches(expression: &str, chunk: &Chunk, index: usize) -> bool {
+        Filter::from_str(expression).unwrap().matches(chunk, index)
+    }
+
+    #[test]
+    fn test_fields() {
+        let private = chunk("ruSt", 2000);
+        let critical = chunk("IHDR", 13);
+
+        assert!(matches("private", &private, 0));
+        assert!(!matches("private", &critical, 0));
+        assert!(matches("critical", &critical, 0));
+        assert!(matches("safe_to_copy", &private, 0));
+        assert!(matches("size > 1024", &private, 0));
+        assert!(matches("size <= 13", &critical, 0));
+        assert!(matches("index == 3", &critical, 3));
+        assert!(matches("index != 3", &critical, 2));
+        assert!(matches("type == ruSt", &private, 0));
+        assert!(matches("type == 'IHDR'", &critical, 0));
+        assert!(matches("type != \"IHDR\"", &private, 0));
+    }
+
+    #[test]
+    fn test_precedence() {
+        let private = chunk("ruSt", 10);
+
+        // && binds tighter than ||
+        assert!(matches("critical && size > 100 || private", &private, 0));
+        assert!(!matches("critical && (size > 100 || private)", &private, 0));
+        // ! binds tighter than &&
+        assert!(!matches("!private && size == 10", &private, 0));
+        assert!(matches("!(critical && private)", &private, 0));
+        assert!(matches("!!private", &private, 0));
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let error = |expression: &str| Filter::from_str(expression).unwrap_err();
+
+        assert_eq!(error("private &&"), FilterError { position: 10, message: "unexpected end of expression".to_string() });
+        assert_eq!(error("size > big").position, 7);
+        assert_eq!(error("colour == 1").message, "unknown field `colour`");
+        assert_eq!(error("(private").position, 8);
+        assert_eq!(error("private)").position, 7);
+        assert_eq!(error("type > ruSt").message, "type can only be compared with == or !=");
+        assert_eq!(error("size 12").position, 5);
+        assert_eq!(error("type == 'ruSt").position, 8);
+        assert_eq!(error("private & critical").message, "unexpected character `&`");
+        assert_eq!(error("").position, 0);
+    }
+}
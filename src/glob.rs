@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use ::glob::{MatchOptions, Pattern};
+
+use crate::Result;
+
+const METACHARACTERS: [char; 4] = ['*', '?', '[', '{'];
+
+/// Whether `path` is a pattern for `expand` rather than a file name: it has pattern characters
+/// and no file of that exact name exists.
+pub(crate) fn is_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(METACHARACTERS) && !path.exists()
+}
+
+/// `pattern` with every `{a,b}` alternation written out, the first alternation first.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let mut depth = 0;
+    let mut alternatives = Vec::new();
+    let mut start = open + 1;
+    for (index, c) in pattern[open..].char_indices().map(|(index, c)| (open + index, c)) {
+        match c {
+            '{' => depth += 1,
+            ',' if depth == 1 => {
+                alternatives.push(&pattern[start..index]);
+                start = index + 1;
+            }
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    alternatives.push(&pattern[start..index]);
+                    let (prefix, suffix) = (&pattern[..open], &pattern[index + 1..]);
+                    return alternatives
+                        .into_iter()
+                        .flat_map(|alternative| expand_braces(&format!("{prefix}{alternative}{suffix}")))
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+    }
+    // An unclosed brace is matched literally.
+    vec![pattern.to_string()]
+}
+
+/// How names match: a leading dot must be matched by a literal dot, as in a shell.
+const OPTIONS: MatchOptions = MatchOptions { case_sensitive: true, require_literal_separator: true, require_literal_leading_dot: true };
+
+/// Whether the file name `name` matches `pattern`, a pattern for one path component: `*`
+/// matches any run of characters, `?` any one character and `[...]` one of a set, like
+/// `[abc]`, `[a-z]` or `[!a]`. A pattern that isn't valid, like `a[b`, only matches itself.
+pub(crate) fn matches_name(pattern: &str, name: &str) -> bool {
+    match Pattern::new(pattern) {
+        Ok(pattern) => pattern.matches_with(name, OPTIONS),
+        Err(_) => pattern == name,
+    }
+}
+
+/// The entries of `dir` whose name matches `component`.
+fn matching_entries(dir: &Path, component: &str) -> Vec<PathBuf> {
+    let listed = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let Ok(entries) = fs::read_dir(listed) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| matches_name(component, name))
+        .map(|name| dir.join(name))
+        .collect()
+}
+
+/// `dir` and every directory below it, for a `**` component. Hidden directories are skipped.
+fn directories_below(dir: &Path) -> Vec<PathBuf> {
+    let mut directories = vec![dir.to_path_buf()];
+    for entry in matching_entries(dir, "*") {
+        if entry.is_dir() {
+            directories.extend(directories_below(&entry));
+        }
+    }
+    directories
+}
+
+/// The files matching one pattern without braces.
+fn expand_one(pattern: &str) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let component = match component {
+            Component::Normal(component) => component.to_string_lossy(),
+            other => {
+                paths.iter_mut().for_each(|path| path.push(other));
+                continue;
+            }
+        };
+        paths = if component == "**" {
+            paths.iter().flat_map(|path| directories_below(path)).collect()
+        } else if component.contains(METACHARACTERS) {
+            paths.iter().flat_map(|path| matching_entries(path, &component)).collect()
+        } else {
+            paths.into_iter().map(|path| path.join(component.as_ref())).collect()
+        };
+    }
+    paths.into_iter().filter(|path| path.is_file()).collect()
+}
+
+/// The files matching `pattern`, sorted.
+///
+/// Besides the `*`, `?` and `[...]` of `matches_name`, a `**` component matches any number of
+/// directories and `{a,b}` matches either alternative, so `{one.png,two.png}` names several
+/// files in one argument.
+pub(crate) fn expand(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = expand_braces(&pattern.to_string_lossy()).iter().flat_map(|pattern| expand_one(pattern)).collect();
+    files.sort();
+    files.dedup();
+    if files.is_empty() {
+        return Err(format!("No files match {}", pattern.display()).into());
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches_name("*.png", "icon.png"));
+        assert!(matches_name("*.png", ".png"));
+        assert!(!matches_name("*.png", "icon.png.bak"));
+        assert!(matches_name("icon-?.png", "icon-2.png"));
+        assert!(!matches_name("icon-?.png", "icon-12.png"));
+        assert!(matches_name("[a-c]*", "banner"));
+        assert!(!matches_name("[!a-c]*", "banner"));
+        assert!(matches_name("[]]", "]"));
+        assert!(matches_name("a[b", "a[b"));
+        assert!(!matches_name("a[b", "ab"));
+        assert!(!matches_name("*", ".hidden"));
+        assert!(!matches_name("[.]*", ".hidden"));
+        assert!(matches_name(".*", ".hidden"));
+        assert!(!matches_name("*", "dir/file"));
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        assert_eq!(expand_braces("{a,b}.png"), ["a.png", "b.png"]);
+        assert_eq!(expand_braces("x{a,{b,c}}{1,2}"), ["xa1", "xa2", "xb1", "xb2", "xc1", "xc2"]);
+        assert_eq!(expand_braces("{a,b"), ["{a,b"]);
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["a.png", "b.png", "c.txt", ".hidden.png", "icons/d.png", "icons/small/e.png", ".cache/f.png"] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        let expand_in = |pattern: &str| -> Vec<String> {
+            let files = expand(&dir.path().join(pattern)).unwrap();
+            files.iter().map(|file| file.strip_prefix(dir.path()).unwrap().to_string_lossy().into_owned()).collect()
+        };
+
+        assert_eq!(expand_in("*.png"), ["a.png", "b.png"]);
+        assert_eq!(expand_in("{c.txt,a.png,a.png}"), ["a.png", "c.txt"]);
+        assert_eq!(expand_in("*/*.png"), ["icons/d.png"]);
+        assert_eq!(expand_in("**/*.png"), ["a.png", "b.png", "icons/d.png", "icons/small/e.png"]);
+        assert_eq!(expand_in(".*/*.png"), [".cache/f.png"]);
+        assert_eq!(expand_in(".*.png"), [".hidden.png"]);
+        assert!(expand(&dir.path().join("*.jpg")).unwrap_err().to_string().starts_with("No files match"));
+        assert!(is_pattern(&dir.path().join("*.png")));
+        assert!(!is_pattern(&dir.path().join("a.png")));
+    }
+}
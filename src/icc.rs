@@ -0,0 +1,112 @@
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::text::{deflate, encode_keyword, inflate, latin1_decode};
+
+pub(crate) const ICCP_CHUNK_TYPE: &str = "iCCP";
+
+/// Length of the ICC profile header, which every profile starts with.
+const ICC_HEADER_LEN: usize = 128;
+
+/// An embedded ICC profile, from an iCCP chunk.
+#[derive(Debug, PartialEq)]
+pub(crate) struct IccProfile {
+    /// The name stored before the profile, a keyword of 1 to 79 Latin-1 characters.
+    pub(crate) name: String,
+    /// The profile itself, decompressed.
+    pub(crate) profile: Vec<u8>,
+}
+
+impl IccProfile {
+    /// Checks that `profile` looks like an ICC profile: a complete header with the `acsp`
+    /// signature and the size it declares.
+    pub(crate) fn new(name: &str, profile: Vec<u8>) -> Result<IccProfile, String> {
+        encode_keyword(name).map_err(|reason| format!("Invalid profile name: {reason}"))?;
+        if profile.len() < ICC_HEADER_LEN || &profile[36..40] != b"acsp" {
+            return Err("Not an ICC profile: no acsp signature in its header".to_string());
+        }
+        let declared = u32::from_be_bytes(profile[..4].try_into().expect("Slice should be of length 4"));
+        if declared as usize != profile.len() {
+            return Err(format!("ICC profile is {} bytes long but its header says {declared}", profile.len()));
+        }
+        Ok(IccProfile { name: name.to_string(), profile })
+    }
+
+    /// Reads the iCCP chunk of `png`, if it has one.
+    pub(crate) fn read(png: &Png) -> Option<Result<IccProfile, String>> {
+        png.chunk_by_type(ICCP_CHUNK_TYPE).map(IccProfile::parse)
+    }
+
+    fn parse(chunk: &Chunk) -> Result<IccProfile, String> {
+        let (name, rest) = chunk.keyword_and_rest()?;
+        let (&method, compressed) = rest.split_first().ok_or("no compression method after the profile name")?;
+        Ok(IccProfile { name: latin1_decode(name), profile: inflate(method, compressed)? })
+    }
+
+    fn to_chunk(&self) -> Result<Chunk<'static>, String> {
+        let mut data = encode_keyword(&self.name)?;
+        data.extend_from_slice(&[0, 0]);
+        data.extend(deflate(&self.profile));
+        Chunk::try_new(ChunkType::from_str(ICCP_CHUNK_TYPE).expect("iCCP is a valid chunk type"), data).map_err(|error| error.to_string())
+    }
+
+    /// Writes the profile into `png`, replacing its iCCP chunk, or adding one before PLTE and
+    /// the image data as the spec requires.
+    pub(crate) fn write(&self, png: &mut Png) -> Result<(), String> {
+        let chunk = self.to_chunk()?;
+        if png.chunk_by_type(ICCP_CHUNK_TYPE).is_some() {
+            png.replace_or_insert_chunk(chunk);
+        } else {
+            png.insert_before_first_of(&["PLTE", "IDAT", "IEND"], chunk);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_profile() -> Vec<u8> {
+        let mut profile = vec![0; 140];
+        profile[..4].copy_from_slice(&140u32.to_be_bytes());
+        profile[36..40].copy_from_slice(b"acsp");
+        profile
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![]);
+        let mut png = Png::from_chunks(vec![chunk("IHDR"), chunk("PLTE"), chunk("IDAT"), chunk("IEND")]);
+        let icc = IccProfile::new("Display P3", testing_profile()).unwrap();
+
+        icc.write(&mut png).unwrap();
+        icc.write(&mut png).unwrap();
+
+        let types: Vec<String> = png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "iCCP", "PLTE", "IDAT", "IEND"]);
+        assert!(png[1].data().starts_with(b"Display P3\0\0\x78"));
+        assert_eq!(IccProfile::read(&png), Some(Ok(icc)));
+    }
+
+    #[test]
+    fn test_new_checks_profile() {
+        assert!(IccProfile::new("", testing_profile()).unwrap_err().starts_with("Invalid profile name"));
+        assert!(IccProfile::new("sRGB", vec![0; 140]).unwrap_err().contains("acsp"));
+
+        let mut truncated = testing_profile();
+        truncated.truncate(130);
+        assert_eq!(IccProfile::new("sRGB", truncated).unwrap_err(), "ICC profile is 130 bytes long but its header says 140");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let chunk = |data: &[u8]| Chunk::new(ChunkType::from_str(ICCP_CHUNK_TYPE).unwrap(), data.to_vec());
+
+        assert!(IccProfile::parse(&chunk(b"name")).is_err());
+        assert!(IccProfile::parse(&chunk(b"name\0")).unwrap_err().contains("compression method"));
+        assert_eq!(IccProfile::parse(&chunk(b"name\0\x01")).unwrap_err(), "unknown compression method 1");
+    }
+}
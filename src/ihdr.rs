@@ -0,0 +1,384 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+
+/// How pixels are stored, from the IHDR color type byte.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(format!("Unknown color type {value}")),
+        }
+    }
+}
+
+impl ColorType {
+    fn code(self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+
+    fn channels(self) -> u32 {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    /// Bit depths the spec allows for this color type.
+    fn bit_depths(self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl fmt::Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "rgb",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale with alpha",
+            ColorType::Rgba => "rgba",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The Adam7 passes as `(x start, y start, x step, y step)`.
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] =
+    [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+/// The image header, the first chunk of every png.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(crate) struct Ihdr {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) bit_depth: u8,
+    pub(crate) color_type: ColorType,
+    pub(crate) interlaced: bool,
+}
+
+impl Ihdr {
+    pub(crate) const LEN: usize = 13;
+
+    /// Reads the header of `png`, if it has a well-formed one.
+    pub(crate) fn from_png(png: &Png) -> Option<Ihdr> {
+        Ihdr::read(png).ok()
+    }
+
+    fn bits_per_pixel(&self) -> u32 {
+        self.color_type.channels() * u32::from(self.bit_depth)
+    }
+
+    /// Bytes per complete pixel, rounded up to 1, which is how far back filters look.
+    pub(crate) fn bytes_per_pixel(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8) as usize
+    }
+
+    /// Bytes in a scanline `width` pixels wide, without its filter type byte.
+    pub(crate) fn row_len(&self, width: u32) -> usize {
+        (u64::from(width) * u64::from(self.bits_per_pixel())).div_ceil(8) as usize
+    }
+
+    /// Width and height of each pass that has pixels: the whole image, or the Adam7 passes.
+    pub(crate) fn passes(&self) -> Vec<(u32, u32)> {
+        let passes: &[(u32, u32, u32, u32)] = if self.interlaced { &ADAM7_PASSES } else { &[(0, 0, 1, 1)] };
+        passes
+            .iter()
+            .map(|&(x_start, y_start, x_step, y_step)| {
+                (self.width.saturating_sub(x_start).div_ceil(x_step), self.height.saturating_sub(y_start).div_ceil(y_step))
+            })
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .collect()
+    }
+
+    /// Size of the image data once inflated: every scanline of every pass, each with its
+    /// filter type byte.
+    pub(crate) fn image_data_len(&self) -> u64 {
+        self.passes().into_iter().map(|(width, height)| u64::from(height) * (1 + self.row_len(width) as u64)).sum()
+    }
+
+    /// Checks the field combination against the spec.
+    pub(crate) fn check(&self) -> crate::Result<()> {
+        if self.width == 0 || self.height == 0 || self.width > i32::MAX as u32 || self.height > i32::MAX as u32 {
+            return Err(format!("{}x{} is not a valid image size", self.width, self.height).into());
+        }
+        if !self.color_type.bit_depths().contains(&self.bit_depth) {
+            return Err(format!("{} images can't have a bit depth of {}", self.color_type, self.bit_depth).into());
+        }
+        Ok(())
+    }
+
+    /// Writes the fields over an existing IHDR payload, leaving the compression and filter
+    /// method bytes as they are.
+    pub(crate) fn write_into(&self, data: &mut [u8]) {
+        data[0..4].copy_from_slice(&self.width.to_be_bytes());
+        data[4..8].copy_from_slice(&self.height.to_be_bytes());
+        data[8] = self.bit_depth;
+        data[9] = self.color_type.code();
+        data[12] = u8::from(self.interlaced);
+    }
+
+    /// One `Field: value` line per header field, as printed by `pngme inspect`.
+    pub(crate) fn field_lines(&self) -> Vec<String> {
+        vec![
+            format!("Width: {}", self.width),
+            format!("Height: {}", self.height),
+            format!("Bit depth: {}", self.bit_depth),
+            format!("Color type: {} ({})", self.color_type, self.color_type.code()),
+            format!("Interlace method: {}", if self.interlaced { "Adam7 (1)" } else { "none (0)" }),
+        ]
+    }
+
+    /// Like `from_png`, but says why the header couldn't be read.
+    pub(crate) fn read(png: &Png) -> Result<Ihdr, String> {
+        let chunk = png.chunk_by_type("IHDR").ok_or("No IHDR chunk")?;
+        Ihdr::try_from(chunk.data())
+    }
+}
+
+impl fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}, {}-bit {}", self.width, self.height, self.bit_depth, self.color_type)?;
+        if self.interlaced {
+            write!(f, ", interlaced")?;
+        }
+        Ok(())
+    }
+}
+
+/// Header fields to change with `pngme ihdr set`.
+#[derive(Debug, Default)]
+pub(crate) struct IhdrEdit {
+    pub(crate) width: Option<u32>,
+    pub(crate) height: Option<u32>,
+    pub(crate) bit_depth: Option<u8>,
+    pub(crate) color_type: Option<ColorType>,
+    pub(crate) interlaced: Option<bool>,
+    /// Allow width and height changes, which desynchronize the pixel data.
+    pub(crate) allow_unsafe: bool,
+}
+
+/// Applies `edit` to the IHDR chunk of `png` and returns warnings worth showing.
+///
+/// The pixel data isn't converted, so a bit depth or color type change is only accepted when
+/// it keeps the number of bits per pixel: the pixel data then has exactly the size the new
+/// header expects.
+pub(crate) fn apply_edit(png: &mut Png, edit: &IhdrEdit) -> crate::Result<Vec<String>> {
+    let index = png
+        .chunks()
+        .position(|chunk| chunk.chunk_type().bytes() == *b"IHDR")
+        .ok_or("No IHDR chunk")?;
+    let current = Ihdr::try_from(png[index].data())?;
+    let edited = Ihdr {
+        width: edit.width.unwrap_or(current.width),
+        height: edit.height.unwrap_or(current.height),
+        bit_depth: edit.bit_depth.unwrap_or(current.bit_depth),
+        color_type: edit.color_type.unwrap_or(current.color_type),
+        interlaced: edit.interlaced.unwrap_or(current.interlaced),
+    };
+    let mut warnings = Vec::new();
+
+    if (edited.width, edited.height) != (current.width, current.height) {
+        if !edit.allow_unsafe {
+            return Err("Changing width or height desynchronizes the pixel data, use --unsafe to do it anyway".into());
+        }
+        warnings.push(format!(
+            "image size changed from {}x{} to {}x{}, the pixel data no longer matches the header",
+            current.width, current.height, edited.width, edited.height
+        ));
+    }
+
+    edited.check()?;
+    if edited.bits_per_pixel() != current.bits_per_pixel() {
+        return Err(format!(
+            "{}-bit {} uses {} bits per pixel and {}-bit {} uses {}, the existing image data can't match the new header",
+            current.bit_depth, current.color_type, current.bits_per_pixel(),
+            edited.bit_depth, edited.color_type, edited.bits_per_pixel()
+        ).into());
+    }
+
+    let has_palette = png.chunk_by_type("PLTE").is_some();
+    match edited.color_type {
+        ColorType::Indexed if !has_palette => return Err("Indexed images need a PLTE chunk".into()),
+        ColorType::Grayscale | ColorType::GrayscaleAlpha if has_palette => {
+            return Err(format!("{} images must not have a PLTE chunk", edited.color_type).into());
+        }
+        _ => {}
+    }
+
+    let mut data = png[index].data().to_vec();
+    edited.write_into(&mut data);
+    let chunk_type = png[index].chunk_type().clone();
+    png.replace_chunk_unchecked(index, Chunk::new(chunk_type, data));
+    Ok(warnings)
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = String;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != Ihdr::LEN {
+            return Err(format!("IHDR should be {} bytes long, not {}", Ihdr::LEN, data.len()));
+        }
+
+        let u32_at = |pos: usize| u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap());
+        Ok(Ihdr {
+            width: u32_at(0),
+            height: u32_at(4),
+            bit_depth: data[8],
+            color_type: ColorType::try_from(data[9])?,
+            interlaced: data[12] == 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ihdr() {
+        let data = [0, 0, 1, 0, 0, 0, 0, 32, 8, 3, 0, 0, 1];
+
+        let ihdr = Ihdr::try_from(data.as_ref()).unwrap();
+
+        assert_eq!(ihdr, Ihdr { width: 256, height: 32, bit_depth: 8, color_type: ColorType::Indexed, interlaced: true });
+    }
+
+    #[test]
+    fn test_parse_ihdr_invalid() {
+        assert!(Ihdr::try_from([0; 12].as_ref()).is_err());
+
+        let mut data = [0; 13];
+        data[9] = 5;
+        assert!(Ihdr::try_from(data.as_ref()).is_err());
+    }
+
+    fn testing_png(ihdr: [u8; 13]) -> Png<'static> {
+        use crate::chunk_type::ChunkType;
+        use std::str::FromStr;
+
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr.to_vec()),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![0; 8]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ])
+    }
+
+    // 16x16 rgb, 8 bits per channel, interlaced
+    const RGB_INTERLACED: [u8; 13] = [0, 0, 0, 16, 0, 0, 0, 16, 8, 2, 0, 0, 1];
+
+    #[test]
+    fn test_fix_interlace() {
+        let mut png = testing_png(RGB_INTERLACED);
+
+        let warnings = apply_edit(&mut png, &IhdrEdit { interlaced: Some(false), ..IhdrEdit::default() }).unwrap();
+
+        assert!(warnings.is_empty());
+        assert!(png.is_modified());
+        assert_eq!(png[0].data(), &[0, 0, 0, 16, 0, 0, 0, 16, 8, 2, 0, 0, 0]);
+        let bytes = png.as_bytes();
+        let reparsed = Png::try_from(bytes.as_slice()).unwrap();
+        assert!(!Ihdr::from_png(&reparsed).unwrap().interlaced);
+    }
+
+    #[test]
+    fn test_refuse_size_change() {
+        let mut png = testing_png(RGB_INTERLACED);
+
+        assert!(apply_edit(&mut png, &IhdrEdit { width: Some(32), ..IhdrEdit::default() }).is_err());
+        assert!(!png.is_modified());
+
+        let edit = IhdrEdit { width: Some(32), allow_unsafe: true, ..IhdrEdit::default() };
+        let warnings = apply_edit(&mut png, &edit).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(Ihdr::from_png(&png).unwrap().width, 32);
+    }
+
+    #[test]
+    fn test_color_changes_must_keep_pixel_size() {
+        let mut png = testing_png(RGB_INTERLACED);
+        let edit = IhdrEdit { color_type: Some(ColorType::Rgba), ..IhdrEdit::default() };
+        assert!(apply_edit(&mut png, &edit).is_err());
+
+        let edit = IhdrEdit { bit_depth: Some(4), ..IhdrEdit::default() };
+        assert!(apply_edit(&mut png, &edit).is_err());
+
+        // 16-bit grayscale and 8-bit grayscale with alpha both use 16 bits per pixel.
+        let mut png = testing_png([0, 0, 0, 16, 0, 0, 0, 16, 16, 0, 0, 0, 0]);
+        let edit = IhdrEdit { bit_depth: Some(8), color_type: Some(ColorType::GrayscaleAlpha), ..IhdrEdit::default() };
+        apply_edit(&mut png, &edit).unwrap();
+        assert_eq!(Ihdr::from_png(&png).unwrap().color_type, ColorType::GrayscaleAlpha);
+    }
+
+    #[test]
+    fn test_indexed_needs_palette() {
+        let mut png = testing_png([0, 0, 0, 16, 0, 0, 0, 16, 8, 0, 0, 0, 0]);
+        let edit = IhdrEdit { color_type: Some(ColorType::Indexed), ..IhdrEdit::default() };
+
+        assert_eq!(apply_edit(&mut png, &edit).unwrap_err().to_string(), "Indexed images need a PLTE chunk");
+    }
+
+    #[test]
+    fn test_image_data_len() {
+        let ihdr = |width, height, bit_depth, color_type, interlaced| Ihdr { width, height, bit_depth, color_type, interlaced };
+
+        assert_eq!(ihdr(3, 2, 8, ColorType::Rgb, false).image_data_len(), 2 * (1 + 9));
+        assert_eq!(ihdr(10, 1, 1, ColorType::Grayscale, false).image_data_len(), 1 + 2);
+        // 1x1 interlaced only has pixels in the first pass.
+        assert_eq!(ihdr(1, 1, 16, ColorType::Rgba, true).image_data_len(), 1 + 8);
+        // 8x8 interlaced: passes of 1x1, 1x1, 2x1, 2x2, 4x2, 4x4 and 8x4 pixels.
+        assert_eq!(ihdr(8, 8, 8, ColorType::Grayscale, true).image_data_len(), 2 + 2 + 3 + 2 * 3 + 2 * 5 + 4 * 5 + 4 * 9);
+    }
+
+    #[test]
+    fn test_display_ihdr() {
+        let ihdr = Ihdr { width: 1920, height: 1080, bit_depth: 8, color_type: ColorType::Rgba, interlaced: false };
+
+        assert_eq!(ihdr.to_string(), "1920x1080, 8-bit rgba");
+    }
+
+    #[test]
+    fn test_field_lines() {
+        let ihdr = Ihdr::try_from([0, 0, 7, 128, 0, 0, 4, 56, 16, 6, 0, 0, 1].as_ref()).unwrap();
+
+        assert_eq!(ihdr.field_lines(), vec![
+            "Width: 1920",
+            "Height: 1080",
+            "Bit depth: 16",
+            "Color type: rgba (6)",
+            "Interlace method: Adam7 (1)",
+        ]);
+    }
+}
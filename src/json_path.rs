@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// Raw bytes of a path that isn't valid UTF-8, when the platform exposes them.
+#[cfg(unix)]
+fn raw_bytes(path: &Path) -> Option<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.to_str().is_none().then(|| path.as_os_str().as_bytes().to_vec())
+}
+
+#[cfg(windows)]
+fn raw_bytes(path: &Path) -> Option<Vec<u8>> {
+    use std::os::windows::ffi::OsStrExt;
+
+    // UTF-16 code units, little endian, since unpaired surrogates have no UTF-8 form.
+    path.to_str().is_none().then(|| path.as_os_str().encode_wide().flat_map(u16::to_le_bytes).collect())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn raw_bytes(_path: &Path) -> Option<Vec<u8>> {
+    None
+}
+
+/// A path as written in JSON output.
+///
+/// Serializes as a `path` field holding the path as a (possibly lossy) string. When the path
+/// isn't valid UTF-8, a `path_base64` field is added with its raw bytes in base64 (UTF-16LE
+/// code units on Windows), so the exact path can be recovered. Meant to be flattened into the
+/// object describing the file.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct JsonPath(PathBuf);
+
+impl JsonPath {
+    pub(crate) fn as_path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl From<PathBuf> for JsonPath {
+    fn from(path: PathBuf) -> JsonPath {
+        JsonPath(path)
+    }
+}
+
+impl Serialize for JsonPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let raw = raw_bytes(&self.0);
+        let mut map = serializer.serialize_map(Some(1 + raw.is_some() as usize))?;
+        map.serialize_entry("path", &self.0.to_string_lossy())?;
+        if let Some(raw) = raw {
+            map.serialize_entry("path_base64", &BASE64.encode(&raw))?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_path() {
+        let json = serde_json::to_value(JsonPath::from(PathBuf::from("dir/ïmage.png"))).unwrap();
+
+        assert_eq!(json, serde_json::json!({ "path": "dir/ïmage.png" }));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+
+        let path = PathBuf::from(OsString::from_vec(b"img\xff.png".to_vec()));
+
+        let json = serde_json::to_value(JsonPath::from(path)).unwrap();
+
+        assert_eq!(json["path"], "img\u{fffd}.png");
+        assert_eq!(json["path_base64"], BASE64.encode(b"img\xff.png"));
+    }
+}
@@ -0,0 +1,103 @@
+//! Reading and writing png chunks, as done by the `pngme` command line.
+//!
+//! ```no_run
+//! use std::str::FromStr;
+//!
+//! use pngme::{Chunk, ChunkType, Png};
+//!
+//! # fn main() -> pngme::Result<()> {
+//! let bytes = std::fs::read("image.png")?;
+//! let mut png = Png::try_from(bytes.as_slice())?;
+//! png.append_chunk(Chunk::new(ChunkType::from_str("ruSt")?, b"hidden message".to_vec()));
+//! std::fs::write("image.png", png.as_bytes())?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod ancillary;
+mod apng;
+mod app;
+mod batch;
+mod capacity;
+mod chunk;
+mod chunk_builder;
+mod chunk_type;
+mod clean;
+mod codec;
+mod cli;
+mod commands;
+mod copy_chunk;
+mod crc_check;
+mod crypto;
+mod encode;
+mod envelope;
+mod error;
+mod escape;
+mod exif;
+mod explode;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod extract;
+mod filter;
+mod glob;
+mod icc;
+mod ihdr;
+mod json_path;
+mod list;
+mod lock;
+mod lsb;
+mod merge;
+mod order;
+mod pipe;
+mod output;
+mod palette;
+mod passphrase;
+mod phys;
+mod png;
+mod png_builder;
+mod preview;
+mod print;
+#[cfg(feature = "python")]
+mod python;
+mod repair;
+mod retype;
+mod scan;
+mod selector;
+mod size;
+mod split;
+mod stamp;
+mod stream;
+mod summary;
+mod text;
+mod text_check;
+mod time;
+mod timeout;
+mod validate;
+mod verify;
+mod walk;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "watch")]
+mod watch;
+mod xmp;
+
+pub type Error = Box<dyn std::error::Error>;
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub use crate::chunk::{Chunk, ChunkReader};
+pub use crate::chunk_builder::ChunkBuilder;
+pub use crate::chunk_type::ChunkType;
+pub use crate::encode::SizeBudgetError;
+pub use crate::error::PngmeError;
+pub use crate::ihdr::ColorType;
+pub use crate::lock::LockTimeout;
+pub use crate::output::{RoundtripMismatch, SerializationMismatch};
+pub use crate::png::{ParseLimits, Png};
+pub use crate::png_builder::PngBuilder;
+pub use crate::summary::{PngSummary, TypeStats};
+pub use crate::timeout::TimedOut;
+pub use crate::validate::ValidationIssue;
+
+/// The `pngme` command line, run by the binary.
+#[doc(hidden)]
+pub use crate::app::main;
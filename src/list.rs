@@ -0,0 +1,160 @@
+use serde::Serialize;
+
+use crate::chunk_type::ChunkType;
+use crate::png::{ChunkRange, Png};
+use crate::size::{format_size, SizeStyle};
+use crate::stream::ChunkIndex;
+
+/// A chunk as listed by `pngme list`.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct ListRow {
+    pub(crate) index: usize,
+    pub(crate) chunk_type: String,
+    pub(crate) length: u64,
+    pub(crate) crc: u32,
+    /// Where the chunk starts in the file, at its length field.
+    pub(crate) offset: u64,
+    /// Where the chunk data starts.
+    pub(crate) data_offset: u64,
+    /// Just past the chunk's CRC.
+    pub(crate) end_offset: u64,
+    pub(crate) critical: bool,
+    pub(crate) public: bool,
+    pub(crate) safe_to_copy: bool,
+}
+
+impl ListRow {
+    fn new(index: usize, chunk_type: &ChunkType, length: u64, crc: u32, range: &ChunkRange) -> ListRow {
+        ListRow {
+            index,
+            chunk_type: chunk_type.to_string(),
+            length,
+            crc,
+            offset: range.offset,
+            data_offset: range.data_offset,
+            end_offset: range.end_offset,
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+        }
+    }
+
+    /// `critical public unsafe`, one word per property bit of the chunk type.
+    fn flags(&self) -> String {
+        let critical = if self.critical { "critical" } else { "ancillary" };
+        let public = if self.public { "public" } else { "private" };
+        let copy = if self.safe_to_copy { "safe" } else { "unsafe" };
+        format!("{critical} {public} {copy}")
+    }
+}
+
+pub(crate) fn rows(png: &Png) -> Vec<ListRow> {
+    png.chunks()
+        .zip(png.chunk_ranges())
+        .enumerate()
+        .map(|(index, (chunk, range))| ListRow::new(index, chunk.chunk_type(), chunk.data().len() as u64, chunk.crc(), &range))
+        .collect()
+}
+
+/// `rows` for a file too large to load, from its chunk headers.
+pub(crate) fn index_rows(index: &ChunkIndex) -> Vec<ListRow> {
+    index
+        .chunks
+        .iter()
+        .enumerate()
+        .map(|(position, chunk)| ListRow::new(position, &chunk.chunk_type, u64::from(chunk.length), chunk.crc, &chunk.range))
+        .collect()
+}
+
+/// The rows as a table with a header line, columns padded to their widest cell.
+pub(crate) fn table(rows: &[ListRow], sizes: SizeStyle) -> Vec<String> {
+    let header = ["#", "TYPE", "LENGTH", "CRC", "OFFSET", "FLAGS"].map(String::from);
+    let cells: Vec<[String; 6]> = std::iter::once(header)
+        .chain(rows.iter().map(|row| {
+            [
+                row.index.to_string(),
+                row.chunk_type.clone(),
+                format_size(row.length, sizes),
+                format!("{:08x}", row.crc),
+                format!("{:#x}", row.offset),
+                row.flags(),
+            ]
+        }))
+        .collect();
+
+    let mut widths = [0; 6];
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    cells
+        .iter()
+        .map(|row| {
+            let line: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{cell:<width$}")).collect();
+            line.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::stream;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png<'static> {
+        let chunk = |chunk_type: &str, len: usize| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; len]);
+        Png::from_chunks(vec![chunk("IHDR", 13), chunk("ruSt", 2048), chunk("IEND", 0)])
+    }
+
+    #[test]
+    fn test_rows() {
+        let rows = rows(&testing_png());
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].chunk_type, "ruSt");
+        assert_eq!(rows[1].length, 2048);
+        assert_eq!(rows[1].offset, 8 + 25);
+        assert_eq!(rows[1].data_offset, 8 + 25 + 8);
+        assert_eq!(rows[1].end_offset, 8 + 25 + 12 + 2048);
+        assert_eq!(rows[1].crc, testing_png()[1].crc());
+        assert!(!rows[1].critical && !rows[1].public && rows[1].safe_to_copy);
+        assert_eq!(rows[1].flags(), "ancillary private safe");
+        assert_eq!(rows[0].flags(), "critical public unsafe");
+    }
+
+    #[test]
+    fn test_rows_slice_original_file() {
+        let bytes = testing_png().as_bytes();
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        let index = stream::index_chunks(Cursor::new(&bytes)).unwrap();
+
+        for rows in [rows(&png), index_rows(&index)] {
+            assert_eq!(rows.len(), png.chunk_count());
+            for (row, chunk) in rows.iter().zip(png.chunks()) {
+                let parsed = Chunk::try_from(&bytes[row.offset as usize..row.end_offset as usize]).unwrap();
+                assert_eq!(parsed.chunk_type(), chunk.chunk_type());
+                assert_eq!(&bytes[row.data_offset as usize..row.end_offset as usize - 4], chunk.data());
+            }
+        }
+    }
+
+    #[test]
+    fn test_table() {
+        let png = testing_png();
+        let crc = |index: usize| format!("{:08x}", png[index].crc());
+
+        let table = table(&rows(&png), SizeStyle::Human);
+
+        assert_eq!(table, vec![
+            "#  TYPE  LENGTH    CRC       OFFSET  FLAGS".to_string(),
+            format!("0  IHDR  13 bytes  {}  0x8     critical public unsafe", crc(0)),
+            format!("1  ruSt  2.0 KiB   {}  0x21    ancillary private safe", crc(1)),
+            format!("2  IEND  0 bytes   {}  0x82d   critical public unsafe", crc(2)),
+        ]);
+    }
+}
@@ -0,0 +1,166 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Exit code used when another pngme kept a file locked for longer than `--lock-timeout`.
+pub(crate) const LOCK_TIMEOUT_EXIT_CODE: i32 = 6;
+
+/// How often a contended lock is tried again.
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A file stayed locked by another process for longer than `--lock-timeout`.
+#[derive(Debug, PartialEq)]
+pub struct LockTimeout {
+    pub(crate) path: PathBuf,
+    pub(crate) timeout: Duration,
+}
+
+impl fmt::Display for LockTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is locked by another pngme, gave up waiting after {}s",
+            self.path.display(),
+            self.timeout.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for LockTimeout {}
+
+/// Exclusive lock on a png, released when dropped.
+#[derive(Debug)]
+pub(crate) struct FileLock {
+    _file: File,
+    path: PathBuf,
+}
+
+impl Drop for FileLock {
+    /// Deletes the lock file, so edits leave nothing next to the png. It's deleted while still
+    /// locked: a process waiting on it then sees it's gone and locks a new one.
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The sidecar file locked for `path`, next to it.
+///
+/// The png itself can't hold the lock: writes replace it with a new file, and a process
+/// waiting on the old one would then lock a file nobody else looks at.
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".pngme-lock");
+    path.with_file_name(name)
+}
+
+/// Takes the exclusive lock guarding `path` against concurrent pngme invocations.
+///
+/// Waits for as long as another process holds it, or up to `timeout` when one is given.
+pub(crate) fn lock(path: &Path, timeout: Option<Duration>) -> crate::Result<FileLock> {
+    let lock_path = lock_path(path);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path)?;
+        if !acquire(&file, deadline)? {
+            return Err(LockTimeout { path: path.to_path_buf(), timeout: timeout.unwrap_or_default() }.into());
+        }
+        if is_current(&file, &lock_path) {
+            return Ok(FileLock { _file: file, path: lock_path });
+        }
+    }
+}
+
+/// Locks `file`, returning false when `deadline` passed first.
+fn acquire(file: &File, deadline: Option<Instant>) -> io::Result<bool> {
+    let Some(deadline) = deadline else {
+        file.lock()?;
+        return Ok(true);
+    };
+
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(true),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => thread::sleep(RETRY_INTERVAL),
+            Err(TryLockError::WouldBlock) => return Ok(false),
+            Err(TryLockError::Error(error)) => return Err(error),
+        }
+    }
+}
+
+/// Whether `file` is still the lock file at `path`, and not one the previous holder deleted
+/// while this process was waiting on it.
+fn is_current(file: &File, path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (file.metadata(), fs::metadata(path)) {
+            (Ok(locked), Ok(current)) => locked.dev() == current.dev() && locked.ino() == current.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = file;
+        path.exists()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_path() {
+        assert_eq!(lock_path(Path::new("assets/logo.png")), PathBuf::from("assets/.logo.png.pngme-lock"));
+    }
+
+    #[test]
+    fn test_contended_lock_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+
+        let held = lock(&path, None).unwrap();
+        let error = lock(&path, Some(Duration::from_millis(100))).unwrap_err();
+        assert!(error.is::<LockTimeout>());
+
+        drop(held);
+        assert!(lock(&path, Some(Duration::ZERO)).is_ok());
+    }
+
+    #[test]
+    fn test_dropped_lock_leaves_no_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+
+        let held = lock(&path, None).unwrap();
+        assert!(lock_path(&path).exists());
+        drop(held);
+
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_waiter_relocks_after_the_holder_deletes_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+
+        let held = lock(&path, None).unwrap();
+        let waiter = {
+            let path = path.clone();
+            thread::spawn(move || lock(&path, None).unwrap())
+        };
+        thread::sleep(RETRY_INTERVAL);
+        drop(held);
+
+        let taken = waiter.join().unwrap();
+        assert!(lock_path(&path).exists());
+        assert!(lock(&path, Some(Duration::ZERO)).unwrap_err().is::<LockTimeout>());
+        drop(taken);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
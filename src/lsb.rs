@@ -0,0 +1,237 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::{ColorType, Ihdr};
+use crate::png::Png;
+use crate::text::deflate;
+
+/// Starts the bits hidden in the image data, so random low bits aren't read as a payload.
+const MAGIC: [u8; 8] = *b"pngmeLSB";
+
+/// The magic, then the chunk type the payload was encoded under and its length.
+const HEADER_LEN: usize = MAGIC.len() + 8;
+
+const NO_PAYLOAD: &str = "No payload hidden in the image data";
+
+/// Only 8 and 16-bit samples that aren't palette indexes hide a bit without a visible change.
+fn check_supported(ihdr: &Ihdr) -> Result<(), String> {
+    if ihdr.color_type == ColorType::Indexed || ihdr.bit_depth < 8 {
+        return Err(format!(
+            "LSB mode needs 8 or 16-bit samples that aren't palette indexes, this is a {}-bit {} image",
+            ihdr.bit_depth, ihdr.color_type
+        ));
+    }
+    Ok(())
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let distance = |value: u8| (estimate - i16::from(value)).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Reverses the filter of `row` in place, `previous` being the unfiltered row above it.
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bytes_per_pixel: usize) -> Result<(), String> {
+    if filter > 4 {
+        return Err(format!("Unknown filter type {filter} in the image data"));
+    }
+    for i in 0..row.len() {
+        let left = if i >= bytes_per_pixel { row[i - bytes_per_pixel] } else { 0 };
+        let up_left = if i >= bytes_per_pixel { previous[i - bytes_per_pixel] } else { 0 };
+        let predictor = match filter {
+            1 => left,
+            2 => previous[i],
+            3 => ((u16::from(left) + u16::from(previous[i])) / 2) as u8,
+            4 => paeth(left, previous[i], up_left),
+            _ => 0,
+        };
+        row[i] = row[i].wrapping_add(predictor);
+    }
+    Ok(())
+}
+
+/// The image data of `png` inflated and unfiltered, every scanline keeping a filter type byte
+/// of 0 (None), so that it is still valid image data for the IHDR.
+fn unfiltered_image_data(png: &Png, ihdr: &Ihdr) -> Result<Vec<u8>, String> {
+    let compressed: Vec<u8> = png.chunks_by_type("IDAT").flat_map(|chunk| chunk.data()).copied().collect();
+    let expected = ihdr.image_data_len();
+    let mut data = Vec::new();
+    ZlibDecoder::new(compressed.as_slice())
+        .take(expected + 1)
+        .read_to_end(&mut data)
+        .map_err(|error| format!("Can't inflate the image data: {error}"))?;
+    if data.len() as u64 != expected {
+        return Err(format!("Image data inflates to {} bytes, the IHDR calls for {expected}", data.len()));
+    }
+
+    let mut start = 0;
+    for (width, height) in ihdr.passes() {
+        let row_len = ihdr.row_len(width);
+        let mut previous = vec![0; row_len];
+        for _ in 0..height {
+            let (filter, row) = data[start..start + 1 + row_len].split_first_mut().expect("Scanlines start with a filter type byte");
+            unfilter(*filter, row, &previous, ihdr.bytes_per_pixel())?;
+            *filter = 0;
+            previous.copy_from_slice(row);
+            start += 1 + row_len;
+        }
+    }
+    Ok(data)
+}
+
+/// Offsets in the unfiltered image data of the bytes whose low bit carries the payload: the
+/// low byte of every sample, skipping filter type bytes.
+fn carriers(ihdr: &Ihdr) -> impl Iterator<Item = usize> {
+    let sample_len = if ihdr.bit_depth == 16 { 2 } else { 1 };
+    let mut rows = Vec::new();
+    let mut start = 0;
+    for (width, height) in ihdr.passes() {
+        let row_len = ihdr.row_len(width);
+        for _ in 0..height {
+            rows.push((start + 1, row_len));
+            start += 1 + row_len;
+        }
+    }
+    rows.into_iter().flat_map(move |(start, len)| (start + sample_len - 1..start + len).step_by(sample_len))
+}
+
+/// How many bytes of chunk data `write` can hide in `png`, from its dimensions and format.
+pub(crate) fn capacity(png: &Png) -> Result<usize, String> {
+    let ihdr = Ihdr::read(png)?;
+    check_supported(&ihdr)?;
+    Ok((carriers(&ihdr).count() / 8).saturating_sub(HEADER_LEN))
+}
+
+/// Replaces the IDAT chunks of `png` with `compressed`, where the first one was.
+fn replace_image_data(png: &mut Png, compressed: &[u8]) {
+    let index = png.chunks().position(|chunk| chunk.chunk_type().bytes() == *b"IDAT").unwrap_or(png.chunk_count());
+    png.remove_chunks_where(|chunk, _| chunk.chunk_type().bytes() == *b"IDAT");
+    for (offset, data) in compressed.chunks(Chunk::MAX_DATA_LEN).enumerate() {
+        let chunk_type = ChunkType::try_from(*b"IDAT").expect("IDAT is a valid chunk type");
+        png.insert_chunk_at_unchecked(index + offset, Chunk::new(chunk_type, data.to_vec()));
+    }
+}
+
+/// Hides `chunk`, its type and data, in the low bits of the image samples of `png` and
+/// re-compresses the image data. Only the low bits change, so the image looks the same.
+pub(crate) fn write(png: &mut Png, chunk: &Chunk) -> Result<(), String> {
+    let ihdr = Ihdr::read(png)?;
+    check_supported(&ihdr)?;
+    let mut data = unfiltered_image_data(png, &ihdr)?;
+    let room = carriers(&ihdr).count() / 8;
+    if HEADER_LEN + chunk.data().len() > room {
+        return Err(format!(
+            "The payload is {} bytes long, the image data only has room for {} in LSB mode",
+            chunk.data().len(),
+            room.saturating_sub(HEADER_LEN)
+        ));
+    }
+
+    let length = u32::try_from(chunk.data().len()).expect("Chunk data fits in a u32");
+    let hidden = [MAGIC.as_slice(), &chunk.chunk_type().bytes(), &length.to_be_bytes(), chunk.data()].concat();
+    let bits = hidden.iter().flat_map(|byte| (0..8).rev().map(move |shift| (byte >> shift) & 1));
+    for (offset, bit) in carriers(&ihdr).zip(bits) {
+        data[offset] = data[offset] & !1 | bit;
+    }
+    replace_image_data(png, &deflate(&data));
+    Ok(())
+}
+
+/// Reads the chunk hidden by `write` back from the image data of `png`.
+pub(crate) fn read(png: &Png) -> Result<Chunk<'static>, String> {
+    let ihdr = Ihdr::read(png)?;
+    check_supported(&ihdr)?;
+    let data = unfiltered_image_data(png, &ihdr)?;
+
+    let mut bits = carriers(&ihdr).map(|offset| data[offset] & 1);
+    let mut next_bytes = |count: usize| -> Option<Vec<u8>> {
+        (0..count).map(|_| (0..8).try_fold(0u8, |byte, _| bits.next().map(|bit| byte << 1 | bit))).collect()
+    };
+    let header = next_bytes(HEADER_LEN).ok_or(NO_PAYLOAD)?;
+    if header[..MAGIC.len()] != MAGIC {
+        return Err(NO_PAYLOAD.to_string());
+    }
+    let chunk_type: [u8; 4] = header[MAGIC.len()..MAGIC.len() + 4].try_into().expect("Slice should be of length 4");
+    let chunk_type = ChunkType::try_from(chunk_type).map_err(|error| format!("Hidden payload has an invalid chunk type: {error}"))?;
+    let length = u32::from_be_bytes(header[MAGIC.len() + 4..].try_into().expect("Slice should be of length 4"));
+    let payload = next_bytes(length as usize).ok_or("Hidden payload is longer than the image data can hold")?;
+    Chunk::try_new(chunk_type, payload).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    /// An image of the given size and format, every scanline with another filter.
+    fn testing_png(width: u32, height: u32, bit_depth: u8, color_type: u8, interlaced: bool) -> Png<'static> {
+        let ihdr = [&width.to_be_bytes()[..], &height.to_be_bytes(), &[bit_depth, color_type, 0, 0, u8::from(interlaced)]].concat();
+        let header = Ihdr::try_from(ihdr.as_slice()).unwrap();
+        let mut data = Vec::new();
+        let mut filter = 0;
+        for (width, height) in header.passes() {
+            for _ in 0..height {
+                data.push(filter % 5);
+                data.extend((0..header.row_len(width)).map(|i| (i * 37 + usize::from(filter) * 11) as u8));
+                filter += 1;
+            }
+        }
+        Png::from_chunks(vec![chunk("IHDR", ihdr), chunk("IDAT", deflate(&data)), chunk("tEXt", b"a\0b".to_vec()), chunk("IEND", vec![])])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (bit_depth, color_type, interlaced) in [(8, 2, false), (16, 6, false), (8, 0, true)] {
+            let mut png = testing_png(32, 8, bit_depth, color_type, interlaced);
+            let before = unfiltered_image_data(&png, &Ihdr::read(&png).unwrap()).unwrap();
+
+            write(&mut png, &chunk("ruSt", b"hi".to_vec())).unwrap();
+
+            let hidden = read(&png).unwrap();
+            assert_eq!((hidden.chunk_type().to_string(), hidden.data()), ("ruSt".to_string(), b"hi".as_slice()));
+            let after = unfiltered_image_data(&png, &Ihdr::read(&png).unwrap()).unwrap();
+            assert!(before.iter().zip(&after).all(|(before, after)| before >> 1 == after >> 1));
+            let types: Vec<String> = png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+            assert_eq!(types, ["IHDR", "IDAT", "tEXt", "IEND"]);
+        }
+    }
+
+    #[test]
+    fn test_capacity_and_unsupported_images() {
+        let mut png = testing_png(4, 3, 8, 2, false);
+        // 4x3 rgb holds 36 bits, less than the header alone.
+        assert_eq!(read(&png).unwrap_err(), NO_PAYLOAD);
+        assert!(write(&mut png, &chunk("ruSt", vec![])).unwrap_err().contains("only has room for 0"));
+        assert_eq!(capacity(&png), Ok(0));
+        // 32x8 rgb holds 768 bits, 96 bytes.
+        assert_eq!(capacity(&testing_png(32, 8, 8, 2, false)), Ok(96 - HEADER_LEN));
+
+        let mut png = testing_png(4, 3, 8, 3, false);
+        assert!(write(&mut png, &chunk("ruSt", vec![])).unwrap_err().starts_with("LSB mode needs 8 or 16-bit samples"));
+        assert!(capacity(&testing_png(4, 3, 4, 0, false)).is_err());
+    }
+
+    #[test]
+    fn test_unfilter() {
+        assert_eq!(paeth(10, 20, 15), 15);
+        let mut row = [1, 2, 3, 4];
+        unfilter(1, &mut row, &[0; 4], 2).unwrap();
+        assert_eq!(row, [1, 2, 4, 6]);
+        unfilter(3, &mut row, &[10, 10, 10, 10], 2).unwrap();
+        assert_eq!(row, [6, 7, 12, 14]);
+        assert!(unfilter(5, &mut row, &[0; 4], 1).is_err());
+    }
+}
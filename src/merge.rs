@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::chunk::Chunk;
+use crate::order;
+use crate::png::Png;
+use crate::Result;
+
+/// What `merge` does when a file has chunks of a type the merged files already have.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub(crate) enum ConflictPolicy {
+    /// Keep the chunks of the file listed first
+    #[default]
+    FirstWins,
+    /// Keep the chunks of the file listed last
+    LastWins,
+    /// Refuse to merge the files
+    FailOnConflict,
+}
+
+/// Parses `--policy`: a chunk type and the policy for it, as `tEXt=last-wins`.
+pub(crate) fn parse_type_policy(s: &str) -> std::result::Result<(String, ConflictPolicy), String> {
+    let (chunk_type, policy) = s.split_once('=').ok_or_else(|| format!("expected TYPE=POLICY, got {s:?}"))?;
+    if chunk_type.len() != 4 {
+        return Err(format!("{chunk_type:?} is not a chunk type"));
+    }
+    Ok((chunk_type.to_string(), ConflictPolicy::from_str(policy, false)?))
+}
+
+/// The conflict policy of every chunk type: `by_type` where it names the type, else `default`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Policies {
+    pub(crate) default: ConflictPolicy,
+    pub(crate) by_type: Vec<(String, ConflictPolicy)>,
+}
+
+impl Policies {
+    fn of(&self, chunk_type: &str) -> ConflictPolicy {
+        self.by_type.iter().rev().find(|(name, _)| name == chunk_type).map_or(self.default, |&(_, policy)| policy)
+    }
+}
+
+/// Merges the ancillary chunks of `overlay`, read from `path`, into `base`, and returns the
+/// types left out because they aren't safe to copy, which `force` merges anyway.
+///
+/// Types `base` doesn't have are added where the placement rules want them. Types it has are
+/// settled by their policy, unless both files have the same chunks of that type.
+pub(crate) fn merge(base: &mut Png<'static>, overlay: &Png, path: &Path, policies: &Policies, force: bool) -> Result<Vec<String>> {
+    let mut types: Vec<String> = Vec::new();
+    for chunk in overlay.chunks().filter(|chunk| !chunk.chunk_type().is_critical()) {
+        let chunk_type = chunk.chunk_type().to_string();
+        if !types.contains(&chunk_type) {
+            types.push(chunk_type);
+        }
+    }
+
+    let mut skipped = Vec::new();
+    for chunk_type in types {
+        let chunks: Vec<Chunk<'static>> = overlay.chunks_by_type(&chunk_type).map(|chunk| chunk.clone().into_owned()).collect();
+        if !chunks[0].chunk_type().is_safe_to_copy() && !force {
+            skipped.push(chunk_type);
+            continue;
+        }
+        let existing: Vec<usize> = base.chunks().enumerate().filter(|(_, chunk)| chunk.chunk_type().to_string() == chunk_type).map(|(index, _)| index).collect();
+        if existing.is_empty() {
+            for chunk in chunks {
+                order::insert_in_place(base, chunk);
+            }
+            continue;
+        }
+        if existing.iter().map(|&index| &base[index]).eq(chunks.iter()) {
+            continue;
+        }
+
+        match policies.of(&chunk_type) {
+            ConflictPolicy::FirstWins => {}
+            ConflictPolicy::LastWins => {
+                for &index in existing.iter().rev() {
+                    base.remove_chunk_at_unchecked(index);
+                }
+                for (offset, chunk) in chunks.into_iter().enumerate() {
+                    base.insert_chunk_at_unchecked(existing[0] + offset, chunk);
+                }
+            }
+            ConflictPolicy::FailOnConflict => {
+                return Err(format!(
+                    "{} has {chunk_type} chunks that differ from the ones already merged; use --policy {chunk_type}=first-wins or last-wins to pick",
+                    path.display()
+                ).into());
+            }
+        }
+    }
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn testing_png(extra: Vec<Chunk<'static>>) -> Png<'static> {
+        let mut chunks = vec![chunk("IHDR", "0123456789abc")];
+        chunks.extend(extra);
+        chunks.extend([chunk("IDAT", "pixels"), chunk("IEND", "")]);
+        Png::from_chunks(chunks)
+    }
+
+    fn contents(png: &Png) -> Vec<String> {
+        png.chunks().map(|chunk| format!("{}:{}", chunk.chunk_type(), String::from_utf8_lossy(chunk.data()))).collect()
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = testing_png(vec![chunk("tEXt", "Title\0Base")]);
+        let overlay = testing_png(vec![chunk("tEXt", "Title\0Overlay"), chunk("IDAT", "other"), chunk("ruSt", "one"), chunk("ruSt", "two")]);
+
+        let skipped = merge(&mut base, &overlay, Path::new("overlay.png"), &Policies::default(), false).unwrap();
+
+        assert!(skipped.is_empty());
+        assert_eq!(contents(&base), ["IHDR:0123456789abc", "tEXt:Title\0Base", "IDAT:pixels", "ruSt:one", "ruSt:two", "IEND:"]);
+    }
+
+    #[test]
+    fn test_merge_policies() {
+        let overlay = testing_png(vec![chunk("tEXt", "Title\0Overlay"), chunk("ruSt", "new")]);
+        let merged = |policies: Policies| {
+            let mut base = testing_png(vec![chunk("tEXt", "Title\0Base"), chunk("tEXt", "Author\0Base"), chunk("ruSt", "old")]);
+            merge(&mut base, &overlay, Path::new("overlay.png"), &policies, false).map(|_| contents(&base)[1..4].to_vec())
+        };
+
+        let by_type = vec![("tEXt".to_string(), ConflictPolicy::LastWins)];
+        assert_eq!(merged(Policies { default: ConflictPolicy::FirstWins, by_type }).unwrap(), ["tEXt:Title\0Overlay", "ruSt:old", "IDAT:pixels"]);
+        let by_type = vec![("ruSt".to_string(), ConflictPolicy::FirstWins)];
+        let error = merged(Policies { default: ConflictPolicy::FailOnConflict, by_type }).unwrap_err();
+        assert!(error.to_string().starts_with("overlay.png has tEXt chunks that differ"));
+
+        // Identical chunks are no conflict.
+        let mut base = testing_png(vec![chunk("tEXt", "Title\0Overlay"), chunk("ruSt", "new")]);
+        merge(&mut base, &overlay, Path::new("overlay.png"), &Policies { default: ConflictPolicy::FailOnConflict, by_type: Vec::new() }, false).unwrap();
+        assert!(!base.is_modified());
+    }
+
+    #[test]
+    fn test_merge_skips_unsafe_to_copy() {
+        let overlay = testing_png(vec![chunk("sRGB", "0"), chunk("ruST", "tied to the image")]);
+
+        let mut base = testing_png(Vec::new());
+        assert_eq!(merge(&mut base, &overlay, Path::new("overlay.png"), &Policies::default(), false).unwrap(), ["sRGB", "ruST"]);
+        assert!(!base.is_modified());
+
+        merge(&mut base, &overlay, Path::new("overlay.png"), &Policies::default(), true).unwrap();
+        assert_eq!(contents(&base), ["IHDR:0123456789abc", "sRGB:0", "IDAT:pixels", "ruST:tied to the image", "IEND:"]);
+    }
+
+    #[test]
+    fn test_parse_type_policy() {
+        assert_eq!(parse_type_policy("tEXt=last-wins").unwrap(), ("tEXt".to_string(), ConflictPolicy::LastWins));
+        assert_eq!(parse_type_policy("ruSt=fail-on-conflict").unwrap().1, ConflictPolicy::FailOnConflict);
+        assert!(parse_type_policy("tEXt").is_err());
+        assert!(parse_type_policy("text=sometimes").is_err());
+        assert!(parse_type_policy("=first-wins").is_err());
+    }
+}
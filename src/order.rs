@@ -0,0 +1,207 @@
+use std::fmt;
+
+use clap::ValueEnum;
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+
+/// Ancillary chunks the spec requires before PLTE and IDAT.
+const BEFORE_PLTE: [&str; 6] = ["cHRM", "cICP", "gAMA", "iCCP", "sBIT", "sRGB"];
+
+/// Ancillary chunks the spec requires after PLTE and before IDAT.
+const BEFORE_IDAT: [&str; 3] = ["bKGD", "hIST", "tRNS"];
+
+/// Position class of a chunk under the spec placement rules, lower classes come first.
+///
+/// Chunks without a placement rule keep their side of the image data.
+/// Apple's CgBI chunk precedes IHDR in iOS-optimized files and stays there.
+fn placement_rank(chunk: &Chunk, seen_idat: bool) -> u8 {
+    let chunk_type = chunk.chunk_type().to_string();
+    match chunk_type.as_str() {
+        "CgBI" => 0,
+        "IHDR" => 1,
+        t if BEFORE_PLTE.contains(&t) => 2,
+        "PLTE" => 3,
+        t if BEFORE_IDAT.contains(&t) => 4,
+        "IDAT" => 6,
+        "IEND" => 8,
+        _ if seen_idat => 7,
+        _ => 5,
+    }
+}
+
+/// Moves chunks so they follow the spec placement rules: IHDR first, the colour space chunks
+/// before PLTE, the PLTE dependent chunks before the image data, contiguous IDAT and IEND last.
+///
+/// The sort is stable, so chunks in the same class keep their relative order.
+pub(crate) fn reorder_spec(png: &mut Png) {
+    let mut seen_idat = false;
+    let ranks: Vec<u8> = png
+        .chunks()
+        .map(|chunk| {
+            seen_idat |= chunk.chunk_type().to_string() == "IDAT";
+            placement_rank(chunk, seen_idat)
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..ranks.len()).collect();
+    order.sort_by_key(|&index| ranks[index]);
+
+    png.reorder_chunks(&order);
+}
+
+/// Inserts `chunk` where the placement rules want a new chunk of its type: before PLTE for the
+/// colour space chunks, before the image data for the PLTE dependent ones, and before IEND for
+/// the others.
+pub(crate) fn insert_in_place<'a>(png: &mut Png<'a>, chunk: Chunk<'a>) {
+    let chunk_type = chunk.chunk_type().to_string();
+    match chunk_type.as_str() {
+        t if BEFORE_PLTE.contains(&t) => png.insert_before_first_of(&["PLTE", "IDAT", "IEND"], chunk),
+        t if BEFORE_IDAT.contains(&t) => png.insert_before_first_of(&["IDAT", "IEND"], chunk),
+        _ => png.insert_before_iend(chunk),
+    }
+}
+
+/// What to do when a png about to be written breaks the chunk ordering rules.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub(crate) enum ChunkOrder {
+    /// Refuse to write the file
+    #[default]
+    Reject,
+    /// Move the chunks to the positions the spec requires, as `cat --reorder-spec` does
+    Fix,
+    /// Write the chunks in the order they are in
+    Keep,
+}
+
+/// A break of the chunk ordering rules every png must follow, at chunk `index`.
+#[derive(Debug, PartialEq)]
+pub(crate) enum OrderViolation {
+    /// IHDR isn't the first chunk, Apple's CgBI aside.
+    IhdrNotFirst { index: usize },
+    PlteAfterIdat { index: usize },
+    /// A chunk of another type between two IDAT chunks.
+    IdatSplit { index: usize, chunk_type: String },
+    /// A chunk after IEND.
+    ChunkAfterIend { index: usize, chunk_type: String },
+}
+
+impl fmt::Display for OrderViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderViolation::IhdrNotFirst { index } => write!(f, "IHDR is chunk {index}, it should be the first"),
+            OrderViolation::PlteAfterIdat { index } => write!(f, "PLTE, chunk {index}, comes after the image data"),
+            OrderViolation::IdatSplit { index, chunk_type } => write!(f, "{chunk_type}, chunk {index}, splits the IDAT chunks"),
+            OrderViolation::ChunkAfterIend { index, chunk_type } => write!(f, "{chunk_type}, chunk {index}, comes after IEND"),
+        }
+    }
+}
+
+/// Checks the ordering rules: IHDR first, PLTE before IDAT, IDAT chunks contiguous and IEND
+/// last. Missing or duplicated chunks are `validate`'s business, not reported here.
+pub(crate) fn violations(png: &Png) -> Vec<OrderViolation> {
+    let types: Vec<String> = png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+    let first_of = |chunk_type: &str| types.iter().position(|found| found == chunk_type);
+    let last_of = |chunk_type: &str| types.iter().rposition(|found| found == chunk_type);
+    let mut violations = Vec::new();
+
+    let first = types.iter().position(|chunk_type| chunk_type != "CgBI");
+    if let Some(index) = first_of("IHDR").filter(|&index| Some(index) != first) {
+        violations.push(OrderViolation::IhdrNotFirst { index });
+    }
+    if let (Some(first_idat), Some(last_idat)) = (first_of("IDAT"), last_of("IDAT")) {
+        if let Some(index) = first_of("PLTE").filter(|&index| index > first_idat) {
+            violations.push(OrderViolation::PlteAfterIdat { index });
+        }
+        for (index, chunk_type) in types.iter().enumerate().take(last_idat).skip(first_idat) {
+            if chunk_type != "IDAT" {
+                violations.push(OrderViolation::IdatSplit { index, chunk_type: chunk_type.clone() });
+            }
+        }
+    }
+    if let Some(iend) = first_of("IEND") {
+        for (index, chunk_type) in types.iter().enumerate().skip(iend + 1) {
+            violations.push(OrderViolation::ChunkAfterIend { index, chunk_type: chunk_type.clone() });
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), chunk_type.as_bytes().to_vec())
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_reorder_spec() {
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR"),
+            chunk("PLTE"),
+            chunk("gAMA"),
+            chunk("IDAT"),
+            chunk("tRNS"),
+            chunk("IDAT"),
+            chunk("tEXt"),
+            chunk("IEND"),
+            chunk("ruSt"),
+        ]);
+
+        reorder_spec(&mut png);
+
+        assert_eq!(types(&png), ["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "IDAT", "tEXt", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_reorder_spec_keeps_valid_order() {
+        let order = ["IHDR", "sRGB", "tEXt", "IDAT", "IDAT", "zTXt", "IEND"];
+        let mut png = Png::from_chunks(order.iter().map(|t| chunk(t)).collect());
+
+        reorder_spec(&mut png);
+
+        assert_eq!(types(&png), order);
+        assert!(!png.is_modified());
+    }
+
+    #[test]
+    fn test_insert_in_place() {
+        let mut png = Png::from_chunks(["IHDR", "PLTE", "IDAT", "IEND"].iter().map(|t| chunk(t)).collect());
+
+        for chunk_type in ["tEXt", "tRNS", "iCCP"] {
+            insert_in_place(&mut png, chunk(chunk_type));
+        }
+
+        assert_eq!(types(&png), ["IHDR", "iCCP", "PLTE", "tRNS", "IDAT", "tEXt", "IEND"]);
+    }
+
+    #[test]
+    fn test_violations() {
+        let png = Png::from_chunks(["ruSt", "IHDR", "IDAT", "tEXt", "IDAT", "PLTE", "IEND", "laTe"].iter().map(|t| chunk(t)).collect());
+
+        assert_eq!(violations(&png), vec![
+            OrderViolation::IhdrNotFirst { index: 1 },
+            OrderViolation::PlteAfterIdat { index: 5 },
+            OrderViolation::IdatSplit { index: 3, chunk_type: "tEXt".to_string() },
+            OrderViolation::ChunkAfterIend { index: 7, chunk_type: "laTe".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_reorder_spec_fixes_violations() {
+        let valid = Png::from_chunks(["CgBI", "IHDR", "PLTE", "IDAT", "IDAT", "IEND"].iter().map(|t| chunk(t)).collect());
+        assert!(violations(&valid).is_empty());
+
+        let mut png = Png::from_chunks(["IDAT", "IHDR", "IEND", "PLTE", "IDAT"].iter().map(|t| chunk(t)).collect());
+        reorder_spec(&mut png);
+        assert!(violations(&png).is_empty());
+    }
+}
@@ -0,0 +1,843 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::chunk::Chunk;
+use crate::error::PngmeError;
+use crate::order::{self, ChunkOrder};
+use crate::png::Png;
+use crate::Result;
+
+/// Serialized bytes disagree with the in-memory model, which means pngme has a bug.
+#[derive(Debug, PartialEq)]
+pub struct SerializationMismatch {
+    pub(crate) expected_len: u64,
+    pub(crate) actual_len: u64,
+    pub(crate) expected_chunks: usize,
+    pub(crate) actual_chunks: Option<usize>,
+}
+
+impl fmt::Display for SerializationMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let actual_chunks = match self.actual_chunks {
+            Some(count) => count.to_string(),
+            None => "unreadable".to_string(),
+        };
+        write!(
+            f,
+            "Internal error: serialized {} bytes with {actual_chunks} chunks but expected {} bytes with {} chunks, the file was not written",
+            self.actual_len, self.expected_len, self.expected_chunks
+        )
+    }
+}
+
+impl std::error::Error for SerializationMismatch {}
+
+/// A file read back after writing doesn't match what pngme meant to write. The original is
+/// kept, since it is only replaced once the new contents have been checked.
+#[derive(Debug, PartialEq)]
+pub struct RoundtripMismatch {
+    pub(crate) path: PathBuf,
+    pub(crate) reason: String,
+}
+
+impl fmt::Display for RoundtripMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Internal error: {} doesn't read back as written ({}), the original was kept", self.path.display(), self.reason)
+    }
+}
+
+impl std::error::Error for RoundtripMismatch {}
+
+/// Exit code used with `--fail-unchanged` when there was nothing to write.
+pub(crate) const UNCHANGED_EXIT_CODE: i32 = 4;
+
+/// The command didn't change the file, reported as an error with `--fail-unchanged`.
+#[derive(Debug)]
+pub(crate) struct Unchanged;
+
+impl fmt::Display for Unchanged {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No changes")
+    }
+}
+
+impl std::error::Error for Unchanged {}
+
+/// Global flags shared by every command that writes a png back to disk.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct WriteOptions {
+    pub(crate) paranoid: bool,
+    pub(crate) fail_unchanged: bool,
+    /// Serialize and verify as usual but don't write anything.
+    pub(crate) check_only: bool,
+    /// Read every written file back and compare it with the in-memory model.
+    pub(crate) verify_roundtrip: bool,
+    /// Allow in-place edits of Apple CgBI pngs.
+    pub(crate) force: bool,
+    /// Refuse to write an output path that is a symbolic link.
+    pub(crate) no_follow_symlinks: bool,
+    /// What to do with a modified png whose chunks break the ordering rules.
+    pub(crate) chunk_order: ChunkOrder,
+    /// Copy a file about to be overwritten to its path with this suffix appended.
+    pub(crate) backup: Option<String>,
+}
+
+/// Parses `--backup=SUFFIX`: text appended to the file name, so no path separators.
+pub(crate) fn parse_backup_suffix(s: &str) -> std::result::Result<String, String> {
+    if s.is_empty() || s.contains(['/', std::path::MAIN_SEPARATOR]) {
+        return Err(format!("{s:?} can't be appended to a file name, use a suffix like .bak or ~"));
+    }
+    Ok(s.to_string())
+}
+
+/// Where `--backup=SUFFIX` copies `path`: the same name with `suffix` appended.
+fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Copies `path` to its backup path when `options` ask for one and it exists, before it is
+/// overwritten. An earlier backup is replaced.
+fn back_up(path: &Path, options: &WriteOptions) -> Result<()> {
+    let Some(suffix) = &options.backup else {
+        return Ok(());
+    };
+    if options.check_only || is_stdio(path) || !path.is_file() {
+        return Ok(());
+    }
+    let backup = backup_path(path, suffix);
+    fs::copy(path, &backup).map_err(|error| format!("Can't back up {} to {}: {error}", path.display(), backup.display()))?;
+    Ok(())
+}
+
+/// Counts the chunks in serialized PNG bytes by walking their length fields.
+fn count_chunks(bytes: &[u8]) -> Option<usize> {
+    let mut pos = Png::STANDARD_HEADER.len();
+    let mut count = 0;
+    while pos < bytes.len() {
+        let length = u32::from_be_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        pos = pos.checked_add(Chunk::METADATA_LEN + length)?;
+        count += 1;
+    }
+    (pos == bytes.len()).then_some(count)
+}
+
+/// Compares `bytes` against the size and chunk count predicted from `png`.
+pub(crate) fn check_serialization(png: &Png, bytes: &[u8]) -> std::result::Result<(), SerializationMismatch> {
+    let expected_len = png.serialized_len();
+    let expected_chunks = png.chunk_count();
+    let chunk_bytes = bytes.len().checked_sub(png.trailing_data().len()).map(|end| &bytes[..end]);
+    let actual_chunks = chunk_bytes.and_then(count_chunks);
+
+    if bytes.len() as u64 != expected_len || actual_chunks != Some(expected_chunks) {
+        return Err(SerializationMismatch { expected_len, actual_len: bytes.len() as u64, expected_chunks, actual_chunks });
+    }
+
+    Ok(())
+}
+
+/// Whether `path` is `-`, which stands for stdin or stdout.
+pub(crate) fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Reads the whole file at `path`, or stdin when `path` is `-`.
+pub(crate) fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    if is_stdio(path) {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        return Ok(bytes);
+    }
+
+    fs::read(path)
+}
+
+/// Writes `bytes` as they are to the file at `path`, or to stdout when `path` is `-`.
+pub(crate) fn write_raw(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if is_stdio(path) {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(bytes)?;
+        return stdout.flush();
+    }
+
+    fs::write(path, bytes)
+}
+
+/// Strictly parses `bytes` and compares every chunk and the trailing data with `png`.
+pub(crate) fn compare_roundtrip(png: &Png, bytes: &[u8]) -> std::result::Result<(), String> {
+    let written = Png::try_from(bytes)?;
+
+    if written.chunk_count() != png.chunk_count() {
+        return Err(format!("{} chunks instead of {}", written.chunk_count(), png.chunk_count()));
+    }
+    for (index, (actual, expected)) in written.chunks().zip(png.chunks()).enumerate() {
+        if actual.chunk_type() != expected.chunk_type() || actual.data() != expected.data() {
+            return Err(format!("chunk {index} is {} but {} was expected", actual.chunk_type(), expected.chunk_type()));
+        }
+    }
+    if written.trailing_data() != png.trailing_data() {
+        return Err("trailing data differs".to_string());
+    }
+
+    Ok(())
+}
+
+/// Applies `policy` to a png about to be written to `path`, returning the reordered copy to
+/// write instead when it had to be fixed.
+///
+/// Only modified pngs are checked: pngme refuses to produce a misordered file, but copies one
+/// it was given as it is.
+fn check_order<'a>(path: &Path, png: &Png<'a>, policy: ChunkOrder) -> Result<Option<Png<'a>>> {
+    if !png.is_modified() || policy == ChunkOrder::Keep {
+        return Ok(None);
+    }
+    let violations = order::violations(png);
+    if violations.is_empty() {
+        return Ok(None);
+    }
+    if policy == ChunkOrder::Reject {
+        let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        return Err(format!(
+            "{} would be written with its chunks out of order: {}; use --chunk-order fix to reorder them or --chunk-order keep to write them anyway",
+            path.display(),
+            violations.join("; ")
+        ).into());
+    }
+
+    let mut fixed = Png::from_chunks(png.chunks().cloned().collect());
+    fixed.set_trailing_data(png.trailing_data().to_vec());
+    order::reorder_spec(&mut fixed);
+    Ok(Some(fixed))
+}
+
+fn write_with(path: &Path, png: &Png, options: WriteOptions, serialize: impl Fn(&Png) -> Vec<u8>) -> Result<()> {
+    let Some(staged) = stage(path, png, &options, serialize)? else {
+        return Ok(());
+    };
+    if let Err(error) = back_up(&staged.target, &options) {
+        let _ = fs::remove_file(&staged.temp);
+        return Err(error);
+    }
+    Ok(staged.put_in_place()?)
+}
+
+/// A checked temporary file, ready to replace `target`.
+struct Staged {
+    temp: PathBuf,
+    target: PathBuf,
+}
+
+impl Staged {
+    /// Renames the temporary file over its target, or copies it in place when the target has
+    /// other hard links.
+    fn put_in_place(self) -> io::Result<()> {
+        if link_count(&self.target) > 1 {
+            return write_in_place(&self.temp, &self.target);
+        }
+        fs::rename(&self.temp, &self.target)
+    }
+}
+
+/// Serializes and checks `png`, then writes it to a temporary file next to the file `path`
+/// stands for. Returns `None` when there is nothing to put in place: with `check_only`, or
+/// when `path` is `-` and `png` went straight to stdout.
+fn stage(path: &Path, png: &Png, options: &WriteOptions, serialize: impl Fn(&Png) -> Vec<u8>) -> Result<Option<Staged>> {
+    let fixed = check_order(path, png, options.chunk_order)?;
+    let png = fixed.as_ref().unwrap_or(png);
+    // Checking the serialization needs the whole file in memory; otherwise it is streamed
+    // straight to its destination.
+    let bytes = if options.paranoid || options.check_only || cfg!(debug_assertions) {
+        let bytes = serialize(png);
+        check_serialization(png, &bytes)?;
+        Some(bytes)
+    } else {
+        None
+    };
+    if options.check_only {
+        return Ok(None);
+    }
+    let write = |out: &mut dyn Write| -> io::Result<()> {
+        match &bytes {
+            Some(bytes) => out.write_all(bytes)?,
+            None => png.write_to(&mut *out)?,
+        }
+        out.flush()
+    };
+
+    if is_stdio(path) {
+        write(&mut BufWriter::new(io::stdout().lock()))?;
+        return Ok(None);
+    }
+
+    // A symlink is written through: its target is the file that gets replaced.
+    let is_symlink = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink());
+    let target = if is_symlink { fs::canonicalize(path)? } else { path.to_path_buf() };
+    if fs::metadata(&target).is_ok_and(|metadata| metadata.permissions().readonly()) {
+        return Err(format!("{} is read-only", target.display()).into());
+    }
+    let temp = temp_path(&target);
+    if let Err(error) = write_temp(&temp, &target, png, options, &write) {
+        let _ = fs::remove_file(&temp);
+        return Err(error);
+    }
+    Ok(Some(Staged { temp, target }))
+}
+
+/// How many names the file at `path` has, 1 where hard links can't be counted.
+fn link_count(path: &Path) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(path).map_or(1, |metadata| metadata.nlink())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        1
+    }
+}
+
+/// Copies the finished temporary file over `target` byte for byte and deletes it. Every hard
+/// link to `target` then sees the new contents, where a rename would only move one name.
+fn write_in_place(temp: &Path, target: &Path) -> io::Result<()> {
+    fs::copy(temp, target)?;
+    fs::OpenOptions::new().write(true).open(target)?.sync_all()?;
+    fs::remove_file(temp)
+}
+
+/// Whether `a` and `b` are the same file under any names: the same device and inode on Unix,
+/// the same canonical path elsewhere.
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match (fs::canonicalize(a), fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Writes the temporary file that replaces `target`, with the permissions of `target`, and
+/// flushes it to disk so the rename can't expose a partly written file after a crash.
+fn write_temp(temp: &Path, target: &Path, png: &Png, options: &WriteOptions, write: &dyn Fn(&mut dyn Write) -> io::Result<()>) -> Result<()> {
+    let mut out = BufWriter::new(fs::File::create(temp)?);
+    write(&mut out)?;
+    let file = out.into_inner().map_err(io::IntoInnerError::into_error)?;
+    if let Ok(metadata) = fs::metadata(target) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.sync_all()?;
+
+    if options.verify_roundtrip {
+        if let Err(reason) = compare_roundtrip(png, &fs::read(temp)?) {
+            return Err(RoundtripMismatch { path: target.to_path_buf(), reason }.into());
+        }
+    }
+    Ok(())
+}
+
+/// Writes `png` to `path`, or to stdout when `path` is `-`.
+///
+/// A file is never written in place: `png` goes to a temporary file next to it, which is
+/// renamed over it once complete, so an interrupted write leaves the original as it was.
+///
+/// Debug builds always check the serialized bytes against the in-memory model before
+/// touching the file; release builds do so when `paranoid` or `check_only` is set. With
+/// `check_only` nothing is written. With `verify_roundtrip` the temporary file is read back,
+/// and the original is kept if it doesn't match `png`.
+///
+/// A file with other hard links is overwritten in place from the finished temporary file
+/// instead of being replaced, so the links keep sharing it. That copy isn't atomic.
+pub(crate) fn write_png(path: &Path, png: &Png, options: WriteOptions) -> Result<()> {
+    write_with(path, png, options, |png| png.as_bytes())
+}
+
+/// Writes back a png a command has edited, skipping the write when nothing changed.
+///
+/// Stdout, `-`, is always written, changed or not, so the next command in a pipeline gets a
+/// png. Apple CgBI files are only written with `force`.
+pub(crate) fn save_png(path: &Path, png: &Png, options: WriteOptions) -> Result<()> {
+    if !needs_saving(path, png, &options)? {
+        if !options.check_only {
+            println!("No changes");
+        }
+        return Ok(());
+    }
+
+    write_png(path, png, options)
+}
+
+/// Whether `save_png` writes `png` back to `path`, or an error when it must not: an Apple
+/// CgBI file without `force`, or an unmodified one with `fail_unchanged`.
+fn needs_saving(path: &Path, png: &Png, options: &WriteOptions) -> Result<bool> {
+    if png.is_cgbi() && !options.force {
+        return Err(PngmeError::AppleCgbi { path: path.to_path_buf() }.into());
+    }
+    if !png.is_modified() {
+        if options.fail_unchanged {
+            return Err(Unchanged.into());
+        }
+        return Ok(is_stdio(path));
+    }
+    Ok(true)
+}
+
+/// Writes `png` to an `-o` path (`-` for stdout), which may be the file it was read from:
+/// like every write it goes through a temporary file, so the input is never truncated while
+/// it's still needed. A symlinked output is written through to its target, unless
+/// `no_follow_symlinks` is set.
+pub(crate) fn write_output(output: &Path, png: &Png, options: WriteOptions) -> Result<()> {
+    let is_symlink = fs::symlink_metadata(output).is_ok_and(|metadata| metadata.file_type().is_symlink());
+    if is_symlink && options.no_follow_symlinks {
+        return Err(format!("{} is a symbolic link, not writing through it", output.display()).into());
+    }
+    write_png(output, png, options)
+}
+
+/// Temporary file a write prepares next to `path`, e.g. `.image.png.pngme-tmp`.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{name}.pngme-tmp"))
+}
+
+/// Writes every png of the group or none of them.
+///
+/// Every file is checked first, the way `save_png` checks it: an Apple CgBI file needs
+/// `force`, and unmodified files are skipped, or fail the group with `fail_unchanged`.
+///
+/// Each file is staged the way `write_png` stages it: checked, then written and synced to a
+/// temporary file next to its destination, with the destination's permissions, through
+/// symlinks, and read back with `verify_roundtrip`. Read-only destinations are refused,
+/// since a rename would replace them anyway. Only once every temporary file exists are the
+/// originals backed up and replaced, in path order; on any earlier failure the temporary files
+/// are deleted and no original is touched.
+///
+/// A crash during the renames leaves the files before some path updated and the ones after it
+/// untouched, with their temporary files still present, so the group can be completed by
+/// renaming the remaining temporary files in the same order.
+pub(crate) fn save_all(files: &[(PathBuf, Png)], options: WriteOptions) -> Result<()> {
+    let mut files: Vec<&(PathBuf, Png)> = files.iter().collect();
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut unchanged = Vec::new();
+    for (path, png) in &files {
+        if !needs_saving(path, png, &options)? {
+            unchanged.push(path);
+        }
+    }
+    for path in &unchanged {
+        if !options.check_only {
+            println!("{}: No changes", path.display());
+        }
+    }
+    files.retain(|(path, _)| !unchanged.contains(&path));
+
+    let mut staged = Vec::with_capacity(files.len());
+    let result = files.iter().try_for_each(|(path, png)| {
+        if is_stdio(path) {
+            return Err("stdout can't be written as part of a group".into());
+        }
+        staged.extend(stage(path, png, &options, |png| png.as_bytes())?);
+        Ok(())
+    });
+
+    if let Err(error) = result {
+        for file in &staged {
+            let _ = fs::remove_file(&file.temp);
+        }
+        return Err(error);
+    }
+
+    for file in &staged {
+        back_up(&file.target, &options)?;
+    }
+    for file in staged {
+        file.put_in_place()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png<'static> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"I am the first chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), b"I am the last chunk".to_vec()),
+        ])
+    }
+
+    #[test]
+    fn test_check_serialization() {
+        let png = testing_png();
+
+        assert!(check_serialization(&png, &png.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_check_serialization_wrong_length() {
+        let png = testing_png();
+        let mut bytes = png.as_bytes();
+        bytes.pop();
+
+        let error = check_serialization(&png, &bytes).unwrap_err();
+
+        assert_eq!(error.actual_len, png.serialized_len() - 1);
+        assert_eq!(error.actual_chunks, None);
+    }
+
+    #[test]
+    fn test_check_serialization_wrong_chunk_count() {
+        let png = testing_png();
+        // Same length, but the two chunks are framed as a single one.
+        let mut bytes = png.as_bytes();
+        let merged_len = (bytes.len() - 8 - Chunk::METADATA_LEN) as u32;
+        bytes[8..12].copy_from_slice(&merged_len.to_be_bytes());
+
+        let error = check_serialization(&png, &bytes).unwrap_err();
+
+        assert_eq!(error.actual_chunks, Some(1));
+    }
+
+    #[test]
+    fn test_broken_serializer_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        let broken = |png: &Png| {
+            let mut bytes = png.as_bytes();
+            bytes.truncate(bytes.len() / 2);
+            bytes
+        };
+        let result = write_with(&path, &testing_png(), WriteOptions { paranoid: true, ..WriteOptions::default() }, broken);
+
+        assert!(result.unwrap_err().is::<SerializationMismatch>());
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+    }
+
+    #[test]
+    fn test_save_png_skips_unmodified() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        save_png(&path, &testing_png(), WriteOptions::default()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+
+        let options = WriteOptions { fail_unchanged: true, ..WriteOptions::default() };
+        assert!(save_png(&path, &testing_png(), options.clone()).unwrap_err().is::<Unchanged>());
+
+        let mut png = testing_png();
+        png.remove_chunk_at_unchecked(0);
+        save_png(&path, &png, options).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_save_png_unchanged_to_stdout() {
+        let options = WriteOptions { fail_unchanged: true, ..WriteOptions::default() };
+
+        assert!(save_png(Path::new("-"), &testing_png(), options).unwrap_err().is::<Unchanged>());
+    }
+
+    #[test]
+    fn test_write_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+
+        write_png(&path, &testing_png(), WriteOptions { paranoid: true, ..WriteOptions::default() }).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), testing_png().as_bytes());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_png_replaces_the_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let link = dir.path().join("link.png");
+        fs::write(&path, b"original").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        write_png(&link, &testing_png(), WriteOptions::default()).unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(&path).unwrap(), testing_png().as_bytes());
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        assert!(!temp_path(&path).exists());
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o440)).unwrap();
+        assert!(write_png(&path, &modified_png(), WriteOptions::default()).unwrap_err().to_string().ends_with("is read-only"));
+        assert_eq!(fs::read(&path).unwrap(), testing_png().as_bytes());
+    }
+
+    #[test]
+    fn test_check_only_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"original").unwrap();
+
+        let mut png = testing_png();
+        png.remove_chunk_at_unchecked(0);
+        let options = WriteOptions { check_only: true, ..WriteOptions::default() };
+
+        save_png(&path, &png, options.clone()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+
+        let options = WriteOptions { fail_unchanged: true, ..options };
+        assert!(save_png(&path, &testing_png(), options).unwrap_err().is::<Unchanged>());
+    }
+
+    fn modified_png() -> Png<'static> {
+        let mut png = testing_png();
+        png.remove_chunk_at_unchecked(0);
+        png
+    }
+
+    fn group(dir: &Path) -> Vec<(PathBuf, Png<'static>)> {
+        (0..5)
+            .map(|index| {
+                let path = dir.join(format!("image{index}.png"));
+                fs::write(&path, b"original").unwrap();
+                (path, modified_png())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_save_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = group(dir.path());
+
+        save_all(&files, WriteOptions::default()).unwrap();
+
+        for (path, png) in &files {
+            assert_eq!(fs::read(path).unwrap(), png.as_bytes());
+        }
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 5);
+    }
+
+    #[test]
+    fn test_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = group(dir.path());
+        let options = WriteOptions { backup: Some(".bak".to_string()), ..WriteOptions::default() };
+
+        save_png(&files[0].0, &modified_png(), options.clone()).unwrap();
+        write_output(&files[1].0, &modified_png(), options.clone()).unwrap();
+        save_all(&files[2..3], options.clone()).unwrap();
+        // Nothing to write, so nothing to back up.
+        save_png(&files[3].0, &testing_png(), options.clone()).unwrap();
+        save_png(&files[4].0, &modified_png(), WriteOptions { check_only: true, ..options }).unwrap();
+
+        for (index, (path, png)) in files.iter().enumerate() {
+            let backup = backup_path(path, ".bak");
+            if index < 3 {
+                assert_eq!(fs::read(path).unwrap(), png.as_bytes());
+                assert_eq!(fs::read(backup).unwrap(), b"original");
+            } else {
+                assert!(!backup.exists());
+            }
+        }
+        assert_eq!(backup_path(Path::new("dir/image.png"), "~"), Path::new("dir/image.png~"));
+        assert!(parse_backup_suffix("../x").is_err());
+        assert!(parse_backup_suffix("").is_err());
+    }
+
+    #[test]
+    fn test_save_all_checks_like_save_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut files = group(dir.path());
+        files[1].1 = testing_png();
+
+        save_all(&files, WriteOptions::default()).unwrap();
+        assert_eq!(fs::read(&files[0].0).unwrap(), modified_png().as_bytes());
+        assert_eq!(fs::read(&files[1].0).unwrap(), b"original");
+
+        let mut files = group(dir.path());
+        let mut cgbi = modified_png();
+        cgbi.insert_chunk_at_unchecked(0, Chunk::new(ChunkType::from_str("CgBI").unwrap(), vec![0x50, 0x00, 0x20, 0x06]));
+        files[3].1 = cgbi;
+        let error = PngmeError::from_boxed(save_all(&files, WriteOptions::default()).unwrap_err());
+        assert_eq!(error.code(), "apple_cgbi");
+        files[3].1 = modified_png();
+        files[4].1 = testing_png();
+        assert!(save_all(&files, WriteOptions { fail_unchanged: true, ..WriteOptions::default() }).unwrap_err().is::<Unchanged>());
+        for (path, _) in &files {
+            assert_eq!(fs::read(path).unwrap(), b"original");
+        }
+        assert!(!temp_path(&files[0].0).exists());
+    }
+
+    #[test]
+    fn test_save_all_read_only_member_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = group(dir.path());
+        let mut permissions = fs::metadata(&files[2].0).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&files[2].0, permissions).unwrap();
+
+        assert!(save_all(&files, WriteOptions::default()).is_err());
+
+        for (path, _) in &files {
+            assert_eq!(fs::read(path).unwrap(), b"original");
+        }
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 5);
+    }
+
+    #[test]
+    fn test_save_all_failed_temp_write_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = group(dir.path());
+        // A directory in the way of the third temporary file makes its write fail.
+        fs::create_dir(temp_path(&files[2].0)).unwrap();
+
+        assert!(save_all(&files, WriteOptions::default()).is_err());
+
+        for (path, _) in &files {
+            assert_eq!(fs::read(path).unwrap(), b"original");
+        }
+        assert!(!temp_path(&files[0].0).exists());
+        assert!(!temp_path(&files[1].0).exists());
+    }
+
+    fn roundtrip_options() -> WriteOptions {
+        WriteOptions { verify_roundtrip: true, ..WriteOptions::default() }
+    }
+
+    #[test]
+    fn test_verify_roundtrip_after_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, testing_png().as_bytes()).unwrap();
+
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"added".to_vec()));
+        write_png(&path, &png, roundtrip_options()).unwrap();
+
+        png.remove_chunk_at_unchecked(0);
+        write_png(&path, &png, roundtrip_options()).unwrap();
+
+        png.replace_chunk_unchecked(0, Chunk::new(ChunkType::from_str("LASt").unwrap(), b"replaced".to_vec()));
+        write_png(&path, &png, roundtrip_options()).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_verify_roundtrip_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let original = testing_png().as_bytes();
+        fs::write(&path, &original).unwrap();
+
+        // Flipping a data byte keeps the length and framing, so only reading back catches it.
+        let corrupting = |png: &Png| {
+            let mut bytes = png.as_bytes();
+            bytes[Png::STANDARD_HEADER.len() + 8] ^= 1;
+            bytes
+        };
+        let mut png = testing_png();
+        png.remove_chunk_at_unchecked(1);
+
+        let error = write_with(&path, &png, roundtrip_options(), corrupting).unwrap_err();
+
+        assert!(error.is::<RoundtripMismatch>());
+        assert_eq!(fs::read(&path).unwrap(), original);
+        assert!(!temp_path(&path).exists());
+    }
+
+    /// Reads `input`, drops its first chunk and writes it to `output` the way commands do.
+    fn rewrite(input: &Path, output: &Path, options: WriteOptions) -> Result<Png<'static>> {
+        let mut png = Png::try_from(fs::read(input)?.as_ref())?.into_owned();
+        png.remove_chunk_at_unchecked(0);
+        write_output(output, &png, options)?;
+        Ok(png)
+    }
+
+    #[test]
+    fn test_write_output_same_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, testing_png().as_bytes()).unwrap();
+
+        let png = rewrite(&path, &dir.path().join(".").join("image.png"), WriteOptions::default()).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), png.as_bytes());
+        assert!(!temp_path(&path).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_output_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let link = dir.path().join("link.png");
+        fs::write(&path, testing_png().as_bytes()).unwrap();
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        let options = WriteOptions { no_follow_symlinks: true, ..WriteOptions::default() };
+        assert!(rewrite(&path, &link, options).is_err());
+        assert_eq!(fs::read(&path).unwrap(), testing_png().as_bytes());
+
+        let png = rewrite(&path, &link, WriteOptions::default()).unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(&path).unwrap(), png.as_bytes());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_output_hardlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let link = dir.path().join("link.png");
+        fs::write(&path, testing_png().as_bytes()).unwrap();
+        fs::hard_link(&path, &link).unwrap();
+
+        assert!(same_file(&path, &link));
+
+        let png = rewrite(&path, &link, WriteOptions::default()).unwrap();
+
+        // Both names still share the file, which now has the new contents.
+        assert!(same_file(&path, &link));
+        assert_eq!(link_count(&path), 2);
+        assert_eq!(fs::read(&link).unwrap(), png.as_bytes());
+        assert_eq!(fs::read(&path).unwrap(), png.as_bytes());
+        assert!(!temp_path(&link).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_same_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        let other = dir.path().join("other.png");
+        let symlink = dir.path().join("symlink.png");
+        fs::write(&path, b"image").unwrap();
+        fs::write(&other, b"image").unwrap();
+        std::os::unix::fs::symlink(&path, &symlink).unwrap();
+
+        assert!(same_file(&path, &dir.path().join(".").join("image.png")));
+        assert!(same_file(&path, &symlink));
+        assert!(!same_file(&path, &other));
+        assert!(!same_file(&path, &dir.path().join("missing.png")));
+    }
+}
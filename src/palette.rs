@@ -0,0 +1,103 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// Most entries a PLTE chunk can hold, for 8-bit indexes.
+pub(crate) const MAX_ENTRIES: usize = 256;
+
+/// One palette color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Rgb {
+    pub(crate) r: u8,
+    pub(crate) g: u8,
+    pub(crate) b: u8,
+}
+
+impl Rgb {
+    /// Two spaces painted in the color with a 24-bit ANSI background escape.
+    pub(crate) fn swatch(&self) -> String {
+        format!("\x1b[48;2;{};{};{}m  \x1b[0m", self.r, self.g, self.b)
+    }
+}
+
+/// `#rrggbb`
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// Reads the entries of a PLTE chunk: 1 to 256 RGB triples.
+pub(crate) fn parse(data: &[u8]) -> Result<Vec<Rgb>, String> {
+    if data.is_empty() || !data.len().is_multiple_of(3) || data.len() / 3 > MAX_ENTRIES {
+        return Err(format!("PLTE data is {} bytes long, it should be a multiple of 3 up to {}", data.len(), MAX_ENTRIES * 3));
+    }
+    Ok(data.chunks(3).map(|rgb| Rgb { r: rgb[0], g: rgb[1], b: rgb[2] }).collect())
+}
+
+/// A palette entry as `pngme palette --json` prints it.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct PaletteEntry {
+    pub(crate) index: usize,
+    pub(crate) color: String,
+    /// From tRNS, when the file has one; entries past its end are opaque.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) alpha: Option<u8>,
+}
+
+/// The entries of `palette`, with their alpha when `alphas`, the tRNS data, is given.
+pub(crate) fn entries(palette: &[Rgb], alphas: Option<&[u8]>) -> Vec<PaletteEntry> {
+    palette
+        .iter()
+        .enumerate()
+        .map(|(index, color)| PaletteEntry {
+            index,
+            color: color.to_string(),
+            alpha: alphas.map(|alphas| alphas.get(index).copied().unwrap_or(u8::MAX)),
+        })
+        .collect()
+}
+
+/// One `3: #ff8000` line per entry, followed by a swatch when `swatches` is set.
+pub(crate) fn lines(palette: &[Rgb], alphas: Option<&[u8]>, swatches: bool) -> Vec<String> {
+    entries(palette, alphas)
+        .into_iter()
+        .zip(palette)
+        .map(|(entry, color)| {
+            let mut line = format!("{}: {}", entry.index, entry.color);
+            if let Some(alpha) = entry.alpha {
+                line.push_str(&format!(" alpha {alpha}"));
+            }
+            if swatches {
+                line.push(' ');
+                line.push_str(&color.swatch());
+            }
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let palette = parse(&[255, 0, 0, 0, 128, 255]).unwrap();
+
+        assert_eq!(palette, [Rgb { r: 255, g: 0, b: 0 }, Rgb { r: 0, g: 128, b: 255 }]);
+        assert_eq!(palette[1].to_string(), "#0080ff");
+        assert!(parse(&[]).is_err());
+        assert!(parse(&[0; 4]).is_err());
+        assert!(parse(&[0; 257 * 3]).is_err());
+    }
+
+    #[test]
+    fn test_lines() {
+        let palette = parse(&[255, 0, 0, 0, 128, 255]).unwrap();
+
+        assert_eq!(lines(&palette, None, false), ["0: #ff0000", "1: #0080ff"]);
+        assert_eq!(lines(&palette, Some(&[0]), false), ["0: #ff0000 alpha 0", "1: #0080ff alpha 255"]);
+        assert_eq!(lines(&palette, None, true)[0], "0: #ff0000 \x1b[48;2;255;0;0m  \x1b[0m");
+    }
+}
@@ -0,0 +1,28 @@
+use crate::Result;
+
+/// Read instead of prompting when set, so scripts can encrypt and decrypt payloads.
+pub(crate) const ENV_VAR: &str = "PNGME_PASSPHRASE";
+
+/// The passphrase in `PNGME_PASSPHRASE`, or else one typed at the terminal without echo.
+///
+/// With `confirm`, a typed passphrase is asked for twice, so a typo can't lock a payload away.
+pub(crate) fn read(confirm: bool) -> Result<String> {
+    let passphrase = match std::env::var(ENV_VAR) {
+        Ok(passphrase) => passphrase,
+        Err(_) => {
+            let prompt = |prompt: &str| {
+                rpassword::prompt_password(prompt).map_err(|error| format!("Can't prompt for a passphrase ({error}), set {ENV_VAR} instead"))
+            };
+            let passphrase = prompt("Passphrase: ")?;
+            if confirm && prompt("Repeat passphrase: ")? != passphrase {
+                return Err("Passphrases don't match".into());
+            }
+            passphrase
+        }
+    };
+
+    if passphrase.is_empty() {
+        return Err("The passphrase is empty".into());
+    }
+    Ok(passphrase)
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+pub(crate) const PHYS_CHUNK_TYPE: &str = "pHYs";
+
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// Largest pixels per unit the spec allows, 2^31 - 1.
+const MAX_PIXELS_PER_UNIT: u32 = i32::MAX as u32;
+
+/// The intended pixel size, from a pHYs chunk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Phys {
+    pub(crate) x_pixels_per_unit: u32,
+    pub(crate) y_pixels_per_unit: u32,
+    /// Whether the unit is the meter; otherwise only the pixel aspect ratio is known.
+    pub(crate) in_meters: bool,
+}
+
+/// Dots per inch for a pixels-per-meter value.
+fn to_dpi(pixels_per_meter: u32) -> f64 {
+    f64::from(pixels_per_meter) * METERS_PER_INCH
+}
+
+impl Phys {
+    pub(crate) const LEN: usize = 9;
+
+    /// The same resolution on both axes, in dots per inch, rounded to whole pixels per meter.
+    pub(crate) fn from_dpi(dpi: f64) -> Result<Phys, String> {
+        let pixels_per_meter = (dpi / METERS_PER_INCH).round();
+        if !(1.0..=f64::from(MAX_PIXELS_PER_UNIT)).contains(&pixels_per_meter) {
+            return Err(format!("{dpi} dpi is out of the range a pHYs chunk can store"));
+        }
+        let pixels_per_meter = pixels_per_meter as u32;
+        Ok(Phys { x_pixels_per_unit: pixels_per_meter, y_pixels_per_unit: pixels_per_meter, in_meters: true })
+    }
+
+    /// Reads the pHYs chunk of `png`, if it has one.
+    pub(crate) fn read(png: &Png) -> Option<Result<Phys, String>> {
+        png.chunk_by_type(PHYS_CHUNK_TYPE).map(|chunk| Phys::try_from(chunk.data()))
+    }
+
+    pub(crate) fn as_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[0..4].copy_from_slice(&self.x_pixels_per_unit.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.y_pixels_per_unit.to_be_bytes());
+        bytes[8] = u8::from(self.in_meters);
+        bytes
+    }
+
+    /// Writes the chunk into `png`, replacing its pHYs chunk, or adding one before the image
+    /// data as the spec requires.
+    pub(crate) fn write(&self, png: &mut Png) {
+        let chunk = Chunk::new(ChunkType::from_str(PHYS_CHUNK_TYPE).expect("pHYs is a valid chunk type"), self.as_bytes().to_vec());
+        if png.chunk_by_type(PHYS_CHUNK_TYPE).is_some() {
+            png.replace_or_insert_chunk(chunk);
+        } else {
+            png.insert_before_first_of(&["IDAT", "IEND"], chunk);
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Phys {
+    type Error = String;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let data: [u8; Self::LEN] =
+            data.try_into().map_err(|_| format!("pHYs data is {} bytes long, it should be {}", data.len(), Self::LEN))?;
+        let x_pixels_per_unit = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let y_pixels_per_unit = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        let in_meters = match data[8] {
+            0 => false,
+            1 => true,
+            unit => return Err(format!("Unknown unit specifier {unit}")),
+        };
+        Ok(Phys { x_pixels_per_unit, y_pixels_per_unit, in_meters })
+    }
+}
+
+/// `72 dpi`, or `300x150 dpi` when the axes differ. Without a unit, only the aspect ratio.
+impl fmt::Display for Phys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (x, y) = (self.x_pixels_per_unit, self.y_pixels_per_unit);
+        match self.in_meters {
+            false => write!(f, "unknown unit, pixel aspect ratio {x}:{y}"),
+            true if x == y => write!(f, "{:.0} dpi ({x} pixels per meter)", to_dpi(x)),
+            true => write!(f, "{:.0}x{:.0} dpi ({x}x{y} pixels per meter)", to_dpi(x), to_dpi(y)),
+        }
+    }
+}
+
+/// Parses a `dpi set` argument: a positive number of dots per inch.
+pub(crate) fn parse_dpi(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(dpi) if dpi.is_finite() && dpi > 0.0 => Ok(dpi),
+        _ => Err(format!("expected a positive number of dots per inch, got {s:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dpi_conversion() {
+        let phys = Phys::from_dpi(300.0).unwrap();
+
+        assert_eq!(phys.x_pixels_per_unit, 11811);
+        assert_eq!(phys.as_bytes(), [0, 0, 0x2e, 0x23, 0, 0, 0x2e, 0x23, 1]);
+        assert_eq!(phys.to_string(), "300 dpi (11811 pixels per meter)");
+        assert_eq!(Phys::from_dpi(72.0).unwrap().to_string(), "72 dpi (2835 pixels per meter)");
+        assert!(Phys::from_dpi(0.01).is_err());
+        assert!(Phys::from_dpi(1e12).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let phys = Phys::try_from([0, 0, 0x2e, 0x23, 0, 0, 0x17, 0x12, 1].as_slice()).unwrap();
+        assert_eq!(phys.to_string(), "300x150 dpi (11811x5906 pixels per meter)");
+
+        let ratio = Phys::try_from([0, 0, 0, 2, 0, 0, 0, 1, 0].as_slice()).unwrap();
+        assert_eq!(ratio.to_string(), "unknown unit, pixel aspect ratio 2:1");
+
+        assert_eq!(Phys::try_from([2; 9].as_slice()).unwrap_err(), "Unknown unit specifier 2");
+        assert!(Phys::try_from([0; 8].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_parse_dpi() {
+        assert_eq!(parse_dpi("300"), Ok(300.0));
+        assert_eq!(parse_dpi("96.5"), Ok(96.5));
+        assert!(parse_dpi("0").is_err());
+        assert!(parse_dpi("-72").is_err());
+        assert!(parse_dpi("NaN").is_err());
+    }
+
+    #[test]
+    fn test_write_replaces_or_inserts_before_image_data() {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![]);
+        let mut png = Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")]);
+
+        Phys::from_dpi(72.0).unwrap().write(&mut png);
+        Phys::from_dpi(300.0).unwrap().write(&mut png);
+
+        let types: Vec<String> = png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "pHYs", "IDAT", "IEND"]);
+        assert_eq!(Phys::read(&png), Some(Phys::from_dpi(300.0)));
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::Result;
+
+/// Runs `command` through the shell, feeding it `input` on stdin, and returns its stdout.
+///
+/// stdin is written from a separate thread while stdout and stderr are read, so large payloads
+/// can't deadlock on full pipes. A non-zero exit status is an error that includes the
+/// command's stderr.
+pub(crate) fn pipe_through(command: &str, input: &[u8]) -> Result<Vec<u8>> {
+    if command.trim().is_empty() {
+        return Err("The --pipe-through command is empty".into());
+    }
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Failed to run {command:?}: {error}"))?;
+
+    let mut stdin = child.stdin.take().expect("stdin should be piped");
+    let input = input.to_vec();
+    // A filter may exit without reading all its input; the exit status reports that case.
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(&input);
+    });
+
+    let output = child.wait_with_output()?;
+    writer.join().expect("stdin writer should not panic");
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{command:?} failed ({}): {}", output.status, stderr.trim_end()).into());
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipe_through() {
+        assert_eq!(pipe_through("tr a-z A-Z", b"hello").unwrap(), b"HELLO");
+    }
+
+    #[test]
+    fn test_pipe_through_large_payload() {
+        let payload: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+
+        assert_eq!(pipe_through("cat", &payload).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_pipe_through_failure_shows_stderr() {
+        let error = pipe_through("echo 'bad key' >&2; exit 2", b"hello").unwrap_err();
+
+        assert!(error.to_string().contains("bad key"));
+    }
+
+    #[test]
+    fn test_pipe_through_empty_command() {
+        assert!(pipe_through("  ", b"hello").is_err());
+    }
+}
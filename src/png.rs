@@ -1,87 +1,512 @@
+use std::borrow::Cow;
 use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Index;
 
-use crate::chunk::Chunk;
-pub(crate) struct Png {
-    chunks: Vec<Chunk>
+use serde::Serialize;
+
+use crate::chunk::{Chunk, ChunkReader};
+use crate::error::PngmeError;
+use crate::order;
+use crate::palette::{self, Rgb};
+
+/// Byte span of a chunk in the serialized file: `offset..end_offset`, with its data starting
+/// at `data_offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(crate) struct ChunkRange {
+    pub(crate) offset: u64,
+    pub(crate) data_offset: u64,
+    pub(crate) end_offset: u64,
+}
+
+/// Bounds on the work done parsing a png from an untrusted source.
+///
+/// The default only has the spec's own limit on chunk sizes; set the fields to tighten it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParseLimits {
+    /// Most chunks accepted, those after IEND included.
+    pub max_chunks: usize,
+    /// Largest data length accepted for a single chunk.
+    pub max_chunk_size: usize,
+    /// Largest input accepted, in bytes.
+    pub max_total_bytes: usize,
+}
+
+impl Default for ParseLimits {
+    /// Only the spec's own limit on chunk sizes.
+    fn default() -> Self {
+        ParseLimits { max_chunks: usize::MAX, max_chunk_size: Chunk::MAX_DATA_LEN, max_total_bytes: usize::MAX }
+    }
+}
+
+impl ParseLimits {
+    fn check_chunk_count(&self, count: usize) -> Result<(), String> {
+        if count >= self.max_chunks {
+            return Err(format!("more than {} chunks", self.max_chunks));
+        }
+        Ok(())
+    }
+
+    fn check_chunk_size(&self, buf: &[u8]) -> Result<(), String> {
+        match Chunk::declared_data_len(buf) {
+            Some(len) if len > self.max_chunk_size as u64 => {
+                Err(format!("chunk declares {len} bytes of data, over the {} bytes limit", self.max_chunk_size))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
-impl Png {
+/// A png file as a list of chunks, plus whatever follows its IEND chunk.
+///
+/// Like its chunks, a png parsed with `try_from` borrows from the parsed buffer.
+#[derive(Debug)]
+pub struct Png<'a> {
+    chunks: Vec<Chunk<'a>>,
+    /// Bytes found after the IEND chunk, kept so the file round-trips unchanged.
+    trailing_data: Cow<'a, [u8]>,
+    /// Set by mutating methods when they actually change the file.
+    modified: bool,
+}
+
+impl<'a> Png<'a> {
+
+    pub const STANDARD_HEADER:[u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+    pub fn from_chunks(chunks: Vec<Chunk<'a>>) -> Png<'a> {
+        Png{ chunks, trailing_data: Cow::Borrowed(&[]), modified: false }
+    }
+
+    /// Like `try_from`, but accepts chunks whose stored CRC doesn't match their data.
+    pub(crate) fn try_from_ignoring_crc(value: &'a [u8]) -> Result<Png<'a>, String> {
+        Self::parse_bytes(value, false, &ParseLimits::default())
+    }
+
+    /// Parses bytes from an untrusted source, giving up once they exceed `limits`.
+    ///
+    /// Never panics, whatever the input.
+    ///
+    /// ```
+    /// use pngme::{ParseLimits, Png, PngmeError};
+    ///
+    /// let limits = ParseLimits { max_chunks: 64, max_total_bytes: 1 << 20, ..ParseLimits::default() };
+    /// let result = Png::parse_untrusted(b"\x89PNG\r\n", limits);
+    /// assert!(matches!(result, Err(PngmeError::InvalidPng { .. })));
+    /// ```
+    pub fn parse_untrusted(value: &'a [u8], limits: ParseLimits) -> Result<Png<'a>, PngmeError> {
+        Self::parse_bytes(value, true, &limits).map_err(|reason| PngmeError::InvalidPng { path: None, reason })
+    }
+
+    fn parse_bytes(value: &'a [u8], check_crc: bool, limits: &ParseLimits) -> Result<Png<'a>, String> {
+        if value.len() > limits.max_total_bytes {
+            return Err(format!("{} bytes, over the {} bytes limit", value.len(), limits.max_total_bytes));
+        }
+        if value.get(..8) != Some(Self::STANDARD_HEADER.as_slice()) {
+            return Err("Data header should match the standard PNG header".to_string());
+        }
+
+        let mut pos = 8;
+        let mut chunks: Vec<Chunk<'a>> = Vec::new();
+        while pos < value.len() {
+            limits.check_chunk_count(chunks.len())?;
+            limits.check_chunk_size(&value[pos..])?;
+            let (chunk, consumed) = if check_crc {
+                Chunk::parse(&value[pos..])?
+            } else {
+                Chunk::parse_ignoring_crc(&value[pos..])?
+            };
+            let is_end = chunk.chunk_type().bytes() == *b"IEND";
+            chunks.push(chunk);
+            pos += consumed;
+            if is_end {
+                break;
+            }
+        }
+
+        pos += Self::parse_after_iend(&value[pos..], &mut chunks, limits)?;
+
+        let mut png = Png::from_chunks(chunks);
+        png.trailing_data = Cow::Borrowed(&value[pos..]);
+        Ok(png)
+    }
+
+    /// Moves the well-formed chunks at the start of `value` into `chunks`, returning how many
+    /// bytes they took.
+    ///
+    /// Older pngme versions appended chunks after IEND, so well-formed chunks there are kept
+    /// as chunks. Whatever follows them, including anything over the size limit, is opaque
+    /// trailing data.
+    fn parse_after_iend(value: &'a [u8], chunks: &mut Vec<Chunk<'a>>, limits: &ParseLimits) -> Result<usize, String> {
+        let mut pos = 0;
+        while limits.check_chunk_size(&value[pos..]).is_ok() {
+            let Ok((chunk, consumed)) = Chunk::parse(&value[pos..]) else {
+                break;
+            };
+            limits.check_chunk_count(chunks.len())?;
+            chunks.push(chunk);
+            pos += consumed;
+        }
+        Ok(pos)
+    }
+
+    /// The png with its borrowed data copied, so it outlives the buffer it was parsed from.
+    pub fn into_owned(self) -> Png<'static> {
+        Png {
+            chunks: self.chunks.into_iter().map(Chunk::into_owned).collect(),
+            trailing_data: Cow::Owned(self.trailing_data.into_owned()),
+            modified: self.modified,
+        }
+    }
+
+    /// Bytes after the IEND chunk, e.g. an archive appended to make a polyglot file.
+    pub(crate) fn trailing_data(&self) -> &[u8] {
+        &self.trailing_data
+    }
+
+    pub(crate) fn set_trailing_data(&mut self, data: Vec<u8>) {
+        if data != *self.trailing_data {
+            self.trailing_data = Cow::Owned(data);
+            self.modified = true;
+        }
+    }
+
+    pub(crate) fn strip_trailing_data(&mut self) -> Vec<u8> {
+        if !self.trailing_data.is_empty() {
+            self.modified = true;
+        }
+        std::mem::take(&mut self.trailing_data).into_owned()
+    }
+
+    /// Offset just past the IEND chunk in the file `as_bytes` would write, if there is one.
+    pub(crate) fn iend_end_offset(&self) -> Option<u64> {
+        let index = self.iend_index()?;
+        Some(self.chunk_ranges()[index].end_offset)
+    }
+
+    fn iend_index(&self) -> Option<usize> {
+        self.chunks.iter().position(|chunk| chunk.chunk_type().bytes() == *b"IEND")
+    }
+
+    /// Drops everything after the IEND chunk, including well-formed chunks, and returns the
+    /// dropped bytes as they were serialized. Returns nothing when there is no IEND chunk.
+    pub(crate) fn truncate_after_iend(&mut self) -> Vec<u8> {
+        let Some(index) = self.iend_index() else {
+            return Vec::new();
+        };
 
-    const STANDARD_HEADER:[u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+        let mut removed = Vec::new();
+        for chunk in self.chunks.drain(index + 1..) {
+            chunk.write_to(&mut removed).expect("Writing to a Vec can't fail");
+        }
+        if !removed.is_empty() {
+            self.modified = true;
+        }
+        removed.extend(self.strip_trailing_data());
+        removed
+    }
 
-    fn from_chunks(chunks: Vec<Chunk>) -> Png {
-        return Png{ chunks: chunks}
+    /// Whether a mutating method changed the file since it was parsed or built.
+    pub fn is_modified(&self) -> bool {
+        self.modified
     }
 
-    pub(crate) fn append_chunk(&mut self, chunk: Chunk) {
+    pub fn append_chunk(&mut self, chunk: Chunk<'a>) {
+        self.modified = true;
         self.chunks.push(chunk);
     }
 
-    pub(crate) fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk, &str> {
+    /// Inserts `chunk` before the IEND chunk, or at the end when there is none, so that readers
+    /// which stop at IEND still see it.
+    pub(crate) fn insert_before_iend(&mut self, chunk: Chunk<'a>) {
+        self.insert_before_first_of(&["IEND"], chunk);
+    }
+
+    /// Inserts `chunk` before the first chunk of any of `chunk_types`, or at the end when there
+    /// is none.
+    pub(crate) fn insert_before_first_of(&mut self, chunk_types: &[&str], chunk: Chunk<'a>) {
+        let index = self
+            .chunks
+            .iter()
+            .position(|existing| chunk_types.contains(&existing.chunk_type().to_string().as_str()))
+            .unwrap_or(self.chunks.len());
+        self.insert_chunk_at_unchecked(index, chunk);
+    }
+
+    /// Inserts `chunk` so that it ends up at `index`, or returns why not: `index` is past the
+    /// chunk count or the chunk would break the ordering rules, e.g. an IDAT after IEND.
+    ///
+    /// Edits only have to keep the order as good as it was, so a misordered file can still be
+    /// edited elsewhere.
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk<'a>) -> Result<(), String> {
+        if index > self.chunks.len() {
+            return Err(format!("Chunk index {index} is out of range, the file has {} chunks", self.chunks.len()));
+        }
+        self.keeping_order(|png| png.insert_chunk_at_unchecked(index, chunk), |png, ()| drop(png.chunks.remove(index)))
+    }
+
+    /// Like `insert_chunk_at`, without any check: `index` must be at most the chunk count.
+    pub(crate) fn insert_chunk_at_unchecked(&mut self, index: usize, chunk: Chunk<'a>) {
+        self.modified = true;
+        self.chunks.insert(index, chunk);
+    }
+
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk<'a>, &str> {
         
         if let Some(pos) = self.chunks.iter().position(|x| x.chunk_type().to_string() == chunk_type) {
             
-            return Ok(self.chunks.remove(pos))
+            return Ok(self.remove_chunk_at_unchecked(pos))
         }
 
-        return Err("There are no chunk of this type");
+        Err("There are no chunk of this type")
     }
 
-    fn header(&self) -> &[u8; 8] {
-        return &Self::STANDARD_HEADER;
+    /// Removes and returns the chunk at `index`, or returns why not: `index` is out of range
+    /// or the removal would break the ordering rules, e.g. leave a duplicate PLTE after the
+    /// image data as the only one.
+    pub fn remove_chunk_at(&mut self, index: usize) -> Result<Chunk<'a>, String> {
+        self.nth_chunk(index)?;
+        self.keeping_order(|png| png.remove_chunk_at_unchecked(index), |png, chunk| png.chunks.insert(index, chunk))
+    }
+
+    /// Like `remove_chunk_at`, without any check: `index` must be in range.
+    pub(crate) fn remove_chunk_at_unchecked(&mut self, index: usize) -> Chunk<'a> {
+        self.modified = true;
+        self.chunks.remove(index)
+    }
+
+    /// Runs `edit`, and `undo` with its result when the edit breaks more ordering rules than
+    /// the file already did.
+    fn keeping_order<T>(&mut self, edit: impl FnOnce(&mut Self) -> T, undo: impl FnOnce(&mut Self, T)) -> Result<T, String> {
+        let (before, modified) = (order::violations(self).len(), self.modified);
+        let result = edit(self);
+        let violations = order::violations(self);
+        if violations.len() <= before {
+            return Ok(result);
+        }
+        undo(self, result);
+        self.modified = modified;
+        let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        Err(format!("The edit would put the chunks out of order: {}", violations.join("; ")))
+    }
+
+    /// Removes every chunk for which `predicate`, given the chunk and its index, returns true,
+    /// and returns the removed chunks in file order.
+    pub fn remove_chunks_where(&mut self, predicate: impl Fn(&Chunk<'a>, usize) -> bool) -> Vec<Chunk<'a>> {
+        let (removed, kept) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .enumerate()
+            .partition::<Vec<_>, _>(|(index, chunk)| predicate(chunk, *index));
+
+        self.chunks = kept.into_iter().map(|(_, chunk)| chunk).collect();
+        if !removed.is_empty() {
+            self.modified = true;
+        }
+        removed.into_iter().map(|(_, chunk)| chunk).collect()
+    }
+
+    /// Puts `chunk` in place of the chunk at `index` and returns the old one, or returns why
+    /// not: `index` is out of range or the chunk would break the ordering rules, e.g. a PLTE in
+    /// place of a tEXt after the image data.
+    ///
+    /// Replacing a chunk with an identical one doesn't mark the file as modified.
+    pub fn replace_chunk(&mut self, index: usize, chunk: Chunk<'a>) -> Result<Chunk<'a>, String> {
+        self.nth_chunk(index)?;
+        self.keeping_order(|png| png.replace_chunk_unchecked(index, chunk), |png, old| drop(std::mem::replace(&mut png.chunks[index], old)))
+    }
+
+    /// Like `replace_chunk`, without any check: `index` must be in range.
+    pub(crate) fn replace_chunk_unchecked(&mut self, index: usize, chunk: Chunk<'a>) -> Chunk<'a> {
+        let old = &self.chunks[index];
+        if old.chunk_type() != chunk.chunk_type() || old.data() != chunk.data() {
+            self.modified = true;
+        }
+        std::mem::replace(&mut self.chunks[index], chunk)
+    }
+
+    /// Puts `chunk` in place of the first chunk of its type, dropping any others of that type,
+    /// or inserts it before IEND when there is none.
+    pub(crate) fn replace_or_insert_chunk(&mut self, chunk: Chunk<'a>) {
+        let existing: Vec<usize> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, existing)| existing.chunk_type() == chunk.chunk_type())
+            .map(|(index, _)| index)
+            .collect();
+
+        match existing.split_first() {
+            Some((&first, duplicates)) => {
+                for &index in duplicates.iter().rev() {
+                    self.remove_chunk_at_unchecked(index);
+                }
+                self.replace_chunk_unchecked(first, chunk);
+            }
+            None => {
+                let index = self.chunks.iter().position(|existing| existing.chunk_type().to_string() == "IEND");
+                self.modified = true;
+                self.chunks.insert(index.unwrap_or(self.chunks.len()), chunk);
+            }
+        }
+    }
+
+    /// Rearranges the chunks so that the chunk at `order[i]` ends up at index `i`.
+    ///
+    /// `order` must be a permutation of the chunk indexes.
+    pub(crate) fn reorder_chunks(&mut self, order: &[usize]) {
+        assert_eq!(order.len(), self.chunks.len(), "Chunk order should cover every chunk");
+        if order.iter().enumerate().all(|(index, &from)| index == from) {
+            return;
+        }
+
+        let mut chunks: Vec<Option<Chunk<'a>>> = std::mem::take(&mut self.chunks).into_iter().map(Some).collect();
+        self.chunks = order
+            .iter()
+            .map(|&from| chunks[from].take().expect("Chunk order should not repeat an index"))
+            .collect();
+        self.modified = true;
+    }
+
+    pub(crate) fn header(&self) -> &[u8; 8] {
+        &Self::STANDARD_HEADER
     }
     
-    fn chunks(&self) -> &[Chunk] {
-        return self.chunks.as_slice();
+    /// Every chunk in file order. `as_slice` on the iterator gives them all at once.
+    pub fn chunks(&self) -> std::slice::Iter<'_, Chunk<'a>> {
+        self.chunks.iter()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Chunk at `index` in file order, with an error naming the chunk count when out of range.
+    pub(crate) fn nth_chunk(&self, index: usize) -> Result<&Chunk<'a>, String> {
+        self.chunks.get(index).ok_or_else(|| {
+            format!("Chunk index {index} is out of range, the file has {} chunks", self.chunks.len())
+        })
     }
 
-    pub(crate) fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk<'a>> {
         self.chunks.iter().find(|&x| x.chunk_type().to_string() == chunk_type)
     }
 
-    pub(crate) fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes: Vec<u8> = vec![];
-        bytes.extend_from_slice(self.header());
+    /// The chunks of type `chunk_type`, in file order.
+    pub fn chunks_by_type<'b>(&'b self, chunk_type: &'b str) -> impl Iterator<Item = &'b Chunk<'a>> {
+        self.chunks.iter().filter(move |&x| x.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Size of the file `as_bytes` would produce, computed without serializing.
+    pub(crate) fn serialized_len(&self) -> u64 {
+        Self::STANDARD_HEADER.len() as u64
+            + self.chunks.iter().map(Chunk::serialized_len).sum::<u64>()
+            + self.trailing_data.len() as u64
+    }
+
+    /// Byte span of every chunk, in file order.
+    ///
+    /// The spans are computed from the current chunks, so they always describe what `as_bytes`
+    /// would write: for a freshly parsed file that is the file on disk, after a mutation it is
+    /// the file as it would be saved.
+    pub(crate) fn chunk_ranges(&self) -> Vec<ChunkRange> {
+        let mut offset = Self::STANDARD_HEADER.len() as u64;
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                let range = ChunkRange {
+                    offset,
+                    data_offset: offset + 8,
+                    end_offset: offset + chunk.serialized_len(),
+                };
+                offset = range.end_offset;
+                range
+            })
+            .collect()
+    }
+
+    /// Whether this is an iOS-optimized png, whose first chunk is Apple's proprietary CgBI.
+    ///
+    /// Chunk framing is standard in these files, but pixel data is not.
+    pub(crate) fn is_cgbi(&self) -> bool {
+        self.chunks.first().is_some_and(|chunk| chunk.chunk_type().to_string() == "CgBI")
+    }
+
+    /// The colors of the PLTE chunk, if there is one.
+    pub(crate) fn palette(&self) -> Option<Result<Vec<Rgb>, String>> {
+        self.chunk_by_type("PLTE").map(|chunk| palette::parse(chunk.data()))
+    }
+
+    /// Whether the file is an animated png, i.e. has an acTL chunk.
+    pub(crate) fn is_apng(&self) -> bool {
+        self.chunks.iter().any(|chunk| chunk.chunk_type().bytes() == *b"acTL")
+    }
+
+    /// Whether the file has at least one IDAT chunk, i.e. an image a viewer can show.
+    pub(crate) fn has_image_data(&self) -> bool {
+        self.chunks.iter().any(|chunk| chunk.chunk_type().bytes() == *b"IDAT")
+    }
 
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.serialized_len() as usize);
+        self.write_to(&mut bytes).expect("Writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Serializes the file straight into `writer`, without building it in memory first.
+    ///
+    /// Writes are small, one per field, so an unbuffered writer such as a `File` should be
+    /// wrapped in a `BufWriter`.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.header())?;
         for chunk in self.chunks() {
-            bytes.extend(chunk.as_bytes())
+            chunk.write_to(&mut writer)?;
         }
-
-        return bytes;
-        
+        writer.write_all(&self.trailing_data)
     }
 
 }
 
 
-impl TryFrom<&[u8]> for Png {
-    type Error = String;
+impl Png<'static> {
+    /// Reads a png from `reader` a chunk at a time, without needing the whole file up front.
+    ///
+    /// Accepts the same files as `try_from`: chunks after IEND are kept as chunks and
+    /// anything else there is trailing data.
+    pub fn from_reader<R: Read>(reader: R) -> crate::Result<Png<'static>> {
+        let mut chunk_reader = ChunkReader::from_png(reader)?;
+        let mut chunks = chunk_reader.by_ref().collect::<crate::Result<Vec<Chunk>>>()?;
+        let mut reader = chunk_reader.into_inner();
 
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest)?;
+        let mut after_iend = Vec::new();
+        let pos = Png::parse_after_iend(&rest, &mut after_iend, &ParseLimits::default())?;
+        chunks.extend(after_iend.into_iter().map(Chunk::into_owned));
+        rest.drain(..pos);
 
-        let data_header:[u8; 8] = value[..8].try_into().expect("Header should be of length 8");
+        let mut png = Png::from_chunks(chunks);
+        png.trailing_data = Cow::Owned(rest);
+        Ok(png)
+    }
+}
 
-        if data_header != Self::STANDARD_HEADER {
-            return Err("Data header should match the standard PNG header".to_string());
-        }
+impl<'a> TryFrom<&'a [u8]> for Png<'a> {
+    type Error = String;
 
-        let mut pos = 8;
-        let mut chunks: Vec<Chunk> = Vec::new();
-        while pos < value.len() {
-            let chunk_data_length= u32::from_be_bytes(value[pos..pos+4].try_into().expect("Chunk length slice should be of length 4"));
-            let pos_end = pos+(chunk_data_length as usize) + 12; //starting position + data + length + chunk type + crc
-            let chunk_result = Chunk::try_from(&value[pos..pos_end]);
-            match chunk_result {
-                Ok(chunk) => chunks.push(chunk),
-                Err(error) => return Err(error)
-            }
-            pos = pos_end;
-        }
-        return Ok(Png::from_chunks(chunks));
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        Png::parse_bytes(value, true, &ParseLimits::default())
     }
 }
 
-impl fmt::Display for Png {
+/// The chunk at `index` in file order, panicking when it's out of range as slices do.
+impl<'a> Index<usize> for Png<'a> {
+    type Output = Chunk<'a>;
+
+    fn index(&self, index: usize) -> &Chunk<'a> {
+        &self.chunks[index]
+    }
+}
+
+impl fmt::Display for Png<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self.as_bytes() )
     }
@@ -93,10 +518,42 @@ mod tests {
     use super::*;
     use crate::chunk_type::ChunkType;
     use crate::chunk::Chunk;
-    use std::str::FromStr;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
     use std::convert::TryFrom;
 
-    fn testing_chunks() -> Vec<Chunk> {
+    /// Arbitrary chunks ending with IEND, then fewer trailing bytes than the smallest chunk, so
+    /// they can't be mistaken for one.
+    impl Arbitrary for Png<'static> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Png<'static>>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            (vec(any::<Chunk>(), 0..8), vec(any::<u8>(), 0..Chunk::METADATA_LEN))
+                .prop_map(|(mut chunks, trailing_data)| {
+                    chunks.push(Chunk::new(ChunkType::try_from(*b"IEND").unwrap(), Vec::new()));
+                    let mut png = Png::from_chunks(chunks);
+                    png.trailing_data = Cow::Owned(trailing_data);
+                    png
+                })
+                .boxed()
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_png_round_trip(png in any::<Png>()) {
+            let bytes = png.as_bytes();
+            let parsed = Png::try_from(bytes.as_slice()).unwrap();
+            let streamed = Png::from_reader(bytes.as_slice()).unwrap();
+
+            prop_assert_eq!(parsed.chunks().as_slice(), png.chunks().as_slice());
+            prop_assert_eq!(parsed.trailing_data(), png.trailing_data());
+            prop_assert_eq!(streamed.as_bytes(), bytes);
+        }
+    }
+
+    fn testing_chunks() -> Vec<Chunk<'static>> {
         vec![
             chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
             chunk_from_strings("miDl", "I am another chunk").unwrap(),
@@ -104,12 +561,12 @@ mod tests {
         ]
     }
 
-    fn testing_png() -> Png {
+    fn testing_png() -> Png<'static> {
         let chunks = testing_chunks();
         Png::from_chunks(chunks)
     }
 
-    fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk, String> {
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk<'static>, String> {
         use std::str::FromStr;
 
         let chunk_type = ChunkType::from_str(chunk_type)?;
@@ -192,6 +649,30 @@ mod tests {
         assert_eq!(chunks.len(), 3);
     }
 
+    #[test]
+    fn test_chunk_count() {
+        let png = testing_png();
+        assert_eq!(png.chunk_count(), 3);
+        assert_eq!(Png::from_chunks(Vec::new()).chunk_count(), 0);
+    }
+
+    #[test]
+    fn test_chunks_iterator() {
+        let png = testing_png();
+        let types: Vec<String> = png.chunks().rev().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["LASt", "miDl", "FrSt"]);
+        assert_eq!(png[1].chunk_type().to_string(), "miDl");
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "I am another first chunk").unwrap());
+        let data: Vec<String> = png.chunks_by_type("FrSt").map(|chunk| chunk.data_as_string().unwrap()).collect();
+        assert_eq!(data, ["I am the first chunk", "I am another first chunk"]);
+        assert_eq!(png.chunks_by_type("teSt").count(), 0);
+    }
+
     #[test]
     fn test_chunk_by_type() {
         let png = testing_png();
@@ -201,6 +682,18 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_nth_chunk() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        let chunk = png.nth_chunk(0).unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "IHDR");
+        assert_eq!(chunk.data().len(), 13);
+
+        let error = png.nth_chunk(png.chunk_count()).unwrap_err();
+        assert!(error.contains(&png.chunk_count().to_string()));
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
@@ -219,6 +712,213 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_parsed_png_is_not_modified() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(!png.is_modified());
+        assert!(!testing_png().is_modified());
+    }
+
+    #[test]
+    fn test_append_modifies() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(png.is_modified());
+    }
+
+    #[test]
+    fn test_remove_modifies_only_on_match() {
+        let mut png = testing_png();
+        assert!(png.remove_first_chunk("TeSt").is_err());
+        assert!(!png.is_modified());
+
+        png.remove_first_chunk("miDl").unwrap();
+        assert!(png.is_modified());
+
+        let mut png = testing_png();
+        png.remove_chunk_at(0).unwrap();
+        assert!(png.is_modified());
+    }
+
+    #[test]
+    fn test_replace_modifies_only_on_change() {
+        let mut png = testing_png();
+        png.replace_chunk(1, chunk_from_strings("miDl", "I am another chunk").unwrap()).unwrap();
+        assert!(!png.is_modified());
+
+        png.replace_chunk(1, chunk_from_strings("miDl", "Different data").unwrap()).unwrap();
+        assert!(png.is_modified());
+        assert_eq!(png[1].data(), b"Different data");
+
+        let mut png = testing_png();
+        png.replace_chunk(1, chunk_from_strings("miDL", "I am another chunk").unwrap()).unwrap();
+        assert!(png.is_modified());
+    }
+
+    #[test]
+    fn test_replace_or_insert_chunk() {
+        let chunk = |chunk_type: &str, data: &str| chunk_from_strings(chunk_type, data).unwrap();
+        let types = |png: &Png| png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect::<Vec<_>>();
+        let mut png = Png::from_chunks(vec![chunk("IHDR", ""), chunk("IDAT", ""), chunk("IEND", "")]);
+
+        png.replace_or_insert_chunk(chunk("ruSt", "one"));
+        assert_eq!(types(&png), ["IHDR", "IDAT", "ruSt", "IEND"]);
+
+        let mut png = Png::from_chunks(vec![chunk("IHDR", ""), chunk("ruSt", "two"), chunk("IDAT", ""), chunk("ruSt", "one"), chunk("IEND", "")]);
+        png.replace_or_insert_chunk(chunk("ruSt", "three"));
+        assert_eq!(types(&png), ["IHDR", "ruSt", "IDAT", "IEND"]);
+        assert_eq!(png[1].data(), b"three");
+    }
+
+    #[test]
+    fn test_edits_keep_chunk_order() {
+        let chunk = |chunk_type: &str| chunk_from_strings(chunk_type, "").unwrap();
+        let testing_png = || Png::from_chunks(vec![chunk("IHDR"), chunk("PLTE"), chunk("IDAT"), chunk("PLTE"), chunk("IEND")]);
+
+        let mut png = testing_png();
+        png.insert_chunk_at(1, chunk("teXt")).unwrap();
+        assert_eq!(png.remove_chunk_at(1).unwrap().chunk_type().to_string(), "teXt");
+
+        let mut png = testing_png();
+        let error = png.insert_chunk_at(5, chunk("IDAT")).unwrap_err();
+        assert!(error.starts_with("The edit would put the chunks out of order: "));
+        assert!(error.contains("IDAT, chunk 5, comes after IEND"));
+        assert!(png.replace_chunk(2, chunk("IEND")).is_err());
+        assert!(png.remove_chunk_at(1).unwrap_err().contains("PLTE, chunk 2, comes after the image data"));
+        assert!(png.insert_chunk_at(6, chunk("teXt")).unwrap_err().contains("out of range"));
+        assert!(png.replace_chunk(5, chunk("teXt")).unwrap_err().contains("out of range"));
+        let types: Vec<String> = png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "PLTE", "IDAT", "PLTE", "IEND"]);
+        assert!(!png.is_modified());
+
+        // A file already out of order can still be edited, as long as it gets no worse.
+        let mut png = Png::from_chunks(vec![chunk("IHDR"), chunk("IEND"), chunk("teXt")]);
+        png.replace_chunk(2, chunk("tiMe")).unwrap();
+        assert!(png.insert_chunk_at(3, chunk("teXt")).is_err());
+        png.remove_chunk_at(2).unwrap();
+    }
+
+    #[test]
+    fn test_reorder_modifies_only_on_change() {
+        let mut png = testing_png();
+        png.reorder_chunks(&[0, 1, 2]);
+        assert!(!png.is_modified());
+
+        png.reorder_chunks(&[2, 0, 1]);
+        assert!(png.is_modified());
+        assert_eq!(&png[0].chunk_type().to_string(), "LASt");
+        assert_eq!(&png[1].chunk_type().to_string(), "FrSt");
+    }
+
+    #[test]
+    fn test_remove_chunks_where() {
+        let mut png = testing_png();
+
+        let removed = png.remove_chunks_where(|chunk, index| index == 0 || chunk.data().len() < 19);
+
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].chunk_type().to_string(), "FrSt");
+        assert_eq!(removed[1].chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunk_count(), 1);
+        assert!(png.is_modified());
+    }
+
+    #[test]
+    fn test_truncate_after_iend() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        let end = png.as_bytes().len() as u64;
+        let late_chunk = chunk_from_strings("LaTe", "after the end").unwrap();
+        png.append_chunk(late_chunk.clone());
+        png.set_trailing_data(b"PK\x05\x06".to_vec());
+
+        assert_eq!(png.iend_end_offset(), Some(end));
+        let removed = png.truncate_after_iend();
+
+        assert_eq!(removed, [late_chunk.as_bytes(), b"PK\x05\x06".to_vec()].concat());
+        assert_eq!(png.as_bytes().len() as u64, end);
+        assert!(png.truncate_after_iend().is_empty());
+    }
+
+    /// Hands out one byte per read, like a slow pipe.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((&byte, rest)), Some(slot)) => {
+                    *slot = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_reader_matches_try_from() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        png.append_chunk(chunk_from_strings("LaTe", "after the end").unwrap());
+        png.set_trailing_data(b"PK\x05\x06".to_vec());
+        let bytes = png.as_bytes();
+
+        let expected = Png::try_from(bytes.as_slice()).unwrap();
+        for read in [Png::from_reader(bytes.as_slice()).unwrap(), Png::from_reader(Trickle(&bytes)).unwrap()] {
+            assert_eq!(read.as_bytes(), bytes);
+            assert_eq!(read.chunk_count(), expected.chunk_count());
+            assert_eq!(read.trailing_data(), expected.trailing_data());
+        }
+    }
+
+    #[test]
+    fn test_from_reader_errors() {
+        let bytes = testing_png().as_bytes();
+        let error = |bytes: &[u8]| Png::from_reader(bytes).err().unwrap().to_string();
+
+        assert!(error(&bytes[..5]).contains("standard PNG header"));
+        assert!(error(&bytes[..bytes.len() - 2]).contains("LASt chunk is truncated"));
+        assert!(error(&bytes[..12]).contains("at least 12 bytes, got 4"));
+
+        let mut corrupt = bytes.clone();
+        corrupt[20] ^= 0xff;
+        assert!(error(&corrupt).contains("Crc doesn't match"));
+    }
+
+    #[test]
+    fn test_write_to_matches_as_bytes() {
+        let mut png = testing_png();
+        png.set_trailing_data(b"trailing".to_vec());
+
+        let mut written = Vec::new();
+        png.write_to(&mut written).unwrap();
+
+        assert_eq!(written, png.as_bytes());
+    }
+
+    #[test]
+    fn test_write_to_reports_writer_errors() {
+        let mut full = [0u8; 20];
+
+        let error = testing_png().write_to(&mut full[..]).unwrap_err();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_strip_trailing_modifies_only_when_present() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.strip_trailing_data();
+        assert!(!png.is_modified());
+
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"trailing");
+        let mut png = Png::try_from(bytes.as_ref()).unwrap();
+        png.strip_trailing_data();
+        assert!(png.is_modified());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -233,6 +933,53 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_serialized_len() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.serialized_len(), PNG_FILE.len() as u64);
+
+        let png = testing_png();
+        assert_eq!(png.serialized_len(), png.as_bytes().len() as u64);
+    }
+
+    #[test]
+    fn test_trailing_data_round_trip() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"PK\x03\x04 appended archive");
+
+        let mut png = Png::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(png.trailing_data(), b"PK\x03\x04 appended archive");
+        assert_eq!(png.as_bytes(), bytes);
+        assert_eq!(png.serialized_len(), bytes.len() as u64);
+
+        png.strip_trailing_data();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_chunks_after_iend_are_kept() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend(chunk_from_strings("ruSt", "appended by pngme").unwrap().as_bytes());
+        bytes.extend_from_slice(b"trailing");
+
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(png.trailing_data(), b"trailing");
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"appended by pngme");
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_try_from_ignoring_crc() {
+        let mut bytes = PNG_FILE.to_vec();
+        // Last byte of the IHDR CRC
+        bytes[32] ^= 0xff;
+
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+        assert_eq!(Png::try_from_ignoring_crc(bytes.as_ref()).unwrap().as_bytes(), PNG_FILE.to_vec());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -497,4 +1244,144 @@ mod tests {
         202, 28, 31, 66, 176, 235, 16, 0, 0, 0, 3, 82, 117, 83, 116, 104, 101, 121, 158, 176, 245,
         160, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
     ];
-}
\ No newline at end of file
+
+    #[test]
+    fn test_chunk_ranges_slice_original_file() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        let ranges = png.chunk_ranges();
+
+        assert_eq!(ranges.len(), png.chunk_count());
+        assert_eq!(ranges[0].offset, 8);
+        for (range, chunk) in ranges.iter().zip(png.chunks()) {
+            let slice = &PNG_FILE[range.offset as usize..range.end_offset as usize];
+            let parsed = Chunk::try_from(slice).unwrap();
+            assert_eq!(parsed.chunk_type(), chunk.chunk_type());
+            assert_eq!(&PNG_FILE[range.data_offset as usize..range.end_offset as usize - 4], chunk.data());
+        }
+    }
+
+    #[test]
+    fn test_chunk_ranges_follow_mutations() {
+        let mut png = testing_png();
+        png.remove_chunk_at_unchecked(0);
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+
+        let bytes = png.as_bytes();
+        let ranges = png.chunk_ranges();
+
+        assert_eq!(ranges.last().unwrap().end_offset, bytes.len() as u64);
+        for (range, chunk) in ranges.iter().zip(png.chunks()) {
+            let parsed = Chunk::try_from(&bytes[range.offset as usize..range.end_offset as usize]).unwrap();
+            assert_eq!(parsed.data(), chunk.data());
+        }
+    }
+
+    fn large_png(chunk_count: usize) -> Vec<u8> {
+        let mut chunks = vec![chunk_from_strings("IHDR", "0123456789abc").unwrap()];
+        chunks.extend((0..chunk_count).map(|index| Chunk::new(ChunkType::try_from(*b"IDAT").unwrap(), vec![index as u8; 1024])));
+        chunks.push(chunk_from_strings("IEND", "").unwrap());
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    /// Inputs that once tripped, or could trip, a parser reading lengths and offsets from the
+    /// file itself.
+    fn tricky_corpus() -> Vec<Vec<u8>> {
+        let valid = large_png(2);
+        let header = Png::STANDARD_HEADER.to_vec();
+        let with = |tail: &[u8]| [header.as_slice(), tail].concat();
+
+        let mut corpus = vec![
+            vec![],
+            header[..5].to_vec(),
+            header.clone(),
+            // A length field cut short, then a type cut short.
+            with(&[0, 0]),
+            with(&[0, 0, 0, 4, b'r', b'u']),
+            // Lengths pointing far past the end of the input.
+            with(&[0xff, 0xff, 0xff, 0xff, b'I', b'D', b'A', b'T']),
+            with(&[0x80, 0, 0, 0, b'I', b'D', b'A', b'T', 0, 0, 0, 0]),
+            with(&[0xff, 0xff, 0xff, 0xf4, b'I', b'D', b'A', b'T', 0, 0, 0, 0]),
+            // A chunk type that isn't letters.
+            with(&[0, 0, 0, 0, 0, 1, 2, 3, 0, 0, 0, 0]),
+            // A missing IEND, then junk after it.
+            valid[..valid.len() - 12].to_vec(),
+            [valid.as_slice(), &[0xff, 0xff, 0xff, 0xff, b'r', b'u']].concat(),
+        ];
+        corpus.extend((0..valid.len()).step_by(97).map(|len| valid[..len].to_vec()));
+        corpus.push(valid);
+        corpus
+    }
+
+    /// Deterministic byte mutations of `bytes`, so the test replays identically everywhere.
+    fn mutations(bytes: &[u8], count: usize) -> Vec<Vec<u8>> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        (0..count)
+            .map(|_| {
+                let mut mutated = bytes.to_vec();
+                for _ in 0..1 + next() % 4 {
+                    let index = next() % mutated.len();
+                    mutated[index] = next() as u8;
+                }
+                mutated
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_untrusted_never_panics() {
+        let mut inputs = tricky_corpus();
+        inputs.extend(mutations(&large_png(2), 2000));
+        let limits = ParseLimits { max_chunks: 8, max_chunk_size: 4096, max_total_bytes: 1 << 16 };
+
+        for input in &inputs {
+            for limits in [limits, ParseLimits::default()] {
+                if let Ok(png) = Png::parse_untrusted(input, limits) {
+                    assert_eq!(png.as_bytes().len() as u64, png.serialized_len());
+                }
+            }
+            let _ = Png::try_from_ignoring_crc(input);
+        }
+    }
+
+    #[test]
+    fn test_parse_untrusted_rejects_truncated_input() {
+        let valid = large_png(1);
+
+        let error = Png::parse_untrusted(&valid[..valid.len() - 20], ParseLimits::default()).err().unwrap();
+
+        assert!(error.to_string().contains("IDAT chunk is truncated"), "{error}");
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let valid = large_png(3);
+        let default = ParseLimits::default();
+        let parse = |limits| Png::parse_untrusted(&valid, limits).map(|png| png.chunk_count()).map_err(|error| error.to_string());
+
+        assert_eq!(parse(ParseLimits { max_chunks: 5, ..default }), Ok(5));
+        assert!(parse(ParseLimits { max_chunks: 4, ..default }).unwrap_err().contains("more than 4 chunks"));
+        assert_eq!(parse(ParseLimits { max_chunk_size: 1024, ..default }), Ok(5));
+        assert!(parse(ParseLimits { max_chunk_size: 1023, ..default }).unwrap_err().contains("1024 bytes of data"));
+        assert_eq!(parse(ParseLimits { max_total_bytes: valid.len(), ..default }), Ok(5));
+        assert!(parse(ParseLimits { max_total_bytes: valid.len() - 1, ..default }).is_err());
+    }
+
+    #[test]
+    fn test_oversized_chunk_after_iend_is_trailing_data() {
+        let valid = large_png(0);
+        let late = Chunk::new(ChunkType::try_from(*b"LaTe").unwrap(), vec![0; 100]).as_bytes();
+        let bytes = [valid.as_slice(), &late].concat();
+
+        let png = Png::parse_untrusted(&bytes, ParseLimits { max_chunk_size: 50, ..ParseLimits::default() }).unwrap();
+
+        assert_eq!(png.chunk_count(), 2);
+        assert_eq!(png.trailing_data(), late.as_slice());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::{ColorType, Ihdr};
+use crate::png::Png;
+use crate::text::deflate;
+use crate::Result;
+
+fn chunk(chunk_type: &[u8; 4], data: Vec<u8>) -> Chunk<'static> {
+    Chunk::new(ChunkType::try_from(*chunk_type).expect("Standard chunk types are valid"), data)
+}
+
+/// Builds a png from raw pixels, so images can be made from scratch rather than only edited.
+///
+/// ```
+/// use pngme::{ColorType, PngBuilder};
+///
+/// # fn main() -> pngme::Result<()> {
+/// // A 2x1 image: one red pixel, one transparent one.
+/// let png = PngBuilder::new(2, 1).color_type(ColorType::Rgba).pixels([255, 0, 0, 255, 0, 0, 0, 0]).build()?;
+/// assert_eq!(png.chunk_count(), 3);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PngBuilder {
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: u8,
+    pixels: Vec<u8>,
+    palette: Vec<[u8; 3]>,
+    chunks: Vec<Chunk<'static>>,
+}
+
+impl PngBuilder {
+    /// An 8-bit RGBA image of `width` by `height` pixels, all transparent black until `pixels`
+    /// is given.
+    pub fn new(width: u32, height: u32) -> PngBuilder {
+        PngBuilder { width, height, color_type: ColorType::Rgba, bit_depth: 8, pixels: Vec::new(), palette: Vec::new(), chunks: Vec::new() }
+    }
+
+    pub fn color_type(mut self, color_type: ColorType) -> PngBuilder {
+        self.color_type = color_type;
+        self
+    }
+
+    /// Bits per sample, or per palette index for indexed images.
+    pub fn bit_depth(mut self, bit_depth: u8) -> PngBuilder {
+        self.bit_depth = bit_depth;
+        self
+    }
+
+    /// The image, row by row from the top, each row packed as the color type and bit depth
+    /// say, with no filter type byte. Samples of 16 bits are big-endian.
+    pub fn pixels(mut self, pixels: impl Into<Vec<u8>>) -> PngBuilder {
+        self.pixels = pixels.into();
+        self
+    }
+
+    /// The RGB colors indexed images refer to, which they need.
+    pub fn palette(mut self, colors: &[[u8; 3]]) -> PngBuilder {
+        self.palette = colors.to_vec();
+        self
+    }
+
+    /// An ancillary chunk written between the header and the image data, e.g. text.
+    pub fn chunk(mut self, chunk: Chunk<'static>) -> PngBuilder {
+        self.chunks.push(chunk);
+        self
+    }
+
+    /// The png: IHDR, PLTE for indexed images, the added chunks, IDAT and IEND.
+    pub fn build(self) -> Result<Png<'static>> {
+        let ihdr = Ihdr { width: self.width, height: self.height, bit_depth: self.bit_depth, color_type: self.color_type, interlaced: false };
+        ihdr.check()?;
+        let row_len = ihdr.row_len(self.width);
+        let expected = row_len as u64 * u64::from(self.height);
+        let pixels = if self.pixels.is_empty() { vec![0; expected as usize] } else { self.pixels };
+        if pixels.len() as u64 != expected {
+            return Err(format!("{}x{} {} pixels at {} bits take {expected} bytes, got {}", self.width, self.height, self.color_type, self.bit_depth, pixels.len()).into());
+        }
+        let palette_max = 1 << self.bit_depth.min(8);
+        if self.color_type == ColorType::Indexed && !(1..=palette_max).contains(&self.palette.len()) {
+            return Err(format!("indexed images at {} bits need a palette of 1 to {palette_max} colors", self.bit_depth).into());
+        }
+
+        let mut header = vec![0; Ihdr::LEN];
+        ihdr.write_into(&mut header);
+        let mut chunks = vec![chunk(b"IHDR", header)];
+        if !self.palette.is_empty() {
+            chunks.push(chunk(b"PLTE", self.palette.concat()));
+        }
+        chunks.extend(self.chunks);
+
+        let mut scanlines = Vec::with_capacity(pixels.len() + self.height as usize);
+        for row in pixels.chunks(row_len) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+        chunks.extend(deflate(&scanlines).chunks(Chunk::MAX_DATA_LEN).map(|data| chunk(b"IDAT", data.to_vec())));
+        chunks.push(chunk(b"IEND", Vec::new()));
+        Ok(Png::from_chunks(chunks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_builder::ChunkBuilder;
+    use crate::verify::verify;
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_build() {
+        let text = ChunkBuilder::new().text("Title", "Dots").build().unwrap();
+        let png = PngBuilder::new(2, 2).color_type(ColorType::Rgb).pixels(vec![255; 12]).chunk(text).build().unwrap();
+        let bytes = png.as_bytes();
+
+        assert_eq!(chunk_types(&png), ["IHDR", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(verify(bytes.as_slice(), true).unwrap(), []);
+        assert_eq!(Ihdr::read(&Png::try_from(bytes.as_slice()).unwrap()).unwrap().color_type, ColorType::Rgb);
+    }
+
+    #[test]
+    fn test_build_indexed() {
+        // 1-bit indexes, 3 pixels packed in the high bits of each row's byte.
+        let png = PngBuilder::new(3, 2)
+            .color_type(ColorType::Indexed)
+            .bit_depth(1)
+            .palette(&[[0, 0, 0], [255, 255, 255]])
+            .pixels([0b1010_0000, 0b0100_0000])
+            .build()
+            .unwrap();
+
+        assert_eq!(chunk_types(&png), ["IHDR", "PLTE", "IDAT", "IEND"]);
+        assert_eq!(verify(png.as_bytes().as_slice(), true).unwrap(), []);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let error = |builder: PngBuilder| builder.build().unwrap_err().to_string();
+
+        assert!(error(PngBuilder::new(2, 2).pixels(vec![0; 15])).contains("take 16 bytes, got 15"));
+        assert!(error(PngBuilder::new(0, 2)).contains("not a valid image size"));
+        assert!(error(PngBuilder::new(2, 2).bit_depth(4)).contains("bit depth of 4"));
+        assert!(error(PngBuilder::new(2, 2).color_type(ColorType::Indexed)).contains("need a palette"));
+    }
+}
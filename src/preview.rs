@@ -0,0 +1,117 @@
+/// Default number of data bytes shown by commands that preview chunk data.
+pub(crate) const DEFAULT_PREVIEW_BYTES: usize = 32;
+
+fn is_text(data: &[u8]) -> bool {
+    match std::str::from_utf8(data) {
+        Ok(text) => !text.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')),
+        Err(_) => false,
+    }
+}
+
+/// Shows at most `max` bytes of `data`: an escaped UTF-8 prefix for text, hex for binary.
+///
+/// Truncated previews end with an ellipsis and the number of hidden bytes. Text is never cut
+/// inside a multi-byte character, so the shown prefix may be a few bytes shorter than `max`.
+/// A `max` of 0 disables the preview.
+pub(crate) fn preview(data: &[u8], max: usize) -> String {
+    if max == 0 || data.is_empty() {
+        return String::new();
+    }
+
+    let (shown, prefix) = if is_text(data) {
+        let text = std::str::from_utf8(data).expect("Text data should be valid UTF-8");
+        let mut end = max.min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        (end, text[..end].escape_debug().to_string())
+    } else {
+        let end = max.min(data.len());
+        let hex: Vec<String> = data[..end].iter().map(|byte| format!("{byte:02x}")).collect();
+        (end, hex.join(" "))
+    };
+
+    if shown < data.len() {
+        format!("{prefix}… (+{} more bytes)", data.len() - shown)
+    } else {
+        prefix
+    }
+}
+
+/// Bytes shown on each line of a hex dump.
+const HEX_DUMP_WIDTH: usize = 16;
+
+/// `data` as `hexdump -C` shows it: the offset of each 16 byte line, counted from `start`, the
+/// bytes in hex, split in two groups of eight, then as ASCII with `.` for anything unprintable.
+pub(crate) fn hex_dump(data: &[u8], start: u64) -> Vec<String> {
+    data.chunks(HEX_DUMP_WIDTH)
+        .enumerate()
+        .map(|(line, bytes)| {
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+            let (left, right) = hex.split_at(hex.len().min(HEX_DUMP_WIDTH / 2));
+            let ascii: String = bytes.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+            let offset = start + (line * HEX_DUMP_WIDTH) as u64;
+            format!("{offset:08x}  {:<23}  {:<23}  |{ascii}|", left.join(" "), right.join(" "))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_short_text() {
+        assert_eq!(preview(b"hello", 32), "hello");
+    }
+
+    #[test]
+    fn test_preview_exactly_max() {
+        assert_eq!(preview(b"hello", 5), "hello");
+    }
+
+    #[test]
+    fn test_preview_truncated_text() {
+        assert_eq!(preview(b"hello world", 5), "hello… (+6 more bytes)");
+    }
+
+    #[test]
+    fn test_preview_max_inside_multibyte_char() {
+        // "€" is 3 bytes long, so a 5 byte limit falls in the middle of it.
+        assert_eq!(preview("ab€cd".as_bytes(), 3), "ab… (+5 more bytes)");
+        assert_eq!(preview("ab€cd".as_bytes(), 4), "ab… (+5 more bytes)");
+        assert_eq!(preview("ab€cd".as_bytes(), 5), "ab€… (+2 more bytes)");
+    }
+
+    #[test]
+    fn test_preview_empty_data() {
+        assert_eq!(preview(b"", 32), "");
+    }
+
+    #[test]
+    fn test_preview_disabled() {
+        assert_eq!(preview(b"hello", 0), "");
+    }
+
+    #[test]
+    fn test_preview_binary() {
+        assert_eq!(preview(&[0, 1, 0xff], 32), "00 01 ff");
+        assert_eq!(preview(&[0, 1, 0xff, 0x10], 2), "00 01… (+2 more bytes)");
+    }
+
+    #[test]
+    fn test_preview_escapes_newlines() {
+        assert_eq!(preview(b"a\nb", 32), "a\\nb");
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let data: Vec<u8> = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".iter().chain(b"abc").copied().collect();
+
+        assert_eq!(hex_dump(&data, 0x10), vec![
+            "00000010  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|",
+            "00000020  61 62 63                                          |abc|",
+        ]);
+        assert!(hex_dump(b"", 0).is_empty());
+    }
+}
@@ -0,0 +1,323 @@
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Serialize;
+
+use crate::ancillary;
+use crate::chunk::Chunk;
+use crate::codec;
+use crate::envelope;
+use crate::ihdr::Ihdr;
+use crate::png::{ChunkRange, Png};
+use crate::preview::{hex_dump, preview};
+use crate::size::{format_size, SizeStyle};
+use crate::stream::ChunkIndex;
+
+/// A chunk as listed by `pngme print --json`.
+#[derive(Debug, Serialize)]
+pub(crate) struct ChunkEntry {
+    pub(crate) index: usize,
+    pub(crate) chunk_type: String,
+    pub(crate) length: u64,
+    #[serde(flatten)]
+    pub(crate) range: ChunkRange,
+}
+
+/// One entry per chunk, with its byte span in the file.
+pub(crate) fn chunk_entries(png: &Png) -> Vec<ChunkEntry> {
+    png.chunks()
+        .zip(png.chunk_ranges())
+        .enumerate()
+        .map(|(index, (chunk, range))| ChunkEntry {
+            index,
+            chunk_type: chunk.chunk_type().to_string(),
+            length: chunk.data().len() as u64,
+            range,
+        })
+        .collect()
+}
+
+/// A decoded payload as printed by `pngme decode --format json`.
+#[derive(Debug, Serialize)]
+pub(crate) struct PayloadEntry {
+    pub(crate) chunk_type: String,
+    /// Length of the decoded payload, not of the chunk data.
+    pub(crate) length: usize,
+    /// CRC of the chunk holding the payload, its first part for a split payload.
+    pub(crate) crc: u32,
+    /// The payload in standard base64.
+    pub(crate) data: String,
+}
+
+impl PayloadEntry {
+    pub(crate) fn new(chunk: &Chunk, payload: &[u8]) -> PayloadEntry {
+        PayloadEntry { chunk_type: chunk.chunk_type().to_string(), length: payload.len(), crc: chunk.crc(), data: BASE64.encode(payload) }
+    }
+}
+
+/// What `pngme decode --format json` writes: the image header fields, when the IHDR can be
+/// read, and the decoded payloads.
+#[derive(Debug, Serialize)]
+pub(crate) struct Decoded {
+    #[serde(flatten)]
+    pub(crate) image: Option<Ihdr>,
+    pub(crate) payloads: Vec<PayloadEntry>,
+}
+
+pub(crate) fn decoded(png: &Png, payloads: Vec<PayloadEntry>) -> Decoded {
+    Decoded { image: Ihdr::from_png(png), payloads }
+}
+
+/// What `pngme print --json` writes: the image header fields, when the IHDR can be read,
+/// and the chunk list.
+#[derive(Debug, Serialize)]
+pub(crate) struct Listing {
+    #[serde(flatten)]
+    pub(crate) image: Option<Ihdr>,
+    pub(crate) chunk_count: usize,
+    pub(crate) chunks: Vec<ChunkEntry>,
+}
+
+pub(crate) fn listing(png: &Png) -> Listing {
+    Listing { image: Ihdr::from_png(png), chunk_count: png.chunk_count(), chunks: chunk_entries(png) }
+}
+
+/// `photo.png — 1920x1080, 8-bit rgba, 14 chunks`, without the image part when the IHDR
+/// can't be read.
+pub(crate) fn header_line(file: &Path, png: &Png) -> String {
+    describe_file(file, Ihdr::from_png(png), png.chunk_count())
+}
+
+fn describe_file(file: &Path, image: Option<Ihdr>, chunk_count: usize) -> String {
+    let chunks = format!("{chunk_count} chunks");
+    match image {
+        Some(ihdr) => format!("{} — {ihdr}, {chunks}", file.display()),
+        None => format!("{} — {chunks}", file.display()),
+    }
+}
+
+/// `listing` for a file too large to load, from its chunk headers.
+pub(crate) fn index_listing(index: &ChunkIndex) -> Listing {
+    let chunks = index
+        .chunks
+        .iter()
+        .enumerate()
+        .map(|(position, chunk)| ChunkEntry {
+            index: position,
+            chunk_type: chunk.chunk_type.to_string(),
+            length: u64::from(chunk.length),
+            range: chunk.range,
+        })
+        .collect();
+    Listing { image: index.ihdr, chunk_count: index.chunks.len(), chunks }
+}
+
+/// `header_line` for a file too large to load.
+pub(crate) fn index_header_line(file: &Path, index: &ChunkIndex) -> String {
+    describe_file(file, index.ihdr, index.chunks.len())
+}
+
+/// `chunk_lines` for a file too large to load: no previews, since the data isn't read.
+pub(crate) fn index_lines(index: &ChunkIndex, offsets: bool, sizes: SizeStyle) -> Vec<String> {
+    index
+        .chunks
+        .iter()
+        .enumerate()
+        .map(|(position, chunk)| {
+            let mut line = format!("{position}: {} ({})", chunk.chunk_type, format_size(u64::from(chunk.length), sizes));
+            if offsets {
+                line.push_str(&offsets_note(&chunk.range));
+            }
+            line
+        })
+        .collect()
+}
+
+fn offsets_note(range: &ChunkRange) -> String {
+    format!(" [{:#x}..{:#x}, data at {:#x}]", range.offset, range.end_offset, range.data_offset)
+}
+
+/// `3: IDAT (1.5 KiB)`, with the byte range when `offsets` is set.
+fn chunk_heading(index: usize, chunk: &Chunk, range: &ChunkRange, offsets: bool, sizes: SizeStyle) -> String {
+    let mut line = format!("{index}: {} ({})", chunk.chunk_type(), format_size(chunk.data().len() as u64, sizes));
+    if offsets {
+        line.push_str(&offsets_note(range));
+    }
+    line
+}
+
+/// One line per chunk: index, type, data length, optionally its byte span, and a preview of
+/// the payload. Chunks pngme can interpret, like tRNS, show a description instead of the
+/// preview.
+pub(crate) fn chunk_lines(png: &Png, preview_bytes: usize, offsets: bool, sizes: SizeStyle) -> Vec<String> {
+    png.chunks()
+        .zip(png.chunk_ranges())
+        .enumerate()
+        .map(|(index, (chunk, range))| {
+            let line = chunk_heading(index, chunk, &range, offsets, sizes);
+            let data_preview = match ancillary::describe(png, chunk) {
+                Some(Ok(description)) => description,
+                _ => {
+                    let payload = codec::open(chunk.data()).unwrap_or_else(|_| envelope::unwrap(chunk.data()).to_vec());
+                    preview(&payload, preview_bytes)
+                }
+            };
+            if data_preview.is_empty() {
+                line
+            } else {
+                format!("{line} {data_preview}")
+            }
+        })
+        .collect()
+}
+
+/// `print --hex`: every chunk's line followed by a dump of its raw data, with file offsets.
+pub(crate) fn hex_lines(png: &Png, offsets: bool, sizes: SizeStyle) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (index, (chunk, range)) in png.chunks().zip(png.chunk_ranges()).enumerate() {
+        lines.push(chunk_heading(index, chunk, &range, offsets, sizes));
+        lines.extend(hex_dump(chunk.data(), range.data_offset).into_iter().map(|dump| format!("  {dump}")));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png<'static> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"I am the first chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("miDl").unwrap(), vec![0, 1, 2, 3]),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_chunk_lines() {
+        let lines = chunk_lines(&testing_png(), 32, false, SizeStyle::Human);
+
+        assert_eq!(lines, vec![
+            "0: FrSt (20 bytes) I am the first chunk",
+            "1: miDl (4 bytes) 00 01 02 03",
+            "2: LASt (0 bytes)",
+        ]);
+    }
+
+    #[test]
+    fn test_preview_bytes_changes_output_length() {
+        let png = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("loNg").unwrap(), "x".repeat(100).into_bytes())]);
+
+        let long = chunk_lines(&png, 64, false, SizeStyle::Human)[0].len();
+        let short = chunk_lines(&png, 8, false, SizeStyle::Human)[0].len();
+        let none = chunk_lines(&png, 0, false, SizeStyle::Human)[0].len();
+
+        assert!(long > short);
+        assert!(short > none);
+        assert_eq!(chunk_lines(&png, 0, false, SizeStyle::Human)[0], "0: loNg (100 bytes)");
+    }
+
+    #[test]
+    fn test_chunk_lines_size_style() {
+        let png = Png::from_chunks(vec![Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![0; 1536])]);
+
+        assert_eq!(chunk_lines(&png, 0, false, SizeStyle::Human)[0], "0: IDAT (1.5 KiB)");
+        assert_eq!(chunk_lines(&png, 0, false, SizeStyle::Bytes)[0], "0: IDAT (1536 bytes)");
+    }
+
+    #[test]
+    fn test_chunk_lines_with_offsets() {
+        let lines = chunk_lines(&testing_png(), 0, true, SizeStyle::Human);
+
+        assert_eq!(lines, vec![
+            "0: FrSt (20 bytes) [0x8..0x28, data at 0x10]",
+            "1: miDl (4 bytes) [0x28..0x38, data at 0x30]",
+            "2: LASt (0 bytes) [0x38..0x44, data at 0x40]",
+        ]);
+    }
+
+    #[test]
+    fn test_hex_lines() {
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0, b'h', b'i', 0xff]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ]);
+
+        assert_eq!(hex_lines(&png, false, SizeStyle::Human), vec![
+            "0: ruSt (4 bytes)",
+            "  00000010  00 68 69 ff                                       |.hi.|",
+            "1: IEND (0 bytes)",
+        ]);
+    }
+
+    #[test]
+    fn test_header_line() {
+        // 1920x1080 rgba
+        let ihdr = vec![0, 0, 7, 128, 0, 0, 4, 56, 8, 6, 0, 0, 0];
+        let mut png = testing_png();
+        png.replace_chunk_unchecked(0, Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr));
+
+        assert_eq!(header_line(Path::new("photo.png"), &png), "photo.png — 1920x1080, 8-bit rgba, 3 chunks");
+        assert_eq!(header_line(Path::new("photo.png"), &testing_png()), "photo.png — 3 chunks");
+    }
+
+    #[test]
+    fn test_listing_json() {
+        let mut png = testing_png();
+        png.replace_chunk_unchecked(0, Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]));
+
+        let json = serde_json::to_value(listing(&png)).unwrap();
+        assert_eq!(json["width"], 2);
+        assert_eq!(json["height"], 1);
+        assert_eq!(json["color_type"], "rgb");
+        assert_eq!(json["chunk_count"], 3);
+        assert_eq!(json["chunks"][1]["chunk_type"], "miDl");
+
+        let json = serde_json::to_value(listing(&testing_png())).unwrap();
+        assert!(json.get("width").is_none());
+        assert_eq!(json["chunk_count"], 3);
+    }
+
+    #[test]
+    fn test_chunk_entries_json() {
+        let json = serde_json::to_value(chunk_entries(&testing_png())).unwrap();
+
+        assert_eq!(json[1], serde_json::json!({
+            "index": 1,
+            "chunk_type": "miDl",
+            "length": 4,
+            "offset": 40,
+            "data_offset": 48,
+            "end_offset": 56,
+        }));
+    }
+
+    #[test]
+    fn test_payload_entry_json() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"stored".to_vec());
+
+        let json = serde_json::to_value(PayloadEntry::new(&chunk, b"hi!")).unwrap();
+
+        assert_eq!(json, serde_json::json!({ "chunk_type": "ruSt", "length": 3, "crc": chunk.crc(), "data": "aGkh" }));
+    }
+
+    #[test]
+    fn test_chunk_lines_describe_ancillary_chunks() {
+        // 1x1 indexed image with a 3 entry palette
+        let ihdr = vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0];
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr),
+            Chunk::new(ChunkType::from_str("PLTE").unwrap(), vec![0; 9]),
+            Chunk::new(ChunkType::from_str("tRNS").unwrap(), vec![0, 128]),
+            Chunk::new(ChunkType::from_str("bKGD").unwrap(), vec![2]),
+        ]);
+
+        let lines = chunk_lines(&png, 0, false, SizeStyle::Human);
+
+        assert_eq!(lines[2], "2: tRNS (2 bytes) 2 palette entries with alpha");
+        assert_eq!(lines[3], "3: bKGD (1 bytes) palette index 2");
+    }
+}
@@ -0,0 +1,232 @@
+//! Bindings for Python, built with `maturin build --features python` from the `pyproject.toml`
+//! at the root, so image pipelines written in Python can read and write chunks without
+//! running the `pngme` binary.
+//!
+//! ```python
+//! import pngme
+//!
+//! png = pngme.Png(open("image.png", "rb").read())
+//! png.append_chunk(pngme.Chunk(pngme.ChunkType("ruSt"), b"hidden message"))
+//! open("image.png", "wb").write(bytes(png))
+//! ```
+
+use std::str::FromStr;
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+fn value_error(error: impl std::fmt::Display) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// The four-letter type of a chunk, e.g. `IHDR` or `ruSt`.
+#[pyclass(name = "ChunkType", module = "pngme", eq, frozen, from_py_object)]
+#[derive(Clone, PartialEq)]
+struct PyChunkType(ChunkType);
+
+#[pymethods]
+impl PyChunkType {
+    /// Raises `ValueError` unless `name` is four ASCII letters.
+    #[new]
+    fn new(name: &str) -> PyResult<PyChunkType> {
+        ChunkType::from_str(name).map(PyChunkType).map_err(value_error)
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ChunkType('{}')", self.0)
+    }
+
+    fn __hash__(&self) -> u32 {
+        u32::from_be_bytes(self.0.bytes())
+    }
+
+    fn __bytes__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.bytes())
+    }
+
+    #[getter]
+    fn is_valid(&self) -> bool {
+        self.0.is_valid()
+    }
+
+    #[getter]
+    fn is_critical(&self) -> bool {
+        self.0.is_critical()
+    }
+
+    #[getter]
+    fn is_public(&self) -> bool {
+        self.0.is_public()
+    }
+
+    #[getter]
+    fn is_reserved_bit_valid(&self) -> bool {
+        self.0.is_reserved_bit_valid()
+    }
+
+    #[getter]
+    fn is_safe_to_copy(&self) -> bool {
+        self.0.is_safe_to_copy()
+    }
+}
+
+/// A chunk type given either as a `ChunkType` or as its name.
+#[derive(FromPyObject)]
+enum ChunkTypeArg {
+    ChunkType(PyChunkType),
+    Name(String),
+}
+
+impl ChunkTypeArg {
+    fn into_chunk_type(self) -> PyResult<ChunkType> {
+        match self {
+            ChunkTypeArg::ChunkType(chunk_type) => Ok(chunk_type.0),
+            ChunkTypeArg::Name(name) => ChunkType::from_str(&name).map_err(value_error),
+        }
+    }
+}
+
+/// A png chunk: its type, its data and the CRC of both.
+#[pyclass(name = "Chunk", module = "pngme", frozen, from_py_object)]
+#[derive(Clone)]
+struct PyChunk(Chunk<'static>);
+
+#[pymethods]
+impl PyChunk {
+    /// `chunk_type` is a `ChunkType` or its name. Raises `ValueError` when `data` doesn't have
+    /// the size a standard chunk type requires.
+    #[new]
+    fn new(chunk_type: ChunkTypeArg, data: Vec<u8>) -> PyResult<PyChunk> {
+        Chunk::try_new(chunk_type.into_chunk_type()?, data).map(PyChunk).map_err(value_error)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Chunk('{}', {} bytes)", self.0.chunk_type(), self.0.data().len())
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.data().len()
+    }
+
+    /// The whole chunk as written in a file: length, type, data and CRC.
+    fn __bytes__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.as_bytes())
+    }
+
+    #[getter]
+    fn chunk_type(&self) -> PyChunkType {
+        PyChunkType(self.0.chunk_type().clone())
+    }
+
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.0.data())
+    }
+
+    #[getter]
+    fn crc(&self) -> u32 {
+        self.0.crc()
+    }
+
+    /// The data decoded as UTF-8, raising `ValueError` when it isn't.
+    fn data_as_string(&self) -> PyResult<String> {
+        self.0.data_as_string().map_err(value_error)
+    }
+}
+
+/// A png file as a list of chunks.
+#[pyclass(name = "Png", module = "pngme")]
+struct PyPng(Png<'static>);
+
+#[pymethods]
+impl PyPng {
+    /// Parses the bytes of a png file, raising `ValueError` when they aren't one.
+    #[new]
+    fn new(data: &[u8]) -> PyResult<PyPng> {
+        Png::try_from(data).map(|png| PyPng(png.into_owned())).map_err(value_error)
+    }
+
+    /// Builds a png from its chunks, signature excluded.
+    #[staticmethod]
+    fn from_chunks(chunks: Vec<PyChunk>) -> PyPng {
+        PyPng(Png::from_chunks(chunks.into_iter().map(|chunk| chunk.0).collect()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Png({} chunks)", self.0.chunk_count())
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.chunk_count()
+    }
+
+    /// The whole file, signature included.
+    fn __bytes__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.as_bytes())
+    }
+
+    /// A copy of every chunk, in file order.
+    fn chunks(&self) -> Vec<PyChunk> {
+        self.0.chunks().cloned().map(PyChunk).collect()
+    }
+
+    /// The first chunk of type `chunk_type`, or `None`.
+    fn chunk_by_type(&self, chunk_type: &str) -> Option<PyChunk> {
+        self.0.chunk_by_type(chunk_type).cloned().map(PyChunk)
+    }
+
+    /// Adds `chunk` at the end of the file, after IEND if there is one.
+    fn append_chunk(&mut self, chunk: PyChunk) {
+        self.0.append_chunk(chunk.0);
+    }
+
+    /// Adds `chunk` before IEND, where readers that stop at IEND still see it.
+    fn insert_chunk(&mut self, chunk: PyChunk) {
+        self.0.insert_before_iend(chunk.0);
+    }
+
+    /// Removes and returns the first chunk of type `chunk_type`, raising `KeyError` when there
+    /// is none.
+    fn remove_first_chunk(&mut self, chunk_type: &str) -> PyResult<PyChunk> {
+        self.0.remove_first_chunk(chunk_type).map(PyChunk).map_err(|error| PyKeyError::new_err(error.to_string()))
+    }
+}
+
+#[pymodule]
+fn pngme(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyChunkType>()?;
+    module.add_class::<PyChunk>()?;
+    module.add_class::<PyPng>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let chunk = |chunk_type: &str, size: usize| PyChunk::new(ChunkTypeArg::Name(chunk_type.to_string()), vec![0; size]).unwrap();
+        let mut png = PyPng::from_chunks(vec![chunk("IHDR", 13), chunk("IDAT", 8), chunk("IEND", 0)]);
+        png.insert_chunk(PyChunk::new(ChunkTypeArg::ChunkType(PyChunkType::new("ruSt").unwrap()), b"hidden".to_vec()).unwrap());
+
+        let again = PyPng::new(&png.0.as_bytes()).unwrap();
+        let types: Vec<String> = again.chunks().iter().map(|chunk| chunk.chunk_type().__str__()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(again.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(), "hidden");
+        Python::initialize();
+        Python::attach(|py| {
+            assert!(PyChunkType::new("ru1t").err().unwrap().is_instance_of::<PyValueError>(py));
+            assert!(png.remove_first_chunk("teSt").err().unwrap().is_instance_of::<PyKeyError>(py));
+        });
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+/// One change `pngme repair` made to get a loadable file.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Fix {
+    CrcRecomputed { chunk_type: String, offset: u64 },
+    /// Bytes before IEND that don't form a complete chunk, from `offset` to the end of the file.
+    TailDropped { offset: u64, size: u64, reason: String },
+    IendAppended,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fix::CrcRecomputed { chunk_type, offset } => write!(f, "offset {offset:#x}: recomputed the {chunk_type} CRC"),
+            Fix::TailDropped { offset, size, reason } => write!(f, "offset {offset:#x}: dropped {size} bytes that aren't a complete chunk ({reason})"),
+            Fix::IendAppended => write!(f, "appended the missing IEND chunk"),
+        }
+    }
+}
+
+/// Rebuilds a slightly corrupted png: wrong CRCs are recomputed, whatever can't be read as a
+/// chunk before IEND is dropped, and a missing IEND is appended. Anything after IEND is kept
+/// as it is.
+///
+/// The chunks are appended one by one, so the png counts as modified and `save_png` writes it
+/// even when only CRCs, which the model doesn't keep, were fixed. Callers should check the
+/// fixes first.
+pub(crate) fn repair(bytes: &[u8]) -> Result<(Png<'_>, Vec<Fix>), String> {
+    if bytes.get(..8) != Some(Png::STANDARD_HEADER.as_slice()) {
+        return Err("Data header should match the standard PNG header, the file can't be repaired".to_string());
+    }
+
+    let mut png = Png::from_chunks(Vec::new());
+    let mut fixes = Vec::new();
+    let mut pos = 8;
+    let mut has_iend = false;
+    while pos < bytes.len() && !has_iend {
+        let (chunk, consumed) = match Chunk::parse_ignoring_crc(&bytes[pos..]) {
+            Ok(parsed) => parsed,
+            Err(reason) => {
+                fixes.push(Fix::TailDropped { offset: pos as u64, size: (bytes.len() - pos) as u64, reason });
+                pos = bytes.len();
+                break;
+            }
+        };
+        let stored = &bytes[pos + consumed - 4..pos + consumed];
+        if chunk.crc().to_be_bytes() != stored {
+            fixes.push(Fix::CrcRecomputed { chunk_type: chunk.chunk_type().to_string(), offset: pos as u64 });
+        }
+        has_iend = chunk.chunk_type().bytes() == *b"IEND";
+        png.append_chunk(chunk);
+        pos += consumed;
+    }
+
+    if !has_iend {
+        png.append_chunk(Chunk::new(ChunkType::from_str("IEND").expect("IEND is a valid chunk type"), Vec::new()));
+        fixes.push(Fix::IendAppended);
+    }
+    png.set_trailing_data(bytes[pos..].to_vec());
+    Ok((png, fixes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_bytes() -> Vec<u8> {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), chunk_type.as_bytes().to_vec());
+        Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")]).as_bytes()
+    }
+
+    #[test]
+    fn test_intact_file() {
+        let bytes = [testing_bytes(), b"trailing".to_vec()].concat();
+
+        let (png, fixes) = repair(&bytes).unwrap();
+
+        assert!(fixes.is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_recomputes_crcs() {
+        let mut bytes = testing_bytes();
+        // The IDAT CRC, after the signature, IHDR and the IDAT header and data.
+        bytes[8 + 16 + 12] ^= 0xff;
+
+        let (png, fixes) = repair(&bytes).unwrap();
+
+        assert_eq!(fixes, vec![Fix::CrcRecomputed { chunk_type: "IDAT".to_string(), offset: 24 }]);
+        assert_eq!(png.as_bytes(), testing_bytes());
+        assert!(Png::try_from(png.as_bytes().as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_drops_truncated_chunk_and_appends_iend() {
+        let mut bytes = testing_bytes();
+        bytes.truncate(8 + 16 + 14);
+
+        let (png, fixes) = repair(&bytes).unwrap();
+
+        assert!(matches!(&fixes[0], Fix::TailDropped { offset: 24, size: 14, reason } if reason.contains("IDAT chunk is truncated")));
+        assert_eq!(fixes[1], Fix::IendAppended);
+        let types: Vec<String> = png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IEND"]);
+        assert!(png.trailing_data().is_empty());
+        assert!(png.is_modified());
+    }
+
+    #[test]
+    fn test_bad_signature() {
+        let mut bytes = testing_bytes();
+        bytes[1] = b'Q';
+
+        assert!(repair(&bytes).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::PngmeError;
+use crate::png::Png;
+use crate::selector::ChunkSelector;
+use crate::Result;
+
+/// Gives the chunks `selector` picks the type `new_type`, keeping their data and place in the
+/// file, and returns how many were retyped. Each gets the CRC of its new type.
+pub(crate) fn retype(png: &mut Png, selector: &ChunkSelector, new_type: &ChunkType) -> Result<usize> {
+    let matches = selector.matches(png);
+    if matches.is_empty() {
+        return Err(PngmeError::ChunkNotFound { chunk_type: selector.to_string() }.into());
+    }
+    for &(_, index) in &matches {
+        let chunk = Chunk::try_new(new_type.clone(), png[index].data().to_vec())?;
+        png.replace_chunk(index, chunk).map_err(|reason| format!("Can't retype {}, chunk {index}: {reason}", selector.chunk_type))?;
+    }
+    Ok(matches.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png() -> Png<'static> {
+        Png::from_chunks(vec![chunk("IHDR", &[0; 13]), chunk("olDn", b"one"), chunk("IDAT", &[0; 8]), chunk("olDn", b"two"), chunk("IEND", b"")])
+    }
+
+    #[test]
+    fn test_retype() {
+        let mut png = testing_png();
+        let new_type = ChunkType::from_str("neWn").unwrap();
+
+        assert_eq!(retype(&mut png, &ChunkSelector::from_str("olDn[*]").unwrap(), &new_type).unwrap(), 2);
+        let types: Vec<String> = png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "neWn", "IDAT", "neWn", "IEND"]);
+        assert_eq!(png[3].data(), b"two");
+        assert_eq!(png[3].crc(), chunk("neWn", b"two").crc());
+        // The file parses again, so the recomputed CRCs are the ones written.
+        assert!(Png::try_from(png.as_bytes().as_slice()).is_ok());
+
+        let mut png = testing_png();
+        assert_eq!(retype(&mut png, &ChunkSelector::from_str("olDn").unwrap(), &new_type).unwrap(), 1);
+        assert_eq!(png[3].chunk_type().to_string(), "olDn");
+    }
+
+    #[test]
+    fn test_retype_errors() {
+        let mut png = testing_png();
+        let selector = |s: &str| ChunkSelector::from_str(s).unwrap();
+
+        assert!(retype(&mut png, &selector("noNe"), &ChunkType::from_str("neWn").unwrap()).unwrap_err().to_string().contains("noNe"));
+        assert!(retype(&mut png, &selector("olDn"), &ChunkType::from_str("IHDR").unwrap()).unwrap_err().to_string().contains("13 bytes"));
+        let error = retype(&mut png, &selector("olDn[1]"), &ChunkType::from_str("PLTE").unwrap()).unwrap_err().to_string();
+        assert!(error.contains("after the image data"));
+        assert!(!png.is_modified());
+    }
+}
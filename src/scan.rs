@@ -0,0 +1,252 @@
+use serde::Serialize;
+
+use crate::envelope::{self, EnvelopeHeader};
+use crate::json_path::JsonPath;
+use crate::png::Png;
+use crate::size::{format_size, SizeStyle};
+
+/// Chunk types defined by the PNG spec and its registered extensions.
+const STANDARD_CHUNK_TYPES: [&str; 34] = [
+    "IHDR", "PLTE", "IDAT", "IEND", "tRNS", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCV",
+    "cLLI", "tEXt", "zTXt", "iTXt", "bKGD", "hIST", "pHYs", "sPLT", "eXIf", "tIME", "acTL", "fcTL",
+    "fdAT", "oFFs", "pCAL", "sCAL", "gIFg", "gIFx", "gIFt", "sTER", "dSIG", "fRAc",
+];
+
+/// Ancillary chunks larger than this are reported: metadata is rarely more than a few KiB.
+pub(crate) const LARGE_ANCILLARY_SIZE: u64 = 64 << 10;
+
+/// Something in a png that may hide a payload, as reported by `pngme scan`.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Finding {
+    /// A chunk type neither the spec nor a registered extension defines.
+    NonStandardChunk { chunk_type: String, index: usize },
+    /// A chunk type whose third letter is lowercase, which the spec reserves.
+    InvalidReservedBit { chunk_type: String, index: usize },
+    /// An ancillary chunk over `LARGE_ANCILLARY_SIZE`.
+    LargeAncillaryChunk { chunk_type: String, index: usize, size: u64 },
+    /// Bytes after the IEND chunk, chunks or not, which viewers ignore.
+    DataAfterIend { offset: u64, size: u64 },
+    /// No IEND chunk, so there is no telling where the image was meant to end.
+    MissingIend,
+}
+
+impl Finding {
+    pub(crate) fn line(&self, sizes: SizeStyle) -> String {
+        match self {
+            Finding::NonStandardChunk { chunk_type, index } => format!("{chunk_type} at chunk {index} is not a standard chunk type"),
+            Finding::InvalidReservedBit { chunk_type, index } => {
+                format!("{chunk_type} at chunk {index} has an invalid reserved bit (third letter should be uppercase)")
+            }
+            Finding::LargeAncillaryChunk { chunk_type, index, size } => {
+                format!("{chunk_type} at chunk {index} is unusually large for an ancillary chunk ({})", format_size(*size, sizes))
+            }
+            Finding::DataAfterIend { offset, size } => format!("{} after IEND, from offset {offset:#x}", format_size(*size, sizes)),
+            Finding::MissingIend => "no IEND chunk".to_string(),
+        }
+    }
+}
+
+/// Everything in `png` that looks like it could carry hidden data, in file order.
+pub(crate) fn findings(png: &Png) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for (index, chunk) in png.chunks().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        let name = chunk_type.to_string();
+        if !STANDARD_CHUNK_TYPES.contains(&name.as_str()) {
+            findings.push(Finding::NonStandardChunk { chunk_type: name.clone(), index });
+        }
+        if chunk_type.has_invalid_reserved_bit() {
+            findings.push(Finding::InvalidReservedBit { chunk_type: name.clone(), index });
+        }
+        let size = chunk.data().len() as u64;
+        if !chunk_type.is_critical() && size > LARGE_ANCILLARY_SIZE {
+            findings.push(Finding::LargeAncillaryChunk { chunk_type: name, index, size });
+        }
+    }
+
+    match png.iend_end_offset() {
+        Some(offset) if offset < png.serialized_len() => {
+            findings.push(Finding::DataAfterIend { offset, size: png.serialized_len() - offset });
+        }
+        Some(_) => {}
+        None => findings.push(Finding::MissingIend),
+    }
+
+    findings
+}
+
+/// What `pngme scan` found in a file.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct ScanReport {
+    pub(crate) findings: Vec<Finding>,
+    pub(crate) payloads: Vec<PayloadEntry>,
+}
+
+pub(crate) fn scan(png: &Png) -> ScanReport {
+    ScanReport { findings: findings(png), payloads: pngme_payloads(png) }
+}
+
+/// One png of a `pngme scan --recursive` report.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct FileScan {
+    #[serde(flatten)]
+    pub(crate) path: JsonPath,
+    #[serde(flatten)]
+    pub(crate) report: ScanReport,
+}
+
+/// A file `pngme scan --recursive` couldn't scan.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct ScanError {
+    #[serde(flatten)]
+    pub(crate) path: JsonPath,
+    pub(crate) error: String,
+}
+
+/// What `pngme scan --recursive` found in a directory tree, every png in path order.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct TreeReport {
+    pub(crate) files: Vec<FileScan>,
+    pub(crate) errors: Vec<ScanError>,
+}
+
+impl TreeReport {
+    /// `12 png(s) scanned, 2 with findings: 3 finding(s), 1 pngme payload(s)`
+    pub(crate) fn summary_line(&self) -> String {
+        let flagged = self.files.iter().filter(|file| !file.report.findings.is_empty()).count();
+        let findings: usize = self.files.iter().map(|file| file.report.findings.len()).sum();
+        let payloads: usize = self.files.iter().map(|file| file.report.payloads.len()).sum();
+        let mut line = format!("{} png(s) scanned, {flagged} with findings: {findings} finding(s), {payloads} pngme payload(s)", self.files.len());
+        if !self.errors.is_empty() {
+            line.push_str(&format!(", {} file(s) couldn't be scanned", self.errors.len()));
+        }
+        line
+    }
+}
+
+/// A chunk carrying a pngme envelope, as listed by `pngme scan --pngme`.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct PayloadEntry {
+    pub(crate) index: usize,
+    pub(crate) chunk_type: String,
+    pub(crate) version: u8,
+    /// Whether this build can read the envelope's format version.
+    pub(crate) supported: bool,
+    pub(crate) flags: Vec<&'static str>,
+}
+
+impl PayloadEntry {
+    pub(crate) fn line(&self) -> String {
+        let mut line = format!("{}: {} format v{}", self.index, self.chunk_type, self.version);
+        if !self.supported {
+            line.push_str(" (not readable by this pngme)");
+        }
+        if !self.flags.is_empty() {
+            line.push_str(&format!(", {}", self.flags.join(", ")));
+        }
+        line
+    }
+}
+
+/// Every chunk of `png` that carries a pngme envelope, whatever its format version.
+pub(crate) fn pngme_payloads(png: &Png) -> Vec<PayloadEntry> {
+    png.chunks()
+        .enumerate()
+        .filter_map(|(index, chunk)| {
+            let header = EnvelopeHeader::parse(chunk.data())?;
+            Some(PayloadEntry {
+                index,
+                chunk_type: chunk.chunk_type().to_string(),
+                version: header.version,
+                supported: header.is_supported(),
+                flags: envelope::flag_names(header.flags),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::envelope::FLAG_CHECKSUM;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_findings() {
+        let chunk = |chunk_type: &str, size: usize| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; size]);
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", 13),
+            chunk("ruSt", 5),
+            chunk("zTXt", LARGE_ANCILLARY_SIZE as usize + 1),
+            chunk("IDAT", 100_000),
+            chunk("IEND", 0),
+        ]);
+        png.append_chunk(chunk("afTr", 4));
+        png.append_chunk(chunk("Rust", 4));
+        let iend_end = png.iend_end_offset().unwrap();
+
+        let findings = findings(&png);
+
+        assert_eq!(findings, vec![
+            Finding::NonStandardChunk { chunk_type: "ruSt".to_string(), index: 1 },
+            Finding::LargeAncillaryChunk { chunk_type: "zTXt".to_string(), index: 2, size: LARGE_ANCILLARY_SIZE + 1 },
+            Finding::NonStandardChunk { chunk_type: "afTr".to_string(), index: 5 },
+            Finding::NonStandardChunk { chunk_type: "Rust".to_string(), index: 6 },
+            Finding::InvalidReservedBit { chunk_type: "Rust".to_string(), index: 6 },
+            Finding::DataAfterIend { offset: iend_end, size: 32 },
+        ]);
+        assert_eq!(findings[4].line(SizeStyle::Human), "Rust at chunk 6 has an invalid reserved bit (third letter should be uppercase)");
+        assert_eq!(findings[5].line(SizeStyle::Human), format!("32 bytes after IEND, from offset {iend_end:#x}"));
+    }
+
+    #[test]
+    fn test_no_findings() {
+        let chunk = |chunk_type: &str, size: usize| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; size]);
+        let png = Png::from_chunks(vec![chunk("IHDR", 13), chunk("tEXt", 20), chunk("IDAT", 100), chunk("IEND", 0)]);
+
+        assert!(findings(&png).is_empty());
+        assert_eq!(findings(&Png::from_chunks(vec![chunk("IHDR", 13)])), vec![Finding::MissingIend]);
+    }
+
+    #[test]
+    fn test_tree_report() {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![]);
+        let clean = Png::from_chunks(vec![chunk("IHDR"), chunk("IEND")]);
+        let flagged = Png::from_chunks(vec![chunk("IHDR"), chunk("ruSt")]);
+        let report = TreeReport {
+            files: vec![
+                FileScan { path: PathBuf::from("a.png").into(), report: scan(&clean) },
+                FileScan { path: PathBuf::from("b/c.png").into(), report: scan(&flagged) },
+            ],
+            errors: vec![ScanError { path: PathBuf::from("d.png").into(), error: "Permission denied".to_string() }],
+        };
+
+        assert_eq!(report.summary_line(), "2 png(s) scanned, 1 with findings: 2 finding(s), 0 pngme payload(s), 1 file(s) couldn't be scanned");
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["files"][1]["path"], "b/c.png");
+        assert_eq!(json["files"][1]["findings"][1]["kind"], "missing_iend");
+        assert_eq!(json["errors"][0]["error"], "Permission denied");
+    }
+
+    #[test]
+    fn test_pngme_payloads() {
+        let chunk = |chunk_type: &str, data: Vec<u8>| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data);
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", vec![0; 13]),
+            chunk("ruSt", envelope::wrap(EnvelopeHeader::new(FLAG_CHECKSUM), b"hello")),
+            chunk("raWs", b"raw payload".to_vec()),
+            chunk("neWr", envelope::wrap(EnvelopeHeader { version: 3, flags: 0 }, b"future")),
+        ]);
+
+        let payloads = pngme_payloads(&png);
+
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0].line(), "1: ruSt format v1, checksum");
+        assert_eq!(payloads[1].line(), "3: neWr format v3 (not readable by this pngme)");
+    }
+}
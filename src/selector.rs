@@ -0,0 +1,161 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+/// Which chunks of a type a selector picks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Occurrence {
+    /// The occurrence at this 0-based position among chunks of the type.
+    Nth(usize),
+    All,
+}
+
+/// A chunk type argument with an optional occurrence: `ruSt`, `ruSt[2]` or `ruSt[*]`.
+///
+/// A bare type selects its first occurrence.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ChunkSelector {
+    pub(crate) chunk_type: String,
+    pub(crate) occurrence: Occurrence,
+}
+
+impl ChunkSelector {
+    /// The selected chunks as `(occurrence, index in the file)` pairs, in file order.
+    pub(crate) fn matches(&self, png: &Png) -> Vec<(usize, usize)> {
+        let mut matches = png
+            .chunks()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.chunk_type().to_string() == self.chunk_type)
+            .enumerate()
+            .map(|(occurrence, (index, _))| (occurrence, index));
+
+        match self.occurrence {
+            Occurrence::Nth(n) => matches.nth(n).into_iter().collect(),
+            Occurrence::All => matches.collect(),
+        }
+    }
+
+    /// This selector with the occurrence given by a flag such as `--all`, if any. A flag can't
+    /// be combined with an occurrence in brackets.
+    pub(crate) fn with_occurrence(&self, occurrence: Option<Occurrence>) -> Result<ChunkSelector, String> {
+        match occurrence {
+            None => Ok(self.clone()),
+            Some(_) if self.occurrence != Occurrence::Nth(0) => {
+                Err(format!("`{self}` already selects an occurrence, drop the brackets or the flag"))
+            }
+            Some(occurrence) => Ok(ChunkSelector { occurrence, ..self.clone() }),
+        }
+    }
+}
+
+impl FromStr for ChunkSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (chunk_type, occurrence) = match s.split_once('[') {
+            None => (s, Occurrence::Nth(0)),
+            Some((chunk_type, rest)) => {
+                let inner = rest
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("`{s}` should end with `]` after the occurrence"))?;
+                let occurrence = match inner {
+                    "*" => Occurrence::All,
+                    _ if !inner.is_empty() && inner.bytes().all(|byte| byte.is_ascii_digit()) => {
+                        Occurrence::Nth(inner.parse().map_err(|_| format!("occurrence `{inner}` is too large"))?)
+                    }
+                    _ => return Err(format!("invalid occurrence `{inner}` in `{s}`, expected a number or `*`")),
+                };
+                (chunk_type, occurrence)
+            }
+        };
+
+        ChunkType::from_str(chunk_type)?;
+        Ok(ChunkSelector { chunk_type: chunk_type.to_string(), occurrence })
+    }
+}
+
+impl fmt::Display for ChunkSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.occurrence {
+            Occurrence::Nth(0) => write!(f, "{}", self.chunk_type),
+            Occurrence::Nth(n) => write!(f, "{}[{n}]", self.chunk_type),
+            Occurrence::All => write!(f, "{}[*]", self.chunk_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+
+    fn selector(s: &str) -> Result<ChunkSelector, String> {
+        ChunkSelector::from_str(s)
+    }
+
+    #[test]
+    fn test_parse_bare_type() {
+        assert_eq!(selector("ruSt"), Ok(ChunkSelector { chunk_type: "ruSt".to_string(), occurrence: Occurrence::Nth(0) }));
+    }
+
+    #[test]
+    fn test_parse_occurrence() {
+        assert_eq!(selector("ruSt[2]").unwrap().occurrence, Occurrence::Nth(2));
+        assert_eq!(selector("ruSt[0]").unwrap().occurrence, Occurrence::Nth(0));
+        assert_eq!(selector("ruSt[*]").unwrap().occurrence, Occurrence::All);
+    }
+
+    #[test]
+    fn test_parse_invalid_occurrence() {
+        for invalid in ["ruSt[-1]", "ruSt[+1]", "ruSt[x]", "ruSt[]", "ruSt[1.5]", "ruSt[**]", "ruSt[ 1]"] {
+            assert!(selector(invalid).is_err(), "{invalid} should be rejected");
+        }
+        assert!(selector("ruSt[99999999999999999999999]").unwrap_err().contains("too large"));
+    }
+
+    #[test]
+    fn test_parse_trailing_junk() {
+        for invalid in ["ruSt[1]x", "ruSt[1][2]", "ruSt[1", "ruSt]", "ruSt[*]]"] {
+            assert!(selector(invalid).is_err(), "{invalid} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_type() {
+        assert!(selector("ruS[1]").is_err());
+        assert!(selector("ru1t").is_err());
+        assert!(selector("[1]").is_err());
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for s in ["ruSt", "ruSt[2]", "ruSt[*]"] {
+            assert_eq!(selector(s).unwrap().to_string(), s);
+        }
+        assert_eq!(selector("ruSt[0]").unwrap().to_string(), "ruSt");
+    }
+
+    #[test]
+    fn test_matches() {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![]);
+        let png = Png::from_chunks(vec![chunk("IHDR"), chunk("ruSt"), chunk("IDAT"), chunk("ruSt"), chunk("ruSt"), chunk("IEND")]);
+
+        assert_eq!(selector("ruSt").unwrap().matches(&png), vec![(0, 1)]);
+        assert_eq!(selector("ruSt[2]").unwrap().matches(&png), vec![(2, 4)]);
+        assert_eq!(selector("ruSt[*]").unwrap().matches(&png), vec![(0, 1), (1, 3), (2, 4)]);
+        assert!(selector("ruSt[3]").unwrap().matches(&png).is_empty());
+        assert!(selector("teSt[*]").unwrap().matches(&png).is_empty());
+    }
+
+    #[test]
+    fn test_with_occurrence() {
+        let bare = selector("ruSt").unwrap();
+
+        assert_eq!(bare.with_occurrence(None), Ok(bare.clone()));
+        assert_eq!(bare.with_occurrence(Some(Occurrence::All)).unwrap().to_string(), "ruSt[*]");
+        assert_eq!(bare.with_occurrence(Some(Occurrence::Nth(2))).unwrap().to_string(), "ruSt[2]");
+        assert!(selector("ruSt[1]").unwrap().with_occurrence(Some(Occurrence::All)).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+/// How sizes are printed in human-readable output. JSON output always has exact integers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum SizeStyle {
+    /// Binary prefixes with one decimal from 1 KiB up, e.g. `1.4 MiB`.
+    #[default]
+    Human,
+    /// Exact byte counts, e.g. `1468006 bytes`.
+    Bytes,
+}
+
+const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Formats a size in bytes for people or scripts, depending on `style`.
+///
+/// Sizes under 1 KiB are exact in both styles. Larger ones are rounded to one decimal in the
+/// largest unit that keeps the rounded value under 1024, so 1048575 bytes is `1.0 MiB`, not
+/// `1024.0 KiB`.
+pub(crate) fn format_size(bytes: u64, style: SizeStyle) -> String {
+    if style == SizeStyle::Bytes || bytes < 1024 {
+        return format!("{bytes} bytes");
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    for (index, unit) in UNITS.iter().enumerate() {
+        let rounded = (value * 10.0).round() / 10.0;
+        if rounded < 1024.0 || index == UNITS.len() - 1 {
+            return format!("{rounded:.1} {unit}");
+        }
+        value /= 1024.0;
+    }
+    unreachable!("the last unit is always used")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn human(bytes: u64) -> String {
+        format_size(bytes, SizeStyle::Human)
+    }
+
+    #[test]
+    fn test_small_sizes_are_exact() {
+        assert_eq!(human(0), "0 bytes");
+        assert_eq!(human(1023), "1023 bytes");
+    }
+
+    #[test]
+    fn test_unit_boundaries() {
+        assert_eq!(human(1024), "1.0 KiB");
+        assert_eq!(human(1024 * 1023), "1023.0 KiB");
+        assert_eq!(human(1024 * 1024 - 52), "1023.9 KiB");
+        assert_eq!(human(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(human(1024 * 1024), "1.0 MiB");
+        assert_eq!(human(1_468_006), "1.4 MiB");
+        assert_eq!(human(5 << 30), "5.0 GiB");
+        assert_eq!(human(u64::MAX), "16.0 EiB");
+    }
+
+    #[test]
+    fn test_bytes_style_is_exact() {
+        assert_eq!(format_size(1023, SizeStyle::Bytes), "1023 bytes");
+        assert_eq!(format_size(1_468_006, SizeStyle::Bytes), "1468006 bytes");
+        assert_eq!(format_size(u64::MAX, SizeStyle::Bytes), format!("{} bytes", u64::MAX));
+    }
+}
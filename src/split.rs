@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+
+use crate::chunk::Chunk;
+use crate::envelope::{self, EnvelopeHeader, FLAG_PART};
+use crate::png::Png;
+use crate::Result;
+
+/// Bytes each part adds in front of its slice of the payload: an envelope header flagged as
+/// a part, then the part's index and the number of parts, both big-endian u32.
+pub(crate) const PART_OVERHEAD: usize = envelope::HEADER_LEN + 8;
+
+/// Where a part goes in its payload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Part {
+    pub(crate) index: u32,
+    pub(crate) count: u32,
+}
+
+/// Reads the part header of `data`, if it is a part, and returns it with the payload slice.
+pub(crate) fn parse(data: &[u8]) -> Option<(Part, &[u8])> {
+    let header = EnvelopeHeader::parse(data)?;
+    if header.flags & FLAG_PART == 0 || data.len() < PART_OVERHEAD {
+        return None;
+    }
+    let field = |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    let part = Part { index: field(envelope::HEADER_LEN), count: field(envelope::HEADER_LEN + 4) };
+    Some((part, &data[PART_OVERHEAD..]))
+}
+
+/// Cuts a sealed payload into the data of chunks of at most `max_chunk_size` bytes each,
+/// numbered in order. A payload that already fits is returned whole, without part headers.
+pub(crate) fn split(payload: &[u8], max_chunk_size: usize) -> Result<Vec<Vec<u8>>> {
+    if payload.len() <= max_chunk_size {
+        return Ok(vec![payload.to_vec()]);
+    }
+    if max_chunk_size <= PART_OVERHEAD {
+        return Err(format!("Chunks must be larger than {PART_OVERHEAD} bytes to hold a part of a split payload").into());
+    }
+
+    let slices: Vec<&[u8]> = payload.chunks(max_chunk_size - PART_OVERHEAD).collect();
+    let count = u32::try_from(slices.len()).map_err(|_| "Payload would be split into too many parts")?;
+    let header = EnvelopeHeader::new(FLAG_PART).as_bytes();
+    Ok(slices
+        .iter()
+        .enumerate()
+        .map(|(index, slice)| [&header[..], &(index as u32).to_be_bytes(), &count.to_be_bytes(), slice].concat())
+        .collect())
+}
+
+/// Joins the parts of the split payload stored in chunks of type `chunk_type`.
+pub(crate) fn join(png: &Png, chunk_type: &str) -> Result<Vec<u8>> {
+    let mut parts: Vec<(Part, &[u8])> = png.chunks_by_type(chunk_type).filter_map(|chunk| parse(chunk.data())).collect();
+    parts.sort_by_key(|(part, _)| part.index);
+    let count = parts.first().map(|(part, _)| part.count).ok_or_else(|| format!("No {chunk_type} chunk holds a part of a split payload"))?;
+
+    for (expected, (part, _)) in (0..).zip(&parts) {
+        if part.count != count {
+            return Err(format!("The parts in {chunk_type} chunks disagree on their number, {count} or {}", part.count).into());
+        }
+        if part.index < expected {
+            return Err(format!("Part {} of the payload split across {chunk_type} chunks appears twice", part.index).into());
+        }
+        if part.index > expected {
+            return Err(format!("Part {expected} of {count} of the payload split across {chunk_type} chunks is missing").into());
+        }
+    }
+    if parts.len() as u64 != u64::from(count) {
+        return Err(format!("Part {} of {count} of the payload split across {chunk_type} chunks is missing", parts.len()).into());
+    }
+
+    Ok(parts.iter().flat_map(|(_, slice)| slice.iter().copied()).collect())
+}
+
+/// A payload and the chunk it was read from.
+pub(crate) type Payload<'a> = (&'a Chunk<'a>, Cow<'a, [u8]>);
+
+/// The data of `chunks`, each with the chunk it came from, and the parts of a split payload
+/// joined back together. A split payload comes back once, with its first selected part,
+/// however many were selected.
+pub(crate) fn payloads<'a>(png: &Png, chunks: &[&'a Chunk<'a>]) -> Result<Vec<Payload<'a>>> {
+    let mut joined_types = Vec::new();
+    let mut payloads = Vec::new();
+
+    for chunk in chunks {
+        if parse(chunk.data()).is_none() {
+            payloads.push((*chunk, Cow::Borrowed(chunk.data())));
+            continue;
+        }
+        let chunk_type = chunk.chunk_type().to_string();
+        if !joined_types.contains(&chunk_type) {
+            payloads.push((*chunk, Cow::Owned(join(png, &chunk_type)?)));
+            joined_types.push(chunk_type);
+        }
+    }
+
+    Ok(payloads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn png_with(chunk_type: &str, datas: Vec<Vec<u8>>) -> Png<'static> {
+        let chunk = |data: Vec<u8>| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data);
+        Png::from_chunks(datas.into_iter().map(chunk).collect())
+    }
+
+    #[test]
+    fn test_split_and_join() {
+        let payload: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        let parts = split(&payload, 100).unwrap();
+
+        assert_eq!(parts.len(), 12);
+        assert!(parts.iter().all(|part| part.len() <= 100));
+        assert_eq!(parse(&parts[11]).unwrap().0, Part { index: 11, count: 12 });
+        assert_eq!(join(&png_with("ruSt", parts), "ruSt").unwrap(), payload);
+    }
+
+    #[test]
+    fn test_small_payload_is_not_split() {
+        assert_eq!(split(b"hello", 100).unwrap(), vec![b"hello".to_vec()]);
+        assert!(split(b"hello", 5).is_ok());
+        assert!(split(&[0; 100], PART_OVERHEAD).is_err());
+    }
+
+    #[test]
+    fn test_join_in_any_order() {
+        let payload = b"a payload split in several parts".to_vec();
+        let mut parts = split(&payload, PART_OVERHEAD + 5).unwrap();
+        parts.reverse();
+
+        assert_eq!(join(&png_with("ruSt", parts), "ruSt").unwrap(), payload);
+    }
+
+    #[test]
+    fn test_join_incomplete() {
+        let parts = split(&[7; 50], PART_OVERHEAD + 10).unwrap();
+        let error = |parts: Vec<Vec<u8>>| join(&png_with("ruSt", parts), "ruSt").unwrap_err().to_string();
+
+        assert!(error(parts[1..].to_vec()).contains("Part 0 of 5"));
+        assert!(error(parts[..4].to_vec()).contains("Part 4 of 5"));
+        assert!(error([parts.clone(), vec![parts[2].clone()]].concat()).contains("Part 2 of the payload"));
+        assert!(error(vec![b"not a part".to_vec()]).contains("No ruSt chunk"));
+    }
+
+    #[test]
+    fn test_payloads() {
+        let mut chunks = vec![Chunk::new(ChunkType::from_str("teXt").unwrap(), b"plain".to_vec())];
+        for part in split(&[1; 40], PART_OVERHEAD + 20).unwrap() {
+            chunks.push(Chunk::new(ChunkType::from_str("ruSt").unwrap(), part));
+        }
+        let png = Png::from_chunks(chunks);
+        let selected: Vec<&Chunk> = png.chunks().collect();
+
+        let payloads = payloads(&png, &selected).unwrap();
+
+        let datas: Vec<&Cow<[u8]>> = payloads.iter().map(|(_, data)| data).collect();
+        assert_eq!(datas, [&Cow::Borrowed(&b"plain"[..]), &Cow::Owned(vec![1; 40])]);
+        assert_eq!(payloads[1].0.crc(), png[1].crc());
+    }
+}
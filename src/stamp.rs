@@ -0,0 +1,207 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{SecondsFormat, Utc};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope::{self, EnvelopeHeader};
+use crate::png::Png;
+use crate::Result;
+
+/// Private, ancillary, safe-to-copy chunk holding the provenance record.
+pub(crate) const STAMP_CHUNK_TYPE: &str = "stMp";
+
+/// Provenance record: ordered key/value pairs serialized as a simple TLV list.
+///
+/// Each entry is a big-endian u16 key length, the UTF-8 key, a big-endian u32 value length
+/// and the UTF-8 value.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Stamp {
+    entries: Vec<(String, String)>,
+}
+
+impl Stamp {
+    /// Builds the record written by `pngme stamp`: the pngme version, the current time unless
+    /// `deterministic` is set, then the user provided pairs.
+    pub(crate) fn new(deterministic: bool, pairs: &[(String, String)]) -> Stamp {
+        let mut entries = vec![("pngme_version".to_string(), env!("CARGO_PKG_VERSION").to_string())];
+        if !deterministic {
+            entries.push(("timestamp".to_string(), Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)));
+        }
+        entries.extend(pairs.iter().cloned());
+
+        Stamp { entries }
+    }
+
+    pub(crate) fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (key, value) in &self.entries {
+            bytes.extend_from_slice(&(key.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(key.as_bytes());
+            bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(value.as_bytes());
+        }
+        bytes
+    }
+
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        let map = self
+            .entries
+            .iter()
+            .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+            .collect();
+        serde_json::Value::Object(map)
+    }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err("Stamp record is truncated".into());
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+impl TryFrom<&[u8]> for Stamp {
+    type Error = crate::Error;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        let mut bytes = value;
+        let mut entries = Vec::new();
+
+        while !bytes.is_empty() {
+            let key_len = u16::from_be_bytes(take(&mut bytes, 2)?.try_into()?) as usize;
+            let key = String::from_utf8(take(&mut bytes, key_len)?.to_vec())?;
+            let value_len = u32::from_be_bytes(take(&mut bytes, 4)?.try_into()?) as usize;
+            let value = String::from_utf8(take(&mut bytes, value_len)?.to_vec())?;
+            entries.push((key, value));
+        }
+
+        Ok(Stamp { entries })
+    }
+}
+
+impl fmt::Display for Stamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (key, value) in &self.entries {
+            writeln!(f, "{key}: {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a `key=value` command line argument.
+pub(crate) fn parse_key_value(s: &str) -> std::result::Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected key=value, got {s:?}")),
+    }
+}
+
+/// Writes `stamp` into `png`, replacing any previous record in place.
+pub(crate) fn apply_stamp(png: &mut Png, stamp: &Stamp) {
+    let chunk_type = ChunkType::from_str(STAMP_CHUNK_TYPE).expect("Stamp chunk type should be valid");
+    let chunk = Chunk::new(chunk_type, envelope::wrap(EnvelopeHeader::new(0), &stamp.as_bytes()));
+
+    png.replace_or_insert_chunk(chunk);
+}
+
+pub(crate) fn read_stamp(png: &Png) -> Result<Option<Stamp>> {
+    match png.chunk_by_type(STAMP_CHUNK_TYPE) {
+        Some(chunk) => Ok(Some(Stamp::try_from(envelope::unwrap(chunk.data()))?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png<'static> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), b"header".to_vec()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ])
+    }
+
+    fn pairs() -> Vec<(String, String)> {
+        vec![
+            ("git_sha".to_string(), "4f2a9c1".to_string()),
+            ("build_id".to_string(), "1234".to_string()),
+            ("author".to_string(), "Zoë 🦀".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_stamp_round_trip() {
+        let mut png = testing_png();
+        let stamp = Stamp::new(true, &pairs());
+        apply_stamp(&mut png, &stamp);
+
+        let read = read_stamp(&png).unwrap().unwrap();
+
+        assert_eq!(read, stamp);
+        assert_eq!(read.entries[0], ("pngme_version".to_string(), env!("CARGO_PKG_VERSION").to_string()));
+        assert_eq!(read.entries[3], ("author".to_string(), "Zoë 🦀".to_string()));
+    }
+
+    #[test]
+    fn test_stamp_has_timestamp_unless_deterministic() {
+        let stamp = Stamp::new(false, &[]);
+        assert_eq!(stamp.entries[1].0, "timestamp");
+
+        let stamp = Stamp::new(true, &[]);
+        assert_eq!(stamp.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_restamp_replaces_record() {
+        let mut png = testing_png();
+        apply_stamp(&mut png, &Stamp::new(true, &pairs()));
+        png.append_chunk(Chunk::new(ChunkType::from_str("teSt").unwrap(), b"after".to_vec()));
+
+        let second = Stamp::new(true, &[("build_id".to_string(), "5678".to_string())]);
+        apply_stamp(&mut png, &second);
+
+        assert_eq!(png.chunks_by_type(STAMP_CHUNK_TYPE).count(), 1);
+        assert_eq!(png[1].chunk_type().to_string(), STAMP_CHUNK_TYPE);
+        assert_eq!(read_stamp(&png).unwrap().unwrap(), second);
+    }
+
+    #[test]
+    fn test_identical_restamp_leaves_file_unmodified() {
+        let bytes = {
+            let mut png = testing_png();
+            apply_stamp(&mut png, &Stamp::new(true, &pairs()));
+            png.as_bytes()
+        };
+        let mut png = Png::try_from(bytes.as_ref()).unwrap();
+
+        apply_stamp(&mut png, &Stamp::new(true, &pairs()));
+
+        assert!(!png.is_modified());
+    }
+
+    #[test]
+    fn test_truncated_stamp() {
+        let bytes = Stamp::new(true, &pairs()).as_bytes();
+
+        assert!(Stamp::try_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_parse_key_value() {
+        assert_eq!(parse_key_value("git=abc=def").unwrap(), ("git".to_string(), "abc=def".to_string()));
+        assert!(parse_key_value("novalue").is_err());
+        assert!(parse_key_value("=value").is_err());
+    }
+
+    #[test]
+    fn test_stamp_json() {
+        let stamp = Stamp::new(true, &pairs());
+
+        assert_eq!(stamp.to_json()["author"], "Zoë 🦀");
+    }
+}
@@ -0,0 +1,215 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::Ihdr;
+use crate::output;
+use crate::png::{ChunkRange, Png};
+use crate::summary::PngSummary;
+
+/// Files larger than this are listed from their chunk headers alone, seeking past the data,
+/// instead of being loaded: a multi-gigabyte png would otherwise need as much memory.
+pub(crate) const STREAMING_THRESHOLD: u64 = 512 << 20;
+
+/// A chunk as found in the file, without its data.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct IndexedChunk {
+    pub(crate) chunk_type: ChunkType,
+    pub(crate) length: u32,
+    /// The CRC stored in the file, not checked against the data.
+    pub(crate) crc: u32,
+    pub(crate) range: ChunkRange,
+}
+
+/// The chunk layout of a file, read without loading chunk data.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ChunkIndex {
+    /// The chunks up to and including IEND.
+    pub(crate) chunks: Vec<IndexedChunk>,
+    /// The image header, the only chunk whose data is read.
+    pub(crate) ihdr: Option<Ihdr>,
+    /// Size of the whole file, trailing data included.
+    pub(crate) total_size: u64,
+}
+
+impl ChunkIndex {
+    /// Like `Png::summary`, without validation, which needs the chunk data.
+    pub(crate) fn summary(&self) -> PngSummary {
+        let chunks = self.chunks.iter().map(|chunk| (&chunk.chunk_type, u64::from(chunk.length)));
+        PngSummary::from_chunks(chunks, self.total_size, Vec::new())
+    }
+}
+
+/// Reads the chunk headers and stored CRCs of a png, seeking past chunk data. CRCs aren't
+/// checked.
+pub(crate) fn index_chunks(mut reader: impl Read + Seek) -> crate::Result<ChunkIndex> {
+    let total_size = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut signature = [0; 8];
+    if total_size < 8 || reader.read_exact(&mut signature).is_err() || signature != Png::STANDARD_HEADER {
+        return Err("Data header should match the standard PNG header".into());
+    }
+
+    let mut chunks = Vec::new();
+    let mut ihdr = None;
+    let mut offset = Png::STANDARD_HEADER.len() as u64;
+    while offset < total_size {
+        if total_size - offset < Chunk::METADATA_LEN as u64 {
+            return Err(format!("Chunk at offset {offset} is truncated").into());
+        }
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let chunk_type = ChunkType::try_from([header[4], header[5], header[6], header[7]])?;
+
+        let end_offset = offset + Chunk::METADATA_LEN as u64 + u64::from(length);
+        if end_offset > total_size {
+            return Err(format!("{chunk_type} chunk at offset {offset} is truncated").into());
+        }
+        if chunks.is_empty() && chunk_type.bytes() == *b"IHDR" && length as usize == Ihdr::LEN {
+            let mut data = [0; Ihdr::LEN];
+            reader.read_exact(&mut data)?;
+            ihdr = Ihdr::try_from(data.as_slice()).ok();
+        }
+        reader.seek(SeekFrom::Start(end_offset - 4))?;
+        let mut crc = [0; 4];
+        reader.read_exact(&mut crc)?;
+        let crc = u32::from_be_bytes(crc);
+
+        let is_end = chunk_type.bytes() == *b"IEND";
+        let range = ChunkRange { offset, data_offset: offset + 8, end_offset };
+        chunks.push(IndexedChunk { chunk_type, length, crc, range });
+        offset = end_offset;
+        if is_end {
+            break;
+        }
+    }
+
+    Ok(ChunkIndex { chunks, ihdr, total_size })
+}
+
+/// The chunk index of `file` if it is a regular file over the streaming threshold. Stdin,
+/// `-`, is never indexed: it can't seek.
+pub(crate) fn index_if_large(file: &Path) -> crate::Result<Option<ChunkIndex>> {
+    if output::is_stdio(file) {
+        return Ok(None);
+    }
+    match file.metadata() {
+        Ok(metadata) if metadata.is_file() && metadata.len() > STREAMING_THRESHOLD => {
+            let reader = BufReader::new(File::open(file)?);
+            Ok(Some(index_chunks(reader)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Cursor};
+
+    /// A png of `idat_count` maximum-size IDAT chunks, generated on the fly: the chunk headers
+    /// are real, everything else reads as zeros, and nothing the size of the file is allocated.
+    struct SparsePng {
+        headers: Vec<(u64, Vec<u8>)>,
+        len: u64,
+        pos: u64,
+    }
+
+    impl SparsePng {
+        fn new(idat_count: usize) -> SparsePng {
+            let mut headers = vec![(0, Png::STANDARD_HEADER.to_vec())];
+            let mut offset = 8;
+            let mut push = |chunk_type: &[u8; 4], length: u32| {
+                headers.push((offset, [length.to_be_bytes().as_slice(), chunk_type].concat()));
+                offset += Chunk::METADATA_LEN as u64 + u64::from(length);
+            };
+            push(b"IHDR", 13);
+            for _ in 0..idat_count {
+                push(b"IDAT", Chunk::MAX_DATA_LEN as u32);
+            }
+            push(b"IEND", 0);
+            SparsePng { headers, len: offset, pos: 0 }
+        }
+    }
+
+    impl Read for SparsePng {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min((self.len - self.pos.min(self.len)) as usize).min(4096);
+            buf[..len].fill(0);
+            for (start, bytes) in &self.headers {
+                for (i, &byte) in bytes.iter().enumerate() {
+                    let at = start + i as u64;
+                    if (self.pos..self.pos + len as u64).contains(&at) {
+                        buf[(at - self.pos) as usize] = byte;
+                    }
+                }
+            }
+            self.pos += len as u64;
+            Ok(len)
+        }
+    }
+
+    impl Seek for SparsePng {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.pos = match pos {
+                SeekFrom::Start(pos) => pos,
+                SeekFrom::End(delta) => self.len.saturating_add_signed(delta),
+                SeekFrom::Current(delta) => self.pos.saturating_add_signed(delta),
+            };
+            Ok(self.pos)
+        }
+    }
+
+    #[test]
+    fn test_index_matches_chunk_ranges() {
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::try_from(*b"IHDR").unwrap(), vec![0, 0, 0, 4, 0, 0, 0, 2, 8, 6, 0, 0, 0]),
+            Chunk::new(ChunkType::try_from(*b"ruSt").unwrap(), b"hello".to_vec()),
+            Chunk::new(ChunkType::try_from(*b"IEND").unwrap(), vec![]),
+        ]);
+
+        let index = index_chunks(Cursor::new(png.as_bytes())).unwrap();
+
+        let ranges: Vec<ChunkRange> = index.chunks.iter().map(|chunk| chunk.range).collect();
+        assert_eq!(ranges, png.chunk_ranges());
+        let crcs: Vec<u32> = index.chunks.iter().map(|chunk| chunk.crc).collect();
+        assert_eq!(crcs, png.chunks().map(Chunk::crc).collect::<Vec<u32>>());
+        assert_eq!(index.total_size, png.serialized_len());
+        assert_eq!(index.ihdr.unwrap().to_string(), "4x2, 8-bit rgba");
+        assert_eq!(index.summary().private_chunks, 1);
+    }
+
+    #[test]
+    fn test_index_past_4_gib() {
+        let sparse = SparsePng::new(3);
+        let total_size = sparse.len;
+
+        let index = index_chunks(sparse).unwrap();
+
+        let idat_size = Chunk::METADATA_LEN as u64 + Chunk::MAX_DATA_LEN as u64;
+        assert_eq!(index.chunks.len(), 5);
+        assert_eq!(index.chunks[3].range.offset, 8 + 25 + 2 * idat_size);
+        assert!(index.chunks[3].range.end_offset > u64::from(u32::MAX));
+        assert_eq!(index.chunks[4].range.end_offset, total_size);
+        assert_eq!(total_size, 8 + 25 + 3 * idat_size + 12);
+
+        let summary = index.summary();
+        assert_eq!(summary.total_size, total_size);
+        assert_eq!(summary.types["IDAT"].bytes, 3 * Chunk::MAX_DATA_LEN as u64);
+    }
+
+    #[test]
+    fn test_index_truncated() {
+        let mut bytes = Png::from_chunks(vec![Chunk::new(ChunkType::try_from(*b"IDAT").unwrap(), vec![0; 100])]).as_bytes();
+        bytes.truncate(bytes.len() - 1);
+
+        let error = index_chunks(Cursor::new(bytes)).unwrap_err();
+
+        assert!(error.to_string().contains("IDAT chunk at offset 8 is truncated"), "{error}");
+        assert!(index_chunks(Cursor::new(b"not a png".to_vec())).is_err());
+    }
+}
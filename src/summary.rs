@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::validate::{self, ValidationIssue};
+
+/// Number of chunks of one type and the total size of their data.
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TypeStats {
+    pub count: usize,
+    pub bytes: u64,
+}
+
+/// Overview of a png, as printed by `pngme stats`.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PngSummary {
+    pub total_size: u64,
+    pub signature_valid: bool,
+    pub chunk_count: usize,
+    pub types: BTreeMap<String, TypeStats>,
+    pub has_iccp: bool,
+    pub has_exif: bool,
+    pub has_time: bool,
+    /// Any of tEXt, zTXt or iTXt.
+    pub has_text: bool,
+    /// Chunks whose second letter is lowercase.
+    pub private_chunks: usize,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl PngSummary {
+    /// Summarizes a file from the type and data length of each of its chunks.
+    pub(crate) fn from_chunks<'a>(
+        chunks: impl Iterator<Item = (&'a ChunkType, u64)>,
+        total_size: u64,
+        issues: Vec<ValidationIssue>,
+    ) -> PngSummary {
+        let mut types: BTreeMap<String, TypeStats> = BTreeMap::new();
+        let mut chunk_count = 0;
+        let mut private_chunks = 0;
+        for (chunk_type, len) in chunks {
+            let stats = types.entry(chunk_type.to_string()).or_default();
+            stats.count += 1;
+            stats.bytes += len;
+            chunk_count += 1;
+            if !chunk_type.is_public() {
+                private_chunks += 1;
+            }
+        }
+        let has = |chunk_type: &str| types.contains_key(chunk_type);
+
+        PngSummary {
+            total_size,
+            signature_valid: true,
+            chunk_count,
+            has_iccp: has("iCCP"),
+            has_exif: has("eXIf"),
+            has_time: has("tIME"),
+            has_text: has("tEXt") || has("zTXt") || has("iTXt"),
+            private_chunks,
+            issues,
+            types,
+        }
+    }
+}
+
+impl Png<'_> {
+    /// Chunk counts and sizes by type, with the issues `validate` finds.
+    pub fn summary(&self) -> PngSummary {
+        let chunks = self.chunks().map(|chunk| (chunk.chunk_type(), chunk.data().len() as u64));
+        PngSummary {
+            signature_valid: self.header() == &Png::STANDARD_HEADER,
+            ..PngSummary::from_chunks(chunks, self.serialized_len(), validate::validate(self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, len: usize) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; len])
+    }
+
+    #[test]
+    fn test_summary() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", 13),
+            chunk("tIME", 7),
+            chunk("tEXt", 10),
+            chunk("IDAT", 100),
+            chunk("IDAT", 50),
+            chunk("ruSt", 5),
+            chunk("tIME", 7),
+            chunk("IEND", 0),
+        ]);
+
+        let summary = png.summary();
+
+        // 8 byte signature, 8 chunks with 12 bytes of framing each, 192 bytes of data
+        assert_eq!(summary.total_size, 8 + 8 * 12 + 192);
+        assert!(summary.signature_valid);
+        assert_eq!(summary.chunk_count, 8);
+        assert_eq!(summary.types["IDAT"], TypeStats { count: 2, bytes: 150 });
+        assert_eq!(summary.types["tIME"], TypeStats { count: 2, bytes: 14 });
+        assert_eq!(summary.types.len(), 6);
+        assert!(!summary.has_iccp);
+        assert!(!summary.has_exif);
+        assert!(summary.has_time);
+        assert!(summary.has_text);
+        assert_eq!(summary.private_chunks, 1);
+        assert_eq!(summary.issues, vec![ValidationIssue::DuplicateChunk { chunk_type: "tIME".to_string(), indexes: vec![1, 6] }]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_summary_json() {
+        let png = Png::from_chunks(vec![chunk("IHDR", 13), chunk("iCCP", 3), chunk("IEND", 0)]);
+
+        let json = serde_json::to_value(png.summary()).unwrap();
+
+        assert_eq!(json["has_iccp"], true);
+        assert_eq!(json["types"]["iCCP"]["bytes"], 3);
+        assert_eq!(json["issues"], serde_json::json!([{ "kind": "no_image_data" }]));
+    }
+}
@@ -0,0 +1,406 @@
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::codec::{self, MAX_DECOMPRESSED_LEN};
+use crate::error::PngmeError;
+use crate::png::Png;
+
+/// The three standard text chunk types.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub(crate) enum TextKind {
+    /// Uncompressed Latin-1 text
+    #[value(name = "tEXt")]
+    Text,
+    /// Zlib-compressed Latin-1 text
+    #[value(name = "zTXt")]
+    Compressed,
+    /// UTF-8 text with an optional language tag
+    #[value(name = "iTXt")]
+    International,
+}
+
+impl TextKind {
+    pub(crate) fn chunk_type(self) -> &'static str {
+        match self {
+            TextKind::Text => "tEXt",
+            TextKind::Compressed => "zTXt",
+            TextKind::International => "iTXt",
+        }
+    }
+
+    fn of(chunk: &Chunk) -> Option<TextKind> {
+        let chunk_type = chunk.chunk_type().to_string();
+        [TextKind::Text, TextKind::Compressed, TextKind::International].into_iter().find(|kind| kind.chunk_type() == chunk_type)
+    }
+}
+
+impl fmt::Display for TextKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.chunk_type())
+    }
+}
+
+/// Encodes `text` as Latin-1, the only encoding tEXt and zTXt chunks allow.
+fn latin1_encode(text: &str) -> Result<Vec<u8>, String> {
+    text.chars()
+        .map(|character| u8::try_from(character).map_err(|_| format!("{character:?} can't be represented in Latin-1")))
+        .collect()
+}
+
+pub(crate) fn latin1_decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| char::from(byte)).collect()
+}
+
+/// Checks a keyword against the spec: 1 to 79 printable Latin-1 characters, with no leading,
+/// trailing or consecutive spaces. Returns it encoded.
+pub(crate) fn encode_keyword(keyword: &str) -> Result<Vec<u8>, String> {
+    let bytes = latin1_encode(keyword)?;
+    if !(1..=79).contains(&bytes.len()) {
+        return Err(format!("keyword is {} bytes long, it should be 1 to 79", bytes.len()));
+    }
+    if let Some(&byte) = bytes.iter().find(|&&byte| !matches!(byte, 32..=126 | 161..=255)) {
+        return Err(format!("keyword has the non-printable character {:?}", char::from(byte)));
+    }
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return Err("keyword can't have leading, trailing or consecutive spaces".to_string());
+    }
+    Ok(bytes)
+}
+
+fn split_at_nul(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = bytes.iter().position(|&byte| byte == 0)?;
+    Some((&bytes[..end], &bytes[end + 1..]))
+}
+
+/// Inflates a zlib stream, the only compression method (0) the spec defines for text chunks
+/// and iCCP.
+pub(crate) fn inflate(method: u8, compressed: &[u8]) -> Result<Vec<u8>, String> {
+    if method != 0 {
+        return Err(format!("unknown compression method {method}"));
+    }
+    codec::read_limited(flate2::read::ZlibDecoder::new(compressed), MAX_DECOMPRESSED_LEN).map_err(|error| error.to_string())
+}
+
+pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).expect("Writing to a Vec can't fail");
+    encoder.finish().expect("Writing to a Vec can't fail")
+}
+
+/// A keyword and its text, as stored in one of the text chunk types.
+#[derive(Debug, PartialEq)]
+pub(crate) struct TextEntry {
+    pub(crate) kind: TextKind,
+    pub(crate) keyword: String,
+    /// Language tag of an iTXt chunk, e.g. `fr-CA`; empty when unknown or for other kinds.
+    pub(crate) language: String,
+    /// Keyword translated into `language`, only stored by iTXt chunks.
+    pub(crate) translated_keyword: String,
+    pub(crate) text: String,
+}
+
+impl TextEntry {
+    pub(crate) fn new(kind: TextKind, keyword: &str, text: &str) -> TextEntry {
+        TextEntry { kind, keyword: keyword.to_string(), language: String::new(), translated_keyword: String::new(), text: text.to_string() }
+    }
+
+    /// Reads a tEXt, zTXt or iTXt chunk, inflating compressed text.
+    pub(crate) fn parse(chunk: &Chunk) -> Result<TextEntry, String> {
+        let kind = TextKind::of(chunk).ok_or_else(|| format!("{} isn't a text chunk type", chunk.chunk_type()))?;
+        let (keyword, rest) = chunk.keyword_and_rest()?;
+        let mut entry = TextEntry::new(kind, &latin1_decode(keyword), "");
+
+        match kind {
+            TextKind::Text => entry.text = latin1_decode(rest),
+            TextKind::Compressed => {
+                let (&method, compressed) = rest.split_first().ok_or("no compression method after the keyword")?;
+                entry.text = latin1_decode(&inflate(method, compressed)?);
+            }
+            TextKind::International => {
+                let [flag, method, rest @ ..] = rest else {
+                    return Err("no compression flag and method after the keyword".to_string());
+                };
+                let (language, rest) = split_at_nul(rest).ok_or("no NUL after the language tag")?;
+                let (translated_keyword, text) = split_at_nul(rest).ok_or("no NUL after the translated keyword")?;
+                let text = match flag {
+                    0 => text.to_vec(),
+                    1 => inflate(*method, text)?,
+                    _ => return Err(format!("unknown compression flag {flag}")),
+                };
+                let utf8 = |bytes: Vec<u8>, field: &str| String::from_utf8(bytes).map_err(|_| format!("{field} isn't valid UTF-8"));
+                entry.language = utf8(language.to_vec(), "language tag")?;
+                entry.translated_keyword = utf8(translated_keyword.to_vec(), "translated keyword")?;
+                entry.text = utf8(text, "text")?;
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Builds the chunk for the entry, refusing keywords the spec doesn't allow, NULs, and for
+    /// tEXt and zTXt, text that isn't Latin-1. iTXt text is stored uncompressed.
+    pub(crate) fn to_chunk(&self) -> Result<Chunk<'static>, PngmeError> {
+        self.build_chunk(false)
+    }
+
+    /// Like `to_chunk`, but iTXt text is deflated too, with the compression flag set.
+    pub(crate) fn to_compressed_chunk(&self) -> Result<Chunk<'static>, PngmeError> {
+        self.build_chunk(true)
+    }
+
+    fn build_chunk(&self, compress_international: bool) -> Result<Chunk<'static>, PngmeError> {
+        let invalid = |reason: String| PngmeError::InvalidChunkData { chunk_type: self.kind.to_string(), reason };
+        if [&self.text, &self.language, &self.translated_keyword].iter().any(|field| field.contains('\0')) {
+            return Err(invalid("text can't contain a NUL byte".to_string()));
+        }
+        if self.kind != TextKind::International && !(self.language.is_empty() && self.translated_keyword.is_empty()) {
+            return Err(invalid("only iTXt chunks have a language tag".to_string()));
+        }
+
+        let mut data = encode_keyword(&self.keyword).map_err(invalid)?;
+        data.push(0);
+        match self.kind {
+            TextKind::Text => data.extend(latin1_encode(&self.text).map_err(|reason| invalid(format!("{reason}, use iTXt instead")))?),
+            TextKind::Compressed => {
+                let text = latin1_encode(&self.text).map_err(|reason| invalid(format!("{reason}, use iTXt instead")))?;
+                data.push(0);
+                data.extend(deflate(&text));
+            }
+            TextKind::International => {
+                data.extend([u8::from(compress_international), 0]);
+                for field in [&self.language, &self.translated_keyword] {
+                    data.extend_from_slice(field.as_bytes());
+                    data.push(0);
+                }
+                if compress_international {
+                    data.extend(deflate(self.text.as_bytes()));
+                } else {
+                    data.extend_from_slice(self.text.as_bytes());
+                }
+            }
+        }
+        Chunk::try_new(ChunkType::from_str(self.kind.chunk_type()).expect("Text chunk types are valid"), data)
+    }
+}
+
+/// Indexes of the text chunks, of any kind, whose keyword is `keyword`. Keywords are
+/// case-sensitive. Only the keyword is read, so chunks with corrupt text are found too.
+fn find(png: &Png, keyword: &str) -> Vec<usize> {
+    png.chunks()
+        .enumerate()
+        .filter(|(_, chunk)| TextKind::of(chunk).is_some())
+        .filter(|(_, chunk)| chunk.keyword_and_rest().is_ok_and(|(found, _)| latin1_decode(found) == keyword))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn parse_at(png: &Png, index: usize) -> Result<TextEntry, PngmeError> {
+    let chunk = &png[index];
+    TextEntry::parse(chunk).map_err(|reason| PngmeError::InvalidChunkData { chunk_type: chunk.chunk_type().to_string(), reason })
+}
+
+/// The entries stored under `keyword`, one per text chunk in file order.
+pub(crate) fn get(png: &Png, keyword: &str) -> Result<Vec<TextEntry>, PngmeError> {
+    find(png, keyword).into_iter().map(|index| parse_at(png, index)).collect()
+}
+
+/// Stores `text` under `keyword`, in place of the first text chunk with that keyword, dropping
+/// any others, or in a new chunk before IEND.
+///
+/// Without a `kind`, the chunk keeps the kind of the one it replaces, or is a tEXt chunk.
+pub(crate) fn set(png: &mut Png, keyword: &str, text: &str, kind: Option<TextKind>, language: Option<&str>) -> Result<(), PngmeError> {
+    let existing = find(png, keyword);
+    let kind = kind
+        .or_else(|| existing.first().and_then(|&index| TextKind::of(&png[index])))
+        .unwrap_or(TextKind::Text);
+    let mut entry = TextEntry::new(kind, keyword, text);
+    entry.language = language.unwrap_or_default().to_string();
+    let chunk = entry.to_chunk()?;
+
+    match existing.split_first() {
+        Some((&first, duplicates)) => {
+            for &index in duplicates.iter().rev() {
+                png.remove_chunk_at_unchecked(index);
+            }
+            png.replace_chunk_unchecked(first, chunk);
+        }
+        None => png.insert_before_iend(chunk),
+    }
+    Ok(())
+}
+
+/// Rewrites every text chunk with `keyword` as a `to` chunk, keeping its text, and returns how
+/// many there were. The language tag and translated keyword are dropped when leaving iTXt.
+pub(crate) fn convert(png: &mut Png, keyword: &str, to: TextKind) -> Result<usize, PngmeError> {
+    let found = find(png, keyword);
+    for &index in &found {
+        let mut entry = parse_at(png, index)?;
+        if entry.kind == to {
+            continue;
+        }
+        entry.kind = to;
+        if to != TextKind::International {
+            entry.language.clear();
+            entry.translated_keyword.clear();
+        }
+        let chunk = entry.to_chunk()?;
+        png.replace_chunk_unchecked(index, chunk);
+    }
+    Ok(found.len())
+}
+
+/// Removes every text chunk with `keyword` and returns how many there were.
+pub(crate) fn remove(png: &mut Png, keyword: &str) -> usize {
+    let found = find(png, keyword);
+    png.remove_chunks_where(|_, index| found.contains(&index)).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png() -> Png<'static> {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("tEXt", b"Author\0Alice"),
+            chunk("IDAT", &[0; 8]),
+            chunk("tEXt", b"Comment\0caf\xe9"),
+            chunk("tEXt", b"Author\0Bob"),
+            chunk("IEND", b""),
+        ])
+    }
+
+    fn texts(png: &Png, keyword: &str) -> Vec<String> {
+        get(png, keyword).unwrap().into_iter().map(|entry| entry.text).collect()
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_keyword_rules() {
+        assert_eq!(encode_keyword("Title"), Ok(b"Title".to_vec()));
+        assert_eq!(encode_keyword("Création time"), Ok(b"Cr\xe9ation time".to_vec()));
+        assert!(encode_keyword("").is_err());
+        assert!(encode_keyword(&"x".repeat(80)).is_err());
+        assert!(encode_keyword(" Title").is_err());
+        assert!(encode_keyword("Title ").is_err());
+        assert!(encode_keyword("Creation  Time").is_err());
+        assert!(encode_keyword("Ti\ttle").is_err());
+        assert!(encode_keyword("Auteur€").is_err());
+    }
+
+    #[test]
+    fn test_get() {
+        let png = testing_png();
+
+        assert_eq!(texts(&png, "Author"), ["Alice", "Bob"]);
+        assert_eq!(texts(&png, "Comment"), ["café"]);
+        assert!(texts(&png, "author").is_empty());
+    }
+
+    #[test]
+    fn test_set_replaces_and_drops_duplicates() {
+        let mut png = testing_png();
+
+        set(&mut png, "Author", "Carol", None, None).unwrap();
+
+        assert_eq!(texts(&png, "Author"), ["Carol"]);
+        assert_eq!(png[1].data(), b"Author\0Carol");
+        assert_eq!(png.chunk_count(), 5);
+    }
+
+    #[test]
+    fn test_set_inserts_before_iend() {
+        let mut png = testing_png();
+
+        set(&mut png, "Title", "Sunset", None, None).unwrap();
+
+        assert_eq!(types(&png), ["IHDR", "tEXt", "IDAT", "tEXt", "tEXt", "tEXt", "IEND"]);
+        assert_eq!(png[5].data(), b"Title\0Sunset");
+    }
+
+    #[test]
+    fn test_set_refuses_what_text_chunks_cant_hold() {
+        let mut png = testing_png();
+
+        assert!(set(&mut png, "Price", "€5", None, None).unwrap_err().to_string().contains("use iTXt"));
+        assert!(set(&mut png, "Price", "€5", Some(TextKind::Compressed), None).is_err());
+        assert!(set(&mut png, "Price", "5\0", None, None).is_err());
+        assert!(set(&mut png, " Price", "5", None, None).is_err());
+        assert!(set(&mut png, "Price", "5", None, Some("fr")).is_err());
+        assert!(!png.is_modified());
+    }
+
+    #[test]
+    fn test_compressed_and_international_round_trip() {
+        let mut png = testing_png();
+        let long = "a".repeat(1000);
+
+        set(&mut png, "Comment", &long, Some(TextKind::Compressed), None).unwrap();
+        set(&mut png, "Title", "Coucher de soleil €", Some(TextKind::International), Some("fr")).unwrap();
+
+        assert!(png[3].data().len() < 100);
+        assert_eq!(texts(&png, "Comment"), [long]);
+        let title = get(&png, "Title").unwrap().remove(0);
+        assert_eq!(title.kind, TextKind::International);
+        assert_eq!(title.language, "fr");
+        assert_eq!(title.text, "Coucher de soleil €");
+        assert_eq!(png[5].data(), "Title\0\0\0fr\0\0Coucher de soleil €".as_bytes());
+
+        // Without a kind, the replaced chunk keeps its own.
+        set(&mut png, "Comment", "short", None, None).unwrap();
+        assert_eq!(png[3].chunk_type().to_string(), "zTXt");
+    }
+
+    #[test]
+    fn test_parse_compressed_itxt() {
+        let data = [b"Title\0\x01\0en\0Title\0".as_slice(), &deflate("Sunset ☀".as_bytes())].concat();
+
+        let entry = TextEntry::parse(&chunk("iTXt", &data)).unwrap();
+
+        assert_eq!(entry.text, "Sunset ☀");
+        assert_eq!(entry.translated_keyword, "Title");
+        assert!(TextEntry::parse(&chunk("iTXt", b"Title\0\x02\0\0\0text")).is_err());
+        assert!(TextEntry::parse(&chunk("zTXt", b"Title\0\x01x")).is_err());
+        assert!(TextEntry::parse(&chunk("zTXt", b"Title\0\0not zlib")).is_err());
+        assert!(TextEntry::parse(&chunk("iTXt", b"Title\0\0\0\xff\0\0text")).is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let mut png = testing_png();
+
+        assert_eq!(convert(&mut png, "Author", TextKind::Compressed).unwrap(), 2);
+        assert_eq!(types(&png), ["IHDR", "zTXt", "IDAT", "tEXt", "zTXt", "IEND"]);
+        assert_eq!(texts(&png, "Author"), ["Alice", "Bob"]);
+
+        assert_eq!(convert(&mut png, "Comment", TextKind::International).unwrap(), 1);
+        assert_eq!(png[3].data(), "Comment\0\0\0\0\0café".as_bytes());
+
+        set(&mut png, "Comment", "€", None, None).unwrap();
+        assert!(convert(&mut png, "Comment", TextKind::Text).is_err());
+        assert_eq!(convert(&mut png, "Title", TextKind::Text).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut png = testing_png();
+        set(&mut png, "Author", "Carol", Some(TextKind::International), None).unwrap();
+        png.insert_before_iend(chunk("zTXt", b"Author\0\0corrupt"));
+
+        assert_eq!(remove(&mut png, "Author"), 2);
+        assert_eq!(remove(&mut png, "Author"), 0);
+        assert_eq!(texts(&png, "Comment"), ["café"]);
+        assert_eq!(png.chunk_count(), 4);
+    }
+}
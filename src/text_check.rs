@@ -0,0 +1,84 @@
+use std::fmt;
+
+/// Chunk types whose text the spec restricts to Latin-1.
+const LATIN1_CHUNK_TYPES: [&str; 2] = ["tEXt", "zTXt"];
+
+/// Something in encoded text that downstream text consumers may mangle.
+#[derive(Debug, PartialEq)]
+pub(crate) enum TextIssue {
+    /// An embedded NUL, where C-style readers stop.
+    Nul { offset: usize },
+    /// A control character other than newline and tab.
+    ControlChar { offset: usize, character: char },
+    /// A character a Latin-1 text chunk can't represent.
+    NotLatin1 { offset: usize, character: char },
+}
+
+impl fmt::Display for TextIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextIssue::Nul { offset } => write!(f, "NUL byte at offset {offset}, text readers may stop there"),
+            TextIssue::ControlChar { offset, character } => {
+                write!(f, "control character {:?} at offset {offset}", character)
+            }
+            TextIssue::NotLatin1 { offset, character } => {
+                write!(f, "{character:?} at offset {offset} can't be represented in a Latin-1 text chunk")
+            }
+        }
+    }
+}
+
+/// Finds NULs, control characters other than `\n` and `\t`, and, for tEXt and zTXt chunks,
+/// characters outside Latin-1. Offsets are in bytes.
+pub(crate) fn check_text(content: &str, chunk_type: &str) -> Vec<TextIssue> {
+    let latin1 = LATIN1_CHUNK_TYPES.contains(&chunk_type);
+
+    content
+        .char_indices()
+        .filter_map(|(offset, character)| match character {
+            '\0' => Some(TextIssue::Nul { offset }),
+            '\n' | '\t' => None,
+            c if c.is_control() => Some(TextIssue::ControlChar { offset, character }),
+            c if latin1 && c as u32 > 0xff => Some(TextIssue::NotLatin1 { offset, character }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_text() {
+        assert!(check_text("hello\n\tworld", "tEXt").is_empty());
+        assert!(check_text("café", "tEXt").is_empty());
+    }
+
+    #[test]
+    fn test_nul() {
+        assert_eq!(check_text("ab\0c", "ruSt"), vec![TextIssue::Nul { offset: 2 }]);
+    }
+
+    #[test]
+    fn test_control_characters() {
+        assert_eq!(
+            check_text("a\rb\u{1b}", "ruSt"),
+            vec![TextIssue::ControlChar { offset: 1, character: '\r' }, TextIssue::ControlChar { offset: 3, character: '\u{1b}' }]
+        );
+    }
+
+    #[test]
+    fn test_not_latin1() {
+        assert_eq!(check_text("€5", "tEXt"), vec![TextIssue::NotLatin1 { offset: 0, character: '€' }]);
+        assert_eq!(check_text("a€", "zTXt"), vec![TextIssue::NotLatin1 { offset: 1, character: '€' }]);
+        // iTXt and private chunks hold UTF-8.
+        assert!(check_text("€5", "iTXt").is_empty());
+        assert!(check_text("€5", "ruSt").is_empty());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(TextIssue::Nul { offset: 2 }.to_string(), "NUL byte at offset 2, text readers may stop there");
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, NaiveDate, SecondsFormat, Timelike, Utc};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+pub(crate) const TIME_CHUNK_TYPE: &str = "tIME";
+
+/// The time of the last image modification, from a tIME chunk: always UTC, to the second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct LastModified(DateTime<Utc>);
+
+impl LastModified {
+    pub(crate) const LEN: usize = 7;
+
+    /// `time`, without its fractional seconds, which tIME can't hold.
+    pub(crate) fn new(time: DateTime<Utc>) -> LastModified {
+        // A leap second is stored as 1_000_000_000 or more nanoseconds.
+        let leap = if time.nanosecond() >= 1_000_000_000 { 1_000_000_000 } else { 0 };
+        LastModified(time.with_nanosecond(leap).expect("Whole seconds are always valid"))
+    }
+
+    /// Reads the tIME chunk of `png`, if it has one.
+    pub(crate) fn read(png: &Png) -> Option<Result<LastModified, String>> {
+        png.chunk_by_type(TIME_CHUNK_TYPE).map(|chunk| LastModified::try_from(chunk.data()))
+    }
+
+    pub(crate) fn as_bytes(&self) -> [u8; Self::LEN] {
+        let time = self.0;
+        let year: u16 = time.year().try_into().expect("Years are checked when the time is built");
+        let second = if time.nanosecond() >= 1_000_000_000 { 60 } else { time.second() as u8 };
+        let [high, low] = year.to_be_bytes();
+        [high, low, time.month() as u8, time.day() as u8, time.hour() as u8, time.minute() as u8, second]
+    }
+
+    /// Writes the time into `png`, replacing its tIME chunk, or adding one before IEND.
+    pub(crate) fn write(&self, png: &mut Png) {
+        let chunk = Chunk::new(ChunkType::from_str(TIME_CHUNK_TYPE).expect("tIME is a valid chunk type"), self.as_bytes().to_vec());
+        if png.chunk_by_type(TIME_CHUNK_TYPE).is_some() {
+            png.replace_or_insert_chunk(chunk);
+        } else {
+            png.insert_before_iend(chunk);
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for LastModified {
+    type Error = String;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let [high, low, month, day, hour, minute, second]: [u8; Self::LEN] =
+            data.try_into().map_err(|_| format!("tIME data is {} bytes long, it should be {}", data.len(), Self::LEN))?;
+        let year = u16::from_be_bytes([high, low]);
+
+        let date = NaiveDate::from_ymd_opt(i32::from(year), u32::from(month), u32::from(day))
+            .ok_or_else(|| format!("{year:04}-{month:02}-{day:02} is not a valid date"))?;
+        // The spec allows 60 for leap seconds, which chrono writes as 59 plus a whole second.
+        let (second, nano) = if second == 60 { (59, 1_000_000_000) } else { (u32::from(second), 0) };
+        let time = date
+            .and_hms_nano_opt(u32::from(hour), u32::from(minute), second, nano)
+            .ok_or_else(|| format!("{hour:02}:{minute:02}:{:02} is not a valid time", data[6]))?;
+        Ok(LastModified(time.and_utc()))
+    }
+}
+
+impl fmt::Display for LastModified {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+}
+
+/// Parses an RFC 3339 timestamp argument, converting it to UTC.
+pub(crate) fn parse_timestamp(s: &str) -> Result<LastModified, String> {
+    let time = DateTime::parse_from_rfc3339(s).map_err(|error| format!("expected an RFC 3339 timestamp such as 2024-05-01T12:00:00Z: {error}"))?;
+    let time = time.with_timezone(&Utc);
+    if u16::try_from(time.year()).is_err() {
+        return Err(format!("year {} can't be stored in a tIME chunk", time.year()));
+    }
+    Ok(LastModified::new(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let time = parse_timestamp("2024-05-01T12:34:56Z").unwrap();
+
+        assert_eq!(time.as_bytes(), [0x07, 0xe8, 5, 1, 12, 34, 56]);
+        assert_eq!(LastModified::try_from(time.as_bytes().as_slice()), Ok(time));
+        assert_eq!(time.to_string(), "2024-05-01T12:34:56Z");
+    }
+
+    #[test]
+    fn test_parse_timestamp_converts_to_utc() {
+        assert_eq!(parse_timestamp("2024-05-01T23:30:00.75-02:00").unwrap().to_string(), "2024-05-02T01:30:00Z");
+        assert!(parse_timestamp("2024-05-01 12:00").is_err());
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_leap_second() {
+        let time = LastModified::try_from([0x07, 0xd8, 12, 31, 23, 59, 60].as_slice()).unwrap();
+
+        assert_eq!(time.to_string(), "2008-12-31T23:59:60Z");
+        assert_eq!(time.as_bytes()[6], 60);
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        let error = |data: &[u8]| LastModified::try_from(data).unwrap_err();
+
+        assert_eq!(error(&[0x07, 0xe8, 2, 30, 0, 0, 0]), "2024-02-30 is not a valid date");
+        assert_eq!(error(&[0x07, 0xe8, 13, 1, 0, 0, 0]), "2024-13-01 is not a valid date");
+        assert_eq!(error(&[0x07, 0xe8, 1, 1, 24, 0, 0]), "24:00:00 is not a valid time");
+        assert_eq!(error(&[0x07, 0xe8, 1, 1, 0, 0, 61]), "00:00:61 is not a valid time");
+        assert!(error(&[0x07, 0xe8, 1, 1, 0, 0]).contains("6 bytes long"));
+    }
+
+    #[test]
+    fn test_write_replaces_or_inserts_before_iend() {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![]);
+        let mut png = Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")]);
+        let first = parse_timestamp("2024-05-01T12:00:00Z").unwrap();
+        let second = parse_timestamp("2025-01-01T00:00:00Z").unwrap();
+
+        first.write(&mut png);
+        second.write(&mut png);
+
+        let types: Vec<String> = png.chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "tIME", "IEND"]);
+        assert_eq!(LastModified::read(&png), Some(Ok(second)));
+    }
+}
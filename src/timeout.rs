@@ -0,0 +1,89 @@
+use std::fmt;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Exit code used when an operation exceeds `--timeout`.
+pub(crate) const TIMEOUT_EXIT_CODE: i32 = 5;
+
+/// An operation didn't finish within `--timeout`.
+#[derive(Debug, PartialEq)]
+pub struct TimedOut {
+    pub(crate) timeout: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Operation timed out after {}s", self.timeout.as_secs_f64())
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Runs `operation` on a worker thread and gives up waiting after `timeout`.
+///
+/// Without a timeout the operation runs on the current thread. A timed out worker is left
+/// blocked, which is fine for a command line tool that exits right after reporting the error.
+pub(crate) fn with_timeout<T, F>(timeout: Option<Duration>, operation: F) -> Result<T, TimedOut>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let Some(timeout) = timeout else {
+        return Ok(operation());
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(operation());
+    });
+
+    receiver.recv_timeout(timeout).map_err(|_| TimedOut { timeout })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_without_timeout() {
+        assert_eq!(with_timeout(None, || 42), Ok(42));
+    }
+
+    #[test]
+    fn test_finishes_in_time() {
+        assert_eq!(with_timeout(Some(Duration::from_secs(5)), || 42), Ok(42));
+    }
+
+    #[test]
+    fn test_times_out() {
+        let timeout = Duration::from_millis(100);
+        let start = Instant::now();
+
+        let result = with_timeout(Some(timeout), || thread::sleep(Duration::from_secs(5)));
+
+        assert_eq!(result, Err(TimedOut { timeout }));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(TimedOut { timeout: Duration::from_secs(3) }.to_string(), "Operation timed out after 3s");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fifo_read_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let fifo = dir.path().join("fifo");
+        assert!(std::process::Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+        let timeout = Duration::from_millis(200);
+        let start = Instant::now();
+
+        let path = fifo.clone();
+        let result = with_timeout(Some(timeout), move || std::fs::read(path).map(|bytes| bytes.len()));
+
+        assert_eq!(result.unwrap_err(), TimedOut { timeout });
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // Opening the writing end lets the blocked reader see end of file and finish.
+        drop(std::fs::OpenOptions::new().write(true).open(&fifo).unwrap());
+    }
+}
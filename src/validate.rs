@@ -0,0 +1,219 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::ancillary;
+use crate::png::Png;
+
+/// Chunk types the PNG spec allows at most once per file.
+pub(crate) const UNIQUE_CHUNK_TYPES: [&str; 14] = [
+    "IHDR", "PLTE", "gAMA", "cHRM", "sRGB", "iCCP", "sBIT",
+    "bKGD", "hIST", "tRNS", "pHYs", "tIME", "acTL", "IEND",
+];
+
+/// A problem `validate` found in a png.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// A chunk type that must be unique appears several times, at the given chunk indexes.
+    DuplicateChunk { chunk_type: String, indexes: Vec<usize> },
+    /// A chunk type whose third letter is lowercase, which the spec reserves.
+    InvalidReservedBit { chunk_type: String, index: usize },
+    /// The file is in Apple's proprietary CgBI format, which standard decoders can't display.
+    AppleCgbi,
+    /// A chunk whose data doesn't fit the layout required by the image color type.
+    InvalidLayout { chunk_type: String, index: usize, reason: String },
+    /// The file has no IDAT chunk, so there is no image for a viewer to show.
+    NoImageData,
+}
+
+impl ValidationIssue {
+    /// Warnings are reported but don't make the file invalid.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ValidationIssue::InvalidReservedBit { .. } | ValidationIssue::AppleCgbi)
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::DuplicateChunk { chunk_type, indexes } => {
+                let indexes: Vec<String> = indexes.iter().map(|index| index.to_string()).collect();
+                write!(f, "{chunk_type} must be unique but appears at chunks {}", indexes.join(", "))
+            }
+            ValidationIssue::InvalidReservedBit { chunk_type, index } => {
+                write!(f, "{chunk_type} at chunk {index} has an invalid reserved bit (third letter should be uppercase)")
+            }
+            ValidationIssue::AppleCgbi => {
+                write!(f, "Apple CgBI png (iOS-optimized, proprietary): pixel data is byteswapped and not standard zlib")
+            }
+            ValidationIssue::InvalidLayout { chunk_type, index, reason } => {
+                write!(f, "{chunk_type} at chunk {index} is malformed: {reason}")
+            }
+            ValidationIssue::NoImageData => write!(f, "no IDAT chunk, the file has no image data"),
+        }
+    }
+}
+
+fn duplicate_indexes(png: &Png, chunk_type: &str) -> Vec<usize> {
+    png.chunks()
+        .enumerate()
+        .filter(|(_, chunk)| chunk.chunk_type().to_string() == chunk_type)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+pub(crate) fn validate(png: &Png) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    if png.is_cgbi() {
+        issues.push(ValidationIssue::AppleCgbi);
+    }
+
+    if !png.has_image_data() {
+        issues.push(ValidationIssue::NoImageData);
+    }
+
+    for chunk_type in UNIQUE_CHUNK_TYPES {
+        let indexes = duplicate_indexes(png, chunk_type);
+        if indexes.len() > 1 {
+            issues.push(ValidationIssue::DuplicateChunk { chunk_type: chunk_type.to_string(), indexes });
+        }
+    }
+
+    for (index, chunk) in png.chunks().enumerate() {
+        if chunk.chunk_type().has_invalid_reserved_bit() {
+            issues.push(ValidationIssue::InvalidReservedBit { chunk_type: chunk.chunk_type().to_string(), index });
+        }
+        if let Some(Err(reason)) = ancillary::describe(png, chunk) {
+            issues.push(ValidationIssue::InvalidLayout { chunk_type: chunk.chunk_type().to_string(), index, reason });
+        }
+    }
+
+    issues
+}
+
+/// Keeps the first occurrence of every unique chunk type and drops the others.
+///
+/// Returns how many chunks were removed. IDAT and fdAT are never in the unique set, so
+/// they are left alone.
+pub(crate) fn fix_duplicates(png: &mut Png) -> usize {
+    let mut to_remove: Vec<usize> = validate(png)
+        .into_iter()
+        .filter_map(|issue| match issue {
+            ValidationIssue::DuplicateChunk { indexes, .. } => Some(indexes.into_iter().skip(1)),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+
+    // Remove from the back so the remaining indexes stay valid.
+    to_remove.sort_unstable();
+    for &index in to_remove.iter().rev() {
+        png.remove_chunk_at_unchecked(index);
+    }
+
+    to_remove.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn testing_png() -> Png<'static> {
+        Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header"),
+            chunk_from_strings("gAMA", "first"),
+            chunk_from_strings("IDAT", "pixels 1"),
+            chunk_from_strings("gAMA", "second"),
+            chunk_from_strings("IDAT", "pixels 2"),
+            chunk_from_strings("IEND", ""),
+        ])
+    }
+
+    #[test]
+    fn test_detect_duplicates() {
+        let issues = validate(&testing_png());
+
+        assert_eq!(issues, vec![ValidationIssue::DuplicateChunk { chunk_type: "gAMA".to_string(), indexes: vec![1, 3] }]);
+        assert_eq!(issues[0].to_string(), "gAMA must be unique but appears at chunks 1, 3");
+    }
+
+    #[test]
+    fn test_fix_duplicates() {
+        let mut png = testing_png();
+
+        assert_eq!(fix_duplicates(&mut png), 1);
+        assert!(validate(&png).is_empty());
+        assert_eq!(png.chunk_count(), 5);
+        assert_eq!(png.chunk_by_type("gAMA").unwrap().data(), b"first");
+        assert_eq!(png.chunks_by_type("IDAT").count(), 2);
+    }
+
+    #[test]
+    fn test_repeated_idat_is_not_flagged() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header"),
+            chunk_from_strings("IDAT", "pixels 1"),
+            chunk_from_strings("IDAT", "pixels 2"),
+            chunk_from_strings("IDAT", "pixels 3"),
+            chunk_from_strings("IEND", ""),
+        ]);
+
+        assert!(validate(&png).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_reserved_bit_is_warning() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header"),
+            chunk_from_strings("rust", "reserved bit"),
+            chunk_from_strings("IDAT", "pixels"),
+            chunk_from_strings("IEND", ""),
+        ]);
+
+        let issues = validate(&png);
+
+        assert_eq!(issues, vec![ValidationIssue::InvalidReservedBit { chunk_type: "rust".to_string(), index: 1 }]);
+        assert!(issues[0].is_warning());
+    }
+
+    #[test]
+    fn test_detect_invalid_layout() {
+        // 1x1 rgb image
+        let ihdr = vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0];
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr),
+            Chunk::new(ChunkType::from_str("bKGD").unwrap(), vec![0, 255]),
+            Chunk::new(ChunkType::from_str("sBIT").unwrap(), vec![8, 8, 8]),
+            chunk_from_strings("IDAT", "pixels"),
+            chunk_from_strings("IEND", ""),
+        ]);
+
+        let issues = validate(&png);
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].to_string(), "bKGD at chunk 1 is malformed: bKGD should be 6 bytes long for rgb images, not 2");
+        assert!(!issues[0].is_warning());
+    }
+
+    #[test]
+    fn test_missing_image_data_is_error() {
+        let png = Png::from_chunks(vec![chunk_from_strings("IHDR", "header"), chunk_from_strings("IEND", "")]);
+
+        let issues = validate(&png);
+
+        assert_eq!(issues, vec![ValidationIssue::NoImageData]);
+        assert!(!issues[0].is_warning());
+    }
+}
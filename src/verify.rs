@@ -0,0 +1,358 @@
+use std::fmt;
+use std::io::{self, Read};
+
+use flate2::{Decompress, FlushDecompress, Status};
+
+use crate::chunk::CRC32;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::Ihdr;
+use crate::png::Png;
+use crate::Result;
+
+/// A structural problem found by `pngme verify`.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Problem {
+    BadSignature,
+    CrcMismatch { chunk_type: String, offset: u64, stored: u32, computed: u32 },
+    InvalidChunkType { offset: u64, reason: String },
+    /// The file ends inside the chunk at `offset`, whose type is unknown if the header is cut.
+    Truncated { chunk_type: Option<String>, offset: u64 },
+    /// The first chunk, at `offset`, is `chunk_type` instead of IHDR.
+    IhdrNotFirst { chunk_type: String, offset: u64 },
+    MissingIhdr,
+    MissingIend,
+    DataAfterIend { offset: u64, size: u64 },
+    NoImageData,
+    /// The IDAT stream stops inflating in the IDAT chunk at `offset`.
+    ImageDataCorrupt { offset: u64, reason: String },
+    /// The IDAT stream ends before its zlib stream does.
+    ImageDataIncomplete { inflated: u64, expected: u64 },
+    /// The IDAT stream inflates to a size other than the IHDR calls for; `inflated` is `None`
+    /// when it is too long, as inflating stops there.
+    ImageDataSize { inflated: Option<u64>, expected: u64 },
+    /// `--image-data` was asked for but there is no IHDR to size the image from.
+    ImageDataUnchecked { reason: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::BadSignature => write!(f, "offset 0x0: signature doesn't match the standard PNG header"),
+            Problem::CrcMismatch { chunk_type, offset, stored, computed } => {
+                write!(f, "offset {offset:#x}: {chunk_type} CRC mismatch, stored {stored:08x}, computed {computed:08x}")
+            }
+            Problem::InvalidChunkType { offset, reason } => write!(f, "offset {offset:#x}: invalid chunk type: {reason}"),
+            Problem::Truncated { chunk_type: Some(chunk_type), offset } => write!(f, "offset {offset:#x}: {chunk_type} chunk is truncated"),
+            Problem::Truncated { chunk_type: None, offset } => write!(f, "offset {offset:#x}: chunk header is truncated"),
+            Problem::IhdrNotFirst { chunk_type, offset } => write!(f, "offset {offset:#x}: first chunk is {chunk_type}, it should be IHDR"),
+            Problem::MissingIhdr => write!(f, "no IHDR chunk"),
+            Problem::MissingIend => write!(f, "no IEND chunk"),
+            Problem::DataAfterIend { offset, size } => write!(f, "offset {offset:#x}: {size} bytes after IEND, which should end the file"),
+            Problem::NoImageData => write!(f, "no IDAT chunk, the file has no image data"),
+            Problem::ImageDataCorrupt { offset, reason } => write!(f, "offset {offset:#x}: image data doesn't inflate: {reason}"),
+            Problem::ImageDataIncomplete { inflated, expected } => {
+                write!(f, "image data stops after {inflated} of {expected} bytes, its zlib stream is cut short")
+            }
+            Problem::ImageDataSize { inflated: Some(inflated), expected } => {
+                write!(f, "image data inflates to {inflated} bytes, the IHDR calls for {expected}")
+            }
+            Problem::ImageDataSize { inflated: None, expected } => {
+                write!(f, "image data inflates to more than the {expected} bytes the IHDR calls for")
+            }
+            Problem::ImageDataUnchecked { reason } => write!(f, "image data not checked: {reason}"),
+        }
+    }
+}
+
+/// Reads until `buf` is full or the input ends, returning how much was read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(filled)
+}
+
+/// Inflates the IDAT stream as it goes by, only counting the output.
+struct ImageDataCheck {
+    inflater: Decompress,
+    scratch: Vec<u8>,
+    expected: u64,
+    finished: bool,
+    corrupt: Option<Problem>,
+}
+
+impl ImageDataCheck {
+    fn new(ihdr: &Ihdr) -> ImageDataCheck {
+        ImageDataCheck {
+            inflater: Decompress::new(true),
+            scratch: vec![0; 32 * 1024],
+            expected: ihdr.image_data_len(),
+            finished: false,
+            corrupt: None,
+        }
+    }
+
+    /// Feeds data of the IDAT chunk at `offset`. Inflating stops past the expected size, so a
+    /// zlib bomb costs no more than a valid image.
+    fn feed(&mut self, offset: u64, mut data: &[u8]) {
+        while !data.is_empty() && !self.finished && self.corrupt.is_none() && self.inflater.total_out() <= self.expected {
+            let (total_in, total_out) = (self.inflater.total_in(), self.inflater.total_out());
+            match self.inflater.decompress(data, &mut self.scratch, FlushDecompress::None) {
+                Ok(Status::StreamEnd) => self.finished = true,
+                Ok(_) => {}
+                Err(error) => self.corrupt = Some(Problem::ImageDataCorrupt { offset, reason: error.to_string() }),
+            }
+            data = &data[(self.inflater.total_in() - total_in) as usize..];
+            if (self.inflater.total_in(), self.inflater.total_out()) == (total_in, total_out) {
+                break;
+            }
+        }
+    }
+
+    fn finish(self) -> Option<Problem> {
+        let (inflated, expected) = (self.inflater.total_out(), self.expected);
+        if self.corrupt.is_some() {
+            self.corrupt
+        } else if inflated > expected {
+            Some(Problem::ImageDataSize { inflated: None, expected })
+        } else if !self.finished {
+            Some(Problem::ImageDataIncomplete { inflated, expected })
+        } else if inflated != expected {
+            Some(Problem::ImageDataSize { inflated: Some(inflated), expected })
+        } else {
+            None
+        }
+    }
+}
+
+/// Checks the signature, every chunk's CRC and type, and that IHDR comes first, IEND last and
+/// at least one IDAT in between. Unlike `Png::try_from`, every problem is reported, not just
+/// the first; only a truncated chunk stops the walk, since nothing after it can be located.
+///
+/// With `image_data`, the IDAT stream is also inflated and its size checked against the IHDR:
+/// chunks dropped or swapped as a whole keep valid CRCs but break this.
+///
+/// Chunk data is streamed, so files of any size take constant memory.
+pub(crate) fn verify(mut reader: impl Read, image_data: bool) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    let mut signature = [0; 8];
+    let read = read_full(&mut reader, &mut signature)?;
+    if read < signature.len() || signature != Png::STANDARD_HEADER {
+        problems.push(Problem::BadSignature);
+        if read < signature.len() {
+            return Ok(problems);
+        }
+    }
+
+    let mut chunks: Vec<(String, u64)> = Vec::new();
+    let mut offset = signature.len() as u64;
+    let mut header = [0; 8];
+    let mut buf = [0; 8192];
+    let mut ihdr: std::result::Result<Ihdr, String> = Err("no IHDR before the image data".to_string());
+    let mut image_check: Option<std::result::Result<ImageDataCheck, String>> = None;
+    loop {
+        match read_full(&mut reader, &mut header)? {
+            0 => break,
+            8 => {}
+            _ => {
+                problems.push(Problem::Truncated { chunk_type: None, offset });
+                break;
+            }
+        }
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let type_bytes = [header[4], header[5], header[6], header[7]];
+        let chunk_type = String::from_utf8_lossy(&type_bytes).into_owned();
+        if let Err(error) = ChunkType::try_from(type_bytes) {
+            problems.push(Problem::InvalidChunkType { offset, reason: error.to_string() });
+        }
+
+        if image_data && type_bytes == *b"IDAT" && image_check.is_none() {
+            image_check = Some(ihdr.clone().map(|ihdr| ImageDataCheck::new(&ihdr)));
+        }
+        let mut ihdr_data = Vec::new();
+        let mut digest = CRC32.digest();
+        digest.update(&type_bytes);
+        let mut remaining = length as usize;
+        let mut truncated = false;
+        while remaining > 0 && !truncated {
+            let len = remaining.min(buf.len());
+            let read = read_full(&mut reader, &mut buf[..len])?;
+            digest.update(&buf[..read]);
+            match &mut image_check {
+                Some(Ok(check)) if type_bytes == *b"IDAT" => check.feed(offset, &buf[..read]),
+                _ if type_bytes == *b"IHDR" && ihdr_data.len() <= Ihdr::LEN => ihdr_data.extend_from_slice(&buf[..read]),
+                _ => {}
+            }
+            remaining -= read;
+            truncated = read < len;
+        }
+        let mut stored = [0; 4];
+        if truncated || read_full(&mut reader, &mut stored)? < stored.len() {
+            problems.push(Problem::Truncated { chunk_type: Some(chunk_type.clone()), offset });
+            chunks.push((chunk_type, offset));
+            break;
+        }
+
+        let (stored, computed) = (u32::from_be_bytes(stored), digest.finalize());
+        if stored != computed {
+            problems.push(Problem::CrcMismatch { chunk_type: chunk_type.clone(), offset, stored, computed });
+        }
+        if type_bytes == *b"IHDR" && ihdr.is_err() {
+            ihdr = Ihdr::try_from(ihdr_data.as_slice());
+        }
+        chunks.push((chunk_type, offset));
+        offset += 12 + u64::from(length);
+
+        if type_bytes == *b"IEND" {
+            let size = io::copy(&mut reader, &mut io::sink())?;
+            if size > 0 {
+                problems.push(Problem::DataAfterIend { offset, size });
+            }
+            break;
+        }
+    }
+
+    let has = |chunk_type: &str| chunks.iter().any(|(found, _)| found == chunk_type);
+    match chunks.first() {
+        _ if !has("IHDR") => problems.push(Problem::MissingIhdr),
+        Some((chunk_type, offset)) if chunk_type != "IHDR" => {
+            problems.push(Problem::IhdrNotFirst { chunk_type: chunk_type.clone(), offset: *offset });
+        }
+        _ => {}
+    }
+    if !has("IDAT") {
+        problems.push(Problem::NoImageData);
+    }
+    if !has("IEND") {
+        problems.push(Problem::MissingIend);
+    }
+    match image_check {
+        Some(Ok(check)) => problems.extend(check.finish()),
+        Some(Err(reason)) => problems.push(Problem::ImageDataUnchecked { reason }),
+        None => {}
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str) -> Chunk<'static> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), chunk_type.as_bytes().to_vec())
+    }
+
+    fn testing_bytes(types: &[&str]) -> Vec<u8> {
+        Png::from_chunks(types.iter().map(|chunk_type| chunk(chunk_type)).collect()).as_bytes()
+    }
+
+    #[test]
+    fn test_valid_file() {
+        let bytes = testing_bytes(&["IHDR", "IDAT", "IEND"]);
+
+        assert_eq!(verify(bytes.as_slice(), false).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_reports_every_crc_mismatch() {
+        let mut bytes = testing_bytes(&["IHDR", "ruSt", "IDAT", "IEND"]);
+        // Each chunk is 16 bytes: the data of ruSt starts at 8 + 16 + 8, that of IDAT 16 later.
+        bytes[32] ^= 0xff;
+        bytes[48] ^= 0xff;
+
+        let problems = verify(bytes.as_slice(), false).unwrap();
+
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(&problems[0], Problem::CrcMismatch { chunk_type, offset: 24, .. } if chunk_type == "ruSt"));
+        assert!(matches!(&problems[1], Problem::CrcMismatch { chunk_type, offset: 40, .. } if chunk_type == "IDAT"));
+        assert!(problems[0].to_string().starts_with("offset 0x18: ruSt CRC mismatch"));
+    }
+
+    #[test]
+    fn test_structure() {
+        let problems = verify(testing_bytes(&["ruSt", "IHDR"]).as_slice(), false).unwrap();
+        assert_eq!(problems, vec![
+            Problem::IhdrNotFirst { chunk_type: "ruSt".to_string(), offset: 8 },
+            Problem::NoImageData,
+            Problem::MissingIend,
+        ]);
+
+        let problems = verify(testing_bytes(&["ruSt"]).as_slice(), false).unwrap();
+        assert_eq!(problems[0], Problem::MissingIhdr);
+    }
+
+    #[test]
+    fn test_truncated_and_trailing_data() {
+        let mut bytes = testing_bytes(&["IHDR", "IDAT", "IEND"]);
+        bytes.truncate(bytes.len() - 20);
+        let problems = verify(bytes.as_slice(), false).unwrap();
+        assert_eq!(problems, vec![Problem::Truncated { chunk_type: Some("IDAT".to_string()), offset: 24 }, Problem::MissingIend]);
+
+        let mut bytes = testing_bytes(&["IHDR", "IDAT", "IEND"]);
+        bytes.extend_from_slice(b"PK\x03\x04");
+        let problems = verify(bytes.as_slice(), false).unwrap();
+        assert_eq!(problems, vec![Problem::DataAfterIend { offset: 56, size: 4 }]);
+    }
+
+    #[test]
+    fn test_bad_signature_and_chunk_type() {
+        let mut bytes = testing_bytes(&["IHDR", "IDAT", "IEND"]);
+        bytes[0] = 0;
+        bytes[28] = b'1';
+
+        let problems = verify(bytes.as_slice(), false).unwrap();
+
+        assert_eq!(problems[0], Problem::BadSignature);
+        assert!(matches!(problems[1], Problem::InvalidChunkType { offset: 24, .. }));
+        assert!(matches!(problems[2], Problem::CrcMismatch { offset: 24, .. }));
+        assert_eq!(verify(b"\x89PN".as_slice(), false).unwrap(), vec![Problem::BadSignature]);
+    }
+
+    /// A 4x2 8-bit grayscale image, with its image data split over two IDAT chunks.
+    fn testing_image(image_data: &[u8]) -> Vec<Chunk<'static>> {
+        let ihdr = [0, 0, 0, 4, 0, 0, 0, 2, 8, 0, 0, 0, 0].to_vec();
+        let compressed = crate::text::deflate(image_data);
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+        vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), first.to_vec()),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), second.to_vec()),
+            chunk("IEND"),
+        ]
+    }
+
+    fn verify_image_data(chunks: Vec<Chunk>) -> Vec<Problem> {
+        verify(Png::from_chunks(chunks).as_bytes().as_slice(), true).unwrap()
+    }
+
+    #[test]
+    fn test_image_data() {
+        let pixels: Vec<u8> = (0..10).collect();
+        assert_eq!(verify_image_data(testing_image(&pixels)), vec![]);
+
+        assert_eq!(verify_image_data(testing_image(&pixels[..9])), vec![Problem::ImageDataSize { inflated: Some(9), expected: 10 }]);
+        assert_eq!(verify_image_data(testing_image(&[0; 100])), vec![Problem::ImageDataSize { inflated: None, expected: 10 }]);
+
+        let mut chunks = testing_image(&pixels);
+        chunks.remove(2);
+        assert!(matches!(verify_image_data(chunks)[..], [Problem::ImageDataIncomplete { inflated: 0..10, expected: 10 }]));
+
+        // Swapped IDAT chunks keep their CRCs but not the zlib stream.
+        let mut chunks = testing_image(&pixels);
+        chunks.swap(1, 2);
+        assert!(matches!(verify_image_data(chunks)[..], [Problem::ImageDataCorrupt { offset: 33, .. }]));
+
+        let mut chunks = testing_image(&pixels);
+        chunks[0] = chunk("IHDR");
+        assert!(matches!(verify_image_data(chunks)[..], [Problem::ImageDataUnchecked { .. }]));
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::glob;
+use crate::png::Png;
+
+/// Which entries of a directory tree `walk` looks at.
+#[derive(Debug, Default)]
+pub(crate) struct WalkOptions {
+    /// How many directory levels to descend below the root, `None` for no limit.
+    pub(crate) max_depth: Option<usize>,
+    /// Also look at entries whose name starts with a dot.
+    pub(crate) hidden: bool,
+    /// Descend into symbolic links to directories, which may lead out of the tree.
+    pub(crate) follow_symlinks: bool,
+    /// Skip files and directories whose name matches one of these patterns, like `*.bak`.
+    pub(crate) exclude: Vec<String>,
+}
+
+impl WalkOptions {
+    fn skips(&self, name: &str) -> bool {
+        (!self.hidden && name.starts_with('.')) || self.exclude.iter().any(|pattern| glob::matches_name(pattern, name))
+    }
+}
+
+/// The pngs found by `walk`, and the entries it couldn't read.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Walk {
+    pub(crate) pngs: Vec<PathBuf>,
+    pub(crate) unreadable: Vec<(PathBuf, String)>,
+}
+
+/// Whether `path` starts with the png signature, whatever its extension.
+pub(crate) fn has_png_signature(path: &Path) -> std::io::Result<bool> {
+    let mut header = [0; 8];
+    match fs::File::open(path)?.read_exact(&mut header) {
+        Ok(()) => Ok(header == Png::STANDARD_HEADER),
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Every png in the tree under `root`, identified by its signature rather than its extension,
+/// in path order.
+///
+/// Symbolic links to files are read either way. With `follow_symlinks`, a link back up the
+/// tree is skipped rather than followed forever.
+pub(crate) fn walk(root: &Path, options: &WalkOptions) -> Walk {
+    let mut walker = WalkDir::new(root).follow_links(options.follow_symlinks).sort_by_file_name();
+    if let Some(max_depth) = options.max_depth {
+        // The files of the root are one level below it for walkdir.
+        walker = walker.max_depth(max_depth.saturating_add(1));
+    }
+
+    let mut walk = Walk::default();
+    let entries = walker.into_iter().filter_entry(|entry| entry.depth() == 0 || !options.skips(&entry.file_name().to_string_lossy()));
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) if error.loop_ancestor().is_some() => continue,
+            Err(error) => {
+                let path = error.path().unwrap_or(root).to_path_buf();
+                let reason = error.io_error().map_or_else(|| error.to_string(), ToString::to_string);
+                walk.unreadable.push((path, reason));
+                continue;
+            }
+        };
+        let path = entry.into_path();
+        if path.is_file() {
+            match has_png_signature(&path) {
+                Ok(true) => walk.pngs.push(path),
+                Ok(false) => {}
+                Err(error) => walk.unreadable.push((path, error.to_string())),
+            }
+        }
+    }
+    walk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = [Png::STANDARD_HEADER.as_slice(), b"rest"].concat();
+        for (file, contents) in [
+            ("a.png", png.as_slice()),
+            ("renamed.dat", &png),
+            ("fake.png", b"not a png"),
+            ("short", b"\x89P"),
+            (".hidden/b.png", &png),
+            ("sub/c.png", &png),
+            ("sub/deeper/d.png", &png),
+            ("sub/old.bak", &png),
+        ] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let found = |options: &WalkOptions| -> Vec<String> {
+            let walk = walk(dir.path(), options);
+            assert!(walk.unreadable.is_empty());
+            walk.pngs.iter().map(|path| path.strip_prefix(dir.path()).unwrap().to_string_lossy().into_owned()).collect()
+        };
+
+        assert_eq!(found(&WalkOptions::default()), ["a.png", "renamed.dat", "sub/c.png", "sub/deeper/d.png", "sub/old.bak"]);
+        assert_eq!(found(&WalkOptions { max_depth: Some(1), exclude: vec!["*.bak".to_string()], ..Default::default() }), [
+            "a.png",
+            "renamed.dat",
+            "sub/c.png"
+        ]);
+        assert_eq!(found(&WalkOptions { hidden: true, max_depth: Some(0), ..Default::default() }), ["a.png", "renamed.dat"]);
+        assert_eq!(found(&WalkOptions { hidden: true, exclude: vec!["sub".to_string()], ..Default::default() }), [
+            ".hidden/b.png",
+            "a.png",
+            "renamed.dat"
+        ]);
+
+        let missing = walk(&dir.path().join("missing"), &WalkOptions::default());
+        assert_eq!(missing.unreadable.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        fs::create_dir_all(tree.join("sub")).unwrap();
+        fs::create_dir_all(dir.path().join("outside")).unwrap();
+        let png = [Png::STANDARD_HEADER.as_slice(), b"rest"].concat();
+        fs::write(tree.join("sub/a.png"), &png).unwrap();
+        fs::write(dir.path().join("outside/b.png"), &png).unwrap();
+        symlink(&tree, tree.join("sub/cycle")).unwrap();
+        symlink(dir.path().join("outside"), tree.join("outside")).unwrap();
+        symlink(tree.join("sub/a.png"), tree.join("link.png")).unwrap();
+        let found = |follow_symlinks: bool| -> Vec<String> {
+            let walk = walk(&tree, &WalkOptions { follow_symlinks, ..Default::default() });
+            assert!(walk.unreadable.is_empty(), "{:?}", walk.unreadable);
+            walk.pngs.iter().map(|path| path.strip_prefix(&tree).unwrap().to_string_lossy().into_owned()).collect()
+        };
+
+        assert_eq!(found(false), ["link.png", "sub/a.png"]);
+        // The cycle back to `tree` is walked once, not forever.
+        assert_eq!(found(true), ["link.png", "outside/b.png", "sub/a.png"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_reports_unreadable_directories() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let png = [Png::STANDARD_HEADER.as_slice(), b"rest"].concat();
+        fs::write(dir.path().join("a.png"), &png).unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(locked.join("b.png"), &png).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // Permissions don't stop root, so there is nothing to check then.
+        let readable = fs::read_dir(&locked).is_ok();
+
+        let walk = walk(dir.path(), &WalkOptions::default());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        if !readable {
+            assert_eq!(walk.pngs, [dir.path().join("a.png")]);
+            assert_eq!(walk.unreadable.len(), 1);
+            assert_eq!(walk.unreadable[0].0, locked);
+            assert!(walk.unreadable[0].1.contains("ermission denied"), "{:?}", walk.unreadable);
+        }
+    }
+}
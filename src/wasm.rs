@@ -0,0 +1,52 @@
+//! Bindings for browsers, built with `wasm-pack build --no-default-features --features wasm`,
+//! so pages can hide and read payloads in images without uploading them.
+
+use wasm_bindgen::prelude::*;
+
+use crate::codec::{self, Settings};
+use crate::encode::{self, Position};
+use crate::png::Png;
+
+fn js_error(error: impl std::fmt::Display) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+/// Returns the png in `bytes` with `payload` stored in a new `chunk_type` chunk before IEND,
+/// in the envelope `pngme decode` reads.
+#[wasm_bindgen]
+pub fn encode(bytes: &[u8], chunk_type: &str, payload: &[u8]) -> Result<Vec<u8>, JsError> {
+    let mut png = Png::try_from(bytes).map_err(js_error)?;
+    let chunk = encode::build_chunk(chunk_type, payload, false, 0, Settings::default(), false).map_err(js_error)?;
+    Position::BeforeIend.insert(&mut png, &[chunk]).map_err(js_error)?;
+    Ok(png.as_bytes())
+}
+
+/// Returns the payload of the first `chunk_type` chunk of the png in `bytes`, as stored by
+/// `encode` or `pngme encode` without encryption.
+#[wasm_bindgen]
+pub fn decode(bytes: &[u8], chunk_type: &str) -> Result<Vec<u8>, JsError> {
+    let png = Png::try_from(bytes).map_err(js_error)?;
+    let chunk = png.chunk_by_type(chunk_type).ok_or_else(|| js_error(format!("No {chunk_type} chunk in the png")))?;
+    codec::open(chunk.data()).map_err(js_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    // Only the success paths: building a JsError needs a JavaScript host.
+    #[test]
+    fn test_round_trip() {
+        let chunk = |chunk_type: &str, size: usize| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![0; size]);
+        let png = Png::from_chunks(vec![chunk("IHDR", 13), chunk("IDAT", 8), chunk("IEND", 0)]);
+
+        let encoded = encode(&png.as_bytes(), "ruSt", b"hidden").unwrap();
+
+        let types: Vec<String> = Png::try_from(encoded.as_slice()).unwrap().chunks().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(decode(&encoded, "ruSt").unwrap(), b"hidden");
+    }
+}
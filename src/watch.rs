@@ -0,0 +1,287 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+/// What a file looked like when it was last looked at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Snapshot {
+    modified: SystemTime,
+    len: u64,
+}
+
+/// `None` when the file doesn't exist (or can't be read).
+fn snapshot(path: &Path) -> Option<Snapshot> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(Snapshot { modified: metadata.modified().ok()?, len: metadata.len() })
+}
+
+enum Message {
+    Event(notify::Result<notify::Event>),
+    Stop,
+}
+
+/// Ends a `wait` in progress, or the next one, from another thread, e.g. a Ctrl-C handler.
+#[derive(Clone)]
+pub(crate) struct Stopper(Sender<Message>);
+
+impl Stopper {
+    pub(crate) fn stop(&self) {
+        let _ = self.0.send(Message::Stop);
+    }
+}
+
+/// The filesystem events of one directory, and stop requests, as they arrive.
+struct Events {
+    // Events stop when the watcher is dropped.
+    _watcher: RecommendedWatcher,
+    sender: Sender<Message>,
+    receiver: Receiver<Message>,
+}
+
+impl Events {
+    fn new(dir: &Path) -> notify::Result<Events> {
+        let (sender, receiver) = mpsc::channel();
+        let events = sender.clone();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = events.send(Message::Event(event));
+        })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(Events { _watcher: watcher, sender, receiver })
+    }
+
+    /// Blocks until something happens to a path `relevant` accepts, then until nothing more
+    /// has happened to one for `debounce`. `None` once stopped.
+    fn settle(&self, debounce: Duration, relevant: impl Fn(&Path) -> bool) -> Option<()> {
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let message = match deadline {
+                None => self.receiver.recv().ok()?,
+                Some(deadline) => match self.receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(message) => message,
+                    Err(RecvTimeoutError::Timeout) => return Some(()),
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                },
+            };
+            match message {
+                Message::Stop => return None,
+                Message::Event(Ok(event)) => {
+                    if !matches!(event.kind, EventKind::Access(_)) && event.paths.iter().any(|path| relevant(path)) {
+                        deadline = Some(Instant::now() + debounce);
+                    }
+                }
+                // A lost event only delays a change until the next one; the snapshots tell
+                // what actually changed.
+                Message::Event(Err(_)) => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Change {
+    /// The file was written, or created again after being removed.
+    Updated,
+    /// The file was removed.
+    Removed,
+}
+
+/// Watches a file for changes.
+///
+/// The directory holding it is watched rather than the file itself, so a file that is
+/// removed and created again, as editors often save, keeps being followed.
+pub(crate) struct Watcher {
+    path: PathBuf,
+    debounce: Duration,
+    last: Option<Snapshot>,
+    events: Events,
+}
+
+impl Watcher {
+    /// Starts watching `path`. Its current state is not reported as a change.
+    pub(crate) fn new(path: &Path, debounce: Duration) -> notify::Result<Watcher> {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let events = Events::new(dir)?;
+        Ok(Watcher { path: path.to_path_buf(), debounce, last: snapshot(path), events })
+    }
+
+    pub(crate) fn stopper(&self) -> Stopper {
+        Stopper(self.events.sender.clone())
+    }
+
+    /// Blocks until the file changes, or returns `None` once stopped.
+    ///
+    /// Updates are only reported once the file has stayed the same for the debounce delay, so
+    /// a burst of writes is reported once, after the last of them.
+    pub(crate) fn wait(&mut self) -> Option<Change> {
+        loop {
+            self.events.settle(self.debounce, |path| path.file_name() == self.path.file_name())?;
+            let current = snapshot(&self.path);
+            if current == self.last {
+                continue;
+            }
+            self.last = current;
+            return Some(match current {
+                Some(_) => Change::Updated,
+                None => Change::Removed,
+            });
+        }
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// The files directly in `dir`, hidden ones aside. Unreadable entries are left out.
+fn dir_snapshot(dir: &Path) -> BTreeMap<PathBuf, Snapshot> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return BTreeMap::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !is_hidden(&entry.path()))
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .filter_map(|entry| Some((entry.path(), snapshot(&entry.path())?)))
+        .collect()
+}
+
+/// Watches the files of a directory for changes, the way `Watcher` watches a single file.
+pub(crate) struct DirWatcher {
+    dir: PathBuf,
+    debounce: Duration,
+    last: BTreeMap<PathBuf, Snapshot>,
+    events: Events,
+}
+
+impl DirWatcher {
+    /// Starts watching `dir`. The files already in it are not reported as changes.
+    pub(crate) fn new(dir: &Path, debounce: Duration) -> notify::Result<DirWatcher> {
+        let events = Events::new(dir)?;
+        Ok(DirWatcher { dir: dir.to_path_buf(), debounce, last: dir_snapshot(dir), events })
+    }
+
+    pub(crate) fn stopper(&self) -> Stopper {
+        Stopper(self.events.sender.clone())
+    }
+
+    /// Blocks until files are created or written in the directory, and returns them in path
+    /// order once the directory has stayed the same for the debounce delay, or returns `None`
+    /// once stopped. Removals aren't reported.
+    pub(crate) fn wait(&mut self) -> Option<Vec<PathBuf>> {
+        loop {
+            self.events.settle(self.debounce, |path| !is_hidden(path))?;
+            let current = dir_snapshot(&self.dir);
+            let changed: Vec<PathBuf> =
+                current.iter().filter(|(path, snapshot)| self.last.get(*path) != Some(snapshot)).map(|(path, _)| path.clone()).collect();
+            self.last = current;
+            if !changed.is_empty() {
+                return Some(changed);
+            }
+        }
+    }
+
+    /// Takes the current state of the directory as the new baseline, so writes made since the
+    /// last `wait`, like the ones of the `--on-change` operation, aren't reported.
+    pub(crate) fn rescan(&mut self) {
+        self.last = dir_snapshot(&self.dir);
+    }
+}
+
+/// Splits an `--on-change` operation into arguments at whitespace, keeping what is inside
+/// single or double quotes together.
+pub(crate) fn split_operation(operation: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in operation.chars() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => word.get_or_insert_default().push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    if let Some(open) = quote {
+        return Err(format!("Unclosed {open} in the operation {operation:?}"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_reports_updates_and_removal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("version.json");
+        fs::write(&path, "1").unwrap();
+        let mut watcher = Watcher::new(&path, Duration::from_millis(50)).unwrap();
+
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(150));
+                fs::write(&path, "22").unwrap();
+                fs::write(&path, "333").unwrap();
+            })
+        };
+        assert_eq!(watcher.wait(), Some(Change::Updated));
+        writer.join().unwrap();
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(watcher.wait(), Some(Change::Removed));
+
+        fs::write(&path, "4444").unwrap();
+        assert_eq!(watcher.wait(), Some(Change::Updated));
+
+        // Other files in the directory are no change.
+        fs::write(dir.path().join("other.json"), "1").unwrap();
+        watcher.stopper().stop();
+        assert_eq!(watcher.wait(), None);
+    }
+
+    #[test]
+    fn test_dir_watcher_reports_new_and_written_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.png"), "1").unwrap();
+        fs::write(dir.path().join("b.png"), "1").unwrap();
+        let mut watcher = DirWatcher::new(dir.path(), Duration::from_millis(50)).unwrap();
+
+        fs::write(dir.path().join("b.png"), "22").unwrap();
+        fs::write(dir.path().join("c.png"), "1").unwrap();
+        fs::write(dir.path().join(".hidden.png"), "1").unwrap();
+        assert_eq!(watcher.wait().unwrap(), [dir.path().join("b.png"), dir.path().join("c.png")]);
+
+        fs::remove_file(dir.path().join("a.png")).unwrap();
+        fs::write(dir.path().join("c.png"), "333").unwrap();
+        assert_eq!(watcher.wait().unwrap(), [dir.path().join("c.png")]);
+
+        let stopper = watcher.stopper();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            stopper.stop();
+        });
+        assert_eq!(watcher.wait(), None);
+    }
+
+    #[test]
+    fn test_split_operation() {
+        assert_eq!(split_operation("  clean  --keep tEXt ").unwrap(), ["clean", "--keep", "tEXt"]);
+        assert_eq!(split_operation(r#"text set {} Comment "build 42" ''"#).unwrap(), ["text", "set", "{}", "Comment", "build 42", ""]);
+        assert!(split_operation("text set {} Comment 'build").unwrap_err().starts_with("Unclosed '"));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::error::PngmeError;
+use crate::png::Png;
+use crate::text::{self, TextKind};
+use crate::Result;
+
+/// Keyword of the iTXt chunk photo tools store the XMP packet in.
+pub(crate) const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+/// The XMP packet of `png`, from the first text chunk with the XMP keyword.
+pub(crate) fn read(png: &Png) -> std::result::Result<Option<String>, PngmeError> {
+    Ok(text::get(png, XMP_KEYWORD)?.into_iter().next().map(|entry| entry.text))
+}
+
+/// Stores `packet` in an uncompressed iTXt chunk, as the XMP spec asks, replacing any other
+/// chunk holding XMP.
+///
+/// The packet isn't parsed, only checked to have the root element of an XMP document.
+pub(crate) fn write(png: &mut Png, packet: &str) -> Result<()> {
+    if !packet.contains("<x:xmpmeta") && !packet.contains("<rdf:RDF") {
+        return Err("Not an XMP packet: it has no x:xmpmeta or rdf:RDF element".into());
+    }
+    Ok(text::set(png, XMP_KEYWORD, packet, Some(TextKind::International), None)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    const PACKET: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF/></x:xmpmeta>"#;
+
+    fn testing_png() -> Png<'static> {
+        let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), vec![]);
+        Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut png = testing_png();
+        assert_eq!(read(&png).unwrap(), None);
+
+        write(&mut png, "<x:xmpmeta/>").unwrap();
+        write(&mut png, PACKET).unwrap();
+
+        assert_eq!(read(&png).unwrap().as_deref(), Some(PACKET));
+        let chunk = &png[2];
+        assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+        // Keyword, NUL, then the compression flag and method, both 0.
+        assert_eq!(&chunk.data()[XMP_KEYWORD.len()..XMP_KEYWORD.len() + 3], [0, 0, 0]);
+        assert_eq!(png.chunk_count(), 4);
+    }
+
+    #[test]
+    fn test_rejects_non_xmp() {
+        let mut png = testing_png();
+
+        assert!(write(&mut png, "<html></html>").is_err());
+        assert_eq!(png.chunk_count(), 3);
+    }
+}
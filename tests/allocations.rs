@@ -0,0 +1,62 @@
+//! Allocation counts of parsing and serializing. A counting global allocator would count the
+//! allocations of every other test too, so it gets a test binary of its own.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use pngme::{Chunk, ChunkType, Png};
+
+thread_local! {
+    static COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts allocations per thread, so tests running in parallel don't disturb each other.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = COUNT.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = COUNT.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Runs `operation` and returns its result with the number of allocations it made.
+fn count<T>(operation: impl FnOnce() -> T) -> (T, usize) {
+    let before = COUNT.with(Cell::get);
+    let result = operation();
+    (result, COUNT.with(Cell::get) - before)
+}
+
+fn large_png(chunk_count: usize) -> Vec<u8> {
+    let chunk = |chunk_type: &[u8; 4], data: Vec<u8>| Chunk::new(ChunkType::try_from(*chunk_type).unwrap(), data);
+    let mut chunks = vec![chunk(b"IHDR", b"0123456789abc".to_vec())];
+    chunks.extend((0..chunk_count).map(|index| chunk(b"IDAT", vec![index as u8; 1024])));
+    chunks.push(chunk(b"IEND", Vec::new()));
+    Png::from_chunks(chunks).as_bytes()
+}
+
+#[test]
+fn test_parse_borrows_chunk_data() {
+    let bytes = large_png(1000);
+
+    let (png, parse_allocations) = count(|| Png::try_from(bytes.as_slice()).unwrap());
+    // Chunk data is borrowed from `bytes`, so only the chunk list grows.
+    assert!(parse_allocations <= 16, "{parse_allocations} allocations");
+    assert!(png.chunks().all(|chunk| bytes.as_ptr_range().contains(&chunk.data().as_ptr())));
+
+    let (serialized, serialize_allocations) = count(|| png.as_bytes());
+    assert_eq!(serialize_allocations, 1);
+    assert_eq!(serialized, bytes);
+}
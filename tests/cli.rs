@@ -0,0 +1,1128 @@
+//! End-to-end tests running the `pngme` binary.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::{Duration, Instant};
+
+use pngme::Png;
+
+/// What a `pngme` run printed, and the code it exited with.
+struct Run {
+    code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+impl Run {
+    /// The report printed on stderr with `--errors json`, after any warnings.
+    fn error_report(&self) -> serde_json::Value {
+        let last_line = self.stderr.lines().last().unwrap_or_default();
+        serde_json::from_str(last_line).unwrap_or_else(|_| panic!("stderr isn't a JSON report: {}", self.stderr))
+    }
+}
+
+impl From<Output> for Run {
+    fn from(output: Output) -> Run {
+        Run {
+            code: output.status.code().expect("pngme was killed by a signal"),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+}
+
+/// The `pngme` command for `args`, with every placeholder of `paths` in them replaced by its path.
+fn command(args: &[&str], paths: &[(&str, &Path)]) -> Command {
+    let args = args.iter().map(|arg| {
+        paths
+            .iter()
+            .fold(arg.to_string(), |arg, (placeholder, path)| arg.replace(placeholder, &path.display().to_string()))
+    });
+    let mut command = Command::new(env!("CARGO_BIN_EXE_pngme"));
+    command.args(args);
+    command
+}
+
+/// Runs `pngme` with `args`, replacing every placeholder of `paths` in them with its path.
+fn pngme(args: &[&str], paths: &[(&str, &Path)]) -> Run {
+    Run::from(command(args, paths).output().unwrap())
+}
+
+/// A serialized chunk.
+fn chunk(chunk_type: &str, data: &[u8]) -> Vec<u8> {
+    const CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+    let mut digest = CRC32.digest();
+    digest.update(chunk_type.as_bytes());
+    digest.update(data);
+
+    let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(chunk_type.as_bytes());
+    bytes.extend_from_slice(data);
+    bytes.extend_from_slice(&digest.finalize().to_be_bytes());
+    bytes
+}
+
+/// Writes a png holding `chunks` to `path`.
+fn write_png(path: &Path, chunks: &[(&str, &[u8])]) {
+    let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (chunk_type, data) in chunks {
+        bytes.extend(chunk(chunk_type, data));
+    }
+    fs::write(path, bytes).unwrap();
+}
+
+/// Writes `image.png` into `dir`: an IHDR, a `ruSt` chunk holding "hello", an IDAT and an IEND.
+fn testing_file(dir: &Path) -> PathBuf {
+    let path = dir.join("image.png");
+    write_png(&path, &[("IHDR", &[0; 13]), ("ruSt", b"hello"), ("IDAT", &[0; 8]), ("IEND", &[])]);
+    path
+}
+
+fn load(path: &Path) -> Png<'static> {
+    Png::from_reader(fs::File::open(path).unwrap()).unwrap()
+}
+
+fn chunk_types(path: &Path) -> Vec<String> {
+    load(path).chunks().map(|chunk| chunk.chunk_type().to_string()).collect()
+}
+
+/// The text payload `pngme decode` prints for `selector`.
+fn decoded(path: &Path, selector: &str) -> String {
+    let run = pngme(&["--quiet", "decode", "FILE", selector], &[("FILE", path)]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    run.stdout.strip_suffix('\n').unwrap_or(&run.stdout).to_string()
+}
+
+/// Runs `args` with `--check-only`, then for real on a fresh copy, returning both exit
+/// codes and whether the check-only run left the file untouched.
+fn compare_runs(args: &[&str]) -> (i32, i32, bool) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let original = fs::read(&path).unwrap();
+
+    let check = pngme(&[args, &["--check-only"]].concat(), &[("FILE", &path)]).code;
+    let untouched = fs::read(&path).unwrap() == original;
+    let real = pngme(args, &[("FILE", &path)]).code;
+
+    (check, real, untouched)
+}
+
+#[test]
+fn test_io_error_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing.png");
+
+    let run = pngme(&["--errors", "json", "print", "FILE"], &[("FILE", &missing)]);
+
+    assert_eq!(run.code, 1);
+    let report = run.error_report();
+    assert_eq!(report["code"], "io");
+    assert_eq!(report["path"], missing.display().to_string());
+}
+
+#[test]
+fn test_invalid_png_error_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.png");
+    fs::write(&path, b"not a png").unwrap();
+
+    let run = pngme(&["--errors", "json", "print", "FILE"], &[("FILE", &path)]);
+
+    assert_eq!(run.code, 1);
+    let report = run.error_report();
+    assert_eq!(report["code"], "invalid_png");
+    assert_eq!(report["path"], path.display().to_string());
+}
+
+#[test]
+fn test_chunk_not_found_error_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let out = dir.path().join("out");
+
+    for args in [&["decode", "FILE", "noNe"][..], &["extract", "FILE", "noNe", "--dir", "OUT"]] {
+        let run = pngme(&[&["--errors", "json"], args].concat(), &[("FILE", &path), ("OUT", &out)]);
+
+        assert_eq!(run.code, 1, "{args:?}");
+        let report = run.error_report();
+        assert_eq!(report["code"], "chunk_not_found", "{args:?}");
+        assert_eq!(report["chunk_type"], "noNe", "{args:?}");
+        assert_eq!(report["path"], path.display().to_string(), "{args:?}");
+    }
+}
+
+#[test]
+fn test_invalid_chunk_type_error_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let original = fs::read(&path).unwrap();
+
+    let run = pngme(&["--errors", "json", "encode", "FILE", "ru5t", "message"], &[("FILE", &path)]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error_report()["code"], "invalid_chunk_type");
+    assert_eq!(fs::read(&path).unwrap(), original);
+}
+
+#[test]
+fn test_size_budget_error_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+
+    let run = pngme(&["--errors", "json", "encode", "FILE", "teSt", "message", "--max-output-size", "10"], &[("FILE", &path)]);
+
+    assert_eq!(run.code, 3);
+    let report = run.error_report();
+    assert_eq!(report["code"], "size_budget_exceeded");
+    assert!(report["message"].as_str().unwrap().contains("10"), "{report}");
+    assert!(run.stdout.is_empty());
+}
+
+#[test]
+fn test_edits_leave_no_lock_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+
+    for args in [
+        &["encode", "FILE", "teSt", "message"][..],
+        &["stamp", "FILE"],
+        &["text", "set", "FILE", "Comment", "hello"],
+        &["text", "del", "FILE", "Comment"],
+        &["retype", "FILE", "teSt", "tiMe"],
+        &["remove", "FILE", "tiMe"],
+    ] {
+        assert_eq!(pngme(args, &[("FILE", &path)]).code, 0, "{args:?}");
+
+        let names: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, ["image.png"], "{args:?}");
+    }
+}
+
+#[test]
+fn test_image_header_and_quiet() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let header = format!("{} — 0x0, 0-bit grayscale, 4 chunks", path.display());
+    let files = [("FILE", path.as_path())];
+
+    for command in ["print", "list"] {
+        let run = pngme(&[command, "FILE"], &files);
+        assert_eq!(run.stdout.lines().next(), Some(header.as_str()), "{command}");
+
+        for args in [&["--quiet", command, "FILE"], &[command, "FILE", "-q"]] {
+            let run = pngme(args, &files);
+            assert_eq!(run.code, 0, "{args:?}");
+            assert!(!run.stdout.contains(&header), "{args:?}");
+        }
+    }
+
+    // decode keeps stdout for the payload.
+    let run = pngme(&["decode", "FILE", "ruSt"], &files);
+    assert_eq!((run.stdout.as_str(), run.stderr.trim_end()), ("hello\n", header.as_str()));
+    let run = pngme(&["--quiet", "decode", "FILE", "ruSt"], &files);
+    assert_eq!((run.stdout.as_str(), run.stderr.as_str()), ("hello\n", ""));
+}
+
+#[test]
+fn test_decode_json_has_image_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("image.png");
+    // 2x1, 8-bit rgb
+    let ihdr = [0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0];
+    write_png(&path, &[("IHDR", &ihdr), ("ruSt", b"hello"), ("IDAT", &[0; 8]), ("IEND", &[])]);
+
+    let run = pngme(&["decode", "FILE", "ruSt", "--format", "json"], &[("FILE", &path)]);
+
+    assert_eq!(run.code, 0);
+    assert!(run.stderr.is_empty(), "{}", run.stderr);
+    let json: serde_json::Value = serde_json::from_str(&run.stdout).unwrap();
+    assert_eq!((&json["width"], &json["height"], &json["color_type"]), (&2.into(), &1.into(), &"rgb".into()));
+    assert_eq!(json["payloads"][0]["chunk_type"], "ruSt");
+    assert_eq!(json["payloads"][0]["data"], "aGVsbG8=");
+}
+
+#[test]
+fn test_check_only_success_matches_real_run() {
+    let (check, real, untouched) = compare_runs(&["encode", "FILE", "teSt", "message"]);
+
+    assert_eq!(check, 0);
+    assert_eq!(check, real);
+    assert!(untouched);
+}
+
+#[test]
+fn test_check_only_chunk_not_found_matches_real_run() {
+    let (check, real, untouched) = compare_runs(&["remove", "FILE", "noNe"]);
+
+    assert_eq!(check, 1);
+    assert_eq!(check, real);
+    assert!(untouched);
+}
+
+#[test]
+fn test_check_only_size_budget_matches_real_run() {
+    let (check, real, untouched) = compare_runs(&["encode", "FILE", "teSt", "message", "--max-output-size", "10"]);
+
+    assert_eq!(check, 3);
+    assert_eq!(check, real);
+    assert!(untouched);
+}
+
+#[test]
+fn test_verify_roundtrip_encode_and_remove() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "message", "--verify-roundtrip"], &files).code, 0);
+    assert_eq!(pngme(&["remove", "FILE", "ruSt", "--verify-roundtrip"], &files).code, 0);
+
+    assert_eq!(chunk_types(&path), ["IHDR", "IDAT", "teSt", "IEND"]);
+}
+
+#[test]
+fn test_encode_pipe_through() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "hello", "--pipe-through", "rev"], &files).code, 0);
+    assert_eq!(decoded(&path, "teSt"), "olleh");
+
+    assert_ne!(pngme(&["encode", "FILE", "faIl", "hello", "--pipe-through", "exit 1"], &files).code, 0);
+    assert!(load(&path).chunk_by_type("faIl").is_none());
+}
+
+fn cgbi_file(dir: &Path) -> PathBuf {
+    let path = dir.join("ios.png");
+    let ihdr = [0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0];
+    write_png(&path, &[("CgBI", &[0x50, 0x00, 0x20, 0x06]), ("IHDR", &ihdr), ("IDAT", &[0; 8]), ("IEND", &[])]);
+    path
+}
+
+#[test]
+fn test_cgbi_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = cgbi_file(dir.path());
+    let original = fs::read(&path).unwrap();
+    let files = [("FILE", path.as_path())];
+
+    // Read-only commands proceed.
+    assert_eq!(pngme(&["print", "FILE"], &files).code, 0);
+    assert_eq!(pngme(&["stats", "FILE"], &files).code, 0);
+    assert_eq!(pngme(&["check", "FILE"], &files).code, 0);
+
+    // Edits need --force.
+    let run = pngme(&["--errors", "json", "encode", "FILE", "teSt", "hello"], &files);
+    assert_eq!(run.error_report()["code"], "apple_cgbi");
+    assert_eq!(fs::read(&path).unwrap(), original);
+
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "hello", "--force"], &files).code, 0);
+    assert_eq!(pngme(&["cat", "FILE", "--reorder-spec", "-o", "FILE"], &files).code, 0);
+    let types = chunk_types(&path);
+    assert_eq!(types[0], "CgBI");
+    assert!(types.contains(&"teSt".to_string()));
+}
+
+#[test]
+fn test_cgbi_metadata_edits_need_force() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = cgbi_file(dir.path());
+    let (exif, profile_path, xmp) = (dir.path().join("exif.bin"), dir.path().join("profile.icc"), dir.path().join("xmp.xml"));
+    fs::write(&exif, b"MM\0*\0\0\0\x08\0\0\0\0\0\0").unwrap();
+    let mut profile = vec![0; 200];
+    profile[..4].copy_from_slice(&200u32.to_be_bytes());
+    profile[36..40].copy_from_slice(b"acsp");
+    fs::write(&profile_path, &profile).unwrap();
+    fs::write(&xmp, r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"/>"#).unwrap();
+    let files = [("FILE", path.as_path()), ("EXIF", &exif), ("ICC", &profile_path), ("XMP", &xmp)];
+
+    for command in [
+        &["ihdr", "set", "FILE", "--interlace", "0"][..],
+        &["text", "set", "FILE", "Title", "Icon"],
+        &["text", "convert", "FILE", "Title", "--to", "iTXt"],
+        &["text", "del", "FILE", "Title"],
+        &["time", "set", "FILE", "2024-05-01T12:00:00Z"],
+        &["dpi", "set", "FILE", "144"],
+        &["exif", "set", "FILE", "EXIF"],
+        &["exif", "strip", "FILE"],
+        &["icc", "embed", "FILE", "ICC"],
+        &["xmp", "set", "FILE", "XMP"],
+    ] {
+        let original = fs::read(&path).unwrap();
+        let run = pngme(&[&["--errors", "json"], command].concat(), &files);
+        assert_eq!(run.error_report()["code"], "apple_cgbi", "{command:?}");
+        assert_eq!(fs::read(&path).unwrap(), original, "{command:?}");
+
+        assert_eq!(pngme(&[command, &["--force"]].concat(), &files).code, 0, "{command:?}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_timeout_on_fifo() {
+    let dir = tempfile::tempdir().unwrap();
+    let fifo = dir.path().join("fifo");
+    assert!(Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+    let start = Instant::now();
+
+    let run = pngme(&["print", "FIFO", "--timeout", "1"], &[("FIFO", &fifo)]);
+
+    assert_eq!(run.code, 5);
+    assert!(start.elapsed() < Duration::from_secs(3));
+}
+
+#[test]
+fn test_strict_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+
+    assert_eq!(pngme(&["encode", "FILE", "tEXt", "price: 5€"], &files).code, 0);
+    assert_ne!(pngme(&["encode", "FILE", "tEXt", "price: 5€", "--strict-text"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "FILE", "tEXt", "price: 5", "--strict-text"], &files).code, 0);
+    assert_eq!(load(&path).chunks_by_type("tEXt").count(), 2);
+}
+
+#[test]
+fn test_encode_escaped_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let out = dir.path().join("payload.bin");
+    let files = [("FILE", path.as_path()), ("OUT", &out)];
+
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "--escaped", r"\x00\x01hello\xff\n"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "teSt", "-o", "OUT"], &files).code, 0);
+    assert_eq!(fs::read(&out).unwrap(), b"\x00\x01hello\xff\n");
+
+    assert_ne!(pngme(&["encode", "FILE", "baD", "--escaped", r"\x1"], &files).code, 0);
+}
+
+#[test]
+fn test_decode_binary_payload_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let out = dir.path().join("payload.bin");
+    let files = [("FILE", path.as_path()), ("OUT", &out)];
+
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "--escaped", r"\x89PNG\x00\xff\xfe"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "teSt", "--output", "OUT"], &files).code, 0);
+    assert_eq!(fs::read(&out).unwrap(), b"\x89PNG\x00\xff\xfe");
+
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "again"], &files).code, 0);
+    assert_ne!(pngme(&["decode", "FILE", "teSt[*]", "-o", "OUT"], &files).code, 0);
+    assert_eq!(fs::read(&out).unwrap(), b"\x89PNG\x00\xff\xfe");
+}
+
+#[test]
+fn test_base64_payloads() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let (out, raw) = (dir.path().join("payload.b64"), dir.path().join("payload.bin"));
+    let files = [("FILE", path.as_path()), ("OUT", &out), ("RAW", &raw)];
+
+    // "\x89PNG\x00\xff" in base64.
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "iVBORwD/", "--base64"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "teSt", "-o", "RAW"], &files).code, 0);
+    assert_eq!(fs::read(&raw).unwrap(), b"\x89PNG\x00\xff");
+    assert_eq!(pngme(&["decode", "FILE", "teSt", "--base64", "-o", "OUT"], &files).code, 0);
+    assert_eq!(fs::read_to_string(&out).unwrap(), "iVBORwD/");
+
+    // Wrapped text decodes as is; bad padding and the URL-safe alphabet don't.
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "iVBO\nRwD/\n", "--base64"], &files).code, 0);
+    for invalid in ["not base64!", "iVBORwD", "iVBORw=", "iVBORwD_"] {
+        assert_eq!(pngme(&["encode", "FILE", "teSt", invalid, "--base64"], &files).code, 1, "{invalid}");
+    }
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "--escaped", "x", "--base64"], &files).code, 2);
+}
+
+#[test]
+fn test_file_patterns() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = testing_file(dir.path());
+    let second = dir.path().join("second.png");
+    fs::copy(&first, &second).unwrap();
+    fs::write(dir.path().join("broken.png"), b"not a png").unwrap();
+    let has_chunk = |path: &Path, chunk_type: &str| load(path).chunk_by_type(chunk_type).is_some();
+
+    // broken.png fails, the other files are still processed.
+    let pattern = dir.path().join("*.png");
+    let files = [("PATTERN", pattern.as_path())];
+    assert_eq!(pngme(&["remove", "PATTERN", "ruSt", "--all"], &files).code, 1);
+    assert!(!has_chunk(&first, "ruSt") && !has_chunk(&second, "ruSt"));
+
+    let (pattern, jpegs) = (dir.path().join("{image,second}.png"), dir.path().join("*.jpg"));
+    let files = [("PATTERN", pattern.as_path()), ("JPEGS", &jpegs)];
+    assert_eq!(pngme(&["encode", "PATTERN", "teSt", "hi"], &files).code, 0);
+    assert!(has_chunk(&first, "teSt") && has_chunk(&second, "teSt"));
+    assert_eq!(pngme(&["list", "PATTERN"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "PATTERN", "teSt", "-o", "out.bin"], &files).code, 1);
+    assert_eq!(pngme(&["list", "JPEGS"], &files).code, 1);
+}
+
+#[test]
+fn test_dry_runs_leave_the_file_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "hi"], &files).code, 0);
+    let original = fs::read(&path).unwrap();
+
+    assert_eq!(pngme(&["encode", "FILE", "ruSt", "more", "--dry-run", "--position", "after-ihdr"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "FILE", "ruSt", "more", "--dry-run", "--position", "index 9"], &files).code, 1);
+    assert_eq!(pngme(&["remove", "FILE", "ruSt", "--all", "--dry-run"], &files).code, 0);
+    assert_eq!(pngme(&["clean", "FILE", "--dry-run"], &files).code, 0);
+    assert_eq!(pngme(&["exif", "strip", "FILE", "--dry-run"], &files).code, 0);
+    assert_eq!(fs::read(&path).unwrap(), original);
+}
+
+#[test]
+fn test_scan_recursive() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    fs::copy(&path, dir.path().join("sub/renamed.dat")).unwrap();
+    let files = [("DIR", dir.path()), ("FILE", &path)];
+
+    assert_eq!(pngme(&["scan", "DIR", "--recursive", "--max-depth", "0"], &files).code, 0);
+    assert_eq!(pngme(&["scan", "--recursive", "--json", "FILE"], &files).code, 1);
+
+    let mut broken = b"\x89PNG\r\n\x1a\n".to_vec();
+    broken.extend_from_slice(b"truncated");
+    fs::write(dir.path().join("sub/broken.png"), broken).unwrap();
+    assert_eq!(pngme(&["scan", "DIR", "-r"], &files).code, 1);
+    assert_eq!(pngme(&["scan", "DIR", "-r", "--exclude", "broken.*"], &files).code, 0);
+    assert_eq!(pngme(&["scan", "FILE", "--hidden"], &files).code, 2);
+}
+
+#[test]
+fn test_encode_compressed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    let text = "all work and no play ".repeat(500);
+
+    assert_eq!(pngme(&["encode", "FILE", "zlIb", &text, "--compress"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "FILE", "zsTd", &text, "--compress=zstd", "--checksum"], &files).code, 0);
+    let png = load(&path);
+    for chunk_type in ["zlIb", "zsTd"] {
+        assert!(png.chunk_by_type(chunk_type).unwrap().data().len() < text.len() / 10, "{chunk_type}");
+        assert_eq!(decoded(&path, chunk_type), text);
+    }
+
+    assert_eq!(pngme(&["encode", "FILE", "ruSt", "text", "--compress=gzip"], &files).code, 2);
+    assert_eq!(pngme(&["encode", "FILE", "ruSt", "text", "--compress", "--raw-payload"], &files).code, 2);
+}
+
+#[test]
+fn test_encrypt_decrypt() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    let with_passphrase = |args: &[&str], passphrase: &str| {
+        Run::from(command(args, &files).env("PNGME_PASSPHRASE", passphrase).output().unwrap())
+    };
+
+    let run = with_passphrase(&["encode", "FILE", "seCr", "attack at dawn", "--encrypt", "--compress"], "correct horse");
+    assert_eq!(run.code, 0);
+    let png = load(&path);
+    assert!(!png.chunk_by_type("seCr").unwrap().data().windows(6).any(|window| window == b"attack"));
+
+    let run = with_passphrase(&["--quiet", "decode", "FILE", "seCr", "--decrypt"], "correct horse");
+    assert_eq!((run.code, run.stdout.as_str()), (0, "attack at dawn\n"));
+    assert_ne!(with_passphrase(&["decode", "FILE", "seCr"], "correct horse").code, 0);
+    assert_ne!(with_passphrase(&["decode", "FILE", "seCr", "--decrypt"], "wrong").code, 0);
+}
+
+#[test]
+fn test_split_payload() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let out = dir.path().join("out.bin");
+    let files = [("FILE", path.as_path()), ("OUT", &out)];
+    let text = "0123456789".repeat(30);
+
+    assert_eq!(pngme(&["encode", "FILE", "spLt", &text, "--split-size", "100"], &files).code, 0);
+    assert_eq!(load(&path).chunks_by_type("spLt").count(), 4);
+
+    assert_eq!(pngme(&["decode", "FILE", "spLt", "-o", "OUT"], &files).code, 0);
+    assert_eq!(fs::read(&out).unwrap(), text.as_bytes());
+    // Selecting every part still decodes a single payload.
+    assert_eq!(pngme(&["decode", "FILE", "spLt", "--all", "-o", "OUT"], &files).code, 0);
+    assert_eq!(fs::read(&out).unwrap(), text.as_bytes());
+
+    assert_eq!(pngme(&["remove", "FILE", "spLt[2]"], &files).code, 0);
+    assert_ne!(pngme(&["decode", "FILE", "spLt"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "FILE", "spLt", "text", "--split-size", "100", "--raw-payload"], &files).code, 2);
+}
+
+#[test]
+fn test_encode_input_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let (input, out) = (dir.path().join("key.bin"), dir.path().join("out.bin"));
+    let files = [("FILE", path.as_path()), ("INPUT", &input), ("OUT", &out)];
+    let key: Vec<u8> = (0..=255).collect();
+    fs::write(&input, &key).unwrap();
+
+    assert_eq!(pngme(&["encode", "FILE", "keYs", "--input-file", "INPUT"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "keYs", "-o", "OUT"], &files).code, 0);
+    assert_eq!(fs::read(&out).unwrap(), key);
+
+    assert_ne!(pngme(&["encode", "FILE", "keYs", "--input-file", "missing.bin"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "FILE", "keYs", "text", "--input-file", "INPUT"], &files).code, 2);
+    assert_eq!(pngme(&["encode", "FILE", "keYs"], &files).code, 2);
+}
+
+#[test]
+fn test_encode_position() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+
+    assert_eq!(pngme(&["encode", "FILE", "enDa", "default"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "FILE", "hdRa", "first", "--position", "after-ihdr"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "FILE", "inDx", "third", "--position", "index 2"], &files).code, 0);
+    assert_eq!(chunk_types(&path), ["IHDR", "hdRa", "inDx", "ruSt", "IDAT", "enDa", "IEND"]);
+
+    assert_ne!(pngme(&["encode", "FILE", "faRr", "away", "--position", "index=8"], &files).code, 0);
+    assert_eq!(chunk_types(&path).len(), 7);
+}
+
+#[test]
+fn test_chunk_order_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+
+    assert_ne!(pngme(&["encode", "FILE", "laTe", "late", "--position", "index 4"], &files).code, 0);
+    assert_eq!(chunk_types(&path), ["IHDR", "ruSt", "IDAT", "IEND"]);
+
+    assert_eq!(pngme(&["encode", "FILE", "laTe", "late", "--position", "index 4", "--chunk-order", "keep"], &files).code, 0);
+    assert_eq!(chunk_types(&path), ["IHDR", "ruSt", "IDAT", "IEND", "laTe"]);
+
+    // A file that was already out of order can still be edited once the order is fixed.
+    assert_eq!(pngme(&["encode", "FILE", "erLy", "early", "--position", "index 0", "--chunk-order", "fix"], &files).code, 0);
+    assert_eq!(chunk_types(&path), ["IHDR", "erLy", "ruSt", "IDAT", "laTe", "IEND"]);
+}
+
+#[test]
+fn test_encode_without_image_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("empty.png");
+    write_png(&path, &[("IHDR", &[0; 13]), ("IEND", &[])]);
+    let original = fs::read(&path).unwrap();
+    let files = [("FILE", path.as_path())];
+
+    assert_ne!(pngme(&["encode", "FILE", "teSt", "hidden"], &files).code, 0);
+    assert_eq!(fs::read(&path).unwrap(), original);
+    assert_ne!(pngme(&["check", "FILE"], &files).code, 0);
+
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "hidden", "--allow-no-image"], &files).code, 0);
+    assert!(load(&path).chunk_by_type("teSt").is_some());
+}
+
+#[test]
+fn test_concurrent_encodes_keep_both_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+
+    std::thread::scope(|scope| {
+        for chunk_type in ["alPh", "beTa"] {
+            let path = &path;
+            scope.spawn(move || {
+                for index in 0..10 {
+                    let content = format!("{chunk_type} {index}");
+                    assert_eq!(pngme(&["encode", "FILE", chunk_type, &content], &[("FILE", path)]).code, 0);
+                }
+            });
+        }
+    });
+
+    let png = load(&path);
+    assert_eq!(png.chunks_by_type("alPh").count(), 10);
+    assert_eq!(png.chunks_by_type("beTa").count(), 10);
+}
+
+#[test]
+fn test_lock_timeout_exit_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    let held = fs::File::create(dir.path().join(".image.png.pngme-lock")).unwrap();
+    held.lock().unwrap();
+
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "hello", "--lock-timeout", "0"], &files).code, 6);
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "hello", "--no-lock"], &files).code, 0);
+}
+
+#[test]
+fn test_bad_ihdr_still_prints() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.png");
+    write_png(&path, &[("IHDR", &[0; 5]), ("ruSt", b"hello"), ("IEND", &[])]);
+    let files = [("FILE", path.as_path())];
+
+    assert_eq!(pngme(&["print", "FILE"], &files).code, 0);
+    assert_eq!(pngme(&["print", "FILE", "--quiet"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "ruSt"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "ruSt", "-q"], &files).code, 0);
+}
+
+#[test]
+fn test_truncate_appended_zip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let original = fs::read(&path).unwrap();
+    // An empty zip archive: just the end of central directory record.
+    let zip = [b"PK\x05\x06".as_slice(), &[0; 18]].concat();
+    fs::write(&path, [original.as_slice(), &zip].concat()).unwrap();
+    let saved = dir.path().join("trailing.zip");
+    let files = [("FILE", path.as_path()), ("SAVED", &saved)];
+
+    assert_eq!(pngme(&["truncate", "FILE", "--save-trailing", "SAVED"], &files).code, 0);
+    assert_eq!(fs::read(&path).unwrap(), original);
+    assert_eq!(fs::read(&saved).unwrap(), zip);
+
+    assert_eq!(pngme(&["truncate", "FILE", "--fail-unchanged"], &files).code, 4);
+    assert_eq!(fs::read(&path).unwrap(), original);
+}
+
+#[test]
+fn test_occurrence_selectors() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let out = dir.path().join("out");
+    let files = [("FILE", path.as_path()), ("OUT", &out)];
+    for content in ["first", "second", "third"] {
+        assert_eq!(pngme(&["encode", "FILE", "teSt", content], &files).code, 0);
+    }
+
+    assert_eq!(pngme(&["decode", "FILE", "teSt[2]"], &files).code, 0);
+    assert_ne!(pngme(&["decode", "FILE", "teSt[3]"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "teSt", "--index", "2"], &files).code, 0);
+    assert_ne!(pngme(&["decode", "FILE", "teSt", "--index", "3"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "teSt", "--all"], &files).code, 0);
+    assert_ne!(pngme(&["decode", "FILE", "teSt[1]", "--all"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "teSt", "--all", "--index", "1"], &files).code, 2);
+
+    assert_eq!(pngme(&["extract", "FILE", "teSt[1]", "--dir", "OUT"], &files).code, 0);
+    assert!(out.join("teSt_001.bin").exists());
+    assert_eq!(decoded(&path, "teSt[1]"), "second");
+
+    assert_eq!(pngme(&["remove", "FILE", "teSt[1]"], &files).code, 0);
+    assert_eq!([decoded(&path, "teSt[0]"), decoded(&path, "teSt[1]")], ["first", "third"]);
+    assert_eq!(load(&path).chunks_by_type("teSt").count(), 2);
+
+    assert_eq!(pngme(&["remove", "FILE", "teSt[*]"], &files).code, 0);
+    assert!(load(&path).chunk_by_type("teSt").is_none());
+
+    for content in ["fourth", "fifth"] {
+        assert_eq!(pngme(&["encode", "FILE", "teSt", content], &files).code, 0);
+    }
+    assert_eq!(pngme(&["remove", "FILE", "teSt[1]", "--all"], &files).code, 1);
+    assert_eq!(pngme(&["remove", "FILE", "teSt", "--all"], &files).code, 0);
+    assert!(load(&path).chunk_by_type("teSt").is_none());
+
+    assert_eq!(pngme(&["remove", "FILE", "teSt[-1]"], &files).code, 2);
+    assert_eq!(pngme(&["decode", "FILE", "teSt[1]x"], &files).code, 2);
+}
+
+#[test]
+fn test_retype() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    for content in ["first", "second"] {
+        assert_eq!(pngme(&["encode", "FILE", "teSt", content], &files).code, 0);
+    }
+
+    assert_eq!(pngme(&["retype", "FILE", "teSt", "neWt"], &files).code, 0);
+    assert_eq!(decoded(&path, "neWt"), "first");
+    assert_eq!(load(&path).chunks_by_type("teSt").count(), 1);
+
+    assert_eq!(pngme(&["retype", "FILE", "teSt", "neWt", "--all"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "neWt[1]"], &files).code, 0);
+    assert_ne!(pngme(&["retype", "FILE", "teSt", "neWt"], &files).code, 0);
+    assert_ne!(pngme(&["retype", "FILE", "neWt", "nwet"], &files).code, 0);
+    assert_eq!(pngme(&["retype", "FILE", "neWt[1]", "nwet", "--force"], &files).code, 0);
+    assert_ne!(pngme(&["retype", "FILE", "neWt[1]", "neWt", "--all"], &files).code, 0);
+}
+
+#[test]
+fn test_copy_chunk() {
+    let (source_dir, destination_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let source = testing_file(source_dir.path());
+    let destination = testing_file(destination_dir.path());
+    let files = [("SOURCE", source.as_path()), ("DESTINATION", &destination)];
+    assert_eq!(pngme(&["encode", "SOURCE", "teSt", "copied"], &files).code, 0);
+
+    assert_eq!(pngme(&["copy-chunk", "SOURCE", "DESTINATION", "teSt"], &files).code, 0);
+    assert_eq!(decoded(&destination, "teSt"), "copied");
+
+    assert_ne!(pngme(&["copy-chunk", "SOURCE", "DESTINATION", "IDAT"], &files).code, 0);
+    assert_ne!(pngme(&["copy-chunk", "SOURCE", "DESTINATION", "noNe"], &files).code, 0);
+    assert_ne!(pngme(&["copy-chunk", "-", "-", "teSt"], &files).code, 0);
+}
+
+#[test]
+fn test_merge() {
+    let dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+    let [base, one, two] = dirs.each_ref().map(|dir| testing_file(dir.path()));
+    let out = dirs[0].path().join("out.png");
+    let files = [("BASE", base.as_path()), ("ONE", &one), ("TWO", &two), ("OUT", &out)];
+    assert_eq!(pngme(&["encode", "ONE", "teSt", "one"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "TWO", "teSt", "two"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "TWO", "twOo", "only in two"], &files).code, 0);
+
+    assert_eq!(pngme(&["merge", "BASE", "ONE", "TWO", "-o", "OUT"], &files).code, 0);
+    assert_eq!(decoded(&out, "teSt"), "one");
+    assert_eq!(decoded(&out, "twOo"), "only in two");
+
+    assert_eq!(pngme(&["merge", "BASE", "ONE", "TWO", "-o", "OUT", "--on-conflict", "last-wins"], &files).code, 0);
+    assert_eq!(decoded(&out, "teSt"), "two");
+    assert_ne!(pngme(&["merge", "BASE", "ONE", "TWO", "-o", "OUT", "--on-conflict", "fail-on-conflict"], &files).code, 0);
+    let run = pngme(&["merge", "BASE", "ONE", "TWO", "-o", "OUT", "--on-conflict", "fail-on-conflict", "--policy", "teSt=last-wins"], &files);
+    assert_eq!(run.code, 0);
+    assert_eq!(decoded(&out, "teSt"), "two");
+
+    assert_eq!(pngme(&["merge", "BASE", "-o", "OUT"], &files).code, 2);
+    assert_eq!(pngme(&["merge", "BASE", "ONE", "-o", "OUT", "--policy", "teSt=sometimes"], &files).code, 2);
+}
+
+#[test]
+fn test_remove_matching() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    assert_eq!(pngme(&["encode", "FILE", "biGg", &"x".repeat(2000)], &files).code, 0);
+    assert_eq!(pngme(&["encode", "FILE", "smAl", "tiny"], &files).code, 0);
+    let before = fs::read(&path).unwrap();
+
+    assert_eq!(pngme(&["remove", "FILE", "--matching", "private && size>1024", "--dry-run"], &files).code, 0);
+    assert_eq!(fs::read(&path).unwrap(), before);
+
+    assert_eq!(pngme(&["remove", "FILE", "--matching", "private && size>1024 || type == ruSt"], &files).code, 0);
+    assert_eq!(chunk_types(&path), ["IHDR", "IDAT", "smAl", "IEND"]);
+
+    assert_eq!(pngme(&["remove", "FILE", "--matching", "size >"], &files).code, 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_encode_all_or_nothing_keeps_modes_and_symlinks() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    let target = dir.path().join("target.png");
+    let link = dir.path().join("link.png");
+    fs::copy(&path, &target).unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+    let manifest = dir.path().join("files.txt");
+    fs::write(&manifest, format!("{}\n", link.display())).unwrap();
+    let files = [("FILE", path.as_path()), ("MANIFEST", &manifest)];
+
+    let run = pngme(&["encode", "FILE", "teSt", "group", "--files-from", "MANIFEST", "--all-or-nothing"], &files);
+    assert_eq!(run.code, 0);
+
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+    assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    for file in [&path, &target] {
+        assert!(load(file).chunk_by_type("teSt").is_some());
+    }
+    let names: Vec<String> = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    assert!(!names.iter().any(|name| name.ends_with(".pngme-tmp")), "{names:?}");
+}
+
+#[test]
+fn test_remove_missing_chunk_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let before = fs::read(&path).unwrap();
+    let files = [("FILE", path.as_path())];
+
+    let run = pngme(&["--errors", "json", "remove", "FILE", "noNe"], &files);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error_report()["code"], "chunk_not_found");
+    assert_eq!(fs::read(&path).unwrap(), before);
+    // A filter that matches nothing is not an error.
+    assert_eq!(pngme(&["remove", "FILE", "--matching", "type == noNe"], &files).code, 0);
+}
+
+#[test]
+fn test_text_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    let text_types = || chunk_types(&path).into_iter().filter(|chunk_type| chunk_type.ends_with("Xt")).collect::<Vec<_>>();
+
+    assert_eq!(pngme(&["text", "set", "FILE", "Author", "Alice"], &files).code, 0);
+    assert_eq!(pngme(&["text", "set", "FILE", "Author", "Bob"], &files).code, 0);
+    assert_eq!(pngme(&["text", "get", "FILE", "Author"], &files).stdout, "Bob\n");
+    assert_eq!(text_types(), ["tEXt"]);
+    assert_eq!(chunk_types(&path).last().unwrap(), "IEND");
+
+    assert_ne!(pngme(&["text", "set", "FILE", "Author ", "Bob"], &files).code, 0);
+    assert_ne!(pngme(&["text", "get", "FILE", "Title"], &files).code, 0);
+
+    assert_eq!(pngme(&["text", "set", "FILE", "Title", "Soleil ☀", "--type", "iTXt", "--language", "fr"], &files).code, 0);
+    assert_eq!(pngme(&["text", "convert", "FILE", "Author", "--to", "zTXt"], &files).code, 0);
+    assert_eq!(text_types(), ["zTXt", "iTXt"]);
+    assert_eq!(pngme(&["text", "get", "FILE", "Author"], &files).stdout, "Bob\n");
+    assert_ne!(pngme(&["text", "convert", "FILE", "Title", "--to", "tEXt"], &files).code, 0);
+    assert_ne!(pngme(&["text", "convert", "FILE", "Missing", "--to", "tEXt"], &files).code, 0);
+    assert_eq!(pngme(&["text", "set", "FILE", "Title", "x", "--type", "text"], &files).code, 2);
+
+    assert_eq!(pngme(&["text", "del", "FILE", "Author"], &files).code, 0);
+    assert_eq!(text_types(), ["iTXt"]);
+    assert_ne!(pngme(&["text", "get", "FILE", "Author"], &files).code, 0);
+    let before = fs::read(&path).unwrap();
+    assert_ne!(pngme(&["text", "del", "FILE", "Author"], &files).code, 0);
+    assert_eq!(fs::read(&path).unwrap(), before);
+}
+
+#[test]
+fn test_time_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+
+    assert_ne!(pngme(&["time", "get", "FILE"], &files).code, 0);
+    assert_eq!(pngme(&["time", "set", "FILE", "2024-05-01T14:00:00+02:00"], &files).code, 0);
+    assert_eq!(pngme(&["time", "get", "FILE"], &files).stdout, "2024-05-01T12:00:00Z\n");
+
+    assert_eq!(pngme(&["time", "set", "FILE"], &files).code, 0);
+    assert_ne!(pngme(&["time", "get", "FILE"], &files).stdout, "2024-05-01T12:00:00Z\n");
+    assert_eq!(pngme(&["time", "set", "FILE", "May 1st"], &files).code, 2);
+}
+
+#[test]
+fn test_dpi_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+
+    assert_ne!(pngme(&["dpi", "get", "FILE"], &files).code, 0);
+    assert_eq!(pngme(&["dpi", "set", "FILE", "300"], &files).code, 0);
+    assert_eq!(pngme(&["dpi", "get", "FILE"], &files).stdout, "300 dpi (11811 pixels per meter)\n");
+    assert_eq!(chunk_types(&path), ["IHDR", "ruSt", "pHYs", "IDAT", "IEND"]);
+
+    assert_eq!(pngme(&["dpi", "set", "FILE", "-72"], &files).code, 2);
+}
+
+#[test]
+fn test_exif_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let exif = dir.path().join("exif.bin");
+    let files = [("FILE", path.as_path()), ("EXIF", &exif)];
+    let has_exif = || load(&path).chunk_by_type("eXIf").is_some();
+    // Big-endian TIFF with one directory holding Make = "Acme".
+    fs::write(&exif, b"Exif\0\0MM\0*\0\0\0\x08\0\x01\x01\x0f\0\x02\0\0\0\x05\0\0\0\x1a\0\0\0\0Acme\0").unwrap();
+
+    assert_ne!(pngme(&["exif", "get", "FILE"], &files).code, 0);
+    assert_eq!(pngme(&["exif", "set", "FILE", "EXIF"], &files).code, 0);
+    assert!(has_exif());
+    assert_eq!(pngme(&["exif", "get", "FILE"], &files).code, 0);
+
+    assert_eq!(pngme(&["exif", "strip", "FILE"], &files).code, 0);
+    assert!(!has_exif());
+    assert_eq!(pngme(&["exif", "strip", "FILE"], &files).code, 0);
+
+    fs::write(&exif, b"not exif").unwrap();
+    assert_ne!(pngme(&["exif", "set", "FILE", "EXIF"], &files).code, 0);
+    assert!(!has_exif());
+}
+
+#[test]
+fn test_icc_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let (profile_path, extracted) = (dir.path().join("profile.icc"), dir.path().join("extracted.icc"));
+    let files = [("FILE", path.as_path()), ("ICC", &profile_path), ("EXTRACTED", &extracted)];
+    let mut profile = vec![0; 200];
+    profile[..4].copy_from_slice(&200u32.to_be_bytes());
+    profile[36..40].copy_from_slice(b"acsp");
+    fs::write(&profile_path, &profile).unwrap();
+
+    assert_ne!(pngme(&["icc", "extract", "FILE", "-o", "EXTRACTED"], &files).code, 0);
+    assert_eq!(pngme(&["icc", "embed", "FILE", "ICC", "--name", "Test profile"], &files).code, 0);
+    assert_eq!(pngme(&["icc", "extract", "FILE", "-o", "EXTRACTED"], &files).code, 0);
+    assert_eq!(fs::read(&extracted).unwrap(), profile);
+    assert!(load(&path).chunk_by_type("iCCP").unwrap().data().starts_with(b"Test profile\0"));
+
+    fs::write(&profile_path, b"not a profile").unwrap();
+    assert_ne!(pngme(&["icc", "embed", "FILE", "ICC"], &files).code, 0);
+}
+
+#[test]
+fn test_xmp_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let xmp = dir.path().join("xmp.xml");
+    let files = [("FILE", path.as_path()), ("XMP", &xmp)];
+    let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF/></x:xmpmeta>"#;
+    fs::write(&xmp, packet).unwrap();
+
+    assert_ne!(pngme(&["xmp", "get", "FILE"], &files).code, 0);
+    assert_eq!(pngme(&["xmp", "set", "FILE", "XMP"], &files).code, 0);
+    assert_eq!(pngme(&["xmp", "get", "FILE"], &files).stdout.trim_end(), packet);
+
+    fs::write(&xmp, b"\xff<x:xmpmeta/>").unwrap();
+    assert_ne!(pngme(&["xmp", "set", "FILE", "XMP"], &files).code, 0);
+}
+
+#[test]
+fn test_palette() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    assert_ne!(pngme(&["palette", "FILE"], &files).code, 0);
+
+    let ihdr = [0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0];
+    write_png(&path, &[("IHDR", &ihdr), ("PLTE", &[255, 0, 0, 0, 0, 255]), ("tRNS", &[0]), ("IDAT", &[0; 8]), ("IEND", &[])]);
+
+    assert_eq!(pngme(&["palette", "FILE"], &files).code, 0);
+    let run = pngme(&["palette", "FILE", "--json"], &files);
+    assert_eq!(run.code, 0);
+    assert!(run.stdout.contains("#0000ff"), "{}", run.stdout);
+}
+
+#[test]
+fn test_frames() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    assert_ne!(pngme(&["frames", "FILE"], &files).code, 0);
+
+    let fctl = |sequence_number: u32| [sequence_number.to_be_bytes().as_slice(), &[0, 0, 0, 1, 0, 0, 0, 1], &[0; 8], &[0, 1, 0, 10, 0, 0]].concat();
+    let (first, second) = (fctl(0), fctl(1));
+    write_png(
+        &path,
+        &[("IHDR", &[0; 13]), ("acTL", &[0, 0, 0, 2, 0, 0, 0, 0]), ("fcTL", &first), ("IDAT", &[0; 8]), ("fcTL", &second), ("fdAT", &[0, 0, 0, 2, 0]), ("IEND", &[])],
+    );
+    let animation = || -> serde_json::Value { serde_json::from_str(&pngme(&["frames", "FILE", "--json"], &files).stdout).unwrap() };
+
+    assert_eq!(pngme(&["frames", "FILE"], &files).code, 0);
+    assert_eq!(animation()["frames"].as_array().unwrap().len(), 2);
+    assert_eq!(animation()["frames"][1]["fdat_sequence_numbers"], serde_json::json!([2]));
+
+    // A raw fdAT chunk with a stale sequence number only warns.
+    let run = pngme(&["encode", "FILE", "fdAT", "--escaped", r"\x00\x00\x00\x01", "--raw-payload"], &files);
+    assert_eq!(run.code, 0);
+    assert!(run.stderr.contains("breaks the APNG frame sequence numbers"), "{}", run.stderr);
+    assert_eq!(animation()["problems"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_sign_and_hmac() {
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let (key, public_key, other) = (dir.path().join("key"), dir.path().join("key.pub"), dir.path().join("other.pub"));
+    fs::write(&key, [7; 32]).unwrap();
+    fs::write(&public_key, Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap().public_key()).unwrap();
+    fs::write(&other, Ed25519KeyPair::from_seed_unchecked(&[8; 32]).unwrap().public_key()).unwrap();
+    let files = [("FILE", path.as_path()), ("SEED", &key), ("PUBLIC", &public_key), ("OTHER", &other)];
+
+    assert_eq!(pngme(&["encode", "FILE", "siGn", "hello", "--sign", "SEED"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "siGn", "--verify", "PUBLIC"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "siGn", "--verify", "OTHER"], &files).code, 1);
+    assert_eq!(pngme(&["decode", "FILE", "siGn"], &files).code, 0);
+    assert_eq!(pngme(&["encode", "FILE", "taGd", "hello", "--hmac", "tag key"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "taGd", "--hmac", "tag key"], &files).code, 0);
+    assert_eq!(pngme(&["decode", "FILE", "taGd", "--hmac", "wrong"], &files).code, 1);
+    assert_eq!(pngme(&["decode", "FILE", "taGd"], &files).code, 1);
+    // ruSt holds raw data, which can't carry a signature.
+    assert_eq!(pngme(&["decode", "FILE", "ruSt", "--verify", "PUBLIC"], &files).code, 1);
+}
+
+#[test]
+fn test_lsb_mode() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("image.png");
+    let files = [("FILE", path.as_path())];
+    // A 32x32 8-bit grayscale image, all scanlines unfiltered.
+    let ihdr: Vec<u8> = [32u32.to_be_bytes(), 32u32.to_be_bytes()].concat().into_iter().chain([8, 0, 0, 0, 0]).collect();
+    let pixels: Vec<u8> = (0..32 * 33).map(|i| if i % 33 == 0 { 0 } else { i as u8 }).collect();
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&pixels).unwrap();
+    write_png(&path, &[("IHDR", &ihdr), ("IDAT", &encoder.finish().unwrap()), ("IEND", &[])]);
+
+    assert_eq!(pngme(&["encode", "FILE", "ruSt", "hidden", "--mode", "lsb", "--checksum"], &files).code, 0);
+    assert_eq!(load(&path).chunk_count(), 3);
+    let run = pngme(&["--quiet", "decode", "FILE", "ruSt", "--mode", "lsb"], &files);
+    assert_eq!((run.code, run.stdout.as_str()), (0, "hidden\n"));
+    assert_eq!(pngme(&["decode", "FILE", "abCd", "--mode", "lsb"], &files).code, 1);
+    assert_eq!(pngme(&["decode", "FILE", "ruSt"], &files).code, 1);
+    assert_eq!(pngme(&["verify", "FILE", "--image-data"], &files).code, 0);
+
+    let long = "x".repeat(200);
+    assert_eq!(pngme(&["encode", "FILE", "ruSt", &long, "--mode", "lsb"], &files).code, 1);
+    for flag in ["--all-or-nothing", "--allow-no-image"] {
+        assert_eq!(pngme(&["encode", "FILE", "ruSt", "hidden", "--mode", "lsb", flag], &files).code, 2);
+    }
+}
+
+#[test]
+fn test_capacity() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+
+    assert_eq!(pngme(&["capacity", "FILE"], &files).code, 0);
+    assert_eq!(pngme(&["capacity", "FILE", "--mode", "lsb", "--json"], &files).code, 0);
+}
+
+#[test]
+fn test_verify() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    assert_eq!(pngme(&["verify", "FILE"], &files).code, 0);
+    // The testing IDAT chunk doesn't hold a zlib stream.
+    assert_eq!(pngme(&["verify", "FILE", "--image-data"], &files).code, 1);
+
+    let mut bytes = fs::read(&path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    fs::write(&path, bytes).unwrap();
+    assert_eq!(pngme(&["verify", "FILE"], &files).code, 1);
+}
+
+#[test]
+fn test_repair() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    assert_eq!(pngme(&["repair", "FILE"], &files).code, 0);
+
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[8 + 25 - 1] ^= 0xff;
+    bytes.truncate(bytes.len() - 5);
+    fs::write(&path, &bytes).unwrap();
+    assert!(Png::try_from(bytes.as_slice()).is_err());
+
+    assert_eq!(pngme(&["repair", "FILE"], &files).code, 0);
+    assert_eq!(chunk_types(&path), ["IHDR", "ruSt", "IDAT", "IEND"]);
+}
+
+#[test]
+fn test_format_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = testing_file(dir.path());
+    let files = [("FILE", path.as_path())];
+    assert_eq!(pngme(&["encode", "FILE", "teSt", "hello"], &files).code, 0);
+
+    for args in [&["decode", "FILE", "teSt", "--format", "json"][..], &["--format", "json", "list", "FILE"], &["print", "FILE", "--format", "json"]] {
+        let run = pngme(args, &files);
+        assert_eq!(run.code, 0, "{args:?}");
+        assert!(serde_json::from_str::<serde_json::Value>(&run.stdout).is_ok(), "{args:?}: {}", run.stdout);
+    }
+    assert_eq!(pngme(&["list", "FILE", "--format", "yaml"], &files).code, 2);
+}
@@ -0,0 +1,85 @@
+//! Replays the fuzz corpus through the parsers, so its tricky inputs are checked on every
+//! `cargo test`, without a nightly toolchain or libFuzzer.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pngme::{Chunk, ParseLimits, Png};
+
+/// The parse limits the fuzz targets use.
+#[path = "../fuzz/src/lib.rs"]
+mod fuzz;
+
+/// The inputs saved for the `target` fuzz target, with their paths.
+fn corpus(target: &str) -> Vec<(PathBuf, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus").join(target);
+    let mut inputs: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let bytes = fs::read(&path).unwrap();
+            (path, bytes)
+        })
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "{} is empty", dir.display());
+    inputs
+}
+
+fn parse_untrusted(name: &str, limits: ParseLimits) -> Result<Png<'static>, String> {
+    let bytes = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/png").join(name)).unwrap();
+    Png::parse_untrusted(&bytes, limits).map(Png::into_owned).map_err(|error| error.to_string())
+}
+
+#[test]
+fn test_parse_untrusted_corpus() {
+    for (path, bytes) in corpus("png") {
+        for limits in [fuzz::LIMITS, ParseLimits::default()] {
+            if let Ok(png) = Png::parse_untrusted(&bytes, limits) {
+                assert!(png.chunk_count() <= limits.max_chunks, "{}", path.display());
+                assert_eq!(png.as_bytes(), bytes, "{}", path.display());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_untrusted_fixtures() {
+    let default = ParseLimits::default();
+
+    assert_eq!(parse_untrusted("valid.png", default).unwrap().chunk_count(), 3);
+    assert_eq!(parse_untrusted("missing-iend.png", default).unwrap().chunk_count(), 2);
+    assert_eq!(parse_untrusted("chunks-after-iend.png", default).unwrap().chunk_count(), 4);
+    for name in ["truncated-header.png", "truncated-length.png", "truncated-data.png", "overflowing-length.png"] {
+        assert!(parse_untrusted(name, default).is_err(), "{name}");
+    }
+
+    let error = parse_untrusted("length-over-limit.png", ParseLimits { max_chunk_size: 1 << 20, ..default }).unwrap_err();
+    assert!(error.contains("over the 1048576 bytes limit"), "{error}");
+    let error = parse_untrusted("many-chunks.png", ParseLimits { max_chunks: 16, ..default }).unwrap_err();
+    assert!(error.contains("more than 16 chunks"), "{error}");
+}
+
+#[test]
+fn test_png_corpus() {
+    for (path, bytes) in corpus("png") {
+        let parsed = Png::try_from(bytes.as_slice()).map(|png| png.as_bytes());
+        if let Ok(serialized) = &parsed {
+            assert_eq!(serialized, &bytes, "{}", path.display());
+        }
+        let streamed = Png::from_reader(bytes.as_slice()).map(|png| png.as_bytes());
+        assert_eq!(streamed.ok(), parsed.ok(), "{}", path.display());
+    }
+}
+
+#[test]
+fn test_chunk_corpus() {
+    let mut accepted = Vec::new();
+    for (path, bytes) in corpus("chunk") {
+        if let Ok((chunk, consumed)) = Chunk::parse(&bytes) {
+            assert_eq!(chunk.as_bytes(), bytes[..consumed], "{}", path.display());
+            accepted.push(path.file_name().unwrap().to_string_lossy().into_owned());
+        }
+    }
+    assert_eq!(accepted, ["followed-by-more.chunk", "valid.chunk"]);
+}
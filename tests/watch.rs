@@ -0,0 +1,100 @@
+//! Runs `pngme watch` as a separate process, the way it is used, since it only returns once
+//! interrupted.
+#![cfg(all(unix, feature = "watch"))]
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pngme::{Png, PngBuilder};
+
+/// Polls `done` until it holds, failing the test after a generous timeout.
+fn wait_for(what: &str, mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Whether the `chunk_type` chunk of the png at `path` holds `text`. The png may be missing or
+/// mid-replacement while watch works, which counts as not holding it yet.
+fn chunk_contains(path: &Path, chunk_type: &str, text: &[u8]) -> bool {
+    let Ok(bytes) = fs::read(path) else {
+        return false;
+    };
+    let Ok(png) = Png::try_from(bytes.as_slice()) else {
+        return false;
+    };
+    png.chunk_by_type(chunk_type).is_some_and(|chunk| chunk.data().windows(text.len()).any(|window| window == text))
+}
+
+#[test]
+fn test_watch_updates_the_chunk_and_stops_on_ctrl_c() {
+    let dir = tempfile::tempdir().unwrap();
+    let image = dir.path().join("image.png");
+    let payload = dir.path().join("payload.txt");
+    fs::write(&image, PngBuilder::new(1, 1).build().unwrap().as_bytes()).unwrap();
+    fs::write(&payload, "first build").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .arg("watch")
+        .arg(&image)
+        .arg("buIl")
+        .arg("--input-file")
+        .arg(&payload)
+        .args(["--debounce", "50"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    wait_for("the initial update", || chunk_contains(&image, "buIl", b"first build"));
+    fs::write(&payload, "second build").unwrap();
+    wait_for("the update after the payload changed", || chunk_contains(&image, "buIl", b"second build"));
+
+    let kill = Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    assert!(kill.success());
+    let status = child.wait().unwrap();
+    let mut stdout = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut stdout).unwrap();
+
+    assert!(status.success(), "{status}: {stdout}");
+    assert!(stdout.contains("stopped watching"), "{stdout}");
+    assert!(chunk_contains(&image, "buIl", b"second build"));
+}
+
+#[test]
+fn test_watch_on_change_runs_the_operation_on_new_pngs() {
+    let dir = tempfile::tempdir().unwrap();
+    let image = dir.path().join("new.png");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .arg("watch")
+        .arg(dir.path())
+        .args(["--on-change", "encode {} teSt 'seen by watch'", "--debounce", "50"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // The png is written next to its final name and renamed, like a tool saving it would, and
+    // again until the operation has run, since watch may not have started the first time.
+    let staging = dir.path().join(".new.png.part");
+    wait_for("the operation to run", || {
+        fs::write(&staging, PngBuilder::new(1, 1).build().unwrap().as_bytes()).unwrap();
+        fs::rename(&staging, &image).unwrap();
+        thread::sleep(Duration::from_millis(200));
+        chunk_contains(&image, "teSt", b"seen by watch")
+    });
+
+    let kill = Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    assert!(kill.success());
+    let status = child.wait().unwrap();
+    let mut stdout = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut stdout).unwrap();
+
+    assert!(status.success(), "{status}: {stdout}");
+    assert!(stdout.contains("stopped watching"), "{stdout}");
+}